@@ -0,0 +1,45 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use common::ast::Program;
+use common::Symbol;
+use lexer::{cool, Lexer};
+use parser::Parser;
+use semant::ClassTable;
+
+#[path = "../../lexer/benches/programs/mod.rs"]
+mod programs;
+
+use programs::program;
+
+type Backend = fn(&Program, &ClassTable) -> String;
+
+fn bench_emit(c: &mut Criterion) {
+    let lexer = Lexer::new(cool::rules());
+    let inputs = [("small", program(1)), ("large", program(200))];
+    let backends: [(&str, Backend); 5] = [
+        ("ir", |program, table| ir::lower(program, table).to_string()),
+        ("llvm", codegen::llvm::emit),
+        ("c", codegen::c::emit),
+        ("wasm", codegen::wasm::emit),
+        ("x86_64", codegen::x86_64::emit),
+    ];
+
+    let mut group = c.benchmark_group("codegen");
+    for (name, source) in inputs.iter() {
+        let tokens = lexer.lex(source).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("bench.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        for (target, emit) in backends.iter() {
+            group.bench_with_input(BenchmarkId::new(*target, name), &program, |b, program| {
+                b.iter(|| emit(program, &table))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_emit);
+criterion_main!(benches);
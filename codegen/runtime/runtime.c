@@ -0,0 +1,425 @@
+/*
+ * The runtime of COOL programs compiled to LLVM IR or C.
+ *
+ * The compiled program defines the class tables and the prototype objects, this file implements
+ * the methods of the basic classes, allocation and the runtime errors. Objects are laid out like
+ * the structs below, every object starts with an `Object` header.
+ *
+ * Objects are collected by a mark-sweep collector like the one of the bytecode VM: objects never
+ * move, and once as many objects were allocated since the last collection as were live after it
+ * the ones the program can't reach anymore are freed. The compiled program tells the collector
+ * where to find the objects it can reach: every compiled function pushes a `Frame` onto
+ * `cool_frames` with a slot for `self`, each parameter and each value it computes, and pops it
+ * when it returns. Static objects, the prototypes and constants, only refer to other static
+ * objects and are never collected.
+ *
+ * The program's arguments are the options of the collector, like for the x86-64 runtime:
+ * `--gc-stats` prints what it did on exit and `--gc-stress` collects on every allocation.
+ */
+
+#define _POSIX_C_SOURCE 200809L
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+enum { INT_TAG = 2, STRING_TAG = 3, BOOL_TAG = 4 };
+
+/* The type of the entries of vtables, cast to the type of the method before calling it */
+typedef void (*Method)(void);
+
+typedef struct Object {
+    int32_t tag;
+    int32_t size;
+    const Method *vtable;
+} Object;
+
+typedef struct {
+    Object header;
+    int32_t value;
+} Int;
+
+typedef struct {
+    Object header;
+    int32_t value;
+} Bool;
+
+/* The bytes of strings allocated at runtime follow the struct, null terminated */
+typedef struct {
+    Object header;
+    int32_t length;
+    const char *chars;
+} String;
+
+/* The slots of a compiled function that hold objects, or NULL */
+typedef struct Frame {
+    struct Frame *previous;
+    Object **slots;
+    int32_t count;
+} Frame;
+
+/*
+ * Objects allocated at runtime have flags in the top bits of their size, which no object comes
+ * close to. Static objects have none, so the collector can tell them apart.
+ */
+enum { HEAP_OBJECT = 1 << 29, MARKED = 1 << 30, SIZE_MASK = HEAP_OBJECT - 1 };
+
+/*
+ * Collect once this many objects were allocated since the last collection, or as many as were
+ * live after it if that's more.
+ */
+enum { MIN_THRESHOLD = 10000 };
+
+/* Defined by the compiled program, indexed by class tag */
+extern const char *cool_class_names[];
+extern Object *cool_class_protos[];
+
+extern Bool cool_true;
+extern Bool cool_false;
+
+/* The frame of the innermost compiled function that's running */
+Frame *cool_frames;
+
+/* Every object allocated at runtime that wasn't freed yet */
+static Object **heap;
+static size_t heap_length, heap_capacity;
+/* The objects that were marked but whose attributes weren't yet */
+static Object **gray;
+static size_t gray_length, gray_capacity;
+
+static size_t allocated_since_collection, threshold = MIN_THRESHOLD;
+static int gc_stress;
+static size_t stat_collections, stat_allocated, stat_freed;
+
+static void runtime_error(void) {
+    fflush(stdout);
+    exit(1);
+}
+
+static void out_of_memory(void) {
+    fflush(stdout);
+    fprintf(stderr, "Out of memory\n");
+    exit(1);
+}
+
+static void *allocate(size_t size) {
+    void *memory = malloc(size);
+    if (memory == NULL) {
+        out_of_memory();
+    }
+
+    return memory;
+}
+
+/* Make room for `count` more elements in a growable array of objects */
+static void reserve(Object ***objects, size_t length, size_t *capacity, size_t count) {
+    if (length + count > *capacity) {
+        *capacity = (length + count) * 2;
+        *objects = realloc(*objects, *capacity * sizeof(Object *));
+        if (*objects == NULL) {
+            out_of_memory();
+        }
+    }
+}
+
+static void mark(Object *object) {
+    if (object == NULL || !(object->size & HEAP_OBJECT) || object->size & MARKED) {
+        return;
+    }
+
+    object->size |= MARKED;
+    reserve(&gray, gray_length, &gray_capacity, 1);
+    gray[gray_length++] = object;
+}
+
+/* Mark the attributes of the marked objects until there are none left */
+static void trace(void) {
+    while (gray_length > 0) {
+        Object *object = gray[--gray_length];
+        /* Every attribute of the other classes is an object */
+        if (object->tag != INT_TAG && object->tag != STRING_TAG && object->tag != BOOL_TAG) {
+            Object **attributes = (Object **)(object + 1);
+            size_t size = (size_t)(object->size & SIZE_MASK);
+
+            for (size_t i = 0; i < (size - sizeof(Object)) / sizeof(Object *); i++) {
+                mark(attributes[i]);
+            }
+        }
+    }
+}
+
+/* Free the objects the program can't reach from the slots of its frames */
+static void collect(void) {
+    size_t live = 0;
+
+    for (Frame *frame = cool_frames; frame != NULL; frame = frame->previous) {
+        for (int32_t i = 0; i < frame->count; i++) {
+            mark(frame->slots[i]);
+        }
+    }
+    trace();
+
+    for (size_t i = 0; i < heap_length; i++) {
+        if (heap[i]->size & MARKED) {
+            heap[i]->size &= ~MARKED;
+            heap[live++] = heap[i];
+        } else {
+            free(heap[i]);
+        }
+    }
+    stat_collections++;
+    stat_freed += heap_length - live;
+    heap_length = live;
+    allocated_since_collection = 0;
+    threshold = live > MIN_THRESHOLD ? live : MIN_THRESHOLD;
+}
+
+static void print_gc_stats(void) {
+    fprintf(stderr, "GC: %zu collections, %zu objects allocated, %zu freed, %zu live\n",
+            stat_collections, stat_allocated, stat_freed, stat_allocated - stat_freed);
+}
+
+/* Check the arguments of the program before it runs */
+void cool_start(int argc, char **argv) {
+    for (int i = 1; i < argc; i++) {
+        if (strcmp(argv[i], "--gc-stats") == 0) {
+            atexit(print_gc_stats);
+        } else if (strcmp(argv[i], "--gc-stress") == 0) {
+            gc_stress = 1;
+        }
+    }
+}
+
+/*
+ * Allocate a copy of `model` with `extra` bytes after it, collecting garbage first if it's time.
+ *
+ * The objects the caller holds must be in the slots of a frame or be reachable from them.
+ */
+static Object *new_object(const Object *model, size_t extra) {
+    size_t size = (size_t)(model->size & SIZE_MASK);
+    Object *object;
+
+    if (gc_stress || allocated_since_collection >= threshold) {
+        collect();
+    }
+    reserve(&heap, heap_length, &heap_capacity, 1);
+    object = allocate(size + extra);
+    memcpy(object, model, size);
+    object->size = (int32_t)size | HEAP_OBJECT;
+    heap[heap_length++] = object;
+    allocated_since_collection++;
+    stat_allocated++;
+
+    return object;
+}
+
+Object *cool_new(int32_t tag) {
+    return new_object(cool_class_protos[tag], 0);
+}
+
+Object *cool_int(int32_t value) {
+    Int *object = (Int *)cool_new(INT_TAG);
+    object->value = value;
+
+    return &object->header;
+}
+
+Object *cool_bool(int32_t value) {
+    return value ? &cool_true.header : &cool_false.header;
+}
+
+static Object *new_string(const char *chars, int32_t length) {
+    String *object = (String *)new_object(cool_class_protos[STRING_TAG], (size_t)length + 1);
+    char *copy = (char *)(object + 1);
+    memcpy(copy, chars, (size_t)length);
+    copy[length] = '\0';
+    object->length = length;
+    object->chars = copy;
+
+    return &object->header;
+}
+
+int32_t cool_equal(Object *a, Object *b) {
+    if (a == b) {
+        return 1;
+    }
+    if (a == NULL || b == NULL || a->tag != b->tag) {
+        return 0;
+    }
+
+    switch (a->tag) {
+    case INT_TAG:
+        return ((Int *)a)->value == ((Int *)b)->value;
+    case BOOL_TAG:
+        return ((Bool *)a)->value == ((Bool *)b)->value;
+    case STRING_TAG: {
+        String *s = (String *)a, *t = (String *)b;
+        return s->length == t->length && memcmp(s->chars, t->chars, (size_t)s->length) == 0;
+    }
+    default:
+        return 0;
+    }
+}
+
+int32_t cool_divide(int32_t a, int32_t b, const char *file, int32_t line) {
+    if (b == 0) {
+        fflush(stdout);
+        fprintf(stderr, "%s:%d: Division by zero.\n", file, line);
+        runtime_error();
+    }
+    if (a == INT32_MIN && b == -1) {
+        return INT32_MIN;
+    }
+
+    return a / b;
+}
+
+void cool_dispatch_void(const char *file, int32_t line) {
+    fflush(stdout);
+    fprintf(stderr, "%s:%d: Dispatch to void.\n", file, line);
+    runtime_error();
+}
+
+void cool_case_void(const char *file, int32_t line) {
+    fflush(stdout);
+    fprintf(stderr, "%s:%d: Match on void in case statement.\n", file, line);
+    runtime_error();
+}
+
+void cool_case_no_match(Object *object) {
+    fflush(stdout);
+    fprintf(stderr, "No match in case statement for Class %s\n", cool_class_names[object->tag]);
+    runtime_error();
+}
+
+Object *Object_abort(Object *self) {
+    fflush(stdout);
+    fprintf(stderr, "Abort called from class %s\n", cool_class_names[self->tag]);
+    runtime_error();
+
+    return self;
+}
+
+Object *Object_type_name(Object *self) {
+    const char *name = cool_class_names[self->tag];
+
+    return new_string(name, (int32_t)strlen(name));
+}
+
+Object *Object_copy(Object *self) {
+    /* The bytes of a string may be part of the original */
+    if (self->tag == STRING_TAG) {
+        String *string = (String *)self;
+        return new_string(string->chars, string->length);
+    }
+
+    return new_object(self, 0);
+}
+
+Object *IO_out_string(Object *self, Object *s) {
+    String *string = (String *)s;
+    fwrite(string->chars, 1, (size_t)string->length, stdout);
+
+    return self;
+}
+
+Object *IO_out_int(Object *self, Object *i) {
+    printf("%d", ((Int *)i)->value);
+
+    return self;
+}
+
+/* Read a line of input without the line break, returning its length */
+static int32_t read_line(char **line) {
+    size_t capacity = 0;
+    ssize_t length;
+
+    fflush(stdout);
+    *line = NULL;
+    length = getline(line, &capacity, stdin);
+    if (length < 0) {
+        return 0;
+    }
+    if (length > 0 && (*line)[length - 1] == '\n') {
+        length--;
+    }
+
+    return (int32_t)length;
+}
+
+Object *IO_in_string(Object *self) {
+    char *line;
+    int32_t length = read_line(&line);
+    Object *string = new_string(line != NULL ? line : "", length);
+    free(line);
+
+    (void)self;
+    return string;
+}
+
+Object *IO_in_int(Object *self) {
+    char *line;
+    int32_t length = read_line(&line);
+    int32_t i = 0, value = 0, negative = 0;
+
+    while (i < length && (line[i] == ' ' || (line[i] >= '\t' && line[i] <= '\r'))) {
+        i++;
+    }
+    if (i < length && (line[i] == '-' || line[i] == '+')) {
+        negative = line[i] == '-';
+        i++;
+    }
+    for (; i < length && line[i] >= '0' && line[i] <= '9'; i++) {
+        value = (int32_t)((uint32_t)value * 10 + (uint32_t)(line[i] - '0'));
+    }
+    free(line);
+
+    (void)self;
+    return cool_int(negative ? (int32_t)(0u - (uint32_t)value) : value);
+}
+
+Object *String_length(Object *self) {
+    return cool_int(((String *)self)->length);
+}
+
+Object *String_concat(Object *self, Object *s) {
+    String *a = (String *)self, *b = (String *)s;
+    size_t length = (size_t)a->length + (size_t)b->length;
+    String *result = (String *)new_object(cool_class_protos[STRING_TAG], length + 1);
+    char *chars = (char *)(result + 1);
+
+    memcpy(chars, a->chars, (size_t)a->length);
+    memcpy(chars + a->length, b->chars, (size_t)b->length);
+    chars[length] = '\0';
+    result->length = (int32_t)length;
+    result->chars = chars;
+
+    return &result->header;
+}
+
+static void substr_error(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, "%s\n", message);
+    runtime_error();
+}
+
+Object *String_substr(Object *self, Object *i, Object *l) {
+    String *string = (String *)self;
+    int32_t index = ((Int *)i)->value, length = ((Int *)l)->value;
+
+    if (index < 0) {
+        substr_error("Index to substr is negative");
+    }
+    if (length < 0) {
+        substr_error("Length to substr is negative");
+    }
+    if (index > string->length) {
+        substr_error("Index to substr is too big");
+    }
+    if ((int64_t)index + length > string->length) {
+        substr_error("Length to substr too long");
+    }
+
+    return new_string(string->chars + index, length);
+}
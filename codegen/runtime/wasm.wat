@@ -0,0 +1,252 @@
+;; The runtime of COOL programs compiled to WebAssembly.
+;;
+;; These functions are spliced into every compiled module, which defines the class tables, the
+;; prototype objects, the messages of runtime errors and the imported host functions used here.
+;; Objects live in linear memory, all their fields are i32s:
+;;
+;;   0: class tag, 4: size in bytes, 8: index of the vtable in the function table, 12: attributes
+;;
+;; Ints and Bools hold their value at 12, Strings their length at 12 and their bytes from 16.
+;; Objects are allocated by bumping the `$heap` pointer and never freed.
+
+(func $alloc (param $size i32) (result i32)
+  (local $object i32)
+  (local $end i32)
+  (local.set $object (global.get $heap))
+  ;; Keep objects aligned to 4 bytes
+  (local.set $end
+    (i32.and
+      (i32.add (i32.add (local.get $object) (local.get $size)) (i32.const 3))
+      (i32.const -4)))
+  (if (i32.gt_u (local.get $end) (i32.shl (memory.size) (i32.const 16)))
+    (then
+      (if (i32.eq
+            (memory.grow
+              (i32.shr_u
+                (i32.add
+                  (i32.sub (local.get $end) (i32.shl (memory.size) (i32.const 16)))
+                  (i32.const 65535))
+                (i32.const 16)))
+            (i32.const -1))
+        (then (call $fail (global.get $msg_out_of_memory))))))
+  (global.set $heap (local.get $end))
+  (local.get $object))
+
+(func $new (param $tag i32) (result i32)
+  (local $proto i32)
+  (local $object i32)
+  (local.set $proto
+    (i32.load (i32.add (global.get $class_protos) (i32.shl (local.get $tag) (i32.const 2)))))
+  (local.set $object (call $alloc (i32.load offset=4 (local.get $proto))))
+  (memory.copy (local.get $object) (local.get $proto) (i32.load offset=4 (local.get $proto)))
+  (local.get $object))
+
+(func $int (param $value i32) (result i32)
+  (local $object i32)
+  (local.set $object (call $new (i32.const 2)))
+  (i32.store offset=12 (local.get $object) (local.get $value))
+  (local.get $object))
+
+(func $bool (param $value i32) (result i32)
+  (select (global.get $true) (global.get $false) (local.get $value)))
+
+;; A String of `length` bytes, which the caller fills in
+(func $new_string (param $length i32) (result i32)
+  (local $object i32)
+  (local.set $object (call $alloc (i32.add (i32.const 16) (local.get $length))))
+  (memory.copy (local.get $object) (call $class_proto (i32.const 3)) (i32.const 12))
+  (i32.store offset=4 (local.get $object) (i32.add (i32.const 16) (local.get $length)))
+  (i32.store offset=12 (local.get $object) (local.get $length))
+  (local.get $object))
+
+(func $class_proto (param $tag i32) (result i32)
+  (i32.load (i32.add (global.get $class_protos) (i32.shl (local.get $tag) (i32.const 2)))))
+
+(func $class_name (param $object i32) (result i32)
+  (i32.load
+    (i32.add
+      (global.get $class_names)
+      (i32.shl (i32.load (local.get $object)) (i32.const 2)))))
+
+(func $int_to_string (param $value i32) (result i32)
+  (local $magnitude i64)
+  (local $rest i64)
+  (local $length i32)
+  (local $string i32)
+  (local.set $magnitude (i64.extend_i32_s (local.get $value)))
+  (if (i64.lt_s (local.get $magnitude) (i64.const 0))
+    (then
+      (local.set $magnitude (i64.sub (i64.const 0) (local.get $magnitude)))
+      (local.set $length (i32.const 1))))
+  (local.set $rest (local.get $magnitude))
+  (loop $count
+    (local.set $length (i32.add (local.get $length) (i32.const 1)))
+    (local.set $rest (i64.div_u (local.get $rest) (i64.const 10)))
+    (br_if $count (i64.ne (local.get $rest) (i64.const 0))))
+  (local.set $string (call $new_string (local.get $length)))
+  (if (i32.lt_s (local.get $value) (i32.const 0))
+    (then (i32.store8 offset=16 (local.get $string) (i32.const 45))))
+  (local.set $rest (local.get $magnitude))
+  (loop $digits
+    (local.set $length (i32.sub (local.get $length) (i32.const 1)))
+    (i32.store8 offset=16
+      (i32.add (local.get $string) (local.get $length))
+      (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $rest) (i64.const 10)))))
+    (local.set $rest (i64.div_u (local.get $rest) (i64.const 10)))
+    (br_if $digits (i64.ne (local.get $rest) (i64.const 0))))
+  (local.get $string))
+
+;; Report the String `message` as a runtime error, which stops the program
+(func $fail (param $message i32)
+  (call $host_error
+    (i32.add (local.get $message) (i32.const 16))
+    (i32.load offset=12 (local.get $message)))
+  (unreachable))
+
+;; Report `message` as a runtime error at `line` of the String `file`
+(func $fail_at (param $file i32) (param $line i32) (param $message i32)
+  (call $fail
+    (call $String_concat
+      (call $String_concat
+        (call $String_concat (local.get $file) (global.get $msg_colon))
+        (call $int_to_string (local.get $line)))
+      (local.get $message))))
+
+(func $dispatch_void (param $file i32) (param $line i32)
+  (call $fail_at (local.get $file) (local.get $line) (global.get $msg_dispatch_void)))
+
+(func $case_void (param $file i32) (param $line i32)
+  (call $fail_at (local.get $file) (local.get $line) (global.get $msg_case_void)))
+
+(func $case_no_match (param $object i32)
+  (call $fail
+    (call $String_concat (global.get $msg_no_match) (call $class_name (local.get $object)))))
+
+(func $divide (param $a i32) (param $b i32) (param $file i32) (param $line i32) (result i32)
+  (if (i32.eqz (local.get $b))
+    (then
+      (call $fail_at (local.get $file) (local.get $line) (global.get $msg_division_by_zero))))
+  ;; Overflows like the reference runtime instead of trapping
+  (if (i32.and
+        (i32.eq (local.get $a) (i32.const 0x80000000))
+        (i32.eq (local.get $b) (i32.const -1)))
+    (then (return (local.get $a))))
+  (i32.div_s (local.get $a) (local.get $b)))
+
+(func $equal (param $a i32) (param $b i32) (result i32)
+  (local $tag i32)
+  (local $length i32)
+  (local $i i32)
+  (if (i32.eq (local.get $a) (local.get $b))
+    (then (return (i32.const 1))))
+  (if (i32.or (i32.eqz (local.get $a)) (i32.eqz (local.get $b)))
+    (then (return (i32.const 0))))
+  (local.set $tag (i32.load (local.get $a)))
+  (if (i32.ne (local.get $tag) (i32.load (local.get $b)))
+    (then (return (i32.const 0))))
+  ;; Ints and Bools
+  (if (i32.or (i32.eq (local.get $tag) (i32.const 2)) (i32.eq (local.get $tag) (i32.const 4)))
+    (then
+      (return
+        (i32.eq (i32.load offset=12 (local.get $a)) (i32.load offset=12 (local.get $b))))))
+  (if (i32.ne (local.get $tag) (i32.const 3))
+    (then (return (i32.const 0))))
+  (local.set $length (i32.load offset=12 (local.get $a)))
+  (if (i32.ne (local.get $length) (i32.load offset=12 (local.get $b)))
+    (then (return (i32.const 0))))
+  (block $done
+    (loop $bytes
+      (br_if $done (i32.ge_u (local.get $i) (local.get $length)))
+      (if (i32.ne
+            (i32.load8_u offset=16 (i32.add (local.get $a) (local.get $i)))
+            (i32.load8_u offset=16 (i32.add (local.get $b) (local.get $i))))
+        (then (return (i32.const 0))))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br $bytes)))
+  (i32.const 1))
+
+(func $Object_abort (param $self i32) (result i32)
+  (call $fail
+    (call $String_concat (global.get $msg_abort) (call $class_name (local.get $self))))
+  (local.get $self))
+
+(func $Object_type_name (param $self i32) (result i32)
+  (call $class_name (local.get $self)))
+
+(func $Object_copy (param $self i32) (result i32)
+  (local $copy i32)
+  (local.set $copy (call $alloc (i32.load offset=4 (local.get $self))))
+  (memory.copy (local.get $copy) (local.get $self) (i32.load offset=4 (local.get $self)))
+  (local.get $copy))
+
+(func $IO_out_string (param $self i32) (param $s i32) (result i32)
+  (call $host_out_string
+    (i32.add (local.get $s) (i32.const 16))
+    (i32.load offset=12 (local.get $s)))
+  (local.get $self))
+
+(func $IO_out_int (param $self i32) (param $i i32) (result i32)
+  (call $host_out_int (i32.load offset=12 (local.get $i)))
+  (local.get $self))
+
+;; Read a line of at most 1024 bytes. The host writes it into a String allocated for the longest
+;; line, which then shrinks to the line.
+(func $IO_in_string (param $self i32) (result i32)
+  (local $string i32)
+  (local $length i32)
+  (local.set $string (call $new_string (i32.const 1024)))
+  (local.set $length
+    (call $host_in_string (i32.add (local.get $string) (i32.const 16)) (i32.const 1024)))
+  (i32.store offset=4 (local.get $string) (i32.add (i32.const 16) (local.get $length)))
+  (i32.store offset=12 (local.get $string) (local.get $length))
+  (global.set $heap
+    (i32.and
+      (i32.add (i32.add (local.get $string) (i32.add (i32.const 16) (local.get $length)))
+        (i32.const 3))
+      (i32.const -4)))
+  (local.get $string))
+
+(func $IO_in_int (param $self i32) (result i32)
+  (call $int (call $host_in_int)))
+
+(func $String_length (param $self i32) (result i32)
+  (call $int (i32.load offset=12 (local.get $self))))
+
+(func $String_concat (param $self i32) (param $s i32) (result i32)
+  (local $length i32)
+  (local $string i32)
+  (local.set $length (i32.load offset=12 (local.get $self)))
+  (local.set $string
+    (call $new_string (i32.add (local.get $length) (i32.load offset=12 (local.get $s)))))
+  (memory.copy
+    (i32.add (local.get $string) (i32.const 16))
+    (i32.add (local.get $self) (i32.const 16))
+    (local.get $length))
+  (memory.copy
+    (i32.add (i32.add (local.get $string) (i32.const 16)) (local.get $length))
+    (i32.add (local.get $s) (i32.const 16))
+    (i32.load offset=12 (local.get $s)))
+  (local.get $string))
+
+(func $String_substr (param $self i32) (param $i i32) (param $l i32) (result i32)
+  (local $index i32)
+  (local $length i32)
+  (local $string i32)
+  (local.set $index (i32.load offset=12 (local.get $i)))
+  (local.set $length (i32.load offset=12 (local.get $l)))
+  (if (i32.lt_s (local.get $index) (i32.const 0))
+    (then (call $fail (global.get $msg_substr_index_negative))))
+  (if (i32.lt_s (local.get $length) (i32.const 0))
+    (then (call $fail (global.get $msg_substr_length_negative))))
+  (if (i32.gt_s (local.get $index) (i32.load offset=12 (local.get $self)))
+    (then (call $fail (global.get $msg_substr_index_too_big))))
+  (if (i64.gt_s
+        (i64.add (i64.extend_i32_s (local.get $index)) (i64.extend_i32_s (local.get $length)))
+        (i64.extend_i32_s (i32.load offset=12 (local.get $self))))
+    (then (call $fail (global.get $msg_substr_length_too_long))))
+  (local.set $string (call $new_string (local.get $length)))
+  (memory.copy
+    (i32.add (local.get $string) (i32.const 16))
+    (i32.add (i32.add (local.get $self) (i32.const 16)) (local.get $index))
+    (local.get $length))
+  (local.get $string))
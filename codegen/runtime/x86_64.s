@@ -0,0 +1,1090 @@
+# The runtime of COOL programs compiled to x86-64 assembly, for Linux.
+#
+# Compiled programs are linked with this file by `ld` alone, it talks to the kernel through system
+# calls instead of depending on a C library. The compiled program defines the class tables, the
+# prototype objects, `cool_true`, `cool_false` and `cool_main`, which runs `Main.main`.
+#
+# Like with the reference runtime's trap handler, the compiled program also chooses the garbage
+# collector: `_MemMgr_INITIALIZER` and `_MemMgr_COLLECTOR` are `_GenGC_Init` and `_GenGC_Collect`
+# if it was compiled with `-g`, `_NoGC_Init` and `_NoGC_Collect` otherwise, and `_MemMgr_TEST` is
+# 1 if it was compiled with `-t` to collect on every allocation.
+#
+# Objects start with a header of their class tag (4 bytes), their size (4 bytes) and a pointer to
+# their vtable, followed by 8 bytes for every attribute. Ints and Bools hold their value in the
+# low 4 bytes at offset 16, Strings their length at 16 and their bytes from 24. Like with the
+# reference runtime every object is preceded by a GC tag word of -1.
+#
+# Objects are allocated by bumping a pointer through one of two semispaces. Without a collector
+# the program stops when it runs out of one. The generational collector copies the objects
+# allocated since the last collection that the program can still reach right after them in a
+# minor collection, where they're old. Once the old objects outgrow their limit, a major
+# collection copies the ones the program can still reach to the other semispace.
+#
+# The compiled program tells the collector where to find them: the frame of every compiled
+# method starts with the number of slots after it, which hold objects or 0, and frames are chained
+# through %rbp. The old objects that refer to young ones are known from the write barrier
+# `_GenGC_Assign`, which programs compiled with `-g` call after assigning an attribute. Runtime
+# functions that allocate while holding objects look up where they were moved to.
+#
+# The program's arguments are the options of the collector: `--gc-stats` prints what it did on
+# exit and `--gc-stress` collects on every allocation like `-t`.
+#
+# All functions follow the System V ABI.
+
+        .set SYS_READ, 0
+        .set SYS_WRITE, 1
+        .set SYS_MMAP, 9
+        .set SYS_MADVISE, 28
+        .set SYS_EXIT_GROUP, 231
+
+        .set INT_TAG, 2
+        .set STRING_TAG, 3
+        .set BOOL_TAG, 4
+        # The tag of an object that was moved, its new address replaces its vtable
+        .set FORWARDED, -1
+
+        # Both semispaces are reserved up front, pages are only backed by memory when they're used
+        .set SEMISPACE_SHIFT, 31
+        .set SEMISPACE_SIZE, 1 << SEMISPACE_SHIFT
+        # Collect after allocating as many bytes as were live after the last collection, at least
+        # this many. Memory of the other semispace is only given back if it used this many.
+        .set MIN_GC_BYTES, 1 << 20
+        # A minor collection runs after allocating this many young bytes
+        .set NURSERY_BYTES, MIN_GC_BYTES
+        # The size of the assignment table of the write barrier. Once it's full, the next minor
+        # collection scans all old objects instead.
+        .set ASSIGN_TABLE_SIZE, 8 << 20
+        .set MADV_DONTNEED, 4
+        .set BUFFER_SIZE, 4096
+
+        .bss
+        .p2align 3
+heap_next:      .skip 8
+heap_end:       .skip 8
+space_start:    .skip 8
+space_other:    .skip 8
+# How much of the other semispace was used when objects were last allocated there
+other_used:     .skip 8
+gc_limit:       .skip 8
+# Where the young objects of the generational collector start, the old ones are before them
+young_start:    .skip 8
+# How many bytes the old objects may take before a major collection
+old_limit:      .skip 8
+# The addresses of the attributes of old objects that were assigned young objects since the last
+# collection, and whether there were more than fit
+assign_start:   .skip 8
+assign_next:    .skip 8
+assign_end:     .skip 8
+assign_overflow:
+                .skip 8
+# The young objects copied by the last minor collection, which turn into a filler object once
+# they can't be looked up anymore so the old objects can be scanned
+hole_start:     .skip 8
+hole_end:       .skip 8
+gc_stress:      .skip 8
+gc_stats:       .skip 8
+# The objects allocated in the current semispace, and the young ones among them
+space_objects:  .skip 8
+young_objects:  .skip 8
+stat_collections:
+                .skip 8
+stat_allocated: .skip 8
+stat_freed:     .skip 8
+out_length:     .skip 8
+in_start:       .skip 8
+in_end:         .skip 8
+out_buffer:     .skip BUFFER_SIZE
+in_buffer:      .skip BUFFER_SIZE
+
+        .section .rodata
+colon:          .ascii ":"
+newline:        .ascii "\n"
+opt_gc_stats:   .asciz "--gc-stats"
+opt_gc_stress:  .asciz "--gc-stress"
+msg_gc:
+        .ascii "GC: "
+        .set msg_gc_length, . - msg_gc
+msg_gc_collections:
+        .ascii " collections, "
+        .set msg_gc_collections_length, . - msg_gc_collections
+msg_gc_allocated:
+        .ascii " objects allocated, "
+        .set msg_gc_allocated_length, . - msg_gc_allocated
+msg_gc_freed:
+        .ascii " freed, "
+        .set msg_gc_freed_length, . - msg_gc_freed
+msg_gc_live:
+        .ascii " live\n"
+        .set msg_gc_live_length, . - msg_gc_live
+msg_dispatch_void:
+        .ascii ": Dispatch to void.\n"
+        .set msg_dispatch_void_length, . - msg_dispatch_void
+msg_case_void:
+        .ascii ": Match on void in case statement.\n"
+        .set msg_case_void_length, . - msg_case_void
+msg_no_match:
+        .ascii "No match in case statement for Class "
+        .set msg_no_match_length, . - msg_no_match
+msg_abort:
+        .ascii "Abort called from class "
+        .set msg_abort_length, . - msg_abort
+msg_division_by_zero:
+        .ascii ": Division by zero.\n"
+        .set msg_division_by_zero_length, . - msg_division_by_zero
+msg_substr_index_negative:
+        .ascii "Index to substr is negative\n"
+        .set msg_substr_index_negative_length, . - msg_substr_index_negative
+msg_substr_index_too_big:
+        .ascii "Index to substr is too big\n"
+        .set msg_substr_index_too_big_length, . - msg_substr_index_too_big
+msg_substr_length_negative:
+        .ascii "Length to substr is negative\n"
+        .set msg_substr_length_negative_length, . - msg_substr_length_negative
+msg_substr_length_too_long:
+        .ascii "Length to substr too long\n"
+        .set msg_substr_length_too_long_length, . - msg_substr_length_too_long
+msg_out_of_memory:
+        .ascii "Out of memory\n"
+        .set msg_out_of_memory_length, . - msg_out_of_memory
+
+        .text
+
+        .globl _start
+_start:
+        xorl %ebp, %ebp
+        movq (%rsp), %rbx
+        leaq 16(%rsp), %r12
+1:      decq %rbx
+        jle 2f
+        movq (%r12), %rdi
+        leaq opt_gc_stats(%rip), %rsi
+        call c_string_equal
+        orq %rax, gc_stats(%rip)
+        movq (%r12), %rdi
+        leaq opt_gc_stress(%rip), %rsi
+        call c_string_equal
+        orq %rax, gc_stress(%rip)
+        addq $8, %r12
+        jmp 1b
+
+2:      movq _MemMgr_TEST(%rip), %rax
+        orq %rax, gc_stress(%rip)
+        movl $ASSIGN_TABLE_SIZE, %edi
+        call reserve
+        movq %rax, assign_start(%rip)
+        movq %rax, assign_next(%rip)
+        addq $ASSIGN_TABLE_SIZE, %rax
+        movq %rax, assign_end(%rip)
+        movabsq $2 * SEMISPACE_SIZE, %rdi
+        call reserve
+        movq %rax, space_start(%rip)
+        movq %rax, heap_next(%rip)
+        movq %rax, young_start(%rip)
+        movl $SEMISPACE_SIZE, %edx
+        addq %rdx, %rax
+        movq %rax, heap_end(%rip)
+        movq %rax, space_other(%rip)
+        call *_MemMgr_INITIALIZER(%rip)
+
+        call cool_main
+        xorl %edi, %edi
+        jmp exit
+
+# Reserve %rdi bytes of memory, which are only backed by memory when they're used
+reserve:
+        movq %rdi, %rsi
+        xorl %edi, %edi
+        movl $3, %edx                   # PROT_READ | PROT_WRITE
+        movl $0x4022, %r10d             # MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE
+        movq $-1, %r8
+        xorl %r9d, %r9d
+        movl $SYS_MMAP, %eax
+        syscall
+        cmpq $-4096, %rax
+        ja out_of_memory
+        ret
+
+# Flush the output and exit with the status %edi
+exit:
+        pushq %rdi
+        call flush
+        cmpq $0, gc_stats(%rip)
+        je 1f
+        call print_gc_stats
+1:      popq %rdi
+        movl $SYS_EXIT_GROUP, %eax
+        syscall
+
+# Whether the strings ending with a 0 byte at %rdi and %rsi are equal, 1 or 0
+c_string_equal:
+        movb (%rdi), %al
+        cmpb (%rsi), %al
+        jne 1f
+        incq %rdi
+        incq %rsi
+        testb %al, %al
+        jnz c_string_equal
+        movl $1, %eax
+        ret
+1:      xorl %eax, %eax
+        ret
+
+# Write %rdx bytes at %rsi to the file descriptor %edi
+write_all:
+        testq %rdx, %rdx
+        jz 1f
+        movl $SYS_WRITE, %eax
+        syscall
+        testq %rax, %rax
+        js 1f
+        addq %rax, %rsi
+        subq %rax, %rdx
+        jmp write_all
+1:      ret
+
+flush:
+        movl $1, %edi
+        leaq out_buffer(%rip), %rsi
+        movq out_length(%rip), %rdx
+        call write_all
+        movq $0, out_length(%rip)
+        ret
+
+# Print %rdx bytes at %rsi to stdout, through the output buffer
+output:
+        movq out_length(%rip), %rax
+        leaq (%rax,%rdx), %rcx
+        cmpq $BUFFER_SIZE, %rcx
+        jbe 1f
+        pushq %rsi
+        pushq %rdx
+        call flush
+        popq %rdx
+        popq %rsi
+        cmpq $BUFFER_SIZE, %rdx
+        jb 1f
+        movl $1, %edi
+        jmp write_all
+1:      movq out_length(%rip), %rax
+        leaq out_buffer(%rip), %rdi
+        addq %rax, %rdi
+        addq %rdx, out_length(%rip)
+        movq %rdx, %rcx
+        rep movsb
+        ret
+
+# Format the signed value %rdi in decimal, ending at %rsi. Returns where it starts.
+format_int:
+        movq %rdi, %rax
+        movq %rax, %r8
+        testq %rax, %rax
+        jns 1f
+        negq %rax
+1:      movl $10, %ecx
+2:      xorl %edx, %edx
+        divq %rcx
+        addb $'0', %dl
+        decq %rsi
+        movb %dl, (%rsi)
+        testq %rax, %rax
+        jnz 2b
+        testq %r8, %r8
+        jns 3f
+        decq %rsi
+        movb $'-', (%rsi)
+3:      movq %rsi, %rax
+        ret
+
+# Print %rdx bytes at %rsi to stderr
+error_write:
+        movl $2, %edi
+        jmp write_all
+
+# Print the value %rdi to stderr
+error_int:
+        subq $24, %rsp
+        leaq 24(%rsp), %rsi
+        call format_int
+        leaq 24(%rsp), %rdx
+        subq %rax, %rdx
+        movq %rax, %rsi
+        call error_write
+        addq $24, %rsp
+        ret
+
+# Report the runtime error of %rdx bytes at %rsi and exit
+fail:
+        movq %rsi, %rbx
+        movq %rdx, %r12
+        call flush
+        movq %rbx, %rsi
+        movq %r12, %rdx
+        call error_write
+        movl $1, %edi
+        jmp exit
+
+# Report the runtime error of %rcx bytes at %rdx at line %esi of the file named by the String %rdi
+fail_at:
+        movq %rdi, %rbx
+        movl %esi, %r12d
+        movq %rdx, %r13
+        movq %rcx, %r14
+        call flush
+        leaq 24(%rbx), %rsi
+        movq 16(%rbx), %rdx
+        call error_write
+        leaq colon(%rip), %rsi
+        movl $1, %edx
+        call error_write
+        movl %r12d, %edi
+        call error_int
+        movq %r13, %rsi
+        movq %r14, %rdx
+        call error_write
+        movl $1, %edi
+        jmp exit
+
+# Report the runtime error of %rdx bytes at %rsi followed by the class of the object %rdi
+fail_class:
+        movq %rdi, %rbx
+        movq %rsi, %r12
+        movq %rdx, %r13
+        call flush
+        movq %r12, %rsi
+        movq %r13, %rdx
+        call error_write
+        movq %rbx, %rdi
+        call class_name
+        leaq 24(%rax), %rsi
+        movq 16(%rax), %rdx
+        call error_write
+        leaq newline(%rip), %rsi
+        movl $1, %edx
+        call error_write
+        movl $1, %edi
+        jmp exit
+
+# Print what the garbage collector did to stderr
+print_gc_stats:
+        leaq msg_gc(%rip), %rsi
+        movl $msg_gc_length, %edx
+        call error_write
+        movq stat_collections(%rip), %rdi
+        call error_int
+        leaq msg_gc_collections(%rip), %rsi
+        movl $msg_gc_collections_length, %edx
+        call error_write
+        movq stat_allocated(%rip), %rdi
+        call error_int
+        leaq msg_gc_allocated(%rip), %rsi
+        movl $msg_gc_allocated_length, %edx
+        call error_write
+        movq stat_freed(%rip), %rdi
+        call error_int
+        leaq msg_gc_freed(%rip), %rsi
+        movl $msg_gc_freed_length, %edx
+        call error_write
+        movq stat_allocated(%rip), %rdi
+        subq stat_freed(%rip), %rdi
+        call error_int
+        leaq msg_gc_live(%rip), %rsi
+        movl $msg_gc_live_length, %edx
+        jmp error_write
+
+out_of_memory:
+        leaq msg_out_of_memory(%rip), %rsi
+        movl $msg_out_of_memory_length, %edx
+        jmp fail
+
+# The name of the class of the object %rdi, a String
+class_name:
+        movl (%rdi), %eax
+        leaq cool_class_names(%rip), %rdx
+        movq (%rdx,%rax,8), %rax
+        ret
+
+# Allocate %rdi bytes after a GC tag word, which may collect garbage first
+        .globl cool_alloc
+cool_alloc:
+        cmpq $0, gc_stress(%rip)
+        jne 1f
+        movq heap_next(%rip), %rax
+        leaq 15(%rax,%rdi), %rdx
+        andq $-8, %rdx
+        cmpq gc_limit(%rip), %rdx
+        jbe 2f
+1:      pushq %rdi
+        call *_MemMgr_COLLECTOR(%rip)
+        popq %rdi
+        movq heap_next(%rip), %rax
+        leaq 15(%rax,%rdi), %rdx
+        andq $-8, %rdx
+        cmpq heap_end(%rip), %rdx
+        ja out_of_memory
+2:      movq %rdx, heap_next(%rip)
+        movq $-1, (%rax)
+        addq $8, %rax
+        incq space_objects(%rip)
+        incq young_objects(%rip)
+        incq stat_allocated(%rip)
+        ret
+
+# Where the object %rdi is now, after an allocation that may have moved it, by a minor collection
+# and then a major one
+moved:
+        movq %rdi, %rax
+1:      cmpl $FORWARDED, (%rax)
+        jne 2f
+        movq 8(%rax), %rax
+        jmp 1b
+2:      ret
+
+# Without a collector, objects are allocated up to the end of the semispace
+        .globl _NoGC_Init
+_NoGC_Init:
+        movq heap_end(%rip), %rax
+        movq %rax, gc_limit(%rip)
+        ret
+
+        .globl _NoGC_Collect
+_NoGC_Collect:
+        ret
+
+        .globl _GenGC_Init
+_GenGC_Init:
+        movq heap_next(%rip), %rax
+        addq $NURSERY_BYTES, %rax
+        movq %rax, gc_limit(%rip)
+        movq $MIN_GC_BYTES, old_limit(%rip)
+        ret
+
+# The write barrier of the generational collector, called with the address of an attribute in
+# %rdi after it was assigned. An old object that now refers to a young one has the attribute
+# added to the assignment table. Keeps %rax.
+        .globl _GenGC_Assign
+_GenGC_Assign:
+        cmpq young_start(%rip), %rdi
+        jae 1f
+        movq (%rdi), %rdx
+        cmpq young_start(%rip), %rdx
+        jb 1f
+        cmpq heap_end(%rip), %rdx
+        jae 1f
+        movq assign_next(%rip), %rdx
+        cmpq assign_end(%rip), %rdx
+        jae 2f
+        movq %rdi, (%rdx)
+        addq $8, %rdx
+        movq %rdx, assign_next(%rip)
+1:      ret
+2:      movq $1, assign_overflow(%rip)
+        ret
+
+# A minor collection, followed by a major one once the old objects outgrow their limit. A major
+# collection runs instead if there's no room to copy all young objects after them.
+        .globl _GenGC_Collect
+_GenGC_Collect:
+        movq heap_next(%rip), %rax
+        movq %rax, %rdx
+        subq young_start(%rip), %rdx
+        addq %rdx, %rax
+        cmpq heap_end(%rip), %rax
+        ja 1f
+        call collect_minor
+        movq young_start(%rip), %rax
+        subq space_start(%rip), %rax
+        cmpq old_limit(%rip), %rax
+        jbe 2f
+1:      call collect
+2:      movq heap_next(%rip), %rax
+        addq $NURSERY_BYTES, %rax
+        cmpq heap_end(%rip), %rax
+        jbe 3f
+        movq heap_end(%rip), %rax
+3:      movq %rax, gc_limit(%rip)
+        ret
+
+# Copy the objects the program can reach to the other semispace, which objects are allocated in
+# from then on. The slots of the frames of compiled methods are the roots, the attributes of the
+# objects that are copied are forwarded in turn by scanning them in the other semispace. All
+# objects are old afterwards.
+#
+# The objects are copied from the %r9 bytes of the semispace starting at %r13 to the one starting
+# at %r14, into its free memory starting at %r15. %rbx counts them.
+collect:
+        pushq %rbx
+        pushq %r12
+        pushq %r13
+        pushq %r14
+        pushq %r15
+        movq space_start(%rip), %r13
+        movq space_other(%rip), %r14
+        movq %r14, space_start(%rip)
+        movq %r13, space_other(%rip)
+        movq other_used(%rip), %rsi
+        movq heap_next(%rip), %rax
+        subq %r13, %rax
+        movq %rax, other_used(%rip)
+        # The other semispace only holds the forwarding pointers of the last collection, which
+        # aren't needed anymore
+        cmpq $MIN_GC_BYTES, %rsi
+        jb 1f
+        movq %r14, %rdi
+        movl $MADV_DONTNEED, %edx
+        movl $SYS_MADVISE, %eax
+        syscall
+1:      movl $SEMISPACE_SIZE, %r9d
+        movq %r14, %r15
+        xorl %ebx, %ebx
+        call forward_frames
+        movq %r14, %r12
+        call scan_copies
+
+        movq %r15, heap_next(%rip)
+        movq %r15, young_start(%rip)
+        movq assign_start(%rip), %rax
+        movq %rax, assign_next(%rip)
+        movq $0, assign_overflow(%rip)
+        movq $0, hole_start(%rip)
+        movq $0, hole_end(%rip)
+        movl $SEMISPACE_SIZE, %eax
+        addq %r14, %rax
+        movq %rax, heap_end(%rip)
+        movq %r15, %rax
+        subq %r14, %rax
+        cmpq $MIN_GC_BYTES, %rax
+        jae 2f
+        movl $MIN_GC_BYTES, %eax
+2:      addq %r15, %rax
+        cmpq heap_end(%rip), %rax
+        jbe 3f
+        movq heap_end(%rip), %rax
+3:      movq %rax, gc_limit(%rip)
+        subq %r14, %rax
+        movq %rax, old_limit(%rip)
+
+        incq stat_collections(%rip)
+        movq space_objects(%rip), %rax
+        subq %rbx, %rax
+        addq %rax, stat_freed(%rip)
+        movq %rbx, space_objects(%rip)
+        movq $0, young_objects(%rip)
+        popq %r15
+        popq %r14
+        popq %r13
+        popq %r12
+        popq %rbx
+        ret
+
+# Copy the young objects the program can reach right after them, where they're old. The slots of
+# the frames and the attributes in the assignment table are the roots, or all attributes of old
+# objects if it overflowed, besides the attributes of the copies. Uses the registers like
+# `collect`, %r14 is where the copies start.
+collect_minor:
+        pushq %rbx
+        pushq %r12
+        pushq %r13
+        pushq %r14
+        pushq %r15
+        movq young_start(%rip), %r13
+        movq heap_next(%rip), %r14
+        movq %r14, %r9
+        subq %r13, %r9
+        movq %r14, %r15
+        xorl %ebx, %ebx
+        # The objects copied by the last minor collection turn into a filler Int
+        movq hole_start(%rip), %rax
+        movq hole_end(%rip), %rdx
+        subq %rax, %rdx
+        jz 1f
+        subq $8, %rdx
+        movq $-1, (%rax)
+        movl $INT_TAG, 8(%rax)
+        movl %edx, 12(%rax)
+1:      call forward_frames
+
+        cmpq $0, assign_overflow(%rip)
+        jne 3f
+        movq assign_start(%rip), %r12
+2:      cmpq assign_next(%rip), %r12
+        jae 5f
+        movq (%r12), %rdi
+        call forward
+        addq $8, %r12
+        jmp 2b
+3:      movq space_start(%rip), %r12
+4:      cmpq %r13, %r12
+        jae 5f
+        leaq 8(%r12), %rsi
+        movl 12(%r12), %eax
+        addq $15, %rax
+        andq $-8, %rax
+        addq %rax, %r12
+        call scan
+        jmp 4b
+5:      movq assign_start(%rip), %rax
+        movq %rax, assign_next(%rip)
+        movq $0, assign_overflow(%rip)
+        movq %r14, %r12
+        call scan_copies
+
+        movq %r13, hole_start(%rip)
+        movq %r14, hole_end(%rip)
+        movq %r15, heap_next(%rip)
+        movq %r15, young_start(%rip)
+        incq stat_collections(%rip)
+        movq young_objects(%rip), %rax
+        subq %rbx, %rax
+        addq %rax, stat_freed(%rip)
+        subq %rax, space_objects(%rip)
+        movq $0, young_objects(%rip)
+        popq %r15
+        popq %r14
+        popq %r13
+        popq %r12
+        popq %rbx
+        ret
+
+# Forward the objects in the slots of the frames of compiled methods
+forward_frames:
+        movq %rbp, %r12
+1:      testq %r12, %r12
+        jz 4f
+        movq -8(%r12), %rcx
+        leaq -8(%r12), %rdi
+2:      testq %rcx, %rcx
+        jz 3f
+        subq $8, %rdi
+        call forward
+        decq %rcx
+        jmp 2b
+3:      movq (%r12), %r12
+        jmp 1b
+4:      ret
+
+# Scan the copies from %r12 up to the free memory at %r15, which grows as they're scanned
+scan_copies:
+        cmpq %r15, %r12
+        jae 1f
+        leaq 8(%r12), %rsi
+        movl 12(%r12), %eax
+        addq $15, %rax
+        andq $-8, %rax
+        addq %rax, %r12
+        call scan
+        jmp scan_copies
+1:      ret
+
+# Forward the attributes of the object %rsi
+scan:
+        movl (%rsi), %eax
+        # Ints, Strings and Bools don't refer to other objects
+        cmpl $INT_TAG, %eax
+        jb 1f
+        cmpl $BOOL_TAG, %eax
+        jbe 2f
+1:      leaq 16(%rsi), %rdi
+        movl 4(%rsi), %r8d
+        addq %rsi, %r8
+3:      cmpq %r8, %rdi
+        jae 2f
+        call forward
+        addq $8, %rdi
+        jmp 3b
+2:      ret
+
+# Forward the object referred to at %rdi, copying it with its GC tag word if it's among the objects
+# being collected and wasn't copied yet. Keeps %rdi, %rcx, %r8 and %r9.
+forward:
+        movq (%rdi), %rax
+        # Void, constants and other objects aren't among them
+        movq %rax, %rdx
+        subq %r13, %rdx
+        cmpq %r9, %rdx
+        jae 2f
+        cmpl $FORWARDED, (%rax)
+        je 1f
+        pushq %rdi
+        pushq %rcx
+        movq $-1, (%r15)
+        addq $8, %r15
+        movq %rax, %rsi
+        movq %r15, %rdi
+        movl 4(%rax), %ecx
+        rep movsb
+        popq %rcx
+        popq %rdi
+        movl $FORWARDED, (%rax)
+        movq %r15, 8(%rax)
+        movl 4(%rax), %edx
+        addq $7, %rdx
+        andq $-8, %rdx
+        addq %rdx, %r15
+        incq %rbx
+1:      movq 8(%rax), %rax
+        movq %rax, (%rdi)
+2:      ret
+
+# A copy of the prototype object of the class tagged %edi
+        .globl cool_new
+cool_new:
+        pushq %rbx
+        movl %edi, %edi
+        leaq cool_class_protos(%rip), %rax
+        movq (%rax,%rdi,8), %rbx
+        movl 4(%rbx), %edi
+        call cool_alloc
+        movq %rax, %rdi
+        movq %rbx, %rsi
+        movl 4(%rbx), %ecx
+        rep movsb
+        popq %rbx
+        ret
+
+        .globl cool_int
+cool_int:
+        pushq %rdi
+        movl $INT_TAG, %edi
+        call cool_new
+        popq %rdi
+        movl %edi, 16(%rax)
+        ret
+
+        .globl cool_bool
+cool_bool:
+        leaq cool_false(%rip), %rax
+        testl %edi, %edi
+        jz 1f
+        leaq cool_true(%rip), %rax
+1:      ret
+
+# A String of %rdi bytes, which the caller fills in
+new_string:
+        pushq %rbx
+        movq %rdi, %rbx
+        leaq 24(%rdi), %rdi
+        call cool_alloc
+        movq cool_class_protos+8*STRING_TAG(%rip), %rdx
+        movq 8(%rdx), %rcx
+        movq %rcx, 8(%rax)
+        movl $STRING_TAG, (%rax)
+        leal 24(%rbx), %ecx
+        movl %ecx, 4(%rax)
+        movq %rbx, 16(%rax)
+        popq %rbx
+        ret
+
+# Whether the objects %rdi and %rsi are equal: the same object, or Ints, Bools or Strings with the
+# same value
+        .globl cool_equal
+cool_equal:
+        cmpq %rdi, %rsi
+        je .Lequal
+        testq %rdi, %rdi
+        jz .Lnot_equal
+        testq %rsi, %rsi
+        jz .Lnot_equal
+        movl (%rdi), %eax
+        cmpl (%rsi), %eax
+        jne .Lnot_equal
+        cmpl $STRING_TAG, %eax
+        je .Lequal_strings
+        cmpl $INT_TAG, %eax
+        je .Lequal_values
+        cmpl $BOOL_TAG, %eax
+        jne .Lnot_equal
+.Lequal_values:
+        movl 16(%rdi), %eax
+        cmpl 16(%rsi), %eax
+        jne .Lnot_equal
+        jmp .Lequal
+.Lequal_strings:
+        movq 16(%rdi), %rcx
+        cmpq 16(%rsi), %rcx
+        jne .Lnot_equal
+        leaq 24(%rdi), %rdi
+        leaq 24(%rsi), %rsi
+        # Leaves the flags of the comparison of the lengths alone if they're 0
+        repe cmpsb
+        jne .Lnot_equal
+.Lequal:
+        movl $1, %eax
+        ret
+.Lnot_equal:
+        xorl %eax, %eax
+        ret
+
+# %edi / %esi, reporting division by zero at line %ecx of the file named by the String %rdx
+        .globl cool_divide
+cool_divide:
+        testl %esi, %esi
+        jz 2f
+        movl %edi, %eax
+        # Overflows like the reference runtime instead of trapping
+        cmpl $-1, %esi
+        jne 1f
+        negl %eax
+        ret
+1:      cltd
+        idivl %esi
+        ret
+2:      movq %rdx, %rdi
+        movl %ecx, %esi
+        leaq msg_division_by_zero(%rip), %rdx
+        movl $msg_division_by_zero_length, %ecx
+        jmp fail_at
+
+# Report a dispatch to void at line %esi of the file named by the String %rdi
+        .globl cool_dispatch_void
+cool_dispatch_void:
+        leaq msg_dispatch_void(%rip), %rdx
+        movl $msg_dispatch_void_length, %ecx
+        jmp fail_at
+
+        .globl cool_case_void
+cool_case_void:
+        leaq msg_case_void(%rip), %rdx
+        movl $msg_case_void_length, %ecx
+        jmp fail_at
+
+        .globl cool_case_no_match
+cool_case_no_match:
+        leaq msg_no_match(%rip), %rsi
+        movl $msg_no_match_length, %edx
+        jmp fail_class
+
+        .globl Object_abort
+Object_abort:
+        leaq msg_abort(%rip), %rsi
+        movl $msg_abort_length, %edx
+        jmp fail_class
+
+        .globl Object_type_name
+Object_type_name:
+        jmp class_name
+
+        .globl Object_copy
+Object_copy:
+        pushq %rbx
+        movq %rdi, %rbx
+        movl 4(%rdi), %edi
+        call cool_alloc
+        movq %rax, %rdx
+        movq %rbx, %rdi
+        call moved
+        movq %rax, %rsi
+        movq %rdx, %rdi
+        movl 4(%rsi), %ecx
+        rep movsb
+        movq %rdx, %rax
+        popq %rbx
+        ret
+
+        .globl IO_out_string
+IO_out_string:
+        pushq %rdi
+        movq 16(%rsi), %rdx
+        leaq 24(%rsi), %rsi
+        call output
+        popq %rax
+        ret
+
+        .globl IO_out_int
+IO_out_int:
+        pushq %rdi
+        subq $32, %rsp
+        movslq 16(%rsi), %rdi
+        leaq 32(%rsp), %rsi
+        call format_int
+        leaq 32(%rsp), %rdx
+        subq %rax, %rdx
+        movq %rax, %rsi
+        call output
+        addq $32, %rsp
+        popq %rax
+        ret
+
+# Read a line without its line break into memory at %rdi, up to the end of the heap. Returns its
+# length.
+read_line:
+        pushq %rbx
+        pushq %r12
+        movq %rdi, %rbx
+        movq %rdi, %r12
+        call flush
+1:      movq in_start(%rip), %rax
+        cmpq in_end(%rip), %rax
+        jb 2f
+        xorl %edi, %edi
+        leaq in_buffer(%rip), %rsi
+        movl $BUFFER_SIZE, %edx
+        movl $SYS_READ, %eax
+        syscall
+        testq %rax, %rax
+        jle 3f
+        leaq in_buffer(%rip), %rsi
+        movq %rsi, in_start(%rip)
+        addq %rax, %rsi
+        movq %rsi, in_end(%rip)
+        jmp 1b
+2:      movzbl (%rax), %ecx
+        incq %rax
+        movq %rax, in_start(%rip)
+        cmpb $'\n', %cl
+        je 3f
+        cmpq heap_end(%rip), %rbx
+        jae out_of_memory
+        movb %cl, (%rbx)
+        incq %rbx
+        jmp 1b
+3:      movq %rbx, %rax
+        subq %r12, %rax
+        popq %r12
+        popq %rbx
+        ret
+
+# Reads the line right after an empty String at the end of the heap, which then grows to hold it
+        .globl IO_in_string
+IO_in_string:
+        pushq %rbx
+        xorl %edi, %edi
+        call new_string
+        movq %rax, %rbx
+        leaq 24(%rax), %rdi
+        call read_line
+        movq %rax, 16(%rbx)
+        leal 24(%rax), %ecx
+        movl %ecx, 4(%rbx)
+        leaq 24+7(%rbx,%rax), %rdx
+        andq $-8, %rdx
+        movq %rdx, heap_next(%rip)
+        movq %rbx, %rax
+        popq %rbx
+        ret
+
+# Reads the line into the free memory at the end of the heap and parses it like the reference
+# runtime: leading whitespace, an optional sign and digits, ignoring the rest
+        .globl IO_in_int
+IO_in_int:
+        pushq %rbx
+        movq heap_next(%rip), %rbx
+        movq %rbx, %rdi
+        call read_line
+        movq %rbx, %rsi
+        leaq (%rbx,%rax), %rdx
+        xorl %eax, %eax
+        xorl %r8d, %r8d
+1:      cmpq %rdx, %rsi
+        jae 4f
+        movzbl (%rsi), %ecx
+        cmpl $' ', %ecx
+        je 2f
+        subl $'\t', %ecx
+        cmpl $4, %ecx
+        ja 3f
+2:      incq %rsi
+        jmp 1b
+3:      movzbl (%rsi), %ecx
+        cmpl $'+', %ecx
+        je 5f
+        cmpl $'-', %ecx
+        jne 6f
+        movl $1, %r8d
+5:      incq %rsi
+6:      cmpq %rdx, %rsi
+        jae 4f
+        movzbl (%rsi), %ecx
+        subl $'0', %ecx
+        cmpl $9, %ecx
+        ja 4f
+        imull $10, %eax
+        addl %ecx, %eax
+        incq %rsi
+        jmp 6b
+4:      testl %r8d, %r8d
+        jz 7f
+        negl %eax
+7:      movl %eax, %edi
+        popq %rbx
+        jmp cool_int
+
+        .globl String_length
+String_length:
+        movl 16(%rdi), %edi
+        jmp cool_int
+
+        .globl String_concat
+String_concat:
+        pushq %rbx
+        pushq %r12
+        pushq %r13
+        movq %rdi, %rbx
+        movq %rsi, %r12
+        movq 16(%rdi), %rdi
+        addq 16(%rsi), %rdi
+        call new_string
+        movq %rax, %r13
+        movq %rbx, %rdi
+        call moved
+        movq %rax, %rbx
+        movq %r12, %rdi
+        call moved
+        movq %rax, %r12
+        leaq 24(%r13), %rdi
+        leaq 24(%rbx), %rsi
+        movq 16(%rbx), %rcx
+        rep movsb
+        leaq 24(%r12), %rsi
+        movq 16(%r12), %rcx
+        rep movsb
+        movq %r13, %rax
+        popq %r13
+        popq %r12
+        popq %rbx
+        ret
+
+        .globl String_substr
+String_substr:
+        pushq %rbx
+        pushq %r12
+        pushq %r13
+        movq %rdi, %rbx
+        movslq 16(%rsi), %r12
+        movslq 16(%rdx), %r13
+        testq %r12, %r12
+        js 1f
+        testq %r13, %r13
+        js 2f
+        cmpq 16(%rbx), %r12
+        jg 3f
+        leaq (%r12,%r13), %rax
+        cmpq 16(%rbx), %rax
+        jg 4f
+        movq %r13, %rdi
+        call new_string
+        movq %rax, %rdx
+        movq %rbx, %rdi
+        call moved
+        leaq 24(%rax,%r12), %rsi
+        leaq 24(%rdx), %rdi
+        movq %r13, %rcx
+        rep movsb
+        movq %rdx, %rax
+        popq %r13
+        popq %r12
+        popq %rbx
+        ret
+1:      leaq msg_substr_index_negative(%rip), %rsi
+        movl $msg_substr_index_negative_length, %edx
+        jmp fail
+2:      leaq msg_substr_length_negative(%rip), %rsi
+        movl $msg_substr_length_negative_length, %edx
+        jmp fail
+3:      leaq msg_substr_index_too_big(%rip), %rsi
+        movl $msg_substr_index_too_big_length, %edx
+        jmp fail
+4:      leaq msg_substr_length_too_long(%rip), %rsi
+        movl $msg_substr_length_too_long_length, %edx
+        jmp fail
+
+        .section .note.GNU-stack,"",@progbits
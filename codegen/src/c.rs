@@ -0,0 +1,779 @@
+//! Translating a program to C99.
+//!
+//! The output is a single self-contained file: the [runtime](crate::RUNTIME) followed by a struct
+//! for every user defined class, the vtables, prototype objects and class tables, and a function
+//! for every method. Every COOL value is an `Object *`. The program is first lowered to the
+//! [`ir`] of the compiler, every IR value becomes a slot of the frame its function pushes for the
+//! garbage collector and every IR block a label that is jumped to with `goto`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use common::ast::Program;
+use common::{sym, Symbol};
+use ir::function::{
+    ArithOp, BlockId, CompareOp, Constant, FunctionKind, Instruction, InstructionKind, Terminator,
+    VoidCheck,
+};
+use ir::Value;
+use semant::ClassTable;
+
+use crate::layout::{ClassLayout, Layouts, VtableEntry, BOOL_TAG, INT_TAG, STRING_TAG};
+
+/// Emit `program`, which must have been type checked with `table` as its class table.
+pub fn emit(program: &Program, table: &ClassTable) -> String {
+    Emitter::new(table).emit(&ir::lower(program, table))
+}
+
+/// The contents of `bytes` as a C string literal.
+fn c_string(bytes: &[u8]) -> String {
+    let mut result = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                // Escaping `?` avoids trigraphs
+                result.push('\\');
+                result.push(byte as char);
+            }
+            b' '..=b'~' => result.push(byte as char),
+            // Three digits, so a following digit can't be taken as part of the escape
+            _ => write!(result, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+/// The function implementing a method. The runtime names the methods of the basic classes the
+/// same way.
+fn function_name(entry: VtableEntry) -> String {
+    format!("{}_{}", entry.class, entry.method)
+}
+
+/// The C type of objects of a class.
+fn struct_type(layout: &ClassLayout) -> String {
+    match layout.tag {
+        INT_TAG => "Int".to_string(),
+        STRING_TAG => "String".to_string(),
+        BOOL_TAG => "Bool".to_string(),
+        _ if layout.is_basic() => "Object".to_string(),
+        _ => format!("struct cool_{}", layout.name),
+    }
+}
+
+/// The pointer type of a method with `args` formal parameters.
+fn method_type(args: usize) -> String {
+    format!("Object *(*)({})", vec!["Object *"; args + 1].join(", "))
+}
+
+fn signature(name: &str, formals: &[String]) -> String {
+    let params: Vec<_> = std::iter::once("Object *self".to_string())
+        .chain(formals.iter().map(|formal| format!("Object *{}", formal)))
+        .collect();
+
+    format!("Object *{}({})", name, params.join(", "))
+}
+
+struct Emitter {
+    layouts: Layouts,
+    /// Constant objects.
+    constants: String,
+    ints: HashMap<i32, String>,
+    strings: HashMap<Symbol, String>,
+    prototypes: String,
+    functions: String,
+}
+
+impl Emitter {
+    fn new(table: &ClassTable) -> Self {
+        Self {
+            layouts: Layouts::new(table),
+            constants: String::new(),
+            ints: HashMap::new(),
+            strings: HashMap::new(),
+            prototypes: String::new(),
+            functions: String::new(),
+        }
+    }
+
+    fn emit(mut self, module: &ir::Module) -> String {
+        for function in &module.functions {
+            Function::new(&mut self, function).emit(function);
+        }
+        self.main();
+        let zero = self.int_constant(0);
+        let empty = self.string_constant(Symbol::intern(""));
+
+        let mut output = String::new();
+        writeln!(output, "/* Generated by the COOL compiler */\n").unwrap();
+        output.push_str(crate::RUNTIME);
+        output.push('\n');
+        self.types(&mut output);
+        output.push_str(&self.prototypes);
+        output.push('\n');
+        self.vtables(&mut output);
+        output.push_str(&self.constants);
+        output.push('\n');
+        self.objects(&mut output, &zero, &empty);
+        output.push_str(&self.functions);
+
+        output
+    }
+
+    fn types(&self, output: &mut String) {
+        for layout in self
+            .layouts
+            .classes()
+            .iter()
+            .filter(|layout| !layout.is_basic())
+        {
+            writeln!(output, "{} {{\n    Object header;", struct_type(layout)).unwrap();
+            for (name, _) in &layout.attributes {
+                writeln!(output, "    Object *a_{};", name).unwrap();
+            }
+            writeln!(output, "}};\n").unwrap();
+        }
+    }
+
+    fn vtables(&self, output: &mut String) {
+        for layout in self.layouts.classes() {
+            let entries: Vec<_> = layout
+                .vtable
+                .iter()
+                .map(|&entry| format!("(Method){}", function_name(entry)))
+                .collect();
+            writeln!(
+                output,
+                "static const Method {}__vtable[] = {{ {} }};",
+                layout.name,
+                entries.join(", ")
+            )
+            .unwrap();
+        }
+        output.push('\n');
+    }
+
+    /// The prototype objects and class tables, given the default values of `Int`s and `String`s.
+    fn objects(&self, output: &mut String, zero: &str, empty: &str) {
+        let layouts = &self.layouts;
+        for layout in layouts.classes() {
+            let ty = struct_type(layout);
+            let fields = match layout.tag {
+                INT_TAG | BOOL_TAG => ", 0".to_string(),
+                STRING_TAG => ", 0, \"\"".to_string(),
+                _ => layout
+                    .attributes
+                    .iter()
+                    .map(|&(_, ty)| match ty {
+                        sym::INT => format!(", {}", zero),
+                        sym::STRING => format!(", {}", empty),
+                        sym::BOOL => ", &cool_false.header".to_string(),
+                        _ => ", NULL".to_string(),
+                    })
+                    .collect(),
+            };
+            let header = format!(
+                "{}, (int32_t)sizeof({}), {}__vtable",
+                layout.tag, ty, layout.name
+            );
+            if ty == "Object" {
+                writeln!(
+                    output,
+                    "static {} {}__proto = {{ {} }};",
+                    ty, layout.name, header
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    output,
+                    "static {} {}__proto = {{ {{ {} }}{} }};",
+                    ty, layout.name, header, fields
+                )
+                .unwrap();
+            }
+        }
+        for (name, value) in &[("cool_true", 1), ("cool_false", 0)] {
+            writeln!(
+                output,
+                "Bool {} = {{ {{ {}, (int32_t)sizeof(Bool), Bool__vtable }}, {} }};",
+                name, BOOL_TAG, value
+            )
+            .unwrap();
+        }
+        output.push('\n');
+
+        let names: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| c_string(layout.name.as_str().as_bytes()))
+            .collect();
+        let protos: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| format!("(Object *)&{}__proto", layout.name))
+            .collect();
+        let inits: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| format!("{}__init", layout.name))
+            .collect();
+        let parents: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| layout.parent.map_or(-1, |parent| parent as i64).to_string())
+            .collect();
+        writeln!(
+            output,
+            "const char *cool_class_names[] = {{ {} }};",
+            names.join(", ")
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "Object *cool_class_protos[] = {{ {} }};",
+            protos.join(", ")
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "static Object *(*const cool_class_inits[])(Object *) = {{ {} }};",
+            inits.join(", ")
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "static const int32_t cool_class_parents[] = {{ {} }};\n",
+            parents.join(", ")
+        )
+        .unwrap();
+    }
+
+    /// An expression for a constant `Int` object.
+    fn int_constant(&mut self, value: i32) -> String {
+        if let Some(name) = self.ints.get(&value) {
+            return format!("(&{}.header)", name);
+        }
+
+        let name = format!("int_{}", self.ints.len());
+        // INT32_MIN can't be written as a literal
+        let literal = if value == i32::MIN {
+            "INT32_MIN".to_string()
+        } else {
+            value.to_string()
+        };
+        writeln!(
+            self.constants,
+            "static Int {} = {{ {{ {}, (int32_t)sizeof(Int), Int__vtable }}, {} }};",
+            name, INT_TAG, literal
+        )
+        .unwrap();
+        self.ints.insert(value, name.clone());
+
+        format!("(&{}.header)", name)
+    }
+
+    /// An expression for a constant `String` object.
+    fn string_constant(&mut self, value: Symbol) -> String {
+        if let Some(name) = self.strings.get(&value) {
+            return format!("(&{}.header)", name);
+        }
+
+        let name = format!("str_{}", self.strings.len());
+        let bytes = value.as_str().as_bytes();
+        writeln!(
+            self.constants,
+            "static String {} = {{ {{ {}, (int32_t)sizeof(String), String__vtable }}, {}, {} }};",
+            name,
+            STRING_TAG,
+            bytes.len(),
+            c_string(bytes)
+        )
+        .unwrap();
+        self.strings.insert(value, name.clone());
+
+        format!("(&{}.header)", name)
+    }
+
+    fn main(&mut self) {
+        let main = self.layouts.method(sym::MAIN, sym::MAIN_METHOD);
+        writeln!(
+            self.functions,
+            "int main(int argc, char **argv) {{\n    \
+             cool_start(argc, argv);\n    \
+             {}(Main__init(cool_new({})));\n    \
+             fflush(stdout);\n    \
+             return 0;\n\
+             }}",
+            function_name(main),
+            self.layouts.get(sym::MAIN).tag
+        )
+        .unwrap();
+    }
+}
+
+/// Emits one function of the IR.
+///
+/// Every IR value is a slot of the `Frame` of the function, after `self` and the parameters, so the
+/// collector finds the objects the function holds. Constants are expressions for static objects
+/// instead. The phis of a block are assigned on every edge into the block.
+struct Function<'e> {
+    emitter: &'e mut Emitter,
+    class: Symbol,
+    file: String,
+    /// The C expressions of the values that aren't local variables.
+    operands: HashMap<Value, String>,
+    /// The phis assigned on every edge, with the values they're assigned.
+    phis: HashMap<(BlockId, BlockId), Vec<(Value, Value)>>,
+    /// The block being emitted.
+    block: BlockId,
+    body: String,
+    next_temp: usize,
+}
+
+impl<'e> Function<'e> {
+    fn new(emitter: &'e mut Emitter, function: &ir::Function) -> Self {
+        let file_name = emitter.layouts.get(function.class).file_name;
+
+        let mut operands: HashMap<_, _> = std::iter::once((Value::SELF, "self".to_string()))
+            .chain(
+                function
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| (Value::param(index), format!("p_{}", name))),
+            )
+            .collect();
+        let mut phis = HashMap::new();
+        for (id, block) in function.block_ids().zip(&function.blocks) {
+            for instruction in &block.instructions {
+                match (instruction.result, &instruction.kind) {
+                    (Some(result), InstructionKind::Const(constant)) => {
+                        let operand = match constant {
+                            Constant::Int(value) => emitter.int_constant(*value),
+                            Constant::String(value) => emitter.string_constant(*value),
+                            Constant::Bool(true) => "(&cool_true.header)".to_string(),
+                            Constant::Bool(false) => "(&cool_false.header)".to_string(),
+                            Constant::Void => "NULL".to_string(),
+                        };
+                        operands.insert(result, operand);
+                    }
+                    (Some(result), InstructionKind::Phi(incoming)) => {
+                        for &(predecessor, value) in incoming {
+                            phis.entry((predecessor, id))
+                                .or_insert_with(Vec::new)
+                                .push((result, value));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Self {
+            emitter,
+            class: function.class,
+            file: c_string(file_name.as_str().as_bytes()),
+            operands,
+            phis,
+            block: BlockId::ENTRY,
+            body: String::new(),
+            next_temp: 0,
+        }
+    }
+
+    fn emit(mut self, function: &ir::Function) {
+        let name = match function.kind {
+            FunctionKind::Method(method) => format!("{}_{}", function.class, method),
+            FunctionKind::Init => format!("{}__init", function.class),
+        };
+        let params: Vec<_> = function
+            .params
+            .iter()
+            .map(|name| format!("p_{}", name))
+            .collect();
+
+        let locals: Vec<_> = function
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| instruction.result)
+            .filter(|result| !self.operands.contains_key(result))
+            .collect();
+        let roots: Vec<_> = std::iter::once("self".to_string())
+            .chain(params.iter().cloned())
+            .collect();
+        for (index, &local) in locals.iter().enumerate() {
+            self.operands
+                .insert(local, format!("slots[{}]", roots.len() + index));
+        }
+        let count = roots.len() + locals.len();
+        self.statement(format!(
+            "Object *slots[{}] = {{ {} }};",
+            count,
+            roots.join(", ")
+        ));
+        self.statement(format!(
+            "Frame frame = {{ cool_frames, slots, {} }};",
+            count
+        ));
+        self.statement("cool_frames = &frame;".to_string());
+
+        for (id, block) in function.block_ids().zip(&function.blocks) {
+            // Nothing jumps to the entry block
+            if id != BlockId::ENTRY {
+                writeln!(self.body, "{}:", id).unwrap();
+            }
+            self.block = id;
+            for instruction in &block.instructions {
+                self.lower(instruction);
+            }
+            self.terminator(&block.terminator);
+        }
+
+        let signature = signature(&name, &params);
+        writeln!(self.emitter.prototypes, "{};", signature).unwrap();
+        writeln!(
+            self.emitter.functions,
+            "{} {{\n{}}}\n",
+            signature, self.body
+        )
+        .unwrap();
+    }
+
+    /// The C expression holding `value`.
+    fn operand(&self, value: Value) -> String {
+        self.operands[&value].clone()
+    }
+
+    fn statement(&mut self, text: String) {
+        writeln!(self.body, "    {}", text).unwrap();
+    }
+
+    /// Assign the result of `instruction`.
+    fn define(&mut self, instruction: &Instruction, value: String) {
+        let result = self.operand(instruction.result.expect("Instruction without a result"));
+        self.statement(format!("{} = {};", result, value));
+    }
+
+    fn temp(&mut self) -> String {
+        self.next_temp += 1;
+        format!("t{}", self.next_temp)
+    }
+
+    /// The statements that take the edge from the current block to `target`: assigning the phis
+    /// of `target` and jumping to it.
+    fn edge(&mut self, target: BlockId) -> Vec<String> {
+        let copies: Vec<_> = self
+            .phis
+            .get(&(self.block, target))
+            .into_iter()
+            .flatten()
+            .map(|&(result, value)| (self.operand(result), self.operand(value)))
+            .collect();
+
+        let mut statements = vec![];
+        if let [(result, value)] = copies.as_slice() {
+            statements.push(format!("{} = {};", result, value));
+        } else {
+            // The phis may read each other, so read every value before assigning any
+            let temps: Vec<_> = copies.iter().map(|_| self.temp()).collect();
+            for (temp, (_, value)) in temps.iter().zip(&copies) {
+                statements.push(format!("Object *{} = {};", temp, value));
+            }
+            for (temp, (result, _)) in temps.iter().zip(&copies) {
+                statements.push(format!("{} = {};", result, temp));
+            }
+        }
+        statements.push(format!("goto {};", target));
+
+        statements
+    }
+
+    /// Take the edge to `target` if `condition` holds.
+    fn edge_if(&mut self, condition: &str, target: BlockId) {
+        let statements = self.edge(target);
+        match statements.as_slice() {
+            [jump] => self.statement(format!("if ({}) {}", condition, jump)),
+            _ => {
+                self.statement(format!("if ({}) {{", condition));
+                for statement in statements {
+                    self.statement(format!("    {}", statement));
+                }
+                self.statement("}".to_string());
+            }
+        }
+    }
+
+    /// Take the edge to `target`, in a block of its own so the temporaries of the phis are
+    /// declared in a statement.
+    fn jump(&mut self, target: BlockId) {
+        let statements = self.edge(target);
+        match statements.as_slice() {
+            [_] | [_, _] => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            _ => {
+                self.statement("{".to_string());
+                for statement in statements {
+                    self.statement(format!("    {}", statement));
+                }
+                self.statement("}".to_string());
+            }
+        }
+    }
+
+    /// The `int32_t` field of an `Int`.
+    fn int_value(&self, object: Value) -> String {
+        format!("((Int *){})->value", self.operand(object))
+    }
+
+    fn attribute(&self, name: Symbol) -> String {
+        let layout = self.emitter.layouts.get(self.class);
+
+        format!("(({} *)self)->a_{}", struct_type(layout), name)
+    }
+
+    fn call(&self, function: &str, receiver: Value, args: &[Value]) -> String {
+        let args: Vec<_> = std::iter::once(receiver)
+            .chain(args.iter().copied())
+            .map(|arg| self.operand(arg))
+            .collect();
+
+        format!("{}({})", function, args.join(", "))
+    }
+
+    /// Emit `instruction`, except constants and phis.
+    fn lower(&mut self, instruction: &Instruction) {
+        match &instruction.kind {
+            InstructionKind::Const(_) | InstructionKind::Phi(_) => {}
+            InstructionKind::GetAttribute(name) => {
+                let attribute = self.attribute(*name);
+                self.define(instruction, attribute);
+            }
+            InstructionKind::SetAttribute(name, value) => {
+                let attribute = self.attribute(*name);
+                self.statement(format!("{} = {};", attribute, self.operand(*value)));
+            }
+            InstructionKind::Arith { op, lhs, rhs, line } => {
+                let (lhs, rhs) = (self.int_value(*lhs), self.int_value(*rhs));
+                // Arithmetic wraps around like in the reference runtime, which signed overflow
+                // doesn't in C
+                let value = match op {
+                    ArithOp::Add => format!("(int32_t)((uint32_t){} + (uint32_t){})", lhs, rhs),
+                    ArithOp::Sub => format!("(int32_t)((uint32_t){} - (uint32_t){})", lhs, rhs),
+                    ArithOp::Mul => format!("(int32_t)((uint32_t){} * (uint32_t){})", lhs, rhs),
+                    ArithOp::Div => {
+                        format!("cool_divide({}, {}, {}, {})", lhs, rhs, self.file, line)
+                    }
+                };
+                self.define(instruction, format!("cool_int({})", value));
+            }
+            InstructionKind::Compare {
+                op: CompareOp::Eq,
+                lhs,
+                rhs,
+            } => {
+                let value = format!(
+                    "cool_bool(cool_equal({}, {}))",
+                    self.operand(*lhs),
+                    self.operand(*rhs)
+                );
+                self.define(instruction, value);
+            }
+            InstructionKind::Compare { op, lhs, rhs } => {
+                let operator = if *op == CompareOp::Lt { "<" } else { "<=" };
+                let value = format!(
+                    "cool_bool({} {} {})",
+                    self.int_value(*lhs),
+                    operator,
+                    self.int_value(*rhs)
+                );
+                self.define(instruction, value);
+            }
+            InstructionKind::Neg(value) => {
+                let value = format!(
+                    "cool_int((int32_t)(0u - (uint32_t){}))",
+                    self.int_value(*value)
+                );
+                self.define(instruction, value);
+            }
+            InstructionKind::Not(value) => {
+                let value = format!("cool_bool(!((Bool *){})->value)", self.operand(*value));
+                self.define(instruction, value);
+            }
+            InstructionKind::IsVoid(value) => {
+                let value = format!("cool_bool({} == NULL)", self.operand(*value));
+                self.define(instruction, value);
+            }
+            InstructionKind::CheckVoid { value, check, line } => {
+                let object = self.operand(*value);
+                // `self` and constants, the addresses of static objects, are never void
+                if object != "self" && !object.starts_with("(&") {
+                    let handler = match check {
+                        VoidCheck::Dispatch => "cool_dispatch_void",
+                        VoidCheck::Case => "cool_case_void",
+                    };
+                    self.statement(format!(
+                        "if ({} == NULL) {}({}, {});",
+                        object, handler, self.file, line
+                    ));
+                }
+            }
+            InstructionKind::Dispatch {
+                receiver,
+                class,
+                method,
+                args,
+            } => {
+                let slot = self.emitter.layouts.get(*class).slot(*method);
+                let function = format!(
+                    "(({}){}->vtable[{}])",
+                    method_type(args.len()),
+                    self.operand(*receiver),
+                    slot
+                );
+                let call = self.call(&function, *receiver, args);
+                self.define(instruction, call);
+            }
+            InstructionKind::StaticDispatch {
+                receiver,
+                class,
+                method,
+                args,
+            } => {
+                let entry = self.emitter.layouts.method(*class, *method);
+                let call = self.call(&function_name(entry), *receiver, args);
+                self.define(instruction, call);
+            }
+            InstructionKind::New(class) if *class == sym::SELF_TYPE => {
+                self.define(
+                    instruction,
+                    "cool_class_inits[self->tag](cool_new(self->tag))".to_string(),
+                );
+            }
+            InstructionKind::New(class) => {
+                let tag = self.emitter.layouts.get(*class).tag;
+                self.define(instruction, format!("{}__init(cool_new({}))", class, tag));
+            }
+            InstructionKind::Init(class) => {
+                self.statement(format!("{}__init(self);", class));
+            }
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match terminator {
+            Terminator::Jump(target) => self.jump(*target),
+            Terminator::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                let condition = format!("((Bool *){})->value", self.operand(*condition));
+                self.edge_if(&condition, *then_block);
+                self.jump(*else_block);
+            }
+            Terminator::Case { value, branches } => self.case(*value, branches),
+            Terminator::Return(value) => self.ret(&self.operand(*value)),
+        }
+    }
+
+    /// Emit a `case`, which walks up the class hierarchy from the class of the value until it
+    /// finds a class one of the branches matches.
+    fn case(&mut self, value: Value, branches: &[(Symbol, BlockId)]) {
+        let tags: Vec<_> = branches
+            .iter()
+            .map(|&(class, _)| self.emitter.layouts.get(class).tag)
+            .collect();
+        let tag = self.temp();
+        let object = self.operand(value);
+        self.statement(format!("int32_t {} = {}->tag;", tag, object));
+        let conditions: Vec<_> = tags
+            .iter()
+            .map(|branch_tag| format!(" && {} != {}", tag, branch_tag))
+            .collect();
+        self.statement(format!(
+            "while ({} >= 0{}) {} = cool_class_parents[{}];",
+            tag,
+            conditions.concat(),
+            tag,
+            tag
+        ));
+
+        for (&(_, block), branch_tag) in branches.iter().zip(tags) {
+            self.edge_if(&format!("{} == {}", tag, branch_tag), block);
+        }
+        self.statement(format!("cool_case_no_match({});", object));
+        self.ret("NULL");
+    }
+
+    /// Pop the frame of the function and return `value`.
+    fn ret(&mut self, value: &str) {
+        self.statement("cool_frames = frame.previous;".to_string());
+        self.statement(format!("return {};", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn emit_program(input: &str) -> String {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        emit(&program, &table)
+    }
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(c_string(b"a \"b\"??\n\\"), "\"a \\\"b\\\"\\?\\?\\012\\\\\"");
+    }
+
+    #[test]
+    fn test_emit() {
+        let c = emit_program(
+            "class A { a : Int; f() : Int { a }; };\n\
+             class Main inherits A { b : String <- \"b\"; f() : Int { 1 }; main() : Object { f() }; };\n",
+        );
+
+        assert!(c.contains(
+            "struct cool_Main {\n    Object header;\n    Object *a_a;\n    Object *a_b;\n};"
+        ));
+        assert!(c.contains(
+            "static const Method Main__vtable[] = { (Method)Object_abort, (Method)Object_type_name, \
+             (Method)Object_copy, (Method)Main_f, (Method)Main_main };"
+        ));
+        assert!(c.contains(
+            "Object *Main__init(Object *self) {\n    \
+             Object *slots[1] = { self };\n    \
+             Frame frame = { cool_frames, slots, 1 };\n    \
+             cool_frames = &frame;\n    \
+             A__init(self);"
+        ));
+        assert!(c.contains("static const int32_t cool_class_parents[] = { -1, 0, 0, 0, 0, 0, 5 };"));
+        assert!(c.contains("int main(int argc, char **argv)"));
+    }
+
+    #[test]
+    fn test_method_names_are_apart_from_tables() {
+        let c = emit_program(
+            "class Main { vtable() : Int { 1 }; proto() : Int { 2 }; main() : Object { 0 }; };\n",
+        );
+
+        assert!(c.contains("Object *Main_vtable(Object *self)"));
+        assert!(c.contains("Object *Main_proto(Object *self)"));
+        assert!(c.contains("static const Method Main__vtable[]"));
+        assert!(c.contains("Main__proto = {"));
+        assert!(!c.contains("Main_vtable[]"));
+    }
+}
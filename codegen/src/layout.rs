@@ -0,0 +1,150 @@
+//! The runtime layout of classes shared by the native backends.
+//!
+//! Every class gets a tag, its position in the class table, so the basic classes always have the
+//! same tags. An object starts with a header of its tag, its size and its vtable, followed by its
+//! attributes, inherited attributes first.
+
+use std::collections::HashMap;
+
+use common::{sym, Symbol};
+use semant::ClassTable;
+
+pub const OBJECT_TAG: u32 = 0;
+pub const IO_TAG: u32 = 1;
+pub const INT_TAG: u32 = 2;
+pub const STRING_TAG: u32 = 3;
+pub const BOOL_TAG: u32 = 4;
+
+/// A method in a vtable: the class that defines it and its name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VtableEntry {
+    pub class: Symbol,
+    pub method: Symbol,
+    /// The number of formal parameters.
+    pub args: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassLayout {
+    pub name: Symbol,
+    pub tag: u32,
+    pub parent: Option<u32>,
+    pub file_name: Symbol,
+    /// The attributes of objects of the class and their declared types, inherited attributes
+    /// first.
+    pub attributes: Vec<(Symbol, Symbol)>,
+    /// The number of attributes the class declares itself, the last ones of `attributes`.
+    pub own_attributes: usize,
+    /// The methods of the class, inherited methods first. An overriding method takes the slot of
+    /// the method it overrides.
+    pub vtable: Vec<VtableEntry>,
+    slots: HashMap<Symbol, usize>,
+}
+
+impl ClassLayout {
+    pub fn is_basic(&self) -> bool {
+        self.tag <= BOOL_TAG
+    }
+
+    /// The vtable slot of `method`.
+    pub fn slot(&self, method: Symbol) -> usize {
+        self.slots[&method]
+    }
+
+    /// The index of the attribute `name` in `attributes`.
+    pub fn attribute(&self, name: Symbol) -> Option<usize> {
+        self.attributes
+            .iter()
+            .position(|&(attribute, _)| attribute == name)
+    }
+}
+
+/// The layouts of all classes of a program, indexed by tag.
+#[derive(Debug, Clone)]
+pub struct Layouts {
+    classes: Vec<ClassLayout>,
+    tags: HashMap<Symbol, u32>,
+}
+
+impl Layouts {
+    pub fn new(table: &ClassTable) -> Self {
+        let tags: HashMap<_, _> = table
+            .classes()
+            .enumerate()
+            .map(|(tag, info)| (info.name, tag as u32))
+            .collect();
+
+        let classes = table
+            .classes()
+            .map(|info| {
+                let mut ancestors: Vec<_> = table.ancestors(info.name).collect();
+                ancestors.reverse();
+
+                let mut layout = ClassLayout {
+                    name: info.name,
+                    tag: tags[&info.name],
+                    parent: info.parent.map(|parent| tags[&parent]),
+                    file_name: info.file_name,
+                    attributes: vec![],
+                    own_attributes: info.attributes.len(),
+                    vtable: vec![],
+                    slots: HashMap::new(),
+                };
+                for ancestor in ancestors {
+                    for attribute in &ancestor.attributes {
+                        layout
+                            .attributes
+                            .push((attribute.name, attribute.type_decl));
+                    }
+                    for method in &ancestor.methods {
+                        let entry = VtableEntry {
+                            class: ancestor.name,
+                            method: method.name,
+                            args: method.formals.len(),
+                        };
+                        match layout.slots.get(&method.name) {
+                            Some(&slot) => layout.vtable[slot] = entry,
+                            None => {
+                                layout.slots.insert(method.name, layout.vtable.len());
+                                layout.vtable.push(entry);
+                            }
+                        }
+                    }
+                }
+
+                layout
+            })
+            .collect();
+
+        Self { classes, tags }
+    }
+
+    /// All classes, in order of their tags.
+    pub fn classes(&self) -> &[ClassLayout] {
+        &self.classes
+    }
+
+    pub fn get(&self, class: Symbol) -> &ClassLayout {
+        &self.classes[self.tags[&class] as usize]
+    }
+
+    pub fn by_tag(&self, tag: u32) -> &ClassLayout {
+        &self.classes[tag as usize]
+    }
+
+    /// The layout of the static type `ty` in the class `current`.
+    pub fn of_type(&self, ty: Symbol, current: Symbol) -> &ClassLayout {
+        if ty == sym::SELF_TYPE {
+            self.get(current)
+        } else {
+            self.get(ty)
+        }
+    }
+
+    /// The entry of the method `name` that a static dispatch to `class` calls.
+    pub fn method(&self, class: Symbol, name: Symbol) -> VtableEntry {
+        let layout = self.get(class);
+
+        layout.vtable[layout.slot(name)]
+    }
+}
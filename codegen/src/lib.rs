@@ -0,0 +1,27 @@
+//! Native code generation for COOL.
+//!
+//! The backends share the object layout computed in [`layout`] and the C runtime in [`RUNTIME`].
+//! [`llvm::emit`] lowers a type checked program to the SSA IR of the `ir` crate and turns that into
+//! textual LLVM IR, to be linked with the runtime, and [`c::emit`] turns the program into C99 that
+//! includes the runtime. [`wasm::emit`] turns it into a WebAssembly text module instead, with a
+//! runtime of its own. [`x86_64::emit`] turns it into assembly for x86-64 Linux, to be linked with
+//! a runtime in assembly that has a copying garbage collector into an executable that needs no C
+//! library.
+
+pub mod c;
+pub mod layout;
+pub mod llvm;
+pub mod wasm;
+pub mod x86_64;
+
+/// The C runtime compiled programs are linked with.
+///
+/// Its mark-sweep garbage collector works like the one of the bytecode VM and finds the objects a
+/// program holds in the frames the C and LLVM backends push. Executables linked with it take the
+/// `--gc-stats` and `--gc-stress` options of the x86-64 runtime.
+pub const RUNTIME: &str = include_str!("../runtime/runtime.c");
+
+pub mod prelude {
+    pub use crate::layout::Layouts;
+    pub use crate::{c, llvm, wasm, x86_64, RUNTIME};
+}
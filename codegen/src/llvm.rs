@@ -0,0 +1,933 @@
+//! Emitting a program as textual LLVM IR.
+//!
+//! The program is first lowered to the [`ir`] of the compiler, whose SSA values, blocks and phis
+//! map directly onto LLVM's. Every COOL value is a pointer to an object, `Int`s, `Bool`s and `String`s included, so the IR
+//! only needs opaque pointers and `i32`s. Objects are structs laid out as described in
+//! [`layout`](crate::layout), methods are called through the vtable each object points to and
+//! `new` copies the class' prototype object before running its initializer. The methods of the
+//! basic classes, allocation, garbage collection and runtime errors are implemented by the C
+//! runtime in [`RUNTIME`](crate::RUNTIME). Every function stores the objects it holds in the slots
+//! of a frame it pushes onto the runtime's `cool_frames`, where the collector finds them.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use common::ast::Program;
+use common::{sym, Symbol};
+use ir::function::{
+    ArithOp, BlockId, CompareOp, Constant, Instruction, InstructionKind, Terminator, VoidCheck,
+};
+use ir::Value;
+use semant::ClassTable;
+
+use crate::layout::{Layouts, VtableEntry, BOOL_TAG, INT_TAG, STRING_TAG};
+
+/// The index of the first attribute in the struct of an object, after the header.
+const FIRST_ATTRIBUTE: usize = 3;
+
+/// The type of the `Frame` struct of the runtime: the previous frame, the slots and their number.
+const FRAME_TYPE: &str = "{ ptr, ptr, i32 }";
+
+/// Emit `program`, which must have been type checked with `table` as its class table.
+pub fn emit(program: &Program, table: &ClassTable) -> String {
+    Emitter::new(table).emit(&ir::lower(program, table))
+}
+
+/// The contents of `bytes` as an LLVM string constant, including a terminating null byte.
+fn c_string(bytes: &[u8]) -> String {
+    let mut result = String::from("c\"");
+    for &byte in bytes.iter().chain(&[0]) {
+        if byte.is_ascii_graphic() && byte != b'"' && byte != b'\\' || byte == b' ' {
+            result.push(byte as char);
+        } else {
+            write!(result, "\\{:02X}", byte).unwrap();
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+/// The size of an object of the struct type `ty`, as a constant expression.
+fn size_of(ty: &str) -> String {
+    format!(
+        "ptrtoint (ptr getelementptr ({}, ptr null, i32 1) to i32)",
+        ty
+    )
+}
+
+/// The label of the LLVM block an IR block starts with.
+fn block_label(block: BlockId) -> String {
+    block.to_string()
+}
+
+/// The function implementing a method.
+///
+/// The methods of the basic classes are implemented by the runtime, named like C functions.
+fn function_name(layouts: &Layouts, entry: VtableEntry) -> String {
+    if layouts.get(entry.class).is_basic() {
+        format!("@{}_{}", entry.class, entry.method)
+    } else {
+        format!("@{}.{}", entry.class, entry.method)
+    }
+}
+
+struct Emitter {
+    layouts: Layouts,
+    /// Constant objects, string contents and file names.
+    constants: String,
+    ints: HashMap<i32, String>,
+    strings: HashMap<Symbol, String>,
+    files: HashMap<Symbol, String>,
+    functions: String,
+}
+
+impl Emitter {
+    fn new(table: &ClassTable) -> Self {
+        Self {
+            layouts: Layouts::new(table),
+            constants: String::new(),
+            ints: HashMap::new(),
+            strings: HashMap::new(),
+            files: HashMap::new(),
+            functions: String::new(),
+        }
+    }
+
+    fn emit(mut self, module: &ir::Module) -> String {
+        let mut output = String::new();
+        writeln!(output, "; Generated by the COOL compiler\n").unwrap();
+        self.types(&mut output);
+        self.declarations(&mut output);
+
+        for function in &module.functions {
+            Function::new(&mut self, function).emit(function);
+        }
+        self.main();
+
+        self.tables(&mut output);
+        output.push_str(&self.constants);
+        output.push('\n');
+        output.push_str(&self.functions);
+
+        output
+    }
+
+    fn types(&self, output: &mut String) {
+        for layout in self.layouts.classes() {
+            let fields = match layout.tag {
+                INT_TAG | BOOL_TAG => ", i32".to_string(),
+                STRING_TAG => ", i32, ptr".to_string(),
+                _ => ", ptr".repeat(layout.attributes.len()),
+            };
+            writeln!(
+                output,
+                "%{} = type {{ i32, i32, ptr{} }}",
+                layout.name, fields
+            )
+            .unwrap();
+        }
+        output.push('\n');
+    }
+
+    fn declarations(&self, output: &mut String) {
+        let runtime = [
+            "declare void @cool_start(i32, ptr)",
+            "declare ptr @cool_new(i32)",
+            "declare ptr @cool_int(i32)",
+            "declare ptr @cool_bool(i32)",
+            "declare i32 @cool_equal(ptr, ptr)",
+            "declare i32 @cool_divide(i32, i32, ptr, i32)",
+            "declare void @cool_dispatch_void(ptr, i32) noreturn",
+            "declare void @cool_case_void(ptr, i32) noreturn",
+            "declare void @cool_case_no_match(ptr) noreturn",
+            "declare void @llvm.memset.p0.i64(ptr, i8, i64, i1)",
+            "@cool_frames = external global ptr",
+        ];
+        for declaration in &runtime {
+            writeln!(output, "{}", declaration).unwrap();
+        }
+
+        for layout in self
+            .layouts
+            .classes()
+            .iter()
+            .filter(|layout| layout.is_basic())
+        {
+            for entry in layout
+                .vtable
+                .iter()
+                .filter(|entry| entry.class == layout.name)
+            {
+                let params = vec!["ptr"; entry.args + 1].join(", ");
+                writeln!(
+                    output,
+                    "declare ptr {}({})",
+                    function_name(&self.layouts, *entry),
+                    params
+                )
+                .unwrap();
+            }
+        }
+        output.push('\n');
+    }
+
+    /// The class tables, vtables and prototype objects.
+    fn tables(&mut self, output: &mut String) {
+        let count = self.layouts.classes().len();
+        let layouts = self.layouts.clone();
+        let zero = self.int_constant(0);
+        let empty = self.string_constant(Symbol::intern(""));
+
+        for layout in layouts.classes() {
+            let entries: Vec<_> = layout
+                .vtable
+                .iter()
+                .map(|&entry| format!("ptr {}", function_name(&layouts, entry)))
+                .collect();
+            writeln!(
+                output,
+                "@{}._vtable = constant [{} x ptr] [{}]",
+                layout.name,
+                entries.len(),
+                entries.join(", ")
+            )
+            .unwrap();
+
+            let ty = format!("%{}", layout.name);
+            let fields = match layout.tag {
+                INT_TAG | BOOL_TAG => ", i32 0".to_string(),
+                STRING_TAG => ", i32 0, ptr @str.empty".to_string(),
+                _ => layout
+                    .attributes
+                    .iter()
+                    .map(|&(_, ty)| match ty {
+                        sym::INT => format!(", ptr {}", zero),
+                        sym::STRING => format!(", ptr {}", empty),
+                        sym::BOOL => ", ptr @cool_false".to_string(),
+                        _ => ", ptr null".to_string(),
+                    })
+                    .collect(),
+            };
+            writeln!(
+                output,
+                "@{}._proto = global {} {{ i32 {}, i32 {}, ptr @{}._vtable{} }}",
+                layout.name,
+                ty,
+                layout.tag,
+                size_of(&ty),
+                layout.name,
+                fields
+            )
+            .unwrap();
+
+            writeln!(
+                output,
+                "@{}._name = private constant [{} x i8] {}",
+                layout.name,
+                layout.name.as_str().len() + 1,
+                c_string(layout.name.as_str().as_bytes())
+            )
+            .unwrap();
+        }
+        writeln!(output, "@str.empty = private constant [1 x i8] c\"\\00\"").unwrap();
+        output.push('\n');
+
+        let table = |suffix: &str| -> String {
+            layouts
+                .classes()
+                .iter()
+                .map(|layout| format!("ptr @{}.{}", layout.name, suffix))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let parents: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| format!("i32 {}", layout.parent.map_or(-1, |parent| parent as i64)))
+            .collect();
+        writeln!(
+            output,
+            "@cool_class_names = constant [{} x ptr] [{}]",
+            count,
+            table("_name")
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "@cool_class_protos = constant [{} x ptr] [{}]",
+            count,
+            table("_proto")
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "@cool_class_inits = constant [{} x ptr] [{}]",
+            count,
+            table("_init")
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "@cool_class_parents = constant [{} x i32] [{}]",
+            count,
+            parents.join(", ")
+        )
+        .unwrap();
+
+        for (name, value) in &[("cool_true", 1), ("cool_false", 0)] {
+            writeln!(
+                output,
+                "@{} = global %Bool {{ i32 {}, i32 {}, ptr @Bool._vtable, i32 {} }}",
+                name,
+                BOOL_TAG,
+                size_of("%Bool"),
+                value
+            )
+            .unwrap();
+        }
+        output.push('\n');
+    }
+
+    fn int_constant(&mut self, value: i32) -> String {
+        if let Some(name) = self.ints.get(&value) {
+            return name.clone();
+        }
+
+        let name = format!("@int.{}", self.ints.len());
+        writeln!(
+            self.constants,
+            "{} = private constant %Int {{ i32 {}, i32 {}, ptr @Int._vtable, i32 {} }}",
+            name,
+            INT_TAG,
+            size_of("%Int"),
+            value
+        )
+        .unwrap();
+        self.ints.insert(value, name.clone());
+
+        name
+    }
+
+    fn string_constant(&mut self, value: Symbol) -> String {
+        if let Some(name) = self.strings.get(&value) {
+            return name.clone();
+        }
+
+        let name = format!("@str.{}", self.strings.len());
+        let bytes = value.as_str().as_bytes();
+        writeln!(
+            self.constants,
+            "{}.chars = private constant [{} x i8] {}",
+            name,
+            bytes.len() + 1,
+            c_string(bytes)
+        )
+        .unwrap();
+        writeln!(
+            self.constants,
+            "{} = private constant %String {{ i32 {}, i32 {}, ptr @String._vtable, i32 {}, ptr {}.chars }}",
+            name,
+            STRING_TAG,
+            size_of("%String"),
+            bytes.len(),
+            name
+        )
+        .unwrap();
+        self.strings.insert(value, name.clone());
+
+        name
+    }
+
+    /// The name of a global holding `file_name` as a C string, for runtime errors.
+    fn file_constant(&mut self, file_name: Symbol) -> String {
+        if let Some(name) = self.files.get(&file_name) {
+            return name.clone();
+        }
+
+        let name = format!("@file.{}", self.files.len());
+        let bytes = file_name.as_str().as_bytes();
+        writeln!(
+            self.constants,
+            "{} = private constant [{} x i8] {}",
+            name,
+            bytes.len() + 1,
+            c_string(bytes)
+        )
+        .unwrap();
+        self.files.insert(file_name, name.clone());
+
+        name
+    }
+
+    fn main(&mut self) {
+        let main = self.layouts.method(sym::MAIN, sym::MAIN_METHOD);
+        writeln!(
+            self.functions,
+            "define i32 @main(i32 %argc, ptr %argv) {{\n\
+             entry:\n  \
+             call void @cool_start(i32 %argc, ptr %argv)\n  \
+             %object = call ptr @cool_new(i32 {})\n  \
+             %main = call ptr @Main._init(ptr %object)\n  \
+             call ptr {}(ptr %main)\n  \
+             ret i32 0\n\
+             }}",
+            self.layouts.get(sym::MAIN).tag,
+            function_name(&self.layouts, main)
+        )
+        .unwrap();
+    }
+}
+
+/// Emits one function of the IR.
+///
+/// Every IR value is named after its number, except constants, which are globals. Void checks
+/// and `case` split an IR block into several LLVM blocks, so the phis of a block are only emitted
+/// once the LLVM blocks all IR blocks end in are known.
+///
+/// `self`, the parameters and every value are also stored in a slot of the frame of the function
+/// when they're defined, so the collector finds them. Values are only ever used while they're the
+/// last one stored in their slot.
+struct Function<'e> {
+    emitter: &'e mut Emitter,
+    class: Symbol,
+    file: String,
+    /// The globals holding the values of constants.
+    constants: HashMap<Value, String>,
+    body: String,
+    next_temp: usize,
+    next_label: usize,
+    /// The label of the block being emitted.
+    block: String,
+    /// The number of slots of the frame.
+    slots: usize,
+}
+
+impl<'e> Function<'e> {
+    fn new(emitter: &'e mut Emitter, function: &ir::Function) -> Self {
+        let file_name = emitter.layouts.get(function.class).file_name;
+        let file = emitter.file_constant(file_name);
+
+        let mut constants = HashMap::new();
+        for block in &function.blocks {
+            for instruction in &block.instructions {
+                if let (Some(result), InstructionKind::Const(constant)) =
+                    (instruction.result, &instruction.kind)
+                {
+                    let global = match constant {
+                        Constant::Int(value) => emitter.int_constant(*value),
+                        Constant::String(value) => emitter.string_constant(*value),
+                        Constant::Bool(true) => "@cool_true".to_string(),
+                        Constant::Bool(false) => "@cool_false".to_string(),
+                        Constant::Void => "null".to_string(),
+                    };
+                    constants.insert(result, global);
+                }
+            }
+        }
+
+        Self {
+            emitter,
+            class: function.class,
+            file,
+            constants,
+            body: String::new(),
+            next_temp: 0,
+            next_label: 0,
+            block: String::new(),
+            slots: 0,
+        }
+    }
+
+    fn emit(mut self, function: &ir::Function) {
+        let params: Vec<_> = std::iter::once(Value::SELF)
+            .chain((0..function.params.len()).map(Value::param))
+            .map(|param| self.operand(param))
+            .collect();
+        for param in &params {
+            self.root(param);
+        }
+        let roots = std::mem::take(&mut self.body);
+
+        let mut ends = vec![];
+        let mut bodies = vec![];
+        for id in function.block_ids() {
+            self.block = block_label(id);
+            let block = function.block(id);
+            // The phis come first in the block, they're stored after all of them
+            for instruction in &block.instructions {
+                if let InstructionKind::Phi(_) = instruction.kind {
+                    self.root(&self.result(instruction));
+                }
+            }
+            for instruction in &block.instructions {
+                self.lower(instruction);
+            }
+            self.terminator(&block.terminator);
+
+            ends.push(self.block.clone());
+            bodies.push(std::mem::take(&mut self.body));
+        }
+        self.push_frame();
+        bodies[BlockId::ENTRY.0 as usize].insert_str(0, &(std::mem::take(&mut self.body) + &roots));
+
+        let params: Vec<_> = params
+            .iter()
+            .map(|param| format!("ptr {}", param))
+            .collect();
+        writeln!(
+            self.emitter.functions,
+            "define ptr @{}.{}({}) {{",
+            function.class,
+            function.kind,
+            params.join(", ")
+        )
+        .unwrap();
+        for ((id, block), body) in function.block_ids().zip(&function.blocks).zip(bodies) {
+            writeln!(self.emitter.functions, "{}:", block_label(id)).unwrap();
+            for instruction in &block.instructions {
+                if let InstructionKind::Phi(incoming) = &instruction.kind {
+                    let incoming: Vec<_> = incoming
+                        .iter()
+                        .map(|&(block, value)| {
+                            format!("[ {}, %{} ]", self.operand(value), ends[block.0 as usize])
+                        })
+                        .collect();
+                    writeln!(
+                        self.emitter.functions,
+                        "  {} = phi ptr {}",
+                        self.result(instruction),
+                        incoming.join(", ")
+                    )
+                    .unwrap();
+                }
+            }
+            self.emitter.functions.push_str(&body);
+        }
+        writeln!(self.emitter.functions, "}}\n").unwrap();
+    }
+
+    /// The operand holding `value`.
+    fn operand(&self, value: Value) -> String {
+        match self.constants.get(&value) {
+            Some(global) => global.clone(),
+            None if value == Value::SELF => "%self".to_string(),
+            None => format!("%v{}", value.0),
+        }
+    }
+
+    fn result(&self, instruction: &Instruction) -> String {
+        self.operand(instruction.result.expect("Instruction without a result"))
+    }
+
+    fn temp(&mut self) -> String {
+        self.next_temp += 1;
+        format!("%t{}", self.next_temp)
+    }
+
+    fn label(&mut self, prefix: &str) -> String {
+        self.next_label += 1;
+        format!("{}{}", prefix, self.next_label)
+    }
+
+    fn instruction(&mut self, text: String) {
+        writeln!(self.body, "  {}", text).unwrap();
+    }
+
+    /// Emit an instruction with a result and return the result.
+    fn value(&mut self, text: String) -> String {
+        let temp = self.temp();
+        self.instruction(format!("{} = {}", temp, text));
+
+        temp
+    }
+
+    /// Emit the instruction defining the result of `instruction`.
+    fn define(&mut self, instruction: &Instruction, text: String) {
+        let result = self.result(instruction);
+        self.instruction(format!("{} = {}", result, text));
+        self.root(&result);
+    }
+
+    /// Store `object` in the next slot of the frame.
+    fn root(&mut self, object: &str) {
+        let address = self.value(format!("getelementptr ptr, ptr %slots, i32 {}", self.slots));
+        self.instruction(format!("store ptr {}, ptr {}", object, address));
+        self.slots += 1;
+    }
+
+    /// Allocate the frame with all slots null and make it the innermost one.
+    fn push_frame(&mut self) {
+        self.instruction(format!("%slots = alloca ptr, i32 {}", self.slots));
+        self.instruction(format!(
+            "call void @llvm.memset.p0.i64(ptr %slots, i8 0, i64 {}, i1 false)",
+            self.slots * 8
+        ));
+        self.instruction(format!("%frame = alloca {}", FRAME_TYPE));
+        self.instruction("%previous = load ptr, ptr @cool_frames".to_string());
+        self.instruction("store ptr %previous, ptr %frame".to_string());
+        let address = self.value(format!(
+            "getelementptr {}, ptr %frame, i32 0, i32 1",
+            FRAME_TYPE
+        ));
+        self.instruction(format!("store ptr %slots, ptr {}", address));
+        let address = self.value(format!(
+            "getelementptr {}, ptr %frame, i32 0, i32 2",
+            FRAME_TYPE
+        ));
+        self.instruction(format!("store i32 {}, ptr {}", self.slots, address));
+        self.instruction("store ptr %frame, ptr @cool_frames".to_string());
+    }
+
+    fn start_block(&mut self, label: String) {
+        writeln!(self.body, "{}:", label).unwrap();
+        self.block = label;
+    }
+
+    fn attribute_address(&mut self, name: Symbol) -> String {
+        let index = self
+            .emitter
+            .layouts
+            .get(self.class)
+            .attribute(name)
+            .expect("Unknown attribute");
+
+        self.value(format!(
+            "getelementptr %{}, ptr %self, i32 0, i32 {}",
+            self.class,
+            FIRST_ATTRIBUTE + index
+        ))
+    }
+
+    /// The `i32` field of an `Int` or `Bool` object.
+    fn unbox(&mut self, ty: &str, object: Value) -> String {
+        let address = self.value(format!(
+            "getelementptr %{}, ptr {}, i32 0, i32 {}",
+            ty,
+            self.operand(object),
+            FIRST_ATTRIBUTE
+        ));
+
+        self.value(format!("load i32, ptr {}", address))
+    }
+
+    /// A `Bool` as an `i1`.
+    fn condition(&mut self, object: Value) -> String {
+        let value = self.unbox("Bool", object);
+
+        self.value(format!("icmp ne i32 {}, 0", value))
+    }
+
+    /// Call `handler` with the location of `line` if `object` is void.
+    fn check_void(&mut self, object: Value, handler: &str, line: usize) {
+        let is_void = self.value(format!("icmp eq ptr {}, null", self.operand(object)));
+        let void = self.label("void");
+        let ok = self.label("ok");
+        self.instruction(format!("br i1 {}, label %{}, label %{}", is_void, void, ok));
+        self.start_block(void);
+        self.instruction(format!(
+            "call void {}(ptr {}, i32 {})",
+            handler, self.file, line
+        ));
+        self.instruction("unreachable".to_string());
+        self.start_block(ok);
+    }
+
+    fn tag(&mut self, object: &str) -> String {
+        let address = self.value(format!(
+            "getelementptr %Object, ptr {}, i32 0, i32 0",
+            object
+        ));
+
+        self.value(format!("load i32, ptr {}", address))
+    }
+
+    fn call(&mut self, function: &str, receiver: Value, args: &[Value]) -> String {
+        let args: Vec<_> = std::iter::once(receiver)
+            .chain(args.iter().copied())
+            .map(|arg| format!("ptr {}", self.operand(arg)))
+            .collect();
+
+        format!("call ptr {}({})", function, args.join(", "))
+    }
+
+    /// Emit `instruction`, except phis.
+    fn lower(&mut self, instruction: &Instruction) {
+        match &instruction.kind {
+            InstructionKind::Const(_) | InstructionKind::Phi(_) => {}
+            InstructionKind::GetAttribute(name) => {
+                let address = self.attribute_address(*name);
+                self.define(instruction, format!("load ptr, ptr {}", address));
+            }
+            InstructionKind::SetAttribute(name, value) => {
+                let address = self.attribute_address(*name);
+                self.instruction(format!(
+                    "store ptr {}, ptr {}",
+                    self.operand(*value),
+                    address
+                ));
+            }
+            InstructionKind::Arith { op, lhs, rhs, line } => {
+                let (lhs, rhs) = (self.unbox("Int", *lhs), self.unbox("Int", *rhs));
+                let result = match op {
+                    ArithOp::Add => self.value(format!("add i32 {}, {}", lhs, rhs)),
+                    ArithOp::Sub => self.value(format!("sub i32 {}, {}", lhs, rhs)),
+                    ArithOp::Mul => self.value(format!("mul i32 {}, {}", lhs, rhs)),
+                    ArithOp::Div => self.value(format!(
+                        "call i32 @cool_divide(i32 {}, i32 {}, ptr {}, i32 {})",
+                        lhs, rhs, self.file, line
+                    )),
+                };
+                self.define(instruction, format!("call ptr @cool_int(i32 {})", result));
+            }
+            InstructionKind::Compare {
+                op: CompareOp::Eq,
+                lhs,
+                rhs,
+            } => {
+                let equal = self.value(format!(
+                    "call i32 @cool_equal(ptr {}, ptr {})",
+                    self.operand(*lhs),
+                    self.operand(*rhs)
+                ));
+                self.define(instruction, format!("call ptr @cool_bool(i32 {})", equal));
+            }
+            InstructionKind::Compare { op, lhs, rhs } => {
+                let (lhs, rhs) = (self.unbox("Int", *lhs), self.unbox("Int", *rhs));
+                let predicate = if *op == CompareOp::Lt { "slt" } else { "sle" };
+                let compared = self.value(format!("icmp {} i32 {}, {}", predicate, lhs, rhs));
+                let value = self.value(format!("zext i1 {} to i32", compared));
+                self.define(instruction, format!("call ptr @cool_bool(i32 {})", value));
+            }
+            InstructionKind::Neg(value) => {
+                let value = self.unbox("Int", *value);
+                let negated = self.value(format!("sub i32 0, {}", value));
+                self.define(instruction, format!("call ptr @cool_int(i32 {})", negated));
+            }
+            InstructionKind::Not(value) => {
+                let value = self.unbox("Bool", *value);
+                let negated = self.value(format!("xor i32 {}, 1", value));
+                self.define(instruction, format!("call ptr @cool_bool(i32 {})", negated));
+            }
+            InstructionKind::IsVoid(value) => {
+                let is_void = self.value(format!("icmp eq ptr {}, null", self.operand(*value)));
+                let value = self.value(format!("zext i1 {} to i32", is_void));
+                self.define(instruction, format!("call ptr @cool_bool(i32 {})", value));
+            }
+            InstructionKind::CheckVoid { value, check, line } => {
+                let handler = match check {
+                    VoidCheck::Dispatch => "@cool_dispatch_void",
+                    VoidCheck::Case => "@cool_case_void",
+                };
+                self.check_void(*value, handler, *line);
+            }
+            InstructionKind::Dispatch {
+                receiver,
+                class,
+                method,
+                args,
+            } => {
+                let slot = self.emitter.layouts.get(*class).slot(*method);
+                let vtable_address = self.value(format!(
+                    "getelementptr %Object, ptr {}, i32 0, i32 2",
+                    self.operand(*receiver)
+                ));
+                let vtable = self.value(format!("load ptr, ptr {}", vtable_address));
+                let address =
+                    self.value(format!("getelementptr ptr, ptr {}, i32 {}", vtable, slot));
+                let function = self.value(format!("load ptr, ptr {}", address));
+                let call = self.call(&function, *receiver, args);
+                self.define(instruction, call);
+            }
+            InstructionKind::StaticDispatch {
+                receiver,
+                class,
+                method,
+                args,
+            } => {
+                let entry = self.emitter.layouts.method(*class, *method);
+                let function = function_name(&self.emitter.layouts, entry);
+                let call = self.call(&function, *receiver, args);
+                self.define(instruction, call);
+            }
+            InstructionKind::New(class) if *class == sym::SELF_TYPE => {
+                let tag = self.tag("%self");
+                let object = self.value(format!("call ptr @cool_new(i32 {})", tag));
+                let address = self.value(format!(
+                    "getelementptr ptr, ptr @cool_class_inits, i32 {}",
+                    tag
+                ));
+                let init = self.value(format!("load ptr, ptr {}", address));
+                self.define(instruction, format!("call ptr {}(ptr {})", init, object));
+            }
+            InstructionKind::New(class) => {
+                let tag = self.emitter.layouts.get(*class).tag;
+                let object = self.value(format!("call ptr @cool_new(i32 {})", tag));
+                self.define(
+                    instruction,
+                    format!("call ptr @{}._init(ptr {})", class, object),
+                );
+            }
+            InstructionKind::Init(class) => {
+                self.instruction(format!("call ptr @{}._init(ptr %self)", class));
+            }
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match terminator {
+            Terminator::Jump(target) => {
+                self.instruction(format!("br label %{}", block_label(*target)));
+            }
+            Terminator::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                let condition = self.condition(*condition);
+                self.instruction(format!(
+                    "br i1 {}, label %{}, label %{}",
+                    condition,
+                    block_label(*then_block),
+                    block_label(*else_block)
+                ));
+            }
+            Terminator::Case { value, branches } => self.case(*value, branches),
+            Terminator::Return(value) => {
+                self.instruction("store ptr %previous, ptr @cool_frames".to_string());
+                self.instruction(format!("ret ptr {}", self.operand(*value)));
+            }
+        }
+    }
+
+    /// Emit a `case`, which walks up the class hierarchy from the class of the value until it
+    /// finds a class one of the branches matches.
+    fn case(&mut self, value: Value, branches: &[(Symbol, BlockId)]) {
+        let value = self.operand(value);
+        let tag = self.tag(&value);
+        let start = self.block.clone();
+
+        let (search, parent, no_match) = (
+            self.label("case"),
+            self.label("parent"),
+            self.label("nomatch"),
+        );
+        self.instruction(format!("br label %{}", search));
+
+        self.start_block(search.clone());
+        let current = self.temp();
+        let next = self.temp();
+        self.instruction(format!(
+            "{} = phi i32 [ {}, %{} ], [ {}, %{} ]",
+            current, tag, start, next, parent
+        ));
+        let targets: Vec<_> = branches
+            .iter()
+            .map(|&(class, block)| {
+                let tag = self.emitter.layouts.get(class).tag;
+                format!("i32 {}, label %{}", tag, block_label(block))
+            })
+            .collect();
+        self.instruction(format!(
+            "switch i32 {}, label %{} [ {} ]",
+            current,
+            parent,
+            targets.join(" ")
+        ));
+
+        self.start_block(parent.clone());
+        let address = self.value(format!(
+            "getelementptr i32, ptr @cool_class_parents, i32 {}",
+            current
+        ));
+        self.instruction(format!("{} = load i32, ptr {}", next, address));
+        let at_root = self.value(format!("icmp slt i32 {}, 0", next));
+        self.instruction(format!(
+            "br i1 {}, label %{}, label %{}",
+            at_root, no_match, search
+        ));
+
+        self.start_block(no_match);
+        self.instruction(format!("call void @cool_case_no_match(ptr {})", value));
+        self.instruction("unreachable".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn emit_program(input: &str) -> String {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        emit(&program, &table)
+    }
+
+    #[test]
+    fn test_emit() {
+        let ir = emit_program(
+            "class A { a : Int; f() : Int { a }; };\n\
+             class Main inherits A { b : String <- \"b\"; f() : Int { 1 }; main() : Object { f() }; };\n",
+        );
+
+        assert!(ir.contains("%Main = type { i32, i32, ptr, ptr, ptr }"));
+        assert!(ir.contains(
+            "@Main._vtable = constant [5 x ptr] [ptr @Object_abort, ptr @Object_type_name, \
+             ptr @Object_copy, ptr @Main.f, ptr @Main.main]"
+        ));
+        assert!(ir.contains("define ptr @Main._init(ptr %self)"));
+        assert!(ir.contains("call ptr @A._init(ptr %self)"));
+        assert!(ir.contains("@cool_class_parents = constant [7 x i32] [i32 -1, i32 0, i32 0, i32 0, i32 0, i32 0, i32 5]"));
+        assert!(ir.contains("define i32 @main(i32 %argc, ptr %argv)"));
+    }
+
+    #[test]
+    fn test_frames() {
+        let ir =
+            emit_program("class Main { f(a : Int) : Int { a + 1 }; main() : Object { f(2) }; };\n");
+
+        let f = ir.split("define ptr @Main.f(").nth(1).unwrap();
+        let f = &f[..f.find("\n}").unwrap()];
+        // `self`, `a` and the sum
+        assert!(f.contains("%slots = alloca ptr, i32 3"));
+        assert!(f.contains("store ptr %self, ptr %t"));
+        assert!(f.contains("store ptr %v"));
+        assert!(f.contains("store ptr %frame, ptr @cool_frames"));
+        assert!(f.contains("store ptr %previous, ptr @cool_frames\n  ret ptr"));
+    }
+
+    #[test]
+    fn test_method_names_are_apart_from_tables() {
+        let ir = emit_program(
+            "class Main { name() : Int { 1 }; vtable() : Int { 2 }; proto() : Int { 3 }; \
+             main() : Object { 0 }; };\n",
+        );
+
+        let mut globals: Vec<_> = ir
+            .lines()
+            .filter_map(|line| match line.strip_prefix("define ptr ") {
+                Some(function) => function.split('(').next(),
+                None if line.starts_with('@') => line.split(' ').next(),
+                None => None,
+            })
+            .collect();
+        assert!(globals.contains(&"@Main.vtable"));
+        assert!(globals.contains(&"@Main._vtable"));
+        let count = globals.len();
+        globals.sort_unstable();
+        globals.dedup();
+        assert_eq!(globals.len(), count);
+    }
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(c_string(b"a \"b\"\n\\"), "c\"a \\22b\\22\\0A\\5C\\00\"");
+    }
+}
@@ -0,0 +1,882 @@
+//! Emitting a program as a WebAssembly text module.
+//!
+//! Objects live in linear memory, laid out as described in the [runtime](RUNTIME) that is spliced
+//! into every module. Constants, prototype objects and the class tables are placed in a data
+//! segment by [`Data`], the heap starts after it. All vtables are consecutive in the module's
+//! function table, so an object's vtable is the index of its first method there and dispatching
+//! is a `call_indirect`.
+//!
+//! The module imports the host functions it does IO with from `env`:
+//!
+//! * `out_string(bytes, length)` and `out_int(value)` print to stdout.
+//! * `in_string(buffer, capacity) -> length` reads a line without the line break into `buffer`,
+//!   truncated to `capacity` bytes, and `in_int() -> value` reads a line and parses an integer
+//!   from it like the reference runtime.
+//! * `error(bytes, length)` reports a runtime error and stops the program, the module traps if
+//!   it returns.
+//!
+//! The program is run by calling the exported function `main`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem;
+
+use common::ast::*;
+use common::visit::{self, Visitor};
+use common::{sym, Symbol, SymbolTable};
+use semant::ClassTable;
+
+use crate::layout::{Layouts, VtableEntry, BOOL_TAG, INT_TAG, STRING_TAG};
+
+/// The functions of the runtime, spliced into every module.
+pub const RUNTIME: &str = include_str!("../runtime/wasm.wat");
+
+/// The messages of runtime errors, the runtime refers to them as `$msg_<name>`.
+const MESSAGES: &[(&str, &str)] = &[
+    ("colon", ":"),
+    ("dispatch_void", ": Dispatch to void."),
+    ("case_void", ": Match on void in case statement."),
+    ("no_match", "No match in case statement for Class "),
+    ("abort", "Abort called from class "),
+    ("division_by_zero", ": Division by zero."),
+    ("substr_index_negative", "Index to substr is negative"),
+    ("substr_index_too_big", "Index to substr is too big"),
+    ("substr_length_negative", "Length to substr is negative"),
+    ("substr_length_too_long", "Length to substr too long"),
+    ("out_of_memory", "Out of memory"),
+];
+
+/// The offset of the first attribute of an object, after the header.
+const FIRST_ATTRIBUTE: u32 = 12;
+
+/// The size of a wasm page.
+const PAGE_SIZE: u32 = 65536;
+
+/// Emit `program`, which must have been type checked with `table` as its class table.
+pub fn emit(program: &Program, table: &ClassTable) -> String {
+    Emitter::new(program, table).emit()
+}
+
+/// The function implementing a method.
+///
+/// The methods of the basic classes are implemented by the runtime, named like C functions.
+fn function_name(layouts: &Layouts, entry: VtableEntry) -> String {
+    if layouts.get(entry.class).is_basic() {
+        format!("${}_{}", entry.class, entry.method)
+    } else {
+        format!("${}.{}", entry.class, entry.method)
+    }
+}
+
+/// The contents of `bytes` as a WAT string.
+fn wat_string(bytes: &[u8]) -> String {
+    let mut result = String::from("\"");
+    for &byte in bytes {
+        if byte.is_ascii_graphic() && byte != b'"' && byte != b'\\' || byte == b' ' {
+            result.push(byte as char);
+        } else {
+            write!(result, "\\{:02x}", byte).unwrap();
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+/// The contents of the module's data segment, which starts at address 0.
+struct Data {
+    bytes: Vec<u8>,
+}
+
+impl Data {
+    fn new() -> Self {
+        // Address 0 is void
+        Self { bytes: vec![0; 8] }
+    }
+
+    fn address(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+
+    /// Append `words` and return their address.
+    fn words(&mut self, words: &[u32]) -> u32 {
+        let address = self.address();
+        for word in words {
+            self.bytes.extend_from_slice(&word.to_le_bytes());
+        }
+
+        address
+    }
+
+    /// Append a `String` object and return its address.
+    fn string(&mut self, vtable: u32, value: &[u8]) -> u32 {
+        let address = self.words(&[
+            STRING_TAG,
+            16 + value.len() as u32,
+            vtable,
+            value.len() as u32,
+        ]);
+        self.bytes.extend_from_slice(value);
+        while !self.bytes.len().is_multiple_of(4) {
+            self.bytes.push(0);
+        }
+
+        address
+    }
+}
+
+struct Emitter<'p> {
+    layouts: Layouts,
+    /// The user defined classes, by name.
+    classes: HashMap<Symbol, &'p Class>,
+    /// The index of the vtable of every class in the function table, by tag.
+    vtables: Vec<u32>,
+    data: Data,
+    ints: HashMap<i32, u32>,
+    strings: HashMap<Symbol, u32>,
+    true_address: u32,
+    false_address: u32,
+    functions: String,
+}
+
+impl<'p> Emitter<'p> {
+    fn new(program: &'p Program, table: &ClassTable) -> Self {
+        let layouts = Layouts::new(table);
+        let mut vtables = vec![];
+        let mut index = 0;
+        for layout in layouts.classes() {
+            vtables.push(index);
+            index += layout.vtable.len() as u32;
+        }
+
+        let mut data = Data::new();
+        let bool_vtable = vtables[BOOL_TAG as usize];
+        let bool_size = FIRST_ATTRIBUTE + 4;
+        let true_address = data.words(&[BOOL_TAG, bool_size, bool_vtable, 1]);
+        let false_address = data.words(&[BOOL_TAG, bool_size, bool_vtable, 0]);
+
+        Self {
+            layouts,
+            classes: program
+                .classes
+                .iter()
+                .map(|class| (class.name, class))
+                .collect(),
+            vtables,
+            data,
+            ints: HashMap::new(),
+            strings: HashMap::new(),
+            true_address,
+            false_address,
+            functions: String::new(),
+        }
+    }
+
+    fn emit(mut self) -> String {
+        for tag in 0..self.layouts.classes().len() {
+            let name = self.layouts.by_tag(tag as u32).name;
+            self.init(name);
+            if let Some(class) = self.classes.get(&name).copied() {
+                for feature in &class.features {
+                    if let Feature::Method(method) = feature {
+                        self.method(class, method);
+                    }
+                }
+            }
+        }
+        self.main();
+
+        let mut output = String::new();
+        writeln!(output, ";; Generated by the COOL compiler\n(module").unwrap();
+        self.declarations(&mut output);
+        self.tables(&mut output);
+        output.push('\n');
+        output.push_str(RUNTIME);
+        output.push('\n');
+        output.push_str(&self.functions);
+        output.push_str(")\n");
+
+        output
+    }
+
+    /// The types of methods and the imported host functions.
+    fn declarations(&self, output: &mut String) {
+        let max_args = self
+            .layouts
+            .classes()
+            .iter()
+            .flat_map(|layout| layout.vtable.iter().map(|entry| entry.args))
+            .max()
+            .unwrap_or(0);
+        for args in 0..=max_args {
+            writeln!(
+                output,
+                "(type $method{} (func (param{}) (result i32)))",
+                args,
+                " i32".repeat(args + 1)
+            )
+            .unwrap();
+        }
+
+        let imports = [
+            "(import \"env\" \"out_string\" (func $host_out_string (param i32 i32)))",
+            "(import \"env\" \"out_int\" (func $host_out_int (param i32)))",
+            "(import \"env\" \"in_string\" (func $host_in_string (param i32 i32) (result i32)))",
+            "(import \"env\" \"in_int\" (func $host_in_int (result i32)))",
+            "(import \"env\" \"error\" (func $host_error (param i32 i32)))",
+        ];
+        for import in &imports {
+            writeln!(output, "{}", import).unwrap();
+        }
+        output.push('\n');
+    }
+
+    /// The function table, the data segment and the globals pointing into it.
+    fn tables(&mut self, output: &mut String) {
+        let layouts = self.layouts.clone();
+        let zero = self.int_constant(0);
+        let empty = self.string_constant(Symbol::intern(""));
+        let messages: Vec<_> = MESSAGES
+            .iter()
+            .map(|&(name, message)| (name, self.string_constant(Symbol::intern(message))))
+            .collect();
+
+        // The vtables, followed by the initializers
+        let mut elements: Vec<_> = layouts
+            .classes()
+            .iter()
+            .flat_map(|layout| layout.vtable.iter())
+            .map(|&entry| function_name(&layouts, entry))
+            .collect();
+        let inits = elements.len() as u32;
+        elements.extend(
+            layouts
+                .classes()
+                .iter()
+                .map(|layout| format!("${}._init", layout.name)),
+        );
+        writeln!(output, "(table {} funcref)", elements.len()).unwrap();
+        writeln!(output, "(elem (i32.const 0) func {})", elements.join(" ")).unwrap();
+
+        let mut protos = vec![];
+        for layout in layouts.classes() {
+            let mut words = vec![layout.tag, 0, self.vtables[layout.tag as usize]];
+            match layout.tag {
+                INT_TAG | BOOL_TAG | STRING_TAG => words.push(0),
+                _ => words.extend(layout.attributes.iter().map(|&(_, ty)| match ty {
+                    sym::INT => zero,
+                    sym::STRING => empty,
+                    sym::BOOL => self.false_address,
+                    _ => 0,
+                })),
+            }
+            words[1] = 4 * words.len() as u32;
+            if layout.tag == STRING_TAG {
+                words[1] = 16;
+            }
+            protos.push(self.data.words(&words));
+        }
+        let names: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| self.string_constant(layout.name))
+            .collect();
+        let parents: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| layout.parent.map_or(u32::MAX, |parent| parent))
+            .collect();
+        let init_indices: Vec<_> = (0..layouts.classes().len() as u32)
+            .map(|tag| inits + tag)
+            .collect();
+
+        let globals = [
+            ("class_protos", self.data.words(&protos)),
+            ("class_names", self.data.words(&names)),
+            ("class_parents", self.data.words(&parents)),
+            ("class_inits", self.data.words(&init_indices)),
+            ("true", self.true_address),
+            ("false", self.false_address),
+        ];
+        let heap = (self.data.address() + 7) & !7;
+        let pages = heap / PAGE_SIZE + 1;
+
+        writeln!(output, "(memory (export \"memory\") {})", pages).unwrap();
+        writeln!(output, "(global $heap (mut i32) (i32.const {}))", heap).unwrap();
+        for (name, address) in &globals {
+            writeln!(output, "(global ${} i32 (i32.const {}))", name, address).unwrap();
+        }
+        for (name, address) in &messages {
+            writeln!(output, "(global $msg_{} i32 (i32.const {}))", name, address).unwrap();
+        }
+        writeln!(
+            output,
+            "(data (i32.const 0) {})",
+            wat_string(&self.data.bytes)
+        )
+        .unwrap();
+    }
+
+    /// The address of a constant `Int` object.
+    fn int_constant(&mut self, value: i32) -> u32 {
+        if let Some(&address) = self.ints.get(&value) {
+            return address;
+        }
+
+        let vtable = self.vtables[INT_TAG as usize];
+        let address = self
+            .data
+            .words(&[INT_TAG, FIRST_ATTRIBUTE + 4, vtable, value as u32]);
+        self.ints.insert(value, address);
+
+        address
+    }
+
+    /// The address of a constant `String` object.
+    fn string_constant(&mut self, value: Symbol) -> u32 {
+        if let Some(&address) = self.strings.get(&value) {
+            return address;
+        }
+
+        let vtable = self.vtables[STRING_TAG as usize];
+        let address = self.data.string(vtable, value.as_str().as_bytes());
+        self.strings.insert(value, address);
+
+        address
+    }
+
+    /// A scope with the attributes of `class`.
+    fn attribute_scope(&self, class: Symbol) -> SymbolTable<Variable> {
+        let mut scope = SymbolTable::new();
+        scope.enter_scope();
+        for (index, &(name, _)) in self.layouts.get(class).attributes.iter().enumerate() {
+            scope.add(
+                name,
+                Variable::Attribute(FIRST_ATTRIBUTE + 4 * index as u32),
+            );
+        }
+
+        scope
+    }
+
+    /// Emit the initializer of `class`, which runs its parent's initializer and then the
+    /// initializers of its own attributes.
+    fn init(&mut self, class: Symbol) {
+        let layout = self.layouts.get(class).clone();
+        let scope = self.attribute_scope(class);
+        let mut function = Function::new(self, class, scope);
+
+        if let Some(parent) = layout.parent {
+            let parent = function.emitter.layouts.by_tag(parent).name;
+            function.instruction("local.get $self".to_string());
+            function.instruction(format!("call ${}._init", parent));
+            function.instruction("drop".to_string());
+        }
+
+        if let Some(ast) = function.emitter.classes.get(&class).copied() {
+            let first = layout.attributes.len() - layout.own_attributes;
+            let attributes = ast.features.iter().filter_map(|feature| match feature {
+                Feature::Attribute(attribute) => Some(attribute),
+                Feature::Method(_) => None,
+            });
+            for (index, attribute) in attributes.enumerate() {
+                if !attribute.init.is_no_expr() {
+                    function.instruction("local.get $self".to_string());
+                    function.visit_expr(&attribute.init);
+                    function.instruction(format!(
+                        "i32.store offset={}",
+                        FIRST_ATTRIBUTE + 4 * (first + index) as u32
+                    ));
+                }
+            }
+        }
+
+        function.instruction("local.get $self".to_string());
+        function.finish(&format!("${}._init", class), &[]);
+    }
+
+    fn method(&mut self, class: &Class, method: &Method) {
+        let scope = self.attribute_scope(class.name);
+        let mut function = Function::new(self, class.name, scope);
+
+        function.scope.enter_scope();
+        let mut params = vec![];
+        for formal in &method.formals {
+            let param = format!("$arg.{}", formal.name);
+            function
+                .scope
+                .add(formal.name, Variable::Local(param.clone()));
+            params.push(param);
+        }
+
+        function.visit_expr(&method.body);
+        function.finish(&format!("${}.{}", class.name, method.name), &params);
+    }
+
+    fn main(&mut self) {
+        let main = self.layouts.method(sym::MAIN, sym::MAIN_METHOD);
+        writeln!(
+            self.functions,
+            "(func (export \"main\")\n  \
+             i32.const {}\n  \
+             call $new\n  \
+             call $Main._init\n  \
+             call {}\n  \
+             drop)",
+            self.layouts.get(sym::MAIN).tag,
+            function_name(&self.layouts, main)
+        )
+        .unwrap();
+    }
+}
+
+/// Where a variable is stored.
+#[derive(Debug, Clone)]
+enum Variable {
+    /// A parameter or local of the function.
+    Local(String),
+    /// An attribute of `self`, at an offset.
+    Attribute(u32),
+}
+
+/// Emits the body of one function. Every expression leaves its value on the stack.
+struct Function<'e, 'p> {
+    emitter: &'e mut Emitter<'p>,
+    class: Symbol,
+    /// The address of the name of the file the class is defined in, for runtime errors.
+    file: u32,
+    scope: SymbolTable<Variable>,
+    locals: Vec<String>,
+    body: String,
+    indent: usize,
+    next_label: usize,
+    /// The line of the expression being emitted, for runtime errors.
+    line: usize,
+}
+
+impl<'e, 'p> Function<'e, 'p> {
+    fn new(emitter: &'e mut Emitter<'p>, class: Symbol, scope: SymbolTable<Variable>) -> Self {
+        let file_name = emitter.layouts.get(class).file_name;
+        let file = emitter.string_constant(file_name);
+
+        Self {
+            emitter,
+            class,
+            file,
+            scope,
+            locals: vec![],
+            body: String::new(),
+            indent: 1,
+            next_label: 0,
+            line: 0,
+        }
+    }
+
+    fn finish(self, name: &str, params: &[String]) {
+        let mut header = format!("(func {} (param $self i32)", name);
+        for param in params {
+            write!(header, " (param {} i32)", param).unwrap();
+        }
+        header.push_str(" (result i32)");
+        for local in &self.locals {
+            write!(header, " (local {} i32)", local).unwrap();
+        }
+
+        // Drop the line break after the last instruction
+        writeln!(
+            self.emitter.functions,
+            "{}\n{})\n",
+            header,
+            self.body.trim_end()
+        )
+        .unwrap();
+    }
+
+    fn instruction(&mut self, text: String) {
+        writeln!(self.body, "{}{}", "  ".repeat(self.indent), text).unwrap();
+    }
+
+    /// Start a block with `header`, like `if (result i32)`.
+    fn open(&mut self, header: String) {
+        self.instruction(header);
+        self.indent += 1;
+    }
+
+    fn close(&mut self, footer: &str) {
+        self.indent -= 1;
+        self.instruction(footer.to_string());
+    }
+
+    /// A new local named after `name`.
+    fn local(&mut self, name: &str) -> String {
+        let local = format!("${}{}", name, self.locals.len());
+        self.locals.push(local.clone());
+
+        local
+    }
+
+    fn label(&mut self, prefix: &str) -> String {
+        self.next_label += 1;
+        format!("${}{}", prefix, self.next_label)
+    }
+
+    /// Call `handler` with the location of `line` if the value in `local` is void.
+    fn check_void(&mut self, local: &str, handler: &str, line: usize) {
+        self.instruction(format!("local.get {}", local));
+        self.instruction("i32.eqz".to_string());
+        self.open("if".to_string());
+        self.instruction(format!("i32.const {}", self.file));
+        self.instruction(format!("i32.const {}", line));
+        self.instruction(format!("call {}", handler));
+        self.close("end");
+    }
+
+    /// Pop an `Int` or `Bool` and push its value.
+    fn unbox(&mut self) {
+        self.instruction(format!("i32.load offset={}", FIRST_ATTRIBUTE));
+    }
+
+    /// The default value of a variable of type `ty`.
+    fn default(&mut self, ty: Symbol) -> u32 {
+        match ty {
+            sym::INT => self.emitter.int_constant(0),
+            sym::STRING => self.emitter.string_constant(Symbol::intern("")),
+            sym::BOOL => self.emitter.false_address,
+            _ => 0,
+        }
+    }
+
+    /// Pop a value into a new local named after `name`, and emit `body` with `name` bound to it.
+    fn bind(&mut self, name: Symbol, body: &Expr) {
+        let local = self.local(&format!("{}.", name));
+        self.instruction(format!("local.set {}", local));
+        self.scope.enter_scope();
+        self.scope.add(name, Variable::Local(local));
+        self.visit_expr(body);
+        self.scope.exit_scope();
+    }
+
+    /// Emit the arguments and receiver of a dispatch, which are evaluated in that order like in
+    /// the reference compiler, and check that the receiver isn't void. Returns the local the
+    /// receiver is in and leaves the receiver and the arguments on the stack.
+    fn dispatch_operands(&mut self, receiver: &Expr, args: &[Expr]) -> String {
+        let arg_locals: Vec<_> = args
+            .iter()
+            .map(|arg| {
+                self.visit_expr(arg);
+                let local = self.local("arg");
+                self.instruction(format!("local.set {}", local));
+                local
+            })
+            .collect();
+
+        self.visit_expr(receiver);
+        let local = self.local("receiver");
+        self.instruction(format!("local.set {}", local));
+        if !matches!(receiver.kind, ExprKind::Object(name) if name == sym::SELF) {
+            self.check_void(&local, "$dispatch_void", self.line);
+        }
+
+        self.instruction(format!("local.get {}", local));
+        for arg in arg_locals {
+            self.instruction(format!("local.get {}", arg));
+        }
+
+        local
+    }
+}
+
+impl Visitor for Function<'_, '_> {
+    /// Emit the instructions that leave the value of `expr` on the stack.
+    fn visit_expr(&mut self, expr: &Expr) {
+        let line = mem::replace(&mut self.line, expr.line);
+        visit::walk_expr(self, expr);
+        self.line = line;
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        self.visit_expr(&assign.expr);
+        match self.scope.lookup(assign.name).cloned() {
+            Some(Variable::Local(local)) => self.instruction(format!("local.tee {}", local)),
+            Some(Variable::Attribute(offset)) => {
+                let value = self.local("value");
+                self.instruction(format!("local.set {}", value));
+                self.instruction("local.get $self".to_string());
+                self.instruction(format!("local.get {}", value));
+                self.instruction(format!("i32.store offset={}", offset));
+                self.instruction(format!("local.get {}", value));
+            }
+            None => panic!("Assignment to undeclared {}", assign.name),
+        }
+    }
+
+    fn visit_static_dispatch(&mut self, dispatch: &StaticDispatch) {
+        self.dispatch_operands(&dispatch.expr, &dispatch.args);
+        let entry = self
+            .emitter
+            .layouts
+            .method(dispatch.type_name, dispatch.name);
+        let function = function_name(&self.emitter.layouts, entry);
+        self.instruction(format!("call {}", function));
+    }
+
+    fn visit_dispatch(&mut self, dispatch: &Dispatch) {
+        let receiver = self.dispatch_operands(&dispatch.expr, &dispatch.args);
+        let ty = dispatch.expr.ty.expect("Untyped receiver");
+        let slot = self
+            .emitter
+            .layouts
+            .of_type(ty, self.class)
+            .slot(dispatch.name);
+
+        self.instruction(format!("local.get {}", receiver));
+        self.instruction("i32.load offset=8".to_string());
+        self.instruction(format!("i32.const {}", slot));
+        self.instruction("i32.add".to_string());
+        self.instruction(format!(
+            "call_indirect (type $method{})",
+            dispatch.args.len()
+        ));
+    }
+
+    fn visit_cond(&mut self, cond: &Cond) {
+        self.visit_expr(&cond.pred);
+        self.unbox();
+        self.open("if (result i32)".to_string());
+        self.visit_expr(&cond.then_expr);
+        self.close("else");
+        self.indent += 1;
+        self.visit_expr(&cond.else_expr);
+        self.close("end");
+    }
+
+    fn visit_loop(&mut self, loop_: &Loop) {
+        let (end, start) = (self.label("pool"), self.label("loop"));
+        self.open(format!("block {}", end));
+        self.open(format!("loop {}", start));
+        self.visit_expr(&loop_.pred);
+        self.unbox();
+        self.instruction("i32.eqz".to_string());
+        self.instruction(format!("br_if {}", end));
+        self.visit_expr(&loop_.body);
+        self.instruction("drop".to_string());
+        self.instruction(format!("br {}", start));
+        self.close("end");
+        self.close("end");
+        self.instruction("i32.const 0".to_string());
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        for (i, expr) in block.body.iter().enumerate() {
+            if i > 0 {
+                self.instruction("drop".to_string());
+            }
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        if let_.init.is_no_expr() {
+            let value = self.default(let_.type_decl);
+            self.instruction(format!("i32.const {}", value));
+        } else {
+            self.visit_expr(&let_.init);
+        }
+
+        self.bind(let_.name, &let_.body);
+    }
+
+    fn visit_binary(&mut self, binary: &Binary) {
+        if binary.op == BinaryOp::Eq {
+            self.visit_expr(&binary.lhs);
+            self.visit_expr(&binary.rhs);
+            self.instruction("call $equal".to_string());
+            self.instruction("call $bool".to_string());
+            return;
+        }
+
+        self.visit_expr(&binary.lhs);
+        self.unbox();
+        self.visit_expr(&binary.rhs);
+        self.unbox();
+        let (instruction, result) = match binary.op {
+            BinaryOp::Plus => ("i32.add", "$int"),
+            BinaryOp::Sub => ("i32.sub", "$int"),
+            BinaryOp::Mul => ("i32.mul", "$int"),
+            BinaryOp::Divide => {
+                self.instruction(format!("i32.const {}", self.file));
+                self.instruction(format!("i32.const {}", self.line));
+                ("call $divide", "$int")
+            }
+            BinaryOp::Lt => ("i32.lt_s", "$bool"),
+            BinaryOp::Leq => ("i32.le_s", "$bool"),
+            BinaryOp::Eq => unreachable!(),
+        };
+        self.instruction(instruction.to_string());
+        self.instruction(format!("call {}", result));
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) {
+        match unary.op {
+            UnaryOp::Neg => {
+                self.instruction("i32.const 0".to_string());
+                self.visit_expr(&unary.expr);
+                self.unbox();
+                self.instruction("i32.sub".to_string());
+                self.instruction("call $int".to_string());
+            }
+            UnaryOp::Comp => {
+                self.visit_expr(&unary.expr);
+                self.unbox();
+                self.instruction("i32.eqz".to_string());
+                self.instruction("call $bool".to_string());
+            }
+            UnaryOp::IsVoid => {
+                self.visit_expr(&unary.expr);
+                self.instruction("i32.eqz".to_string());
+                self.instruction("call $bool".to_string());
+            }
+        }
+    }
+
+    fn visit_int_const(&mut self, digits: Symbol) {
+        let value = digits.as_str().bytes().fold(0i32, |value, digit| {
+            value.wrapping_mul(10).wrapping_add(i32::from(digit - b'0'))
+        });
+        let address = self.emitter.int_constant(value);
+        self.instruction(format!("i32.const {}", address));
+    }
+
+    fn visit_bool_const(&mut self, value: bool) {
+        let address = if value {
+            self.emitter.true_address
+        } else {
+            self.emitter.false_address
+        };
+        self.instruction(format!("i32.const {}", address));
+    }
+
+    fn visit_string_const(&mut self, value: Symbol) {
+        let address = self.emitter.string_constant(value);
+        self.instruction(format!("i32.const {}", address));
+    }
+
+    fn visit_new(&mut self, type_name: Symbol) {
+        if type_name == sym::SELF_TYPE {
+            let tag = self.local("tag");
+            self.instruction("local.get $self".to_string());
+            self.instruction("i32.load".to_string());
+            self.instruction(format!("local.tee {}", tag));
+            self.instruction("call $new".to_string());
+            self.instruction("global.get $class_inits".to_string());
+            self.instruction(format!("local.get {}", tag));
+            self.instruction("i32.const 2".to_string());
+            self.instruction("i32.shl".to_string());
+            self.instruction("i32.add".to_string());
+            self.instruction("i32.load".to_string());
+            self.instruction("call_indirect (type $method0)".to_string());
+        } else {
+            let tag = self.emitter.layouts.get(type_name).tag;
+            self.instruction(format!("i32.const {}", tag));
+            self.instruction("call $new".to_string());
+            self.instruction(format!("call ${}._init", type_name));
+        }
+    }
+
+    fn visit_no_expr(&mut self) {
+        self.instruction("i32.const 0".to_string());
+    }
+
+    fn visit_object(&mut self, name: Symbol) {
+        if name == sym::SELF {
+            self.instruction("local.get $self".to_string());
+            return;
+        }
+
+        match self.scope.lookup(name).cloned() {
+            Some(Variable::Local(local)) => self.instruction(format!("local.get {}", local)),
+            Some(Variable::Attribute(offset)) => {
+                self.instruction("local.get $self".to_string());
+                self.instruction(format!("i32.load offset={}", offset));
+            }
+            None => panic!("Undeclared identifier {}", name),
+        }
+    }
+
+    /// Emit a `case`, which walks up the class hierarchy from the class of the value until it
+    /// finds a class one of the branches matches.
+    fn visit_case(&mut self, case: &Case) {
+        self.visit_expr(&case.expr);
+        let value = self.local("case");
+        self.instruction(format!("local.set {}", value));
+        self.check_void(&value, "$case_void", self.line);
+
+        let tag = self.local("tag");
+        self.instruction(format!("local.get {}", value));
+        self.instruction("i32.load".to_string());
+        self.instruction(format!("local.set {}", tag));
+
+        let tags: Vec<_> = case
+            .branches
+            .iter()
+            .map(|branch| self.emitter.layouts.get(branch.type_decl).tag)
+            .collect();
+        let (found, search) = (self.label("found"), self.label("search"));
+        self.open(format!("block {}", found));
+        self.open(format!("loop {}", search));
+        for branch_tag in &tags {
+            self.instruction(format!("local.get {}", tag));
+            self.instruction(format!("i32.const {}", branch_tag));
+            self.instruction("i32.eq".to_string());
+            self.instruction(format!("br_if {}", found));
+        }
+        self.instruction("global.get $class_parents".to_string());
+        self.instruction(format!("local.get {}", tag));
+        self.instruction("i32.const 2".to_string());
+        self.instruction("i32.shl".to_string());
+        self.instruction("i32.add".to_string());
+        self.instruction("i32.load".to_string());
+        self.instruction(format!("local.tee {}", tag));
+        self.instruction("i32.const 0".to_string());
+        self.instruction("i32.lt_s".to_string());
+        self.open("if".to_string());
+        self.instruction(format!("local.get {}", value));
+        self.instruction("call $case_no_match".to_string());
+        self.close("end");
+        self.instruction(format!("br {}", search));
+        self.close("end");
+        self.close("end");
+
+        for (branch, branch_tag) in case.branches.iter().zip(tags) {
+            self.instruction(format!("local.get {}", tag));
+            self.instruction(format!("i32.const {}", branch_tag));
+            self.instruction("i32.eq".to_string());
+            self.open("if (result i32)".to_string());
+            self.instruction(format!("local.get {}", value));
+            self.bind(branch.name, &branch.expr);
+            self.close("else");
+            self.indent += 1;
+        }
+        self.instruction("unreachable".to_string());
+        for _ in &case.branches {
+            self.close("end");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data() {
+        let mut data = Data::new();
+        assert_eq!(data.words(&[1, 2]), 8);
+        assert_eq!(data.string(7, b"ab"), 16);
+        assert_eq!(data.address(), 36);
+        assert_eq!(
+            wat_string(&data.bytes[16..]),
+            wat_string(b"\x03\0\0\0\x12\0\0\0\x07\0\0\0\x02\0\0\0ab\0\0")
+        );
+    }
+}
@@ -0,0 +1,989 @@
+//! Translating a program to x86-64 assembly.
+//!
+//! The output is GNU assembler source for Linux following the System V ABI, to be assembled and
+//! linked with the [runtime](RUNTIME) into an executable that depends on no other library. Every
+//! expression leaves its value in `%rax`, values that are needed later are kept in slots of the
+//! stack frame. A method receives `self` in `%rdi` and its arguments in the following argument
+//! registers, the ones that don't fit are passed on the stack.
+//!
+//! The frame of a method is the stack map of the runtime's garbage collector: it starts with the
+//! number of slots after it, which hold `self`, the arguments and then the other values, which are
+//! all objects or 0. The collector may move every object, so values are only kept in registers
+//! while nothing is allocated.
+//!
+//! Like with the reference compiler, programs only collect garbage if they're compiled with `-g`,
+//! for the runtime's generational collector. They then call its write barrier `_GenGC_Assign` with
+//! the address of every attribute they assign.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem;
+
+use common::ast::*;
+use common::visit::{self, Visitor};
+use common::{sym, Symbol, SymbolTable};
+use semant::ClassTable;
+
+use crate::layout::{Layouts, VtableEntry, BOOL_TAG, INT_TAG, STRING_TAG};
+
+/// The runtime compiled programs are linked with.
+pub const RUNTIME: &str = include_str!("../runtime/x86_64.s");
+
+/// The registers of the arguments of a method, after `self` in `%rdi`.
+const ARG_REGISTERS: &[&str] = &["%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// The offset of the first attribute of an object, after the header.
+const FIRST_ATTRIBUTE: usize = 16;
+
+/// Where a method keeps `self`, the first slot after the header of its frame.
+const SELF: &str = "-16(%rbp)";
+
+/// The offset from the frame pointer of the slot of the formal parameter `index`.
+fn param_offset(index: usize) -> i64 {
+    -8 * (index as i64 + 3)
+}
+
+/// Which garbage collector a compiled program runs, and how.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct GcOptions {
+    /// Collect with the generational collector, like the reference compiler's `-g`.
+    pub generational: bool,
+    /// Collect on every allocation, like the reference compiler's `-t`.
+    pub stress: bool,
+}
+
+/// Emit `program`, which must have been type checked with `table` as its class table.
+pub fn emit(program: &Program, table: &ClassTable) -> String {
+    emit_with_gc(program, table, GcOptions::default())
+}
+
+/// Emit `program` like [`emit`], for the garbage collector chosen by `options`.
+pub fn emit_with_gc(program: &Program, table: &ClassTable, options: GcOptions) -> String {
+    Emitter::new(program, table, options).emit()
+}
+
+/// The function implementing a method.
+///
+/// The methods of the basic classes are implemented by the runtime, named like C functions.
+fn function_name(layouts: &Layouts, entry: VtableEntry) -> String {
+    if layouts.get(entry.class).is_basic() {
+        format!("{}_{}", entry.class, entry.method)
+    } else {
+        format!("{}.{}", entry.class, entry.method)
+    }
+}
+
+/// The contents of `bytes` as an assembler string.
+fn asm_string(bytes: &[u8]) -> String {
+    let mut result = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                result.push('\\');
+                result.push(byte as char);
+            }
+            b' '..=b'~' => result.push(byte as char),
+            // Three digits, so a following digit can't be taken as part of the escape
+            _ => write!(result, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+/// Where the value of a variable is stored.
+#[derive(Debug, Copy, Clone)]
+enum Variable {
+    /// At an offset from the frame pointer.
+    Frame(i64),
+    /// In an attribute of `self`, by its index in the class layout.
+    Attribute(usize),
+}
+
+/// What a dispatch calls.
+enum Callee {
+    /// A function, by its label.
+    Function(String),
+    /// The method in a slot of the receiver's vtable.
+    Slot(usize),
+}
+
+struct Emitter<'p> {
+    layouts: Layouts,
+    gc: GcOptions,
+    /// The user defined classes, by name.
+    classes: HashMap<Symbol, &'p Class>,
+    /// Constant objects.
+    constants: String,
+    ints: HashMap<i32, String>,
+    strings: HashMap<Symbol, String>,
+    functions: String,
+    next_label: usize,
+}
+
+impl<'p> Emitter<'p> {
+    fn new(program: &'p Program, table: &ClassTable, gc: GcOptions) -> Self {
+        Self {
+            layouts: Layouts::new(table),
+            gc,
+            classes: program
+                .classes
+                .iter()
+                .map(|class| (class.name, class))
+                .collect(),
+            constants: String::new(),
+            ints: HashMap::new(),
+            strings: HashMap::new(),
+            functions: String::new(),
+            next_label: 0,
+        }
+    }
+
+    fn emit(mut self) -> String {
+        for tag in 0..self.layouts.classes().len() {
+            let name = self.layouts.by_tag(tag as u32).name;
+            self.init(name);
+            if let Some(class) = self.classes.get(&name).copied() {
+                for feature in &class.features {
+                    if let Feature::Method(method) = feature {
+                        self.method(class, method);
+                    }
+                }
+            }
+        }
+        self.main();
+
+        let mut output = String::new();
+        writeln!(output, "# Generated by the COOL compiler\n").unwrap();
+        writeln!(output, "        .text\n").unwrap();
+        output.push_str(&self.functions);
+        writeln!(output, "        .section .rodata").unwrap();
+        self.vtables(&mut output);
+        self.objects(&mut output);
+        output.push_str(&self.constants);
+        writeln!(output, "\n        .section .note.GNU-stack,\"\",@progbits").unwrap();
+
+        output
+    }
+
+    fn vtables(&self, output: &mut String) {
+        for layout in self.layouts.classes() {
+            let entries: Vec<_> = layout
+                .vtable
+                .iter()
+                .map(|&entry| function_name(&self.layouts, entry))
+                .collect();
+            writeln!(
+                output,
+                "        .p2align 3\n{}._vtable:\n        .quad {}",
+                layout.name,
+                entries.join(", ")
+            )
+            .unwrap();
+        }
+        output.push('\n');
+    }
+
+    /// The prototype objects, class tables and the collector.
+    ///
+    /// Like all constant objects, the prototypes are preceded by a GC tag word of -1.
+    fn objects(&mut self, output: &mut String) {
+        let zero = self.int_constant(0);
+        let empty = self.string_constant(Symbol::intern(""));
+        let names: Vec<_> = (0..self.layouts.classes().len())
+            .map(|tag| {
+                let name = self.layouts.by_tag(tag as u32).name;
+                self.string_constant(name)
+            })
+            .collect();
+
+        let layouts = &self.layouts;
+        for layout in layouts.classes() {
+            let (size, fields) = match layout.tag {
+                INT_TAG | BOOL_TAG => (24, vec![".long 0, 0".to_string()]),
+                STRING_TAG => (24, vec![".quad 0".to_string()]),
+                _ => (
+                    FIRST_ATTRIBUTE + 8 * layout.attributes.len(),
+                    layout
+                        .attributes
+                        .iter()
+                        .map(|&(_, ty)| match ty {
+                            sym::INT => format!(".quad {}", zero),
+                            sym::STRING => format!(".quad {}", empty),
+                            sym::BOOL => ".quad cool_false".to_string(),
+                            _ => ".quad 0".to_string(),
+                        })
+                        .collect(),
+                ),
+            };
+            writeln!(
+                output,
+                "        .p2align 3\n        .quad -1\n{}._proto:\n        .long {}, {}\n        \
+                 .quad {}._vtable",
+                layout.name, layout.tag, size, layout.name
+            )
+            .unwrap();
+            for field in fields {
+                writeln!(output, "        {}", field).unwrap();
+            }
+        }
+        for (name, value) in &[("cool_true", 1), ("cool_false", 0)] {
+            writeln!(
+                output,
+                "        .globl {}\n        .p2align 3\n        .quad -1\n{}:\n        \
+                 .long {}, 24\n        .quad Bool._vtable\n        .long {}, 0",
+                name, name, BOOL_TAG, value
+            )
+            .unwrap();
+        }
+        output.push('\n');
+
+        let protos: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| format!("{}._proto", layout.name))
+            .collect();
+        let inits: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| format!("{}._init", layout.name))
+            .collect();
+        let parents: Vec<_> = layouts
+            .classes()
+            .iter()
+            .map(|layout| layout.parent.map_or(-1, |parent| parent as i64).to_string())
+            .collect();
+        for (name, directive, entries, global) in &[
+            ("cool_class_names", ".quad", names, true),
+            ("cool_class_protos", ".quad", protos, true),
+            ("cool_class_inits", ".quad", inits, false),
+            ("cool_class_parents", ".long", parents, false),
+        ] {
+            if *global {
+                writeln!(output, "        .globl {}", name).unwrap();
+            }
+            writeln!(
+                output,
+                "        .p2align 3\n{}:\n        {} {}",
+                name,
+                directive,
+                entries.join(", ")
+            )
+            .unwrap();
+        }
+
+        let collector = if self.gc.generational {
+            "_GenGC"
+        } else {
+            "_NoGC"
+        };
+        for (name, value) in &[
+            ("_MemMgr_INITIALIZER", format!("{}_Init", collector)),
+            ("_MemMgr_COLLECTOR", format!("{}_Collect", collector)),
+            ("_MemMgr_TEST", (self.gc.stress as u8).to_string()),
+        ] {
+            writeln!(
+                output,
+                "        .globl {}\n        .p2align 3\n{}:\n        .quad {}",
+                name, name, value
+            )
+            .unwrap();
+        }
+        output.push('\n');
+    }
+
+    /// The label of a constant `Int` object.
+    fn int_constant(&mut self, value: i32) -> String {
+        if let Some(label) = self.ints.get(&value) {
+            return label.clone();
+        }
+
+        let label = format!("int.{}", self.ints.len());
+        writeln!(
+            self.constants,
+            "        .p2align 3\n        .quad -1\n{}:\n        .long {}, 24\n        \
+             .quad Int._vtable\n        .long {}, 0",
+            label, INT_TAG, value
+        )
+        .unwrap();
+        self.ints.insert(value, label.clone());
+
+        label
+    }
+
+    /// The label of a constant `String` object.
+    fn string_constant(&mut self, value: Symbol) -> String {
+        if let Some(label) = self.strings.get(&value) {
+            return label.clone();
+        }
+
+        let label = format!("str.{}", self.strings.len());
+        let bytes = value.as_str().as_bytes();
+        writeln!(
+            self.constants,
+            "        .p2align 3\n        .quad -1\n{}:\n        .long {}, {}\n        \
+             .quad String._vtable\n        .quad {}\n        .ascii {}",
+            label,
+            STRING_TAG,
+            FIRST_ATTRIBUTE + 8 + bytes.len(),
+            bytes.len(),
+            asm_string(bytes)
+        )
+        .unwrap();
+        self.strings.insert(value, label.clone());
+
+        label
+    }
+
+    fn label(&mut self) -> String {
+        self.next_label += 1;
+        format!(".L{}", self.next_label)
+    }
+
+    /// A scope with the attributes of `class`.
+    fn attribute_scope(&self, class: Symbol) -> SymbolTable<Variable> {
+        let layout = self.layouts.get(class);
+        let mut scope = SymbolTable::new();
+        scope.enter_scope();
+        for (index, &(name, _)) in layout.attributes.iter().enumerate() {
+            scope.add(name, Variable::Attribute(index));
+        }
+
+        scope
+    }
+
+    /// Emit the initializer of `class`, which runs its parent's initializer and then the
+    /// initializers of its own attributes.
+    fn init(&mut self, class: Symbol) {
+        let layout = self.layouts.get(class).clone();
+        let scope = self.attribute_scope(class);
+        let mut function = Function::new(self, class, scope, 0);
+
+        if let Some(parent) = layout.parent {
+            let parent = function.emitter.layouts.by_tag(parent).name;
+            function.instruction(&format!("movq {}, %rdi", SELF));
+            function.instruction(&format!("call {}._init", parent));
+        }
+
+        if let Some(ast) = function.emitter.classes.get(&class).copied() {
+            let attributes = ast.features.iter().filter_map(|feature| match feature {
+                Feature::Attribute(attribute) => Some(attribute),
+                Feature::Method(_) => None,
+            });
+            for attribute in attributes {
+                if !attribute.init.is_no_expr() {
+                    function.visit_expr(&attribute.init);
+                    let target = *function.scope.lookup(attribute.name).unwrap();
+                    function.store(target);
+                }
+            }
+        }
+
+        function.instruction(&format!("movq {}, %rax", SELF));
+        function.finish(&format!("{}._init", class), 0);
+    }
+
+    fn method(&mut self, class: &Class, method: &Method) {
+        let scope = self.attribute_scope(class.name);
+        let params = method.formals.len();
+        let mut function = Function::new(self, class.name, scope, params);
+
+        function.scope.enter_scope();
+        for (i, formal) in method.formals.iter().enumerate() {
+            function
+                .scope
+                .add(formal.name, Variable::Frame(param_offset(i)));
+        }
+
+        function.visit_expr(&method.body);
+        function.finish(&format!("{}.{}", class.name, method.name), params);
+    }
+
+    fn main(&mut self) {
+        let main = self.layouts.method(sym::MAIN, sym::MAIN_METHOD);
+        writeln!(
+            self.functions,
+            "        .globl cool_main\n\
+             cool_main:\n        \
+             pushq %rbp\n        \
+             movq %rsp, %rbp\n        \
+             subq $16, %rsp\n        \
+             movq $0, -8(%rbp)\n        \
+             movl ${}, %edi\n        \
+             call cool_new\n        \
+             movq %rax, %rdi\n        \
+             call Main._init\n        \
+             movq %rax, %rdi\n        \
+             call {}\n        \
+             leave\n        \
+             ret\n",
+            self.layouts.get(sym::MAIN).tag,
+            function_name(&self.layouts, main)
+        )
+        .unwrap();
+    }
+}
+
+/// Emits the body of one function.
+struct Function<'e, 'p> {
+    emitter: &'e mut Emitter<'p>,
+    class: Symbol,
+    /// The label of the name of the file the class is defined in, a `String`.
+    file: String,
+    scope: SymbolTable<Variable>,
+    body: String,
+    /// The number of slots of the frame in use: the header, `self` and then the arguments.
+    slots: usize,
+    max_slots: usize,
+    /// The line of the expression being emitted, for runtime errors.
+    line: usize,
+}
+
+impl<'e, 'p> Function<'e, 'p> {
+    fn new(
+        emitter: &'e mut Emitter<'p>,
+        class: Symbol,
+        scope: SymbolTable<Variable>,
+        params: usize,
+    ) -> Self {
+        let file_name = emitter.layouts.get(class).file_name;
+        let file = emitter.string_constant(file_name);
+        let slots = 2 + params;
+
+        Self {
+            emitter,
+            class,
+            file,
+            scope,
+            body: String::new(),
+            slots,
+            max_slots: slots,
+            line: 0,
+        }
+    }
+
+    fn finish(self, name: &str, params: usize) {
+        // Keep the stack aligned to 16 bytes at calls
+        let frame = (8 * self.max_slots).div_ceil(16) * 16;
+        let output = &mut self.emitter.functions;
+        writeln!(
+            output,
+            "{}:\n        pushq %rbp\n        movq %rsp, %rbp\n        subq ${}, %rsp\n        \
+             movq ${}, -8(%rbp)\n        movq %rdi, {}",
+            name,
+            frame,
+            self.max_slots - 1,
+            SELF
+        )
+        .unwrap();
+        for i in 0..params {
+            match ARG_REGISTERS.get(i) {
+                Some(register) => writeln!(
+                    output,
+                    "        movq {}, {}(%rbp)",
+                    register,
+                    param_offset(i)
+                )
+                .unwrap(),
+                // The arguments passed on the stack are above the return address
+                None => writeln!(
+                    output,
+                    "        movq {}(%rbp), %rax\n        movq %rax, {}(%rbp)",
+                    16 + 8 * (i - ARG_REGISTERS.len()),
+                    param_offset(i)
+                )
+                .unwrap(),
+            }
+        }
+        // The collector may look at the other slots before they're used
+        for slot in params + 3..=self.max_slots {
+            writeln!(output, "        movq $0, {}(%rbp)", -8 * slot as i64).unwrap();
+        }
+        writeln!(output, "{}        leave\n        ret\n", self.body).unwrap();
+    }
+
+    fn instruction(&mut self, text: &str) {
+        writeln!(self.body, "        {}", text).unwrap();
+    }
+
+    fn place(&mut self, label: &str) {
+        writeln!(self.body, "{}:", label).unwrap();
+    }
+
+    /// Reserve a slot of the frame and return its offset from the frame pointer.
+    fn push_slot(&mut self) -> i64 {
+        self.slots += 1;
+        self.max_slots = self.max_slots.max(self.slots);
+
+        -8 * self.slots as i64
+    }
+
+    fn pop_slot(&mut self) {
+        self.slots -= 1;
+    }
+
+    /// Store `%rax` in a new slot and return its offset.
+    fn save(&mut self) -> i64 {
+        let offset = self.push_slot();
+        self.instruction(&format!("movq %rax, {}(%rbp)", offset));
+
+        offset
+    }
+
+    fn load(&mut self, variable: Variable) {
+        match variable {
+            Variable::Frame(offset) => self.instruction(&format!("movq {}(%rbp), %rax", offset)),
+            Variable::Attribute(index) => {
+                self.instruction(&format!("movq {}, %rcx", SELF));
+                self.instruction(&format!("movq {}(%rcx), %rax", FIRST_ATTRIBUTE + 8 * index));
+            }
+        }
+    }
+
+    /// Store `%rax` in `variable`, calling the write barrier of the generational collector after
+    /// storing it in an attribute.
+    fn store(&mut self, variable: Variable) {
+        match variable {
+            Variable::Frame(offset) => self.instruction(&format!("movq %rax, {}(%rbp)", offset)),
+            Variable::Attribute(index) => {
+                let offset = FIRST_ATTRIBUTE + 8 * index;
+                self.instruction(&format!("movq {}, %rcx", SELF));
+                self.instruction(&format!("movq %rax, {}(%rcx)", offset));
+                if self.emitter.gc.generational {
+                    self.instruction(&format!("leaq {}(%rcx), %rdi", offset));
+                    self.instruction("call _GenGC_Assign");
+                }
+            }
+        }
+    }
+
+    /// Call `handler` with the location of `line` if `%rax`, the value of `expr`, is void.
+    ///
+    /// `self`, constants and new objects are never void.
+    fn check_void(&mut self, expr: &Expr, handler: &str, line: usize) {
+        match &expr.kind {
+            ExprKind::Object(name) if *name == sym::SELF => return,
+            ExprKind::IntConst(_)
+            | ExprKind::StringConst(_)
+            | ExprKind::BoolConst(_)
+            | ExprKind::New(_) => return,
+            _ => {}
+        }
+
+        let label = self.emitter.label();
+        self.instruction("testq %rax, %rax");
+        self.instruction(&format!("jnz {}", label));
+        self.instruction(&format!("leaq {}(%rip), %rdi", self.file));
+        self.instruction(&format!("movl ${}, %esi", line));
+        self.instruction(&format!("call {}", handler));
+        self.place(&label);
+    }
+
+    fn dispatch(&mut self, receiver: &Expr, args: &[Expr], callee: Callee) {
+        // Like in the reference compiler, the arguments are evaluated before the receiver
+        let mut offsets = vec![];
+        for arg in args {
+            self.visit_expr(arg);
+            offsets.push(self.save());
+        }
+        self.visit_expr(receiver);
+        self.check_void(receiver, "cool_dispatch_void", self.line);
+        // The runtime's methods refer to the receiver while they allocate
+        self.save();
+
+        self.instruction("movq %rax, %rdi");
+        for (offset, register) in offsets.iter().zip(ARG_REGISTERS) {
+            self.instruction(&format!("movq {}(%rbp), {}", offset, register));
+        }
+        let stack_args = &offsets[offsets.len().min(ARG_REGISTERS.len())..];
+        // Keep the stack aligned to 16 bytes
+        let padding = stack_args.len() % 2;
+        if padding == 1 {
+            self.instruction("subq $8, %rsp");
+        }
+        for offset in stack_args.iter().rev() {
+            self.instruction(&format!("pushq {}(%rbp)", offset));
+        }
+        match callee {
+            Callee::Function(function) => self.instruction(&format!("call {}", function)),
+            Callee::Slot(slot) => {
+                self.instruction("movq 8(%rdi), %rax");
+                self.instruction(&format!("call *{}(%rax)", 8 * slot));
+            }
+        }
+        if !stack_args.is_empty() {
+            self.instruction(&format!("addq ${}, %rsp", 8 * (stack_args.len() + padding)));
+        }
+
+        for _ in 0..=args.len() {
+            self.pop_slot();
+        }
+    }
+
+    /// The default value of a variable of type `ty` in `%rax`.
+    fn default(&mut self, ty: Symbol) {
+        match ty {
+            sym::INT => {
+                let zero = self.emitter.int_constant(0);
+                self.instruction(&format!("leaq {}(%rip), %rax", zero));
+            }
+            sym::STRING => {
+                let empty = self.emitter.string_constant(Symbol::intern(""));
+                self.instruction(&format!("leaq {}(%rip), %rax", empty));
+            }
+            sym::BOOL => self.instruction("leaq cool_false(%rip), %rax"),
+            _ => self.instruction("xorl %eax, %eax"),
+        }
+    }
+
+    /// Emit `body` with `name` bound to a new variable holding `%rax`.
+    fn bind(&mut self, name: Symbol, body: &Expr) {
+        let offset = self.save();
+        self.scope.enter_scope();
+        self.scope.add(name, Variable::Frame(offset));
+        self.visit_expr(body);
+        self.scope.exit_scope();
+        self.pop_slot();
+    }
+
+    /// Jump to `label` if the `Bool` in `%rax` is false.
+    fn jump_if_false(&mut self, label: &str) {
+        self.instruction("cmpl $0, 16(%rax)");
+        self.instruction(&format!("je {}", label));
+    }
+}
+
+impl Visitor for Function<'_, '_> {
+    /// Emit the instructions that leave the value of `expr` in `%rax`.
+    fn visit_expr(&mut self, expr: &Expr) {
+        let line = mem::replace(&mut self.line, expr.line);
+        visit::walk_expr(self, expr);
+        self.line = line;
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        self.visit_expr(&assign.expr);
+        let target = *self
+            .scope
+            .lookup(assign.name)
+            .unwrap_or_else(|| panic!("Assignment to undeclared {}", assign.name));
+        self.store(target);
+    }
+
+    fn visit_static_dispatch(&mut self, dispatch: &StaticDispatch) {
+        let entry = self
+            .emitter
+            .layouts
+            .method(dispatch.type_name, dispatch.name);
+        let function = function_name(&self.emitter.layouts, entry);
+        self.dispatch(&dispatch.expr, &dispatch.args, Callee::Function(function));
+    }
+
+    fn visit_dispatch(&mut self, dispatch: &Dispatch) {
+        let ty = dispatch.expr.ty.expect("Untyped receiver");
+        let slot = self
+            .emitter
+            .layouts
+            .of_type(ty, self.class)
+            .slot(dispatch.name);
+        self.dispatch(&dispatch.expr, &dispatch.args, Callee::Slot(slot));
+    }
+
+    fn visit_cond(&mut self, cond: &Cond) {
+        let else_label = self.emitter.label();
+        let end = self.emitter.label();
+
+        self.visit_expr(&cond.pred);
+        self.jump_if_false(&else_label);
+        self.visit_expr(&cond.then_expr);
+        self.instruction(&format!("jmp {}", end));
+        self.place(&else_label);
+        self.visit_expr(&cond.else_expr);
+        self.place(&end);
+    }
+
+    fn visit_loop(&mut self, loop_: &Loop) {
+        let start = self.emitter.label();
+        let end = self.emitter.label();
+
+        self.place(&start);
+        self.visit_expr(&loop_.pred);
+        self.jump_if_false(&end);
+        self.visit_expr(&loop_.body);
+        self.instruction(&format!("jmp {}", start));
+        self.place(&end);
+        self.instruction("xorl %eax, %eax");
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        if let_.init.is_no_expr() {
+            self.default(let_.type_decl);
+        } else {
+            self.visit_expr(&let_.init);
+        }
+
+        self.bind(let_.name, &let_.body);
+    }
+
+    fn visit_binary(&mut self, binary: &Binary) {
+        self.visit_expr(&binary.lhs);
+        let lhs = self.save();
+        self.visit_expr(&binary.rhs);
+        self.instruction(&format!("movq {}(%rbp), %rcx", lhs));
+        self.pop_slot();
+
+        match binary.op {
+            BinaryOp::Eq => {
+                self.instruction("movq %rcx, %rdi");
+                self.instruction("movq %rax, %rsi");
+                self.instruction("call cool_equal");
+                self.instruction("movl %eax, %edi");
+                self.instruction("call cool_bool");
+            }
+            // Arithmetic wraps around like in the reference runtime
+            BinaryOp::Plus | BinaryOp::Sub | BinaryOp::Mul => {
+                let instruction = match binary.op {
+                    BinaryOp::Plus => "addl",
+                    BinaryOp::Sub => "subl",
+                    _ => "imull",
+                };
+                self.instruction("movl 16(%rcx), %edi");
+                self.instruction(&format!("{} 16(%rax), %edi", instruction));
+                self.instruction("call cool_int");
+            }
+            BinaryOp::Divide => {
+                self.instruction("movl 16(%rcx), %edi");
+                self.instruction("movl 16(%rax), %esi");
+                self.instruction(&format!("leaq {}(%rip), %rdx", self.file));
+                self.instruction(&format!("movl ${}, %ecx", self.line));
+                self.instruction("call cool_divide");
+                self.instruction("movl %eax, %edi");
+                self.instruction("call cool_int");
+            }
+            BinaryOp::Lt | BinaryOp::Leq => {
+                let set = if binary.op == BinaryOp::Lt {
+                    "setl"
+                } else {
+                    "setle"
+                };
+                self.instruction("movl 16(%rcx), %edx");
+                self.instruction("xorl %edi, %edi");
+                self.instruction("cmpl 16(%rax), %edx");
+                self.instruction(&format!("{} %dil", set));
+                self.instruction("call cool_bool");
+            }
+        }
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) {
+        self.visit_expr(&unary.expr);
+        match unary.op {
+            UnaryOp::Neg => {
+                self.instruction("movl 16(%rax), %edi");
+                self.instruction("negl %edi");
+                self.instruction("call cool_int");
+            }
+            UnaryOp::Comp => {
+                self.instruction("xorl %edi, %edi");
+                self.instruction("cmpl $0, 16(%rax)");
+                self.instruction("sete %dil");
+                self.instruction("call cool_bool");
+            }
+            UnaryOp::IsVoid => {
+                self.instruction("xorl %edi, %edi");
+                self.instruction("testq %rax, %rax");
+                self.instruction("sete %dil");
+                self.instruction("call cool_bool");
+            }
+        }
+    }
+
+    fn visit_int_const(&mut self, digits: Symbol) {
+        let value = digits.as_str().bytes().fold(0i32, |value, digit| {
+            value.wrapping_mul(10).wrapping_add(i32::from(digit - b'0'))
+        });
+        let label = self.emitter.int_constant(value);
+        self.instruction(&format!("leaq {}(%rip), %rax", label));
+    }
+
+    fn visit_bool_const(&mut self, value: bool) {
+        if value {
+            self.instruction("leaq cool_true(%rip), %rax");
+        } else {
+            self.instruction("leaq cool_false(%rip), %rax");
+        }
+    }
+
+    fn visit_string_const(&mut self, value: Symbol) {
+        let label = self.emitter.string_constant(value);
+        self.instruction(&format!("leaq {}(%rip), %rax", label));
+    }
+
+    fn visit_new(&mut self, type_name: Symbol) {
+        if type_name == sym::SELF_TYPE {
+            self.instruction(&format!("movq {}, %rax", SELF));
+            self.instruction("movl (%rax), %edi");
+            self.instruction("call cool_new");
+            self.instruction("movq %rax, %rdi");
+            self.instruction("movl (%rax), %ecx");
+            self.instruction("leaq cool_class_inits(%rip), %rdx");
+            self.instruction("call *(%rdx,%rcx,8)");
+        } else {
+            let tag = self.emitter.layouts.get(type_name).tag;
+            self.instruction(&format!("movl ${}, %edi", tag));
+            self.instruction("call cool_new");
+            self.instruction("movq %rax, %rdi");
+            self.instruction(&format!("call {}._init", type_name));
+        }
+    }
+
+    fn visit_no_expr(&mut self) {
+        self.instruction("xorl %eax, %eax");
+    }
+
+    fn visit_object(&mut self, name: Symbol) {
+        if name == sym::SELF {
+            self.instruction(&format!("movq {}, %rax", SELF));
+            return;
+        }
+
+        let variable = *self
+            .scope
+            .lookup(name)
+            .unwrap_or_else(|| panic!("Undeclared identifier {}", name));
+        self.load(variable);
+    }
+
+    /// Emit a `case`, which walks up the class hierarchy from the class of the value until it
+    /// finds a class one of the branches matches.
+    fn visit_case(&mut self, case: &Case) {
+        self.visit_expr(&case.expr);
+        self.check_void(&case.expr, "cool_case_void", self.line);
+        let value = self.save();
+
+        let labels: Vec<_> = case
+            .branches
+            .iter()
+            .map(|branch| {
+                (
+                    self.emitter.layouts.get(branch.type_decl).tag,
+                    self.emitter.label(),
+                )
+            })
+            .collect();
+        let parent = self.emitter.label();
+        let end = self.emitter.label();
+
+        self.instruction("movl (%rax), %ecx");
+        self.place(&parent);
+        for (tag, label) in &labels {
+            self.instruction(&format!("cmpl ${}, %ecx", tag));
+            self.instruction(&format!("je {}", label));
+        }
+        self.instruction("leaq cool_class_parents(%rip), %rdx");
+        self.instruction("movl (%rdx,%rcx,4), %ecx");
+        self.instruction("cmpl $-1, %ecx");
+        self.instruction(&format!("jne {}", parent));
+        self.instruction(&format!("movq {}(%rbp), %rdi", value));
+        self.instruction("call cool_case_no_match");
+
+        for (branch, (_, label)) in case.branches.iter().zip(&labels) {
+            self.place(label);
+            // The branch's variable is the slot holding the value
+            self.scope.enter_scope();
+            self.scope.add(branch.name, Variable::Frame(value));
+            self.visit_expr(&branch.expr);
+            self.scope.exit_scope();
+            self.instruction(&format!("jmp {}", end));
+        }
+        self.place(&end);
+        self.pop_slot();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn emit_program_with_gc(input: &str, options: GcOptions) -> String {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        emit_with_gc(&program, &table, options)
+    }
+
+    fn emit_program(input: &str) -> String {
+        emit_program_with_gc(input, GcOptions::default())
+    }
+
+    #[test]
+    fn test_asm_string() {
+        assert_eq!(asm_string(b"a \"b\"\n\\"), "\"a \\\"b\\\"\\012\\\\\"");
+    }
+
+    #[test]
+    fn test_emit() {
+        let asm = emit_program(
+            "class A { a : Int; f() : Int { a }; };\n\
+             class Main inherits A { b : String <- \"b\"; f() : Int { 1 }; main() : Object { f() }; };\n",
+        );
+
+        assert!(asm.contains(
+            "Main._vtable:\n        .quad Object_abort, Object_type_name, Object_copy, Main.f, \
+             Main.main"
+        ));
+        assert!(asm.contains("Main._proto:\n        .long 6, 32\n        .quad Main._vtable"));
+        assert!(asm.contains("Main._init:\n        pushq %rbp"));
+        assert!(asm.contains(
+            "Main._init:\n        pushq %rbp\n        movq %rsp, %rbp\n        subq $16, %rsp\n        \
+             movq $1, -8(%rbp)\n        movq %rdi, -16(%rbp)\n        movq -16(%rbp), %rdi\n        \
+             call A._init"
+        ));
+        assert!(asm.contains("cool_class_parents:\n        .long -1, 0, 0, 0, 0, 0, 5"));
+        assert!(asm.contains("cool_main:"));
+    }
+
+    #[test]
+    fn test_generational_gc() {
+        let input = "class Main { a : Int <- 1; main() : Object { a <- 2 }; };\n";
+        let store = "movq -16(%rbp), %rcx\n        movq %rax, 16(%rcx)\n";
+        let barrier = "leaq 16(%rcx), %rdi\n        call _GenGC_Assign\n";
+
+        let asm = emit_program(input);
+        assert!(asm.contains(store));
+        assert!(!asm.contains("_GenGC_Assign"));
+        assert!(asm.contains("_MemMgr_INITIALIZER:\n        .quad _NoGC_Init"));
+        assert!(asm.contains("_MemMgr_COLLECTOR:\n        .quad _NoGC_Collect"));
+        assert!(asm.contains("_MemMgr_TEST:\n        .quad 0"));
+
+        let options = GcOptions {
+            generational: true,
+            stress: true,
+        };
+        let asm = emit_program_with_gc(input, options);
+        // Both the initializer and `main` assign `a`
+        assert_eq!(
+            asm.matches(&format!("{}        {}", store, barrier))
+                .count(),
+            2
+        );
+        assert!(asm.contains("_MemMgr_INITIALIZER:\n        .quad _GenGC_Init"));
+        assert!(asm.contains("_MemMgr_COLLECTOR:\n        .quad _GenGC_Collect"));
+        assert!(asm.contains("_MemMgr_TEST:\n        .quad 1"));
+        // Every constant object is preceded by its GC tag word
+        for label in &["Main._proto:", "cool_true:", "int.0:", "str.0:"] {
+            assert!(
+                asm.contains(&format!("        .quad -1\n{}", label)),
+                "{}",
+                label
+            );
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! The abstract syntax tree of COOL programs.
+//!
+//! The structure follows the reference compiler's `cool-tree`. Every node records the line it
+//! starts on and expressions have room for the type the type checker infers for them.
+
+use crate::Symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Program {
+    pub classes: Vec<Class>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Class {
+    pub name: Symbol,
+    /// The parent class, `Object` when the class has no `inherits` clause.
+    pub parent: Symbol,
+    pub features: Vec<Feature>,
+    /// The file the class was defined in.
+    pub file_name: Symbol,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Feature {
+    Method(Method),
+    Attribute(Attribute),
+}
+
+impl Feature {
+    pub fn name(&self) -> Symbol {
+        match self {
+            Self::Method(method) => method.name,
+            Self::Attribute(attribute) => attribute.name,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Method(method) => method.line,
+            Self::Attribute(attribute) => attribute.line,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Method {
+    pub name: Symbol,
+    pub formals: Vec<Formal>,
+    pub return_type: Symbol,
+    pub body: Expr,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute {
+    pub name: Symbol,
+    pub type_decl: Symbol,
+    /// The initializer, `ExprKind::NoExpr` when there is none.
+    pub init: Expr,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Formal {
+    pub name: Symbol,
+    pub type_decl: Symbol,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CaseBranch {
+    pub name: Symbol,
+    pub type_decl: Symbol,
+    pub expr: Expr,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub line: usize,
+    /// The static type of the expression, `None` until the program has been type checked.
+    pub ty: Option<Symbol>,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, line: usize) -> Self {
+        Self {
+            kind,
+            line,
+            ty: None,
+        }
+    }
+
+    /// The expression used for absent initializers, `no_expr` in the reference compiler.
+    pub fn no_expr(line: usize) -> Self {
+        Self::new(ExprKind::NoExpr, line)
+    }
+
+    pub fn is_no_expr(&self) -> bool {
+        matches!(self.kind, ExprKind::NoExpr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExprKind {
+    Assign(Assign),
+    StaticDispatch(StaticDispatch),
+    Dispatch(Dispatch),
+    Cond(Cond),
+    Loop(Loop),
+    Case(Case),
+    Block(Block),
+    Let(Let),
+    Binary(Binary),
+    Unary(Unary),
+    IntConst(Symbol),
+    BoolConst(bool),
+    StringConst(Symbol),
+    New(Symbol),
+    NoExpr,
+    Object(Symbol),
+}
+
+/// `name <- expr`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Assign {
+    pub name: Symbol,
+    pub expr: Box<Expr>,
+}
+
+/// `expr@type_name.name(args)`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StaticDispatch {
+    pub expr: Box<Expr>,
+    pub type_name: Symbol,
+    pub name: Symbol,
+    pub args: Vec<Expr>,
+}
+
+/// `expr.name(args)`, or `name(args)` which dispatches on `self`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dispatch {
+    pub expr: Box<Expr>,
+    pub name: Symbol,
+    pub args: Vec<Expr>,
+}
+
+/// `if pred then then_expr else else_expr fi`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cond {
+    pub pred: Box<Expr>,
+    pub then_expr: Box<Expr>,
+    pub else_expr: Box<Expr>,
+}
+
+/// `while pred loop body pool`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Loop {
+    pub pred: Box<Expr>,
+    pub body: Box<Expr>,
+}
+
+/// `case expr of branches esac`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Case {
+    pub expr: Box<Expr>,
+    pub branches: Vec<CaseBranch>,
+}
+
+/// `{ body; }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Block {
+    pub body: Vec<Expr>,
+}
+
+/// `let name : type_decl <- init in body`
+///
+/// A `let` with several bindings is represented as nested `Let`s, one per binding.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Let {
+    pub name: Symbol,
+    pub type_decl: Symbol,
+    /// The initializer, `ExprKind::NoExpr` when there is none.
+    pub init: Box<Expr>,
+    pub body: Box<Expr>,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOp {
+    /// `+`
+    Plus,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Divide,
+    /// `<`
+    Lt,
+    /// `=`
+    Eq,
+    /// `<=`
+    Leq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Binary {
+    pub op: BinaryOp,
+    pub lhs: Box<Expr>,
+    pub rhs: Box<Expr>,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnaryOp {
+    /// `~`, integer negation
+    Neg,
+    /// `not`, boolean complement
+    Comp,
+    /// `isvoid`
+    IsVoid,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unary {
+    pub op: UnaryOp,
+    pub expr: Box<Expr>,
+}
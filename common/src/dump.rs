@@ -0,0 +1,321 @@
+//! Printing the AST in the format of the reference compiler's `dump_with_types`.
+//!
+//! This is the format the reference parser and semantic analyzer print the program in. Every
+//! node starts with its line number and every expression ends with its static type, or
+//! `_no_type` before the program has been type checked.
+
+use std::fmt;
+
+use crate::ast::*;
+use crate::visit::{self, Visitor};
+use crate::{escaped_string, Symbol};
+
+/// Displays a program in the reference `dump_with_types` format.
+pub struct Dump<'a>(pub &'a Program);
+
+impl fmt::Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer {
+            f,
+            indent: 0,
+            result: Ok(()),
+        };
+        printer.visit_program(self.0);
+
+        printer.result
+    }
+}
+
+/// Prints every node it visits, the children of a node indented under it.
+struct Printer<'a, 'f> {
+    f: &'a mut fmt::Formatter<'f>,
+    indent: usize,
+    /// Nothing is printed after writing fails.
+    result: fmt::Result,
+}
+
+impl Printer<'_, '_> {
+    fn write(&mut self, args: fmt::Arguments<'_>) {
+        if self.result.is_ok() {
+            self.result = writeln!(self.f, "{:indent$}{}", "", args, indent = self.indent);
+        }
+    }
+
+    fn line(&mut self, line: usize) {
+        self.write(format_args!("#{}", line));
+    }
+
+    fn text(&mut self, text: &str) {
+        self.write(format_args!("{}", text));
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        self.text(symbol.as_str());
+    }
+
+    fn string(&mut self, s: &str) {
+        self.write(format_args!("\"{}\"", escaped_string(s)));
+    }
+
+    /// Print what `f` prints indented one level deeper.
+    fn nested(&mut self, f: impl FnOnce(&mut Self)) {
+        self.indent += 2;
+        f(self);
+        self.indent -= 2;
+    }
+
+    fn args(&mut self, args: &[Expr]) {
+        self.text("(");
+        for arg in args {
+            self.visit_expr(arg);
+        }
+        self.text(")");
+    }
+}
+
+impl Visitor for Printer<'_, '_> {
+    fn visit_program(&mut self, program: &Program) {
+        self.line(program.line);
+        self.text("_program");
+        self.nested(|printer| visit::walk_program(printer, program));
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        self.line(class.line);
+        self.text("_class");
+        self.nested(|printer| {
+            printer.symbol(class.name);
+            printer.symbol(class.parent);
+            printer.string(class.file_name.as_str());
+            printer.text("(");
+            visit::walk_class(printer, class);
+            printer.text(")");
+        });
+    }
+
+    fn visit_method(&mut self, method: &Method) {
+        self.line(method.line);
+        self.text("_method");
+        self.nested(|printer| {
+            printer.symbol(method.name);
+            for formal in &method.formals {
+                printer.visit_formal(formal);
+            }
+            printer.symbol(method.return_type);
+            printer.visit_expr(&method.body);
+        });
+    }
+
+    fn visit_attribute(&mut self, attribute: &Attribute) {
+        self.line(attribute.line);
+        self.text("_attr");
+        self.nested(|printer| {
+            printer.symbol(attribute.name);
+            printer.symbol(attribute.type_decl);
+            printer.visit_expr(&attribute.init);
+        });
+    }
+
+    fn visit_formal(&mut self, formal: &Formal) {
+        self.line(formal.line);
+        self.text("_formal");
+        self.nested(|printer| {
+            printer.symbol(formal.name);
+            printer.symbol(formal.type_decl);
+        });
+    }
+
+    fn visit_case_branch(&mut self, branch: &CaseBranch) {
+        self.line(branch.line);
+        self.text("_branch");
+        self.nested(|printer| {
+            printer.symbol(branch.name);
+            printer.symbol(branch.type_decl);
+            printer.visit_expr(&branch.expr);
+        });
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.line(expr.line);
+        visit::walk_expr(self, expr);
+        match expr.ty {
+            Some(ty) => self.write(format_args!(": {}", ty)),
+            None => self.text(": _no_type"),
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        self.text("_assign");
+        self.nested(|printer| {
+            printer.symbol(assign.name);
+            printer.visit_expr(&assign.expr);
+        });
+    }
+
+    fn visit_static_dispatch(&mut self, dispatch: &StaticDispatch) {
+        self.text("_static_dispatch");
+        self.nested(|printer| {
+            printer.visit_expr(&dispatch.expr);
+            printer.symbol(dispatch.type_name);
+            printer.symbol(dispatch.name);
+            printer.args(&dispatch.args);
+        });
+    }
+
+    fn visit_dispatch(&mut self, dispatch: &Dispatch) {
+        self.text("_dispatch");
+        self.nested(|printer| {
+            printer.visit_expr(&dispatch.expr);
+            printer.symbol(dispatch.name);
+            printer.args(&dispatch.args);
+        });
+    }
+
+    fn visit_cond(&mut self, cond: &Cond) {
+        self.text("_cond");
+        self.nested(|printer| visit::walk_cond(printer, cond));
+    }
+
+    fn visit_loop(&mut self, loop_: &Loop) {
+        self.text("_loop");
+        self.nested(|printer| visit::walk_loop(printer, loop_));
+    }
+
+    fn visit_case(&mut self, case: &Case) {
+        self.text("_typcase");
+        self.nested(|printer| visit::walk_case(printer, case));
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.text("_block");
+        self.nested(|printer| visit::walk_block(printer, block));
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        self.text("_let");
+        self.nested(|printer| {
+            printer.symbol(let_.name);
+            printer.symbol(let_.type_decl);
+            visit::walk_let(printer, let_);
+        });
+    }
+
+    fn visit_binary(&mut self, binary: &Binary) {
+        self.text(match binary.op {
+            BinaryOp::Plus => "_plus",
+            BinaryOp::Sub => "_sub",
+            BinaryOp::Mul => "_mul",
+            BinaryOp::Divide => "_divide",
+            BinaryOp::Lt => "_lt",
+            BinaryOp::Eq => "_eq",
+            BinaryOp::Leq => "_leq",
+        });
+        self.nested(|printer| visit::walk_binary(printer, binary));
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) {
+        self.text(match unary.op {
+            UnaryOp::Neg => "_neg",
+            UnaryOp::Comp => "_comp",
+            UnaryOp::IsVoid => "_isvoid",
+        });
+        self.nested(|printer| visit::walk_unary(printer, unary));
+    }
+
+    fn visit_int_const(&mut self, value: Symbol) {
+        self.text("_int");
+        self.nested(|printer| printer.symbol(value));
+    }
+
+    fn visit_bool_const(&mut self, value: bool) {
+        self.text("_bool");
+        self.nested(|printer| printer.text(if value { "1" } else { "0" }));
+    }
+
+    fn visit_string_const(&mut self, value: Symbol) {
+        self.text("_string");
+        self.nested(|printer| printer.string(value.as_str()));
+    }
+
+    fn visit_new(&mut self, type_name: Symbol) {
+        self.text("_new");
+        self.nested(|printer| printer.symbol(type_name));
+    }
+
+    fn visit_no_expr(&mut self) {
+        self.text("_no_expr");
+    }
+
+    fn visit_object(&mut self, name: Symbol) {
+        self.text("_object");
+        self.nested(|printer| printer.symbol(name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let mut body = Expr::new(
+            ExprKind::Dispatch(Dispatch {
+                expr: Box::new(Expr::new(ExprKind::Object(Symbol::intern("self")), 3)),
+                name: Symbol::intern("out_string"),
+                args: vec![Expr::new(
+                    ExprKind::StringConst(Symbol::intern("Hello,\tworld\n")),
+                    3,
+                )],
+            }),
+            3,
+        );
+        body.ty = Some(Symbol::intern("SELF_TYPE"));
+
+        let program = Program {
+            classes: vec![Class {
+                name: Symbol::intern("Main"),
+                parent: Symbol::intern("IO"),
+                features: vec![Feature::Method(Method {
+                    name: Symbol::intern("main"),
+                    formals: vec![],
+                    return_type: Symbol::intern("Object"),
+                    body,
+                    line: 2,
+                })],
+                file_name: Symbol::intern("hello.cl"),
+                line: 1,
+            }],
+            line: 1,
+        };
+
+        let expected = r#"#1
+_program
+  #1
+  _class
+    Main
+    IO
+    "hello.cl"
+    (
+    #2
+    _method
+      main
+      Object
+      #3
+      _dispatch
+        #3
+        _object
+          self
+        : _no_type
+        out_string
+        (
+        #3
+        _string
+          "Hello,\tworld\n"
+        : _no_type
+        )
+      : SELF_TYPE
+    )
+"#;
+        assert_eq!(Dump(&program).to_string(), expected);
+    }
+}
@@ -1,6 +1,23 @@
+pub mod ast;
+pub mod dump;
+mod line_index;
+mod symbol;
+mod symbol_table;
+mod timing;
+pub mod visit;
+
 use std::fmt;
 
-fn escaped_string(s: &str) -> String {
+pub use crate::line_index::{LineIndex, Position};
+pub use crate::symbol::{sym, Symbol};
+pub use crate::symbol_table::SymbolTable;
+pub use crate::timing::PassTimings;
+
+/// Escape `s` the way the reference lexer prints string constants and errors.
+///
+/// Characters outside printable ASCII are written as one three digit octal escape per byte of
+/// their UTF-8 encoding.
+pub(crate) fn escaped_string(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
 
     for c in s.chars() {
@@ -12,7 +29,11 @@ fn escaped_string(s: &str) -> String {
             '\x08' => result.push_str("\\b"),
             '\x0C' => result.push_str("\\f"),
             c if c.is_ascii() && !c.is_control() => result.push(c),
-            c => result.push_str(&format!("\\{:03o}", c as u32)),
+            c => {
+                for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                    result.push_str(&format!("\\{:03o}", byte));
+                }
+            }
         }
     }
 
@@ -20,6 +41,7 @@ fn escaped_string(s: &str) -> String {
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeywordKind {
     Class,
     Else,
@@ -65,13 +87,14 @@ impl fmt::Display for KeywordKind {
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     // Any sequence of space(ascii 32), \n(10), \f(12), \r(13), \t(9), \v(11)
     Whitespace,
-    ObjectId(String),
-    TypeId(String),
-    Int(String),
-    String(String),
+    ObjectId(Symbol),
+    TypeId(Symbol),
+    Int(Symbol),
+    String(Symbol),
     Bool(bool),
 
     // Comments
@@ -139,7 +162,7 @@ impl fmt::Display for TokenKind {
             Self::ObjectId(s) => write!(f, "OBJECTID {}", s),
             Self::TypeId(s) => write!(f, "TYPEID {}", s),
             Self::Int(v) => write!(f, "INT_CONST {}", v),
-            Self::String(s) => write!(f, "STR_CONST \"{}\"", escaped_string(s)),
+            Self::String(s) => write!(f, "STR_CONST \"{}\"", escaped_string(s.as_str())),
             Self::Bool(b) => write!(f, "BOOL_CONST {}", b),
 
             Self::LineComment => write!(f, ""),
@@ -167,7 +190,7 @@ impl fmt::Display for TokenKind {
             Self::At => write!(f, "'@'"),
             Self::SemiColon => write!(f, "';'"),
             Self::Error(reason) => {
-                if &reason[0..1] == "\0" {
+                if reason.starts_with('\0') {
                     write!(f, "ERROR \"\\000\"")
                 } else {
                     write!(f, "ERROR \"{}\"", escaped_string(reason))
@@ -177,9 +200,15 @@ impl fmt::Display for TokenKind {
     }
 }
 
+/// A token, borrowing the source text it was lexed from.
+///
+/// With the `serde` feature a token can be serialized, but not deserialized since the source text
+/// isn't part of the serialized form.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Token<'s> {
     pub kind: TokenKind,
     pub length: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
     source: &'s str,
 }
 
@@ -195,6 +224,20 @@ impl<'s> Token<'s> {
     pub fn as_str(&self) -> &str {
         &self.source[..self.length]
     }
+
+    /// The byte offset of this token in `input`, the source text it was lexed from.
+    ///
+    /// Returns `None` if the token doesn't point into `input`.
+    pub fn offset_in(&self, input: &str) -> Option<usize> {
+        let start = input.as_ptr() as usize;
+        let token = self.source.as_ptr() as usize;
+
+        if token < start || token + self.length > start + input.len() {
+            return None;
+        }
+
+        Some(token - start)
+    }
 }
 
 impl<'s> fmt::Display for Token<'s> {
@@ -212,13 +255,41 @@ impl<'s> fmt::Debug for Token<'s> {
     }
 }
 pub mod prelude {
-    pub use super::{KeywordKind, Token, TokenKind};
+    pub use super::{KeywordKind, Symbol, SymbolTable, Token, TokenKind};
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn test_offset_in() {
+        let input = "class Main";
+        let token = Token::new(TokenKind::TypeId(Symbol::intern("Main")), 4, &input[6..]);
+
+        assert_eq!(token.offset_in(input), Some(6));
+        assert_eq!(token.offset_in(&input[..8]), None);
+        let copy = String::from(input);
+        assert_eq!(token.offset_in(&copy), None);
+    }
+
+    #[test]
+    fn test_display_non_ascii() {
+        let s = Symbol::intern("\u{e9}\u{20ac}\x01");
+
+        assert_eq!(
+            TokenKind::String(s).to_string(),
+            "STR_CONST \"\\303\\251\\342\\202\\254\\001\""
+        );
+    }
+
+    #[test]
+    fn test_display_empty_error() {
+        assert_eq!(TokenKind::Error(String::new()).to_string(), "ERROR \"\"");
+    }
 }
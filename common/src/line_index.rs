@@ -0,0 +1,109 @@
+use crate::Token;
+
+/// A position in source text as used by editors.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    /// The line, starting at 1 like the line numbers in the lexer output.
+    pub line: usize,
+    /// The column in UTF-16 code units, starting at 0, so characters outside the Basic
+    /// Multilingual Plane count as two.
+    pub column: usize,
+}
+
+/// Maps byte offsets in source text to lines and UTF-16 columns.
+pub struct LineIndex<'s> {
+    source: &'s str,
+    line_starts: Vec<usize>,
+}
+
+impl<'s> LineIndex<'s> {
+    pub fn new(source: &'s str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The position of the byte `offset`.
+    ///
+    /// Returns `None` if `offset` is out of bounds or doesn't fall on a character boundary.
+    pub fn position(&self, offset: usize) -> Option<Position> {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let prefix = self.source.get(self.line_starts[line]..offset)?;
+
+        Some(Position {
+            line: line + 1,
+            column: prefix.encode_utf16().count(),
+        })
+    }
+
+    /// The position where `token` starts, if it was lexed from the indexed source.
+    pub fn token_position(&self, token: &Token<'_>) -> Option<Position> {
+        self.position(token.offset_in(self.source)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Symbol, TokenKind};
+
+    #[test]
+    fn test_position() {
+        let index = LineIndex::new("ab\n\u{e9}\u{1f600}x\n");
+
+        assert_eq!(index.position(0), Some(Position { line: 1, column: 0 }));
+        assert_eq!(index.position(3), Some(Position { line: 2, column: 0 }));
+        assert_eq!(index.position(9), Some(Position { line: 2, column: 3 }));
+        assert_eq!(index.position(11), Some(Position { line: 3, column: 0 }));
+        assert_eq!(index.position(4), None);
+        assert_eq!(index.position(12), None);
+    }
+
+    #[test]
+    fn test_multi_byte_utf8() {
+        // 2, 3 and 3 bytes in UTF-8, but one UTF-16 code unit each
+        let source = "\u{e9}\u{20ac}\u{4e2d}x";
+        let index = LineIndex::new(source);
+
+        assert_eq!(index.position(2), Some(Position { line: 1, column: 1 }));
+        assert_eq!(index.position(5), Some(Position { line: 1, column: 2 }));
+        assert_eq!(index.position(8), Some(Position { line: 1, column: 3 }));
+        assert_eq!(index.position(9), Some(Position { line: 1, column: 4 }));
+        // Inside the euro sign
+        assert_eq!(index.position(3), None);
+        assert_eq!(index.position(4), None);
+    }
+
+    #[test]
+    fn test_surrogate_pairs() {
+        // Both are outside the Basic Multilingual Plane: 4 bytes in UTF-8 and a surrogate pair
+        // in UTF-16
+        let source = "\"\u{1f600}\u{10348}\" x";
+        let index = LineIndex::new(source);
+
+        assert_eq!(index.position(1), Some(Position { line: 1, column: 1 }));
+        assert_eq!(index.position(5), Some(Position { line: 1, column: 3 }));
+        assert_eq!(index.position(9), Some(Position { line: 1, column: 5 }));
+        assert_eq!(index.position(11), Some(Position { line: 1, column: 7 }));
+        for offset in 2..5 {
+            assert_eq!(index.position(offset), None);
+        }
+
+        let token = Token::new(TokenKind::ObjectId(Symbol::intern("x")), 1, &source[11..]);
+        assert_eq!(
+            index.token_position(&token),
+            Some(Position { line: 1, column: 7 })
+        );
+        let elsewhere = String::from(source);
+        assert_eq!(LineIndex::new(&elsewhere).token_position(&token), None);
+    }
+}
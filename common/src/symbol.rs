@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned string.
+///
+/// Identifiers, integer constants and string constants are interned once and then referred to by
+/// a `Symbol`, which is cheap to copy, hash and compare. Two symbols are equal if and only if the
+/// strings they were interned from are equal.
+///
+/// All symbols live in a single process wide table, unlike the reference compiler which keeps
+/// separate `idtable`, `inttable` and `stringtable`. The `TokenKind` (and later the AST node) a
+/// symbol is stored in tells which kind of entry it is.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Intern `s` and return its symbol.
+    pub fn intern(s: &str) -> Self {
+        with_interner(|interner| interner.intern(s))
+    }
+
+    /// The string this symbol was interned from.
+    pub fn as_str(self) -> &'static str {
+        with_interner(|interner| interner.get(self))
+    }
+
+    /// The index of this symbol in the table.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Self::intern(s)
+    }
+}
+
+/// Symbols are serialized as the string they were interned from, since the index is only
+/// meaningful within one process.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+
+        Ok(Self::intern(&s))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symbol({:?})", self.as_str())
+    }
+}
+
+/// Symbols for names the compiler refers to, interned before any other strings.
+pub mod sym {
+    use super::Symbol;
+
+    pub const OBJECT: Symbol = Symbol(0);
+    pub const IO: Symbol = Symbol(1);
+    pub const INT: Symbol = Symbol(2);
+    pub const STRING: Symbol = Symbol(3);
+    pub const BOOL: Symbol = Symbol(4);
+    pub const SELF_TYPE: Symbol = Symbol(5);
+    pub const MAIN: Symbol = Symbol(6);
+
+    pub const SELF: Symbol = Symbol(7);
+    pub const MAIN_METHOD: Symbol = Symbol(8);
+    pub const ABORT: Symbol = Symbol(9);
+    pub const TYPE_NAME: Symbol = Symbol(10);
+    pub const COPY: Symbol = Symbol(11);
+    pub const OUT_STRING: Symbol = Symbol(12);
+    pub const OUT_INT: Symbol = Symbol(13);
+    pub const IN_STRING: Symbol = Symbol(14);
+    pub const IN_INT: Symbol = Symbol(15);
+    pub const LENGTH: Symbol = Symbol(16);
+    pub const CONCAT: Symbol = Symbol(17);
+    pub const SUBSTR: Symbol = Symbol(18);
+    pub const ARG: Symbol = Symbol(19);
+    pub const ARG2: Symbol = Symbol(20);
+
+    pub(super) const PREDEFINED: [&str; 21] = [
+        "Object",
+        "IO",
+        "Int",
+        "String",
+        "Bool",
+        "SELF_TYPE",
+        "Main",
+        "self",
+        "main",
+        "abort",
+        "type_name",
+        "copy",
+        "out_string",
+        "out_int",
+        "in_string",
+        "in_int",
+        "length",
+        "concat",
+        "substr",
+        "arg",
+        "arg2",
+    ];
+}
+
+/// The table of interned strings behind `Symbol`.
+///
+/// Interned strings are leaked and live for the remainder of the program, this lets
+/// `Symbol::as_str` hand out `&'static str` without holding on to the table. There is only one
+/// table, since a `Symbol` handed out by any other would resolve against the wrong strings.
+#[derive(Default)]
+struct Interner {
+    names: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.names.get(s) {
+            return symbol;
+        }
+
+        let symbol = Symbol(self.strings.len() as u32);
+        let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+        self.strings.push(s);
+        self.names.insert(s, symbol);
+
+        symbol
+    }
+
+    /// An interner containing the symbols in `sym`.
+    fn with_predefined() -> Self {
+        let mut interner = Self::default();
+        for s in sym::PREDEFINED.iter() {
+            interner.intern(s);
+        }
+
+        interner
+    }
+
+    fn get(&self, symbol: Symbol) -> &'static str {
+        self.strings[symbol.0 as usize]
+    }
+}
+
+fn with_interner<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Interner) -> R,
+{
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+    let mut interner = INTERNER
+        .get_or_init(|| Mutex::new(Interner::with_predefined()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    f(&mut interner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_is_idempotent() {
+        let a = Symbol::intern("main");
+        let b = Symbol::intern("main");
+        let c = Symbol::intern("Main");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.as_str(), "main");
+        assert_eq!(c.as_str(), "Main");
+    }
+
+    #[test]
+    fn test_predefined() {
+        for (i, s) in sym::PREDEFINED.iter().enumerate() {
+            assert_eq!(Symbol(i as u32).as_str(), *s);
+        }
+
+        assert_eq!(Symbol::intern("SELF_TYPE"), sym::SELF_TYPE);
+        assert_eq!(sym::ARG2.as_str(), "arg2");
+    }
+
+    #[test]
+    fn test_symbols_resolve_across_threads() {
+        let a = Symbol::intern("\"hello\"");
+        let b = std::thread::spawn(|| Symbol::intern("\"hello\""))
+            .join()
+            .unwrap();
+
+        assert_eq!(a, b);
+        assert_eq!(b.as_str(), "\"hello\"");
+    }
+}
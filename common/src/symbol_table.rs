@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::Symbol;
+
+/// A table of the identifiers in scope and what they're bound to, like the reference compiler's
+/// `SymbolTable`.
+///
+/// Scopes nest: an identifier added in an inner scope shadows the same identifier in the scopes
+/// around it until the inner scope is exited.
+#[derive(Debug, Clone)]
+pub struct SymbolTable<T> {
+    /// The innermost scope is last.
+    scopes: Vec<HashMap<Symbol, T>>,
+}
+
+impl<T> SymbolTable<T> {
+    /// A table with no scopes. [`enter_scope`](Self::enter_scope) must be called before anything
+    /// can be added.
+    pub fn new() -> Self {
+        Self { scopes: vec![] }
+    }
+
+    pub fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Exit the innermost scope, forgetting everything added to it.
+    ///
+    /// # Panics
+    ///
+    /// If there's no scope to exit.
+    pub fn exit_scope(&mut self) {
+        self.scopes.pop().expect("No scope to exit");
+    }
+
+    /// The number of scopes entered and not yet exited.
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Bind `name` to `value` in the innermost scope, replacing what it was bound to in that
+    /// scope.
+    ///
+    /// # Panics
+    ///
+    /// If no scope has been entered.
+    pub fn add(&mut self, name: Symbol, value: T) {
+        self.scopes
+            .last_mut()
+            .expect("No scope to add to")
+            .insert(name, value);
+    }
+
+    /// What `name` is bound to in the innermost scope that binds it.
+    pub fn lookup(&self, name: Symbol) -> Option<&T> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    /// What `name` is bound to in the innermost scope, ignoring the scopes around it.
+    pub fn probe(&self, name: Symbol) -> Option<&T> {
+        self.scopes.last()?.get(&name)
+    }
+}
+
+impl<T> Default for SymbolTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        let (x, y) = (Symbol::intern("x"), Symbol::intern("y"));
+        let mut table = SymbolTable::new();
+        assert_eq!(table.lookup(x), None);
+        assert_eq!(table.probe(x), None);
+
+        table.enter_scope();
+        table.add(x, 1);
+        table.add(y, 2);
+        table.enter_scope();
+        assert_eq!(table.lookup(x), Some(&1));
+        assert_eq!(table.probe(x), None);
+
+        table.add(x, 3);
+        assert_eq!(table.lookup(x), Some(&3));
+        assert_eq!(table.probe(x), Some(&3));
+        assert_eq!(table.lookup(y), Some(&2));
+        assert_eq!(table.depth(), 2);
+
+        table.exit_scope();
+        assert_eq!(table.lookup(x), Some(&1));
+        assert_eq!(table.depth(), 1);
+    }
+}
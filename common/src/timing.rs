@@ -0,0 +1,121 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Wall clock time spent in each compiler pass, as reported by `--time-passes`.
+///
+/// Time is accumulated per pass name, across files and threads, and reported in the order the
+/// passes first ran. The total is the wall clock time since the timings were created, when files
+/// are processed concurrently the passes add up to more than that.
+pub struct PassTimings {
+    passes: Mutex<Vec<(&'static str, Duration)>>,
+    started: Instant,
+}
+
+impl Default for PassTimings {
+    fn default() -> Self {
+        Self {
+            passes: Mutex::default(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl PassTimings {
+    /// Run `f`, attributing the time it takes to `pass`.
+    pub fn time<F, T>(&self, pass: &'static str, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let result = f();
+        self.record(pass, start.elapsed());
+
+        result
+    }
+
+    pub fn record(&self, pass: &'static str, duration: Duration) {
+        let mut passes = self
+            .passes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match passes.iter_mut().find(|(name, _)| *name == pass) {
+            Some((_, total)) => *total += duration,
+            None => passes.push((pass, duration)),
+        }
+    }
+
+    pub fn get(&self, pass: &str) -> Option<Duration> {
+        let passes = self
+            .passes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        passes
+            .iter()
+            .find(|(name, _)| *name == pass)
+            .map(|&(_, duration)| duration)
+    }
+
+    /// The wall clock time since the timings were created.
+    pub fn total(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl fmt::Display for PassTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let passes = self
+            .passes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        for (name, duration) in passes.iter() {
+            writeln!(f, "time: {:>10.6}s\t{}", duration.as_secs_f64(), name)?;
+        }
+
+        write!(f, "time: {:>10.6}s\ttotal", self.total().as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings_accumulate() {
+        let timings = PassTimings::default();
+
+        timings.record("lex", Duration::from_millis(2));
+        assert_eq!(timings.time("parse", || 42), 42);
+        timings.record("lex", Duration::from_millis(3));
+
+        assert_eq!(timings.get("lex"), Some(Duration::from_millis(5)));
+        assert!(timings.get("parse").is_some());
+        assert_eq!(timings.get("codegen"), None);
+
+        let report = timings.to_string();
+        let passes: Vec<_> = report
+            .lines()
+            .map(|line| line.rsplit('\t').next().unwrap())
+            .collect();
+        assert_eq!(passes, vec!["lex", "parse", "total"]);
+    }
+
+    #[test]
+    fn test_total_is_wall_time() {
+        let timings = PassTimings::default();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    timings.time("lex", || std::thread::sleep(Duration::from_millis(50)))
+                });
+            }
+        });
+
+        assert!(timings.get("lex").unwrap() >= Duration::from_millis(200));
+        assert!(timings.total() < timings.get("lex").unwrap());
+    }
+}
@@ -0,0 +1,864 @@
+//! Traversal of the AST.
+//!
+//! `Visitor` walks a borrowed tree and `VisitorMut` walks a mutably borrowed one, e.g. to fill in
+//! the types of expressions. `Folder` takes a tree by value and rebuilds it, so it can replace
+//! nodes with different ones, e.g. to simplify expressions. Each `visit_*` or `fold_*` method
+//! defaults to the matching `walk_*` function, which visits the children of the node.
+//! Implementors override the methods for the nodes they are interested in and call the `walk_*`
+//! function themselves when they also want to descend into its children.
+//!
+//! `visit_expr` dispatches to one method per expression variant, so a pass that only cares about
+//! e.g. dispatches only needs to override `visit_dispatch`. Passes that need the line or type of
+//! the expression itself should override `visit_expr` instead.
+
+use std::mem;
+
+use crate::ast::*;
+use crate::Symbol;
+
+pub trait Visitor: Sized {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        walk_class(self, class);
+    }
+
+    fn visit_feature(&mut self, feature: &Feature) {
+        walk_feature(self, feature);
+    }
+
+    fn visit_method(&mut self, method: &Method) {
+        walk_method(self, method);
+    }
+
+    fn visit_attribute(&mut self, attribute: &Attribute) {
+        walk_attribute(self, attribute);
+    }
+
+    fn visit_formal(&mut self, _formal: &Formal) {}
+
+    fn visit_case_branch(&mut self, branch: &CaseBranch) {
+        walk_case_branch(self, branch);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        walk_assign(self, assign);
+    }
+
+    fn visit_static_dispatch(&mut self, dispatch: &StaticDispatch) {
+        walk_static_dispatch(self, dispatch);
+    }
+
+    fn visit_dispatch(&mut self, dispatch: &Dispatch) {
+        walk_dispatch(self, dispatch);
+    }
+
+    fn visit_cond(&mut self, cond: &Cond) {
+        walk_cond(self, cond);
+    }
+
+    fn visit_loop(&mut self, loop_: &Loop) {
+        walk_loop(self, loop_);
+    }
+
+    fn visit_case(&mut self, case: &Case) {
+        walk_case(self, case);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        walk_let(self, let_);
+    }
+
+    fn visit_binary(&mut self, binary: &Binary) {
+        walk_binary(self, binary);
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) {
+        walk_unary(self, unary);
+    }
+
+    fn visit_int_const(&mut self, _value: Symbol) {}
+
+    fn visit_bool_const(&mut self, _value: bool) {}
+
+    fn visit_string_const(&mut self, _value: Symbol) {}
+
+    fn visit_new(&mut self, _type_name: Symbol) {}
+
+    fn visit_no_expr(&mut self) {}
+
+    fn visit_object(&mut self, _name: Symbol) {}
+}
+
+pub fn walk_program<V: Visitor>(visitor: &mut V, program: &Program) {
+    for class in &program.classes {
+        visitor.visit_class(class);
+    }
+}
+
+pub fn walk_class<V: Visitor>(visitor: &mut V, class: &Class) {
+    for feature in &class.features {
+        visitor.visit_feature(feature);
+    }
+}
+
+pub fn walk_feature<V: Visitor>(visitor: &mut V, feature: &Feature) {
+    match feature {
+        Feature::Method(method) => visitor.visit_method(method),
+        Feature::Attribute(attribute) => visitor.visit_attribute(attribute),
+    }
+}
+
+pub fn walk_method<V: Visitor>(visitor: &mut V, method: &Method) {
+    for formal in &method.formals {
+        visitor.visit_formal(formal);
+    }
+    visitor.visit_expr(&method.body);
+}
+
+pub fn walk_attribute<V: Visitor>(visitor: &mut V, attribute: &Attribute) {
+    visitor.visit_expr(&attribute.init);
+}
+
+pub fn walk_case_branch<V: Visitor>(visitor: &mut V, branch: &CaseBranch) {
+    visitor.visit_expr(&branch.expr);
+}
+
+pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Assign(assign) => visitor.visit_assign(assign),
+        ExprKind::StaticDispatch(dispatch) => visitor.visit_static_dispatch(dispatch),
+        ExprKind::Dispatch(dispatch) => visitor.visit_dispatch(dispatch),
+        ExprKind::Cond(cond) => visitor.visit_cond(cond),
+        ExprKind::Loop(loop_) => visitor.visit_loop(loop_),
+        ExprKind::Case(case) => visitor.visit_case(case),
+        ExprKind::Block(block) => visitor.visit_block(block),
+        ExprKind::Let(let_) => visitor.visit_let(let_),
+        ExprKind::Binary(binary) => visitor.visit_binary(binary),
+        ExprKind::Unary(unary) => visitor.visit_unary(unary),
+        ExprKind::IntConst(value) => visitor.visit_int_const(*value),
+        ExprKind::BoolConst(value) => visitor.visit_bool_const(*value),
+        ExprKind::StringConst(value) => visitor.visit_string_const(*value),
+        ExprKind::New(type_name) => visitor.visit_new(*type_name),
+        ExprKind::NoExpr => visitor.visit_no_expr(),
+        ExprKind::Object(name) => visitor.visit_object(*name),
+    }
+}
+
+pub fn walk_assign<V: Visitor>(visitor: &mut V, assign: &Assign) {
+    visitor.visit_expr(&assign.expr);
+}
+
+pub fn walk_static_dispatch<V: Visitor>(visitor: &mut V, dispatch: &StaticDispatch) {
+    visitor.visit_expr(&dispatch.expr);
+    for arg in &dispatch.args {
+        visitor.visit_expr(arg);
+    }
+}
+
+pub fn walk_dispatch<V: Visitor>(visitor: &mut V, dispatch: &Dispatch) {
+    visitor.visit_expr(&dispatch.expr);
+    for arg in &dispatch.args {
+        visitor.visit_expr(arg);
+    }
+}
+
+pub fn walk_cond<V: Visitor>(visitor: &mut V, cond: &Cond) {
+    visitor.visit_expr(&cond.pred);
+    visitor.visit_expr(&cond.then_expr);
+    visitor.visit_expr(&cond.else_expr);
+}
+
+pub fn walk_loop<V: Visitor>(visitor: &mut V, loop_: &Loop) {
+    visitor.visit_expr(&loop_.pred);
+    visitor.visit_expr(&loop_.body);
+}
+
+pub fn walk_case<V: Visitor>(visitor: &mut V, case: &Case) {
+    visitor.visit_expr(&case.expr);
+    for branch in &case.branches {
+        visitor.visit_case_branch(branch);
+    }
+}
+
+pub fn walk_block<V: Visitor>(visitor: &mut V, block: &Block) {
+    for expr in &block.body {
+        visitor.visit_expr(expr);
+    }
+}
+
+pub fn walk_let<V: Visitor>(visitor: &mut V, let_: &Let) {
+    visitor.visit_expr(&let_.init);
+    visitor.visit_expr(&let_.body);
+}
+
+pub fn walk_binary<V: Visitor>(visitor: &mut V, binary: &Binary) {
+    visitor.visit_expr(&binary.lhs);
+    visitor.visit_expr(&binary.rhs);
+}
+
+pub fn walk_unary<V: Visitor>(visitor: &mut V, unary: &Unary) {
+    visitor.visit_expr(&unary.expr);
+}
+
+/// Like `Visitor`, but with mutable access to the nodes.
+pub trait VisitorMut: Sized {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_class_mut(&mut self, class: &mut Class) {
+        walk_class_mut(self, class);
+    }
+
+    fn visit_feature_mut(&mut self, feature: &mut Feature) {
+        walk_feature_mut(self, feature);
+    }
+
+    fn visit_method_mut(&mut self, method: &mut Method) {
+        walk_method_mut(self, method);
+    }
+
+    fn visit_attribute_mut(&mut self, attribute: &mut Attribute) {
+        walk_attribute_mut(self, attribute);
+    }
+
+    fn visit_formal_mut(&mut self, _formal: &mut Formal) {}
+
+    fn visit_case_branch_mut(&mut self, branch: &mut CaseBranch) {
+        walk_case_branch_mut(self, branch);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_assign_mut(&mut self, assign: &mut Assign) {
+        walk_assign_mut(self, assign);
+    }
+
+    fn visit_static_dispatch_mut(&mut self, dispatch: &mut StaticDispatch) {
+        walk_static_dispatch_mut(self, dispatch);
+    }
+
+    fn visit_dispatch_mut(&mut self, dispatch: &mut Dispatch) {
+        walk_dispatch_mut(self, dispatch);
+    }
+
+    fn visit_cond_mut(&mut self, cond: &mut Cond) {
+        walk_cond_mut(self, cond);
+    }
+
+    fn visit_loop_mut(&mut self, loop_: &mut Loop) {
+        walk_loop_mut(self, loop_);
+    }
+
+    fn visit_case_mut(&mut self, case: &mut Case) {
+        walk_case_mut(self, case);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_let_mut(&mut self, let_: &mut Let) {
+        walk_let_mut(self, let_);
+    }
+
+    fn visit_binary_mut(&mut self, binary: &mut Binary) {
+        walk_binary_mut(self, binary);
+    }
+
+    fn visit_unary_mut(&mut self, unary: &mut Unary) {
+        walk_unary_mut(self, unary);
+    }
+
+    fn visit_int_const_mut(&mut self, _value: &mut Symbol) {}
+
+    fn visit_bool_const_mut(&mut self, _value: &mut bool) {}
+
+    fn visit_string_const_mut(&mut self, _value: &mut Symbol) {}
+
+    fn visit_new_mut(&mut self, _type_name: &mut Symbol) {}
+
+    fn visit_no_expr_mut(&mut self) {}
+
+    fn visit_object_mut(&mut self, _name: &mut Symbol) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut>(visitor: &mut V, program: &mut Program) {
+    for class in &mut program.classes {
+        visitor.visit_class_mut(class);
+    }
+}
+
+pub fn walk_class_mut<V: VisitorMut>(visitor: &mut V, class: &mut Class) {
+    for feature in &mut class.features {
+        visitor.visit_feature_mut(feature);
+    }
+}
+
+pub fn walk_feature_mut<V: VisitorMut>(visitor: &mut V, feature: &mut Feature) {
+    match feature {
+        Feature::Method(method) => visitor.visit_method_mut(method),
+        Feature::Attribute(attribute) => visitor.visit_attribute_mut(attribute),
+    }
+}
+
+pub fn walk_method_mut<V: VisitorMut>(visitor: &mut V, method: &mut Method) {
+    for formal in &mut method.formals {
+        visitor.visit_formal_mut(formal);
+    }
+    visitor.visit_expr_mut(&mut method.body);
+}
+
+pub fn walk_attribute_mut<V: VisitorMut>(visitor: &mut V, attribute: &mut Attribute) {
+    visitor.visit_expr_mut(&mut attribute.init);
+}
+
+pub fn walk_case_branch_mut<V: VisitorMut>(visitor: &mut V, branch: &mut CaseBranch) {
+    visitor.visit_expr_mut(&mut branch.expr);
+}
+
+pub fn walk_expr_mut<V: VisitorMut>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Assign(assign) => visitor.visit_assign_mut(assign),
+        ExprKind::StaticDispatch(dispatch) => visitor.visit_static_dispatch_mut(dispatch),
+        ExprKind::Dispatch(dispatch) => visitor.visit_dispatch_mut(dispatch),
+        ExprKind::Cond(cond) => visitor.visit_cond_mut(cond),
+        ExprKind::Loop(loop_) => visitor.visit_loop_mut(loop_),
+        ExprKind::Case(case) => visitor.visit_case_mut(case),
+        ExprKind::Block(block) => visitor.visit_block_mut(block),
+        ExprKind::Let(let_) => visitor.visit_let_mut(let_),
+        ExprKind::Binary(binary) => visitor.visit_binary_mut(binary),
+        ExprKind::Unary(unary) => visitor.visit_unary_mut(unary),
+        ExprKind::IntConst(value) => visitor.visit_int_const_mut(value),
+        ExprKind::BoolConst(value) => visitor.visit_bool_const_mut(value),
+        ExprKind::StringConst(value) => visitor.visit_string_const_mut(value),
+        ExprKind::New(type_name) => visitor.visit_new_mut(type_name),
+        ExprKind::NoExpr => visitor.visit_no_expr_mut(),
+        ExprKind::Object(name) => visitor.visit_object_mut(name),
+    }
+}
+
+pub fn walk_assign_mut<V: VisitorMut>(visitor: &mut V, assign: &mut Assign) {
+    visitor.visit_expr_mut(&mut assign.expr);
+}
+
+pub fn walk_static_dispatch_mut<V: VisitorMut>(visitor: &mut V, dispatch: &mut StaticDispatch) {
+    visitor.visit_expr_mut(&mut dispatch.expr);
+    for arg in &mut dispatch.args {
+        visitor.visit_expr_mut(arg);
+    }
+}
+
+pub fn walk_dispatch_mut<V: VisitorMut>(visitor: &mut V, dispatch: &mut Dispatch) {
+    visitor.visit_expr_mut(&mut dispatch.expr);
+    for arg in &mut dispatch.args {
+        visitor.visit_expr_mut(arg);
+    }
+}
+
+pub fn walk_cond_mut<V: VisitorMut>(visitor: &mut V, cond: &mut Cond) {
+    visitor.visit_expr_mut(&mut cond.pred);
+    visitor.visit_expr_mut(&mut cond.then_expr);
+    visitor.visit_expr_mut(&mut cond.else_expr);
+}
+
+pub fn walk_loop_mut<V: VisitorMut>(visitor: &mut V, loop_: &mut Loop) {
+    visitor.visit_expr_mut(&mut loop_.pred);
+    visitor.visit_expr_mut(&mut loop_.body);
+}
+
+pub fn walk_case_mut<V: VisitorMut>(visitor: &mut V, case: &mut Case) {
+    visitor.visit_expr_mut(&mut case.expr);
+    for branch in &mut case.branches {
+        visitor.visit_case_branch_mut(branch);
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    for expr in &mut block.body {
+        visitor.visit_expr_mut(expr);
+    }
+}
+
+pub fn walk_let_mut<V: VisitorMut>(visitor: &mut V, let_: &mut Let) {
+    visitor.visit_expr_mut(&mut let_.init);
+    visitor.visit_expr_mut(&mut let_.body);
+}
+
+pub fn walk_binary_mut<V: VisitorMut>(visitor: &mut V, binary: &mut Binary) {
+    visitor.visit_expr_mut(&mut binary.lhs);
+    visitor.visit_expr_mut(&mut binary.rhs);
+}
+
+pub fn walk_unary_mut<V: VisitorMut>(visitor: &mut V, unary: &mut Unary) {
+    visitor.visit_expr_mut(&mut unary.expr);
+}
+
+/// Like `Visitor`, but taking the nodes by value and returning the nodes that replace them.
+///
+/// The methods for the kinds of expressions return the kind of the expression that replaces
+/// them, which keeps the line and type of the original expression. Folders that change the type
+/// of an expression override `fold_expr` to set it.
+pub trait Folder: Sized {
+    fn fold_program(&mut self, program: Program) -> Program {
+        walk_program_fold(self, program)
+    }
+
+    fn fold_class(&mut self, class: Class) -> Class {
+        walk_class_fold(self, class)
+    }
+
+    fn fold_feature(&mut self, feature: Feature) -> Feature {
+        walk_feature_fold(self, feature)
+    }
+
+    fn fold_method(&mut self, method: Method) -> Method {
+        walk_method_fold(self, method)
+    }
+
+    fn fold_attribute(&mut self, attribute: Attribute) -> Attribute {
+        walk_attribute_fold(self, attribute)
+    }
+
+    fn fold_formal(&mut self, formal: Formal) -> Formal {
+        formal
+    }
+
+    fn fold_case_branch(&mut self, branch: CaseBranch) -> CaseBranch {
+        walk_case_branch_fold(self, branch)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr_fold(self, expr)
+    }
+
+    fn fold_assign(&mut self, assign: Assign) -> ExprKind {
+        walk_assign_fold(self, assign)
+    }
+
+    fn fold_static_dispatch(&mut self, dispatch: StaticDispatch) -> ExprKind {
+        walk_static_dispatch_fold(self, dispatch)
+    }
+
+    fn fold_dispatch(&mut self, dispatch: Dispatch) -> ExprKind {
+        walk_dispatch_fold(self, dispatch)
+    }
+
+    fn fold_cond(&mut self, cond: Cond) -> ExprKind {
+        walk_cond_fold(self, cond)
+    }
+
+    fn fold_loop(&mut self, loop_: Loop) -> ExprKind {
+        walk_loop_fold(self, loop_)
+    }
+
+    fn fold_case(&mut self, case: Case) -> ExprKind {
+        walk_case_fold(self, case)
+    }
+
+    fn fold_block(&mut self, block: Block) -> ExprKind {
+        walk_block_fold(self, block)
+    }
+
+    fn fold_let(&mut self, let_: Let) -> ExprKind {
+        walk_let_fold(self, let_)
+    }
+
+    fn fold_binary(&mut self, binary: Binary) -> ExprKind {
+        walk_binary_fold(self, binary)
+    }
+
+    fn fold_unary(&mut self, unary: Unary) -> ExprKind {
+        walk_unary_fold(self, unary)
+    }
+
+    fn fold_int_const(&mut self, value: Symbol) -> ExprKind {
+        ExprKind::IntConst(value)
+    }
+
+    fn fold_bool_const(&mut self, value: bool) -> ExprKind {
+        ExprKind::BoolConst(value)
+    }
+
+    fn fold_string_const(&mut self, value: Symbol) -> ExprKind {
+        ExprKind::StringConst(value)
+    }
+
+    fn fold_new(&mut self, type_name: Symbol) -> ExprKind {
+        ExprKind::New(type_name)
+    }
+
+    fn fold_no_expr(&mut self) -> ExprKind {
+        ExprKind::NoExpr
+    }
+
+    fn fold_object(&mut self, name: Symbol) -> ExprKind {
+        ExprKind::Object(name)
+    }
+}
+
+/// Fold the expression in `expr`, reusing its box.
+fn fold_boxed<F: Folder>(folder: &mut F, mut expr: Box<Expr>) -> Box<Expr> {
+    let line = expr.line;
+    *expr = folder.fold_expr(mem::replace(&mut *expr, Expr::no_expr(line)));
+
+    expr
+}
+
+fn fold_exprs<F: Folder>(folder: &mut F, exprs: Vec<Expr>) -> Vec<Expr> {
+    exprs
+        .into_iter()
+        .map(|expr| folder.fold_expr(expr))
+        .collect()
+}
+
+pub fn walk_program_fold<F: Folder>(folder: &mut F, program: Program) -> Program {
+    Program {
+        classes: program
+            .classes
+            .into_iter()
+            .map(|class| folder.fold_class(class))
+            .collect(),
+        ..program
+    }
+}
+
+pub fn walk_class_fold<F: Folder>(folder: &mut F, class: Class) -> Class {
+    Class {
+        features: class
+            .features
+            .into_iter()
+            .map(|feature| folder.fold_feature(feature))
+            .collect(),
+        ..class
+    }
+}
+
+pub fn walk_feature_fold<F: Folder>(folder: &mut F, feature: Feature) -> Feature {
+    match feature {
+        Feature::Method(method) => Feature::Method(folder.fold_method(method)),
+        Feature::Attribute(attribute) => Feature::Attribute(folder.fold_attribute(attribute)),
+    }
+}
+
+pub fn walk_method_fold<F: Folder>(folder: &mut F, method: Method) -> Method {
+    Method {
+        formals: method
+            .formals
+            .into_iter()
+            .map(|formal| folder.fold_formal(formal))
+            .collect(),
+        body: folder.fold_expr(method.body),
+        ..method
+    }
+}
+
+pub fn walk_attribute_fold<F: Folder>(folder: &mut F, attribute: Attribute) -> Attribute {
+    Attribute {
+        init: folder.fold_expr(attribute.init),
+        ..attribute
+    }
+}
+
+pub fn walk_case_branch_fold<F: Folder>(folder: &mut F, branch: CaseBranch) -> CaseBranch {
+    CaseBranch {
+        expr: folder.fold_expr(branch.expr),
+        ..branch
+    }
+}
+
+pub fn walk_expr_fold<F: Folder>(folder: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Assign(assign) => folder.fold_assign(assign),
+        ExprKind::StaticDispatch(dispatch) => folder.fold_static_dispatch(dispatch),
+        ExprKind::Dispatch(dispatch) => folder.fold_dispatch(dispatch),
+        ExprKind::Cond(cond) => folder.fold_cond(cond),
+        ExprKind::Loop(loop_) => folder.fold_loop(loop_),
+        ExprKind::Case(case) => folder.fold_case(case),
+        ExprKind::Block(block) => folder.fold_block(block),
+        ExprKind::Let(let_) => folder.fold_let(let_),
+        ExprKind::Binary(binary) => folder.fold_binary(binary),
+        ExprKind::Unary(unary) => folder.fold_unary(unary),
+        ExprKind::IntConst(value) => folder.fold_int_const(value),
+        ExprKind::BoolConst(value) => folder.fold_bool_const(value),
+        ExprKind::StringConst(value) => folder.fold_string_const(value),
+        ExprKind::New(type_name) => folder.fold_new(type_name),
+        ExprKind::NoExpr => folder.fold_no_expr(),
+        ExprKind::Object(name) => folder.fold_object(name),
+    };
+
+    Expr { kind, ..expr }
+}
+
+pub fn walk_assign_fold<F: Folder>(folder: &mut F, assign: Assign) -> ExprKind {
+    ExprKind::Assign(Assign {
+        expr: fold_boxed(folder, assign.expr),
+        ..assign
+    })
+}
+
+pub fn walk_static_dispatch_fold<F: Folder>(folder: &mut F, dispatch: StaticDispatch) -> ExprKind {
+    ExprKind::StaticDispatch(StaticDispatch {
+        expr: fold_boxed(folder, dispatch.expr),
+        args: fold_exprs(folder, dispatch.args),
+        ..dispatch
+    })
+}
+
+pub fn walk_dispatch_fold<F: Folder>(folder: &mut F, dispatch: Dispatch) -> ExprKind {
+    ExprKind::Dispatch(Dispatch {
+        expr: fold_boxed(folder, dispatch.expr),
+        args: fold_exprs(folder, dispatch.args),
+        ..dispatch
+    })
+}
+
+pub fn walk_cond_fold<F: Folder>(folder: &mut F, cond: Cond) -> ExprKind {
+    ExprKind::Cond(Cond {
+        pred: fold_boxed(folder, cond.pred),
+        then_expr: fold_boxed(folder, cond.then_expr),
+        else_expr: fold_boxed(folder, cond.else_expr),
+    })
+}
+
+pub fn walk_loop_fold<F: Folder>(folder: &mut F, loop_: Loop) -> ExprKind {
+    ExprKind::Loop(Loop {
+        pred: fold_boxed(folder, loop_.pred),
+        body: fold_boxed(folder, loop_.body),
+    })
+}
+
+pub fn walk_case_fold<F: Folder>(folder: &mut F, case: Case) -> ExprKind {
+    ExprKind::Case(Case {
+        expr: fold_boxed(folder, case.expr),
+        branches: case
+            .branches
+            .into_iter()
+            .map(|branch| folder.fold_case_branch(branch))
+            .collect(),
+    })
+}
+
+pub fn walk_block_fold<F: Folder>(folder: &mut F, block: Block) -> ExprKind {
+    ExprKind::Block(Block {
+        body: fold_exprs(folder, block.body),
+    })
+}
+
+pub fn walk_let_fold<F: Folder>(folder: &mut F, let_: Let) -> ExprKind {
+    ExprKind::Let(Let {
+        init: fold_boxed(folder, let_.init),
+        body: fold_boxed(folder, let_.body),
+        ..let_
+    })
+}
+
+pub fn walk_binary_fold<F: Folder>(folder: &mut F, binary: Binary) -> ExprKind {
+    ExprKind::Binary(Binary {
+        lhs: fold_boxed(folder, binary.lhs),
+        rhs: fold_boxed(folder, binary.rhs),
+        ..binary
+    })
+}
+
+pub fn walk_unary_fold<F: Folder>(folder: &mut F, unary: Unary) -> ExprKind {
+    ExprKind::Unary(Unary {
+        expr: fold_boxed(folder, unary.expr),
+        ..unary
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(kind: ExprKind) -> Expr {
+        Expr::new(kind, 1)
+    }
+
+    fn object(name: &str) -> Expr {
+        expr(ExprKind::Object(Symbol::intern(name)))
+    }
+
+    fn int(value: &str) -> Expr {
+        expr(ExprKind::IntConst(Symbol::intern(value)))
+    }
+
+    /// `class Main { x : Int <- 1; main(y : Int) : Int { let z : Int <- x + y in z * 2 }; };`
+    fn program() -> Program {
+        let body = expr(ExprKind::Let(Let {
+            name: Symbol::intern("z"),
+            type_decl: Symbol::intern("Int"),
+            init: Box::new(expr(ExprKind::Binary(Binary {
+                op: BinaryOp::Plus,
+                lhs: Box::new(object("x")),
+                rhs: Box::new(object("y")),
+            }))),
+            body: Box::new(expr(ExprKind::Binary(Binary {
+                op: BinaryOp::Mul,
+                lhs: Box::new(object("z")),
+                rhs: Box::new(int("2")),
+            }))),
+        }));
+
+        Program {
+            classes: vec![Class {
+                name: Symbol::intern("Main"),
+                parent: Symbol::intern("Object"),
+                features: vec![
+                    Feature::Attribute(Attribute {
+                        name: Symbol::intern("x"),
+                        type_decl: Symbol::intern("Int"),
+                        init: int("1"),
+                        line: 1,
+                    }),
+                    Feature::Method(Method {
+                        name: Symbol::intern("main"),
+                        formals: vec![Formal {
+                            name: Symbol::intern("y"),
+                            type_decl: Symbol::intern("Int"),
+                            line: 1,
+                        }],
+                        return_type: Symbol::intern("Int"),
+                        body,
+                        line: 1,
+                    }),
+                ],
+                file_name: Symbol::intern("test.cl"),
+                line: 1,
+            }],
+            line: 1,
+        }
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        exprs: usize,
+        objects: Vec<Symbol>,
+        formals: usize,
+    }
+
+    impl Visitor for Counter {
+        fn visit_expr(&mut self, expr: &Expr) {
+            self.exprs += 1;
+            walk_expr(self, expr);
+        }
+
+        fn visit_object(&mut self, name: Symbol) {
+            self.objects.push(name);
+        }
+
+        fn visit_formal(&mut self, _formal: &Formal) {
+            self.formals += 1;
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_every_node() {
+        let mut counter = Counter::default();
+        counter.visit_program(&program());
+
+        // 1, let, +, x, y, *, z, 2
+        assert_eq!(counter.exprs, 8);
+        assert_eq!(counter.formals, 1);
+        let objects: Vec<_> = counter.objects.iter().map(|o| o.as_str()).collect();
+        assert_eq!(objects, vec!["x", "y", "z"]);
+    }
+
+    struct Typer;
+
+    impl VisitorMut for Typer {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            walk_expr_mut(self, expr);
+            expr.ty = Some(Symbol::intern("Int"));
+        }
+
+        fn visit_object_mut(&mut self, name: &mut Symbol) {
+            if name.as_str() == "x" {
+                *name = Symbol::intern("w");
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_updates_nodes() {
+        let mut program = program();
+        Typer.visit_program_mut(&mut program);
+
+        struct Check(usize);
+        impl Visitor for Check {
+            fn visit_expr(&mut self, expr: &Expr) {
+                assert_eq!(expr.ty, Some(Symbol::intern("Int")));
+                self.0 += 1;
+                walk_expr(self, expr);
+            }
+
+            fn visit_object(&mut self, name: Symbol) {
+                assert_ne!(name.as_str(), "x");
+            }
+        }
+
+        let mut check = Check(0);
+        check.visit_program(&program);
+        assert_eq!(check.0, 8);
+    }
+
+    /// Folds additions of two integer constants into one constant.
+    struct ConstantFolder;
+
+    impl Folder for ConstantFolder {
+        fn fold_binary(&mut self, binary: Binary) -> ExprKind {
+            let binary = match walk_binary_fold(self, binary) {
+                ExprKind::Binary(binary) => binary,
+                other => return other,
+            };
+            match (binary.op, &binary.lhs.kind, &binary.rhs.kind) {
+                (BinaryOp::Plus, ExprKind::IntConst(lhs), ExprKind::IntConst(rhs)) => {
+                    let sum: i32 =
+                        lhs.as_str().parse::<i32>().unwrap() + rhs.as_str().parse::<i32>().unwrap();
+                    ExprKind::IntConst(Symbol::intern(&sum.to_string()))
+                }
+                _ => ExprKind::Binary(binary),
+            }
+        }
+    }
+
+    #[test]
+    fn test_folder_replaces_nodes() {
+        // x + (1 + 2) + (3 + 4)
+        let add = |lhs, rhs| {
+            expr(ExprKind::Binary(Binary {
+                op: BinaryOp::Plus,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            }))
+        };
+        let mut sum = add(
+            add(object("x"), add(int("1"), int("2"))),
+            add(int("3"), int("4")),
+        );
+        sum.ty = Some(Symbol::intern("Int"));
+
+        let folded = ConstantFolder.fold_expr(sum);
+        let mut expected = add(add(object("x"), int("3")), int("7"));
+        expected.ty = Some(Symbol::intern("Int"));
+        assert_eq!(folded, expected);
+
+        // Nodes the folder leaves alone come back as they were
+        let program = program();
+        assert_eq!(ConstantFolder.fold_program(program.clone()), program);
+    }
+}
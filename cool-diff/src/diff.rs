@@ -0,0 +1,210 @@
+use regex::Regex;
+use similar::{capture_diff_slices, group_diff_ops, Algorithm, DiffOp};
+
+use std::borrow::Cow;
+
+use crate::stream::{FileTokens, TokenLine};
+
+/// Differences to ignore when comparing token streams.
+#[derive(Default)]
+pub struct Options {
+    /// Compare tokens without their line numbers.
+    pub ignore_line_numbers: bool,
+    /// Consider all `ERROR` tokens equal, regardless of their message.
+    pub ignore_error_messages: bool,
+    /// Parts of tokens matching any of these are ignored.
+    pub masks: Vec<Regex>,
+    /// The number of equal tokens to show around each divergence.
+    pub context: usize,
+}
+
+impl Options {
+    /// The form of `token` that is compared.
+    fn key<'t>(&self, token: &'t TokenLine) -> Cow<'t, str> {
+        let mut text = Cow::Borrowed(token.text.as_str());
+
+        if self.ignore_error_messages && text.starts_with("ERROR ") {
+            text = Cow::Borrowed("ERROR");
+        }
+
+        for mask in &self.masks {
+            if let Cow::Owned(masked) = mask.replace_all(&text, "*") {
+                text = Cow::Owned(masked);
+            }
+        }
+
+        if self.ignore_line_numbers {
+            text
+        } else {
+            Cow::Owned(format!("#{} {}", token.line_number, text))
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A token line in a divergence.
+#[derive(Debug, Eq, PartialEq)]
+pub enum DiffLine<'a> {
+    /// Equal on both sides, shown as it appears on the left.
+    Context(&'a TokenLine),
+    Left(&'a TokenLine),
+    Right(&'a TokenLine),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Divergence<'a> {
+    /// The files at the same position in the streams have different names.
+    FileName { left: &'a str, right: &'a str },
+    /// A file only occurs in one of the streams.
+    MissingFile { only_in: Side, name: &'a str },
+    /// Tokens that differ, with context.
+    Tokens {
+        file: &'a str,
+        lines: Vec<DiffLine<'a>>,
+    },
+}
+
+/// Align the files and tokens of two streams and find where they diverge.
+pub fn divergences<'a>(
+    left: &'a [FileTokens],
+    right: &'a [FileTokens],
+    options: &Options,
+) -> Vec<Divergence<'a>> {
+    let mut result = vec![];
+
+    for i in 0..left.len().max(right.len()) {
+        match (left.get(i), right.get(i)) {
+            (Some(l), Some(r)) => {
+                if l.name != r.name {
+                    result.push(Divergence::FileName {
+                        left: &l.name,
+                        right: &r.name,
+                    });
+                }
+
+                diff_tokens(l, r, options, &mut result);
+            }
+            (Some(l), None) => result.push(Divergence::MissingFile {
+                only_in: Side::Left,
+                name: &l.name,
+            }),
+            (None, Some(r)) => result.push(Divergence::MissingFile {
+                only_in: Side::Right,
+                name: &r.name,
+            }),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    result
+}
+
+fn diff_tokens<'a>(
+    left: &'a FileTokens,
+    right: &'a FileTokens,
+    options: &Options,
+    result: &mut Vec<Divergence<'a>>,
+) {
+    let left_keys: Vec<_> = left.tokens.iter().map(|t| options.key(t)).collect();
+    let right_keys: Vec<_> = right.tokens.iter().map(|t| options.key(t)).collect();
+    let ops = capture_diff_slices(Algorithm::Myers, &left_keys, &right_keys);
+
+    for hunk in group_diff_ops(ops, options.context) {
+        let mut lines = vec![];
+
+        for op in hunk {
+            let (_, old, new) = op.as_tag_tuple();
+            if !matches!(op, DiffOp::Equal { .. }) {
+                lines.extend(left.tokens[old].iter().map(DiffLine::Left));
+                lines.extend(right.tokens[new].iter().map(DiffLine::Right));
+            } else {
+                lines.extend(left.tokens[old].iter().map(DiffLine::Context));
+            }
+        }
+
+        result.push(Divergence::Tokens {
+            file: &left.name,
+            lines,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::parse;
+
+    fn stream(tokens: &[&str]) -> Vec<FileTokens> {
+        let mut input = String::from("#name \"a.cl\"\n");
+        for t in tokens {
+            input.push_str(t);
+            input.push('\n');
+        }
+
+        parse(&input).unwrap()
+    }
+
+    fn changed<'a>(divergences: &[Divergence<'a>]) -> Vec<String> {
+        divergences
+            .iter()
+            .flat_map(|d| match d {
+                Divergence::Tokens { lines, .. } => lines.iter().collect(),
+                _ => vec![],
+            })
+            .filter_map(|line| match line {
+                DiffLine::Context(_) => None,
+                DiffLine::Left(t) => Some(format!("-{}", t)),
+                DiffLine::Right(t) => Some(format!("+{}", t)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_aligns_after_insertion() {
+        let left = stream(&["#1 CLASS", "#1 TYPEID A", "#1 '{'", "#2 '}'"]);
+        let right = stream(&[
+            "#1 CLASS",
+            "#1 ERROR \"@\"",
+            "#1 TYPEID A",
+            "#1 '{'",
+            "#3 '}'",
+        ]);
+
+        let divergences = divergences(&left, &right, &Options::default());
+
+        assert_eq!(
+            changed(&divergences),
+            vec!["+#1 ERROR \"@\"", "-#2 '}'", "+#3 '}'"]
+        );
+    }
+
+    #[test]
+    fn test_ignored_differences() {
+        let left = stream(&["#1 ERROR \"EOF in comment\"", "#2 OBJECTID a"]);
+        let right = stream(&["#1 ERROR \"EOF in comment.\"", "#3 OBJECTID a"]);
+        let options = Options {
+            ignore_line_numbers: true,
+            ignore_error_messages: true,
+            ..Options::default()
+        };
+
+        assert_eq!(divergences(&left, &right, &options), vec![]);
+    }
+
+    #[test]
+    fn test_masks() {
+        let left = stream(&["#1 INT_CONST 007"]);
+        let right = stream(&["#1 INT_CONST 7"]);
+        let options = Options {
+            masks: vec![Regex::new("[0-9]+$").unwrap()],
+            ..Options::default()
+        };
+
+        assert_eq!(divergences(&left, &right, &options), vec![]);
+    }
+}
@@ -0,0 +1,173 @@
+mod diff;
+mod stream;
+
+use clap::{crate_authors, crate_version, App, Arg};
+use regex::Regex;
+
+use std::fs;
+use std::process::{self, Command};
+
+use crate::diff::{divergences, DiffLine, Divergence, Options, Side};
+
+type Error = Box<dyn std::error::Error + 'static>;
+
+/// Read a token stream from the file at `input`, or from the output of running it as a shell
+/// command.
+fn read_stream(input: &str, is_command: bool) -> Result<String, Error> {
+    if !is_command {
+        let bytes = fs::read(input).map_err(|err| format!("{}: {}", input, err))?;
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+
+    let output = Command::new("sh").arg("-c").arg(input).output()?;
+    if !output.status.success() {
+        eprintln!("warning: `{}` exited with {}", input, output.status);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Print `divergence` in a format similar to a unified diff, with `-` for the left stream and `+`
+/// for the right one.
+fn print_divergence(divergence: &Divergence) {
+    match divergence {
+        Divergence::FileName { left, right } => {
+            println!("@@ -\"{}\" +\"{}\" @@", left, right)
+        }
+        Divergence::MissingFile { only_in, name } => {
+            let side = match only_in {
+                Side::Left => '-',
+                Side::Right => '+',
+            };
+            println!("@@ {}\"{}\" only @@", side, name);
+        }
+        Divergence::Tokens { file, lines } => {
+            let first_line = |side: Side| {
+                lines
+                    .iter()
+                    .find_map(|line| match (line, &side) {
+                        (DiffLine::Left(t), Side::Left) | (DiffLine::Right(t), Side::Right) => {
+                            Some(t.stream_line.to_string())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| "-".into())
+            };
+
+            println!(
+                "@@ \"{}\" -{} +{} @@",
+                file,
+                first_line(Side::Left),
+                first_line(Side::Right)
+            );
+
+            for line in lines {
+                match line {
+                    DiffLine::Context(t) => println!(" {}", t),
+                    DiffLine::Left(t) => println!("-{}", t),
+                    DiffLine::Right(t) => println!("+{}", t),
+                }
+            }
+        }
+    }
+}
+
+fn run() -> Result<bool, Error> {
+    let matches = App::new("cool-diff")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Compares two COOL token streams in the `#name`/`#N TOKEN` format")
+        .arg(
+            Arg::with_name("commands")
+                .short("c")
+                .long("commands")
+                .help("Treat LEFT and RIGHT as shell commands and compare their output"),
+        )
+        .arg(
+            Arg::with_name("ignore-lines")
+                .long("ignore-lines")
+                .help("Ignore the line numbers of tokens"),
+        )
+        .arg(
+            Arg::with_name("ignore-error-messages")
+                .long("ignore-error-messages")
+                .help("Consider all ERROR tokens equal regardless of their message"),
+        )
+        .arg(
+            Arg::with_name("mask")
+                .long("mask")
+                .value_name("REGEX")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Ignore the parts of tokens matching REGEX"),
+        )
+        .arg(
+            Arg::with_name("context")
+                .short("C")
+                .long("context")
+                .value_name("N")
+                .default_value("3")
+                .help("Show N tokens of context around each divergence"),
+        )
+        .arg(
+            Arg::with_name("max")
+                .short("n")
+                .long("max-divergences")
+                .value_name("N")
+                .default_value("5")
+                .help("Report at most the first N divergences"),
+        )
+        .arg(Arg::with_name("LEFT").index(1).required(true))
+        .arg(Arg::with_name("RIGHT").index(2).required(true))
+        .get_matches();
+
+    let options = Options {
+        ignore_line_numbers: matches.is_present("ignore-lines"),
+        ignore_error_messages: matches.is_present("ignore-error-messages"),
+        masks: matches
+            .values_of("mask")
+            .into_iter()
+            .flatten()
+            .map(Regex::new)
+            .collect::<Result<_, _>>()?,
+        context: matches.value_of("context").unwrap().parse()?,
+    };
+    let max: usize = matches.value_of("max").unwrap().parse()?;
+    let is_command = matches.is_present("commands");
+    let left = matches.value_of("LEFT").unwrap();
+    let right = matches.value_of("RIGHT").unwrap();
+
+    let left_stream = read_stream(left, is_command)?;
+    let right_stream = read_stream(right, is_command)?;
+    let left_files = stream::parse(&left_stream).map_err(|err| format!("{}: {}", left, err))?;
+    let right_files = stream::parse(&right_stream).map_err(|err| format!("{}: {}", right, err))?;
+
+    let divergences = divergences(&left_files, &right_files, &options);
+    if divergences.is_empty() {
+        return Ok(false);
+    }
+
+    println!("--- {}", left);
+    println!("+++ {}", right);
+    for divergence in divergences.iter().take(max) {
+        print_divergence(divergence);
+    }
+    if divergences.len() > max {
+        println!("... and {} more divergences", divergences.len() - max);
+    }
+
+    Ok(true)
+}
+
+fn main() {
+    // Exit codes follow diff(1): 0 when equal, 1 when different and 2 on errors
+    match run() {
+        Ok(false) => process::exit(0),
+        Ok(true) => process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(2);
+        }
+    }
+}
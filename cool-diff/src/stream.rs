@@ -0,0 +1,143 @@
+use std::fmt;
+
+/// A token line, `#<line number> <token>`, from the lexer output format.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct TokenLine {
+    /// The line number the lexer reported for the token.
+    pub line_number: usize,
+    /// The token as printed, e.g. `OBJECTID x`.
+    pub text: String,
+    /// The line in the token stream itself, starting at 1.
+    pub stream_line: usize,
+}
+
+impl fmt::Display for TokenLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {}", self.line_number, self.text)
+    }
+}
+
+/// The tokens lexed from one file, introduced by `#name "<file>"`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct FileTokens {
+    pub name: String,
+    pub tokens: Vec<TokenLine>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct ParseError {
+    pub stream_line: usize,
+    pub line: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: expected `#name \"<file>\"` or `#<line> <token>`, found {:?}",
+            self.stream_line, self.line
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a token stream in the format printed by the lexer.
+///
+/// Tokens that occur before the first `#name` line belong to a file with an empty name.
+pub fn parse(input: &str) -> Result<Vec<FileTokens>, ParseError> {
+    let mut files: Vec<FileTokens> = vec![];
+
+    for (i, line) in input.lines().enumerate() {
+        let stream_line = i + 1;
+        let error = || ParseError {
+            stream_line,
+            line: line.to_owned(),
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let rest = line.strip_prefix('#').ok_or_else(error)?;
+
+        if let Some(name) = rest.strip_prefix("name ") {
+            let name = name.trim();
+            let name = name
+                .strip_prefix('"')
+                .and_then(|name| name.strip_suffix('"'))
+                .unwrap_or(name);
+
+            files.push(FileTokens {
+                name: name.to_owned(),
+                tokens: vec![],
+            });
+            continue;
+        }
+
+        let (line_number, text) = rest.split_once(' ').ok_or_else(error)?;
+        let line_number = line_number.parse().map_err(|_| error())?;
+
+        if files.is_empty() {
+            files.push(FileTokens {
+                name: String::new(),
+                tokens: vec![],
+            });
+        }
+
+        files.last_mut().unwrap().tokens.push(TokenLine {
+            line_number,
+            text: text.to_owned(),
+            stream_line,
+        });
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let files =
+            parse("#name \"a.cl\"\n#1 CLASS\n#1 STR_CONST \"a b\"\n\n#name \"b.cl\"\n").unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                FileTokens {
+                    name: "a.cl".into(),
+                    tokens: vec![
+                        TokenLine {
+                            line_number: 1,
+                            text: "CLASS".into(),
+                            stream_line: 2
+                        },
+                        TokenLine {
+                            line_number: 1,
+                            text: "STR_CONST \"a b\"".into(),
+                            stream_line: 3
+                        },
+                    ]
+                },
+                FileTokens {
+                    name: "b.cl".into(),
+                    tokens: vec![]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(
+            parse("#name \"a.cl\"\n#x CLASS\n"),
+            Err(ParseError {
+                stream_line: 2,
+                line: "#x CLASS".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use clap::{crate_authors, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{self, Command, ExitStatus, Stdio};
+
+use common::ast::Program;
+use common::PassTimings;
+use semant::{CheckOptions, ClassTable};
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+fn files_arg() -> Arg<'static, 'static> {
+    Arg::with_name("FILES")
+        .multiple(true)
+        .index(1)
+        .required(true)
+}
+
+fn jobs_arg() -> Arg<'static, 'static> {
+    Arg::with_name("jobs")
+        .short("j")
+        .long("jobs")
+        .value_name("N")
+        .takes_value(true)
+        .help("Lex and parse up to N files concurrently")
+}
+
+fn notes_arg() -> Arg<'static, 'static> {
+    Arg::with_name("notes")
+        .long("notes")
+        .help("Point at the original declaration after errors about redefinitions")
+}
+
+fn time_passes_arg() -> Arg<'static, 'static> {
+    Arg::with_name("time-passes")
+        .long("time-passes")
+        .help("Report the time spent in each pass on stderr")
+}
+
+/// Print `timings` if `--time-passes` was given.
+fn report_timings(matches: &ArgMatches<'_>, timings: &PassTimings) {
+    if matches.is_present("time-passes") {
+        eprintln!("{}", timings);
+    }
+}
+
+/// Lex, parse and type check the files of a `run` or `build` command.
+///
+/// Exits if the program has errors, they have been printed by then.
+fn check_files(
+    matches: &ArgMatches<'_>,
+    timings: &PassTimings,
+) -> Result<(Program, ClassTable), Error> {
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => jobs
+            .parse::<usize>()
+            .map_err(|err| format!("Invalid number of jobs {:?}: {}", jobs, err))?
+            .max(1),
+        None => 1,
+    };
+    let paths: Vec<_> = matches.values_of("FILES").unwrap().collect();
+
+    let options = CheckOptions {
+        jobs,
+        notes: matches.is_present("notes"),
+    };
+
+    match semant::check_files(&paths, &options, timings)? {
+        Some(checked) => Ok(checked),
+        None => {
+            report_timings(matches, timings);
+            process::exit(1)
+        }
+    }
+}
+
+/// `cool run`: compile the program to bytecode and run it.
+fn run(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let timings = PassTimings::default();
+    let (program, table) = check_files(matches, &timings)?;
+    let module = timings.time("compile", || vm::compile(&program, &table));
+
+    if matches.is_present("disassemble") {
+        print!("{}", module);
+        report_timings(matches, &timings);
+        return Ok(());
+    }
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let options = vm::GcOptions {
+        stress: matches.is_present("gc-stress"),
+    };
+    let (result, stats) = timings.time("run", || {
+        vm::run_with_gc(
+            &module,
+            stdin.lock(),
+            BufWriter::new(stdout.lock()),
+            options,
+        )
+    });
+    if let Err(err) = &result {
+        eprintln!("{}", err);
+    }
+    // Like compiled programs, report what the collector did last
+    if matches.is_present("gc-stats") {
+        eprintln!("{}", stats);
+    }
+    report_timings(matches, &timings);
+    if result.is_err() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn target_arg() -> Arg<'static, 'static> {
+    Arg::with_name("target")
+        .long("target")
+        .takes_value(true)
+        .possible_values(&["llvm", "c", "wasm", "x86_64", "ir"])
+        .default_value("llvm")
+        .help("The kind of code to generate")
+}
+
+/// `cool build`: compile the program for one of the native targets, or dump its IR.
+fn build(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let target = matches.value_of("target").unwrap();
+    // Only the x86_64 runtime has a choice of collectors, and only its assembly is linked here
+    let gc = codegen::x86_64::GcOptions {
+        generational: matches.is_present("generational-gc"),
+        stress: matches.is_present("gc-test"),
+    };
+    let link = target == "x86_64" && !matches.is_present("assembly");
+    if target != "x86_64" {
+        for option in &["generational-gc", "gc-test", "assembly"] {
+            if matches.is_present(option) {
+                return Err(format!("--{} only applies to the x86_64 target", option).into());
+            }
+        }
+    }
+
+    let timings = PassTimings::default();
+    let (program, table) = check_files(matches, &timings)?;
+
+    let (code, extension) = timings.time("codegen", || match target {
+        "llvm" => (codegen::llvm::emit(&program, &table), "ll"),
+        "c" => (codegen::c::emit(&program, &table), "c"),
+        "wasm" => (codegen::wasm::emit(&program, &table), "wat"),
+        "x86_64" => (codegen::x86_64::emit_with_gc(&program, &table, gc), "s"),
+        "ir" => (ir::lower(&program, &table).to_string(), "ir"),
+        target => unreachable!("Unknown target {}", target),
+    });
+
+    // Like the reference compiler, write the output next to the first file by default
+    let output = match matches.value_of("output") {
+        Some(output) => output.to_string(),
+        None => Path::new(matches.value_of("FILES").unwrap())
+            .with_extension(if link { "" } else { extension })
+            .display()
+            .to_string(),
+    };
+    if link {
+        if output == "-" {
+            return Err("Can't write an executable to stdout, use -S for the assembly".into());
+        }
+        link_x86_64(&code, Path::new(&output), &timings)?;
+        report_timings(matches, &timings);
+        return Ok(());
+    }
+    timings.time("write", || -> Result<(), Error> {
+        if output == "-" {
+            print!("{}", code);
+        } else {
+            fs::write(output, code)?;
+        }
+
+        Ok(())
+    })?;
+    report_timings(matches, &timings);
+
+    Ok(())
+}
+
+/// Assemble the x86_64 `assembly` and the runtime with `as` and link them into `output` with `ld`.
+fn link_x86_64(assembly: &str, output: &Path, timings: &PassTimings) -> Result<(), Error> {
+    let objects = env::temp_dir().join(format!("cool-{}", process::id()));
+    fs::create_dir_all(&objects)?;
+    let program = objects.join("program.o");
+    let runtime = objects.join("runtime.o");
+
+    let linked = timings
+        .time("assemble", || {
+            assemble(assembly, &program)?;
+            assemble(codegen::x86_64::RUNTIME, &runtime)
+        })
+        .and_then(|()| {
+            timings.time("link", || {
+                let status = Command::new("ld")
+                    .arg(&program)
+                    .arg(&runtime)
+                    .arg("-o")
+                    .arg(output)
+                    .status();
+                check_status("ld", status)
+            })
+        });
+    // The objects are only of use to the linker
+    let _ = fs::remove_dir_all(&objects);
+
+    linked
+}
+
+/// Assemble `assembly` into `object` with `as`, which reads it from its stdin.
+fn assemble(assembly: &str, object: &Path) -> Result<(), Error> {
+    let mut child = Command::new("as")
+        .arg("-o")
+        .arg(object)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run as: {}", err))?;
+    // Closing stdin when the write is done tells `as` it has the whole program
+    let written = child.stdin.take().unwrap().write_all(assembly.as_bytes());
+    check_status("as", child.wait())?;
+    written?;
+
+    Ok(())
+}
+
+/// Turn the exit `status` of the tool `name` into an error if it failed, it has printed why.
+fn check_status(name: &str, status: io::Result<ExitStatus>) -> Result<(), Error> {
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{} failed with {}", name, status).into()),
+        Err(err) => Err(format!("Failed to run {}: {}", name, err).into()),
+    }
+}
+
+/// `cool runtime`: print the runtime programs compiled for a target are linked with.
+fn runtime(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    // The C and wasm targets include their runtime in the code they generate
+    match matches.value_of("target").unwrap() {
+        "llvm" | "c" => print!("{}", codegen::RUNTIME),
+        "wasm" => print!("{}", codegen::wasm::RUNTIME),
+        "x86_64" => print!("{}", codegen::x86_64::RUNTIME),
+        "ir" => return Err("The ir target is a dump of the IR, which has no runtime".into()),
+        target => unreachable!("Unknown target {}", target),
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let matches = App::new("cool")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Compiles and runs COOL programs")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a program on the bytecode VM")
+                .arg(
+                    Arg::with_name("disassemble")
+                        .long("disassemble")
+                        .help("Print the bytecode instead of running it"),
+                )
+                .arg(
+                    Arg::with_name("gc-stats")
+                        .long("gc-stats")
+                        .help("Print what the garbage collector did to stderr"),
+                )
+                .arg(
+                    Arg::with_name("gc-stress")
+                        .long("gc-stress")
+                        .help("Collect garbage after every allocation, for testing"),
+                )
+                .arg(jobs_arg())
+                .arg(notes_arg())
+                .arg(time_passes_arg())
+                .arg(files_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("build")
+                .about("Compiles a program for a native target, into an executable for x86_64")
+                .arg(target_arg())
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("The file to write, - for stdout"),
+                )
+                .arg(
+                    Arg::with_name("assembly")
+                        .short("S")
+                        .long("assembly")
+                        .help("Write the x86_64 assembly instead of assembling and linking it"),
+                )
+                .arg(
+                    Arg::with_name("generational-gc")
+                        .short("g")
+                        .long("generational-gc")
+                        .help("Collect garbage in x86_64 executables, with the generational collector"),
+                )
+                .arg(
+                    Arg::with_name("gc-test")
+                        .short("t")
+                        .long("gc-test")
+                        .help("Collect garbage on every allocation in x86_64 executables"),
+                )
+                .arg(jobs_arg())
+                .arg(notes_arg())
+                .arg(time_passes_arg())
+                .arg(files_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("runtime")
+                .about("Prints the runtime a target links compiled programs with")
+                .arg(target_arg()),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("run", Some(matches)) => run(matches),
+        ("build", Some(matches)) => build(matches),
+        ("runtime", Some(matches)) => runtime(matches),
+        _ => unreachable!("A subcommand is required"),
+    }
+}
@@ -0,0 +1,453 @@
+//! Runs every program in `tests/programs` and compares what it prints with the expected output
+//! next to the program, `<name>.out`. The program reads its input from `<name>.in`, if there is
+//! one.
+//!
+//! Every program runs on the VM and is also compiled by the native backends whose tools are
+//! installed: to C if there is `gcc`, to LLVM IR if there is also `llc` and to an x86-64
+//! executable if there are `as` and `ld` on x86-64 Linux. The executables must print the same.
+//! Every program is also compiled to WebAssembly and run on an interpreter.
+//!
+//! The programs also run with the garbage collector collecting on every allocation, on the VM and
+//! as native executables, and must still print the same.
+//!
+//! Set `BLESS=1` to write the current output of the VM as the expected output instead.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Run `command` from the programs' directory, so the file names in errors don't depend on where
+/// the repository is checked out, and return what it printed to stdout and stderr.
+fn output(mut command: Command, directory: &Path, path: &Path) -> String {
+    let input_path = path.with_extension("in");
+    let input = if input_path.exists() {
+        Stdio::from(File::open(&input_path).unwrap())
+    } else {
+        Stdio::null()
+    };
+
+    let output = command
+        .current_dir(directory)
+        .stdin(input)
+        .output()
+        .unwrap();
+
+    let mut printed = String::from_utf8(output.stdout).unwrap();
+    printed.push_str(&String::from_utf8(output.stderr).unwrap());
+
+    printed
+}
+
+fn run(directory: &Path, path: &Path, args: &[&str]) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cool"));
+    command.arg("run").args(args).arg(path.file_name().unwrap());
+
+    output(command, directory, path)
+}
+
+fn succeeds(command: &mut Command) -> bool {
+    command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The arguments `llc` needs to compile the IR the LLVM backend emits, `None` if there is no
+/// `llc`.
+fn llc_args() -> Option<Vec<&'static str>> {
+    let version = Command::new("llc").arg("--version").output().ok()?;
+    let version = String::from_utf8(version.stdout).ok()?;
+    let major: u32 = version
+        .split("LLVM version ")
+        .nth(1)?
+        .split('.')
+        .next()?
+        .trim()
+        .parse()
+        .ok()?;
+
+    let mut args = vec!["-relocation-model=pic", "-filetype=obj"];
+    // Opaque pointers are the default from LLVM 15
+    if major < 15 {
+        args.push("-opaque-pointers");
+    }
+
+    Some(args)
+}
+
+/// Compile the program with `cool build` for `target` with `options`, into a directory of their
+/// own. Returns the output and the directory, or `None` if the program doesn't compile.
+fn build(
+    directory: &Path,
+    path: &Path,
+    target: &str,
+    options: &[&str],
+    extension: &str,
+) -> Option<(PathBuf, PathBuf)> {
+    let build =
+        PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}{}", target, options.concat()));
+    fs::create_dir_all(&build).unwrap();
+    let output = build
+        .join(path.file_stem().unwrap())
+        .with_extension(extension);
+
+    let built = succeeds(
+        Command::new(env!("CARGO_BIN_EXE_cool"))
+            .args(["build", "--target", target])
+            .args(options)
+            .arg("-o")
+            .arg(&output)
+            .arg(path.file_name().unwrap())
+            .current_dir(directory),
+    );
+
+    Some((output, build)).filter(|_| built)
+}
+
+/// Compile the program to LLVM IR, link it with the runtime and run it with `args`.
+fn run_llvm(directory: &Path, path: &Path, llc_args: &[&str], args: &[&str]) -> String {
+    let (ir, build) = match build(directory, path, "llvm", &[], "ll") {
+        Some(built) => built,
+        None => return format!("Failed to build {}", path.display()),
+    };
+    let object = ir.with_extension("o");
+    let executable = ir.with_extension("");
+    let runtime = build.join("runtime.c");
+
+    let runtime_source = Command::new(env!("CARGO_BIN_EXE_cool"))
+        .args(["runtime", "--target", "llvm"])
+        .output()
+        .unwrap();
+    fs::write(&runtime, runtime_source.stdout).unwrap();
+
+    let built = succeeds(
+        Command::new("llc")
+            .args(llc_args)
+            .arg(&ir)
+            .arg("-o")
+            .arg(&object),
+    ) && succeeds(
+        Command::new("gcc")
+            .arg(&object)
+            .arg(&runtime)
+            .arg("-o")
+            .arg(&executable),
+    );
+    if !built {
+        return format!("Failed to build {}", path.display());
+    }
+
+    let mut command = Command::new(&executable);
+    command.args(args);
+    output(command, directory, path)
+}
+
+/// Compile the program to C, compile that as strict C99 and run it with `args`.
+fn run_c(directory: &Path, path: &Path, args: &[&str]) -> String {
+    let source = match build(directory, path, "c", &[], "c") {
+        Some((source, _)) => source,
+        None => return format!("Failed to build {}", path.display()),
+    };
+    let executable = source.with_extension("");
+
+    let built = succeeds(
+        Command::new("gcc")
+            .args(["-std=c99", "-pedantic-errors"])
+            .arg(&source)
+            .arg("-o")
+            .arg(&executable),
+    );
+    if !built {
+        return format!("Failed to build {}", path.display());
+    }
+
+    let mut command = Command::new(&executable);
+    command.args(args);
+    output(command, directory, path)
+}
+
+/// Compile the program to an x86-64 executable with the build `options` and run it with `args`.
+fn run_x86_64(directory: &Path, path: &Path, options: &[&str], args: &[&str]) -> String {
+    let executable = match build(directory, path, "x86_64", options, "") {
+        Some((executable, _)) => executable,
+        None => return format!("Failed to build {}", path.display()),
+    };
+
+    let mut command = Command::new(&executable);
+    command.args(args);
+    output(command, directory, path)
+}
+
+/// The state of the host functions a wasm module imports.
+struct Host {
+    input: std::vec::IntoIter<Vec<u8>>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Host {
+    fn read_line(&mut self) -> Vec<u8> {
+        self.input.next().unwrap_or_default()
+    }
+}
+
+fn memory(caller: &wasmi::Caller<'_, Host>) -> wasmi::Memory {
+    caller
+        .get_export("memory")
+        .and_then(wasmi::Extern::into_memory)
+        .unwrap()
+}
+
+/// Compile the program to WebAssembly and run it on an interpreter, with host functions that
+/// behave like the reference runtime.
+fn run_wasm(directory: &Path, path: &Path) -> String {
+    use wasmi::{Caller, Engine, Error, Linker, Module, Store};
+
+    let wat = match build(directory, path, "wasm", &[], "wat") {
+        Some((wat, _)) => wat,
+        None => return format!("Failed to build {}", path.display()),
+    };
+    let wasm = wat::parse_file(&wat).unwrap();
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).unwrap();
+
+    let input = fs::read(path.with_extension("in")).unwrap_or_default();
+    let mut lines: Vec<_> = input
+        .split(|&byte| byte == b'\n')
+        .map(<[u8]>::to_vec)
+        .collect();
+    if input.ends_with(b"\n") {
+        lines.pop();
+    }
+    let host = Host {
+        input: lines.into_iter(),
+        stdout: vec![],
+        stderr: vec![],
+    };
+    let mut store = Store::new(&engine, host);
+
+    let mut linker = <Linker<Host>>::new(&engine);
+    linker
+        .func_wrap(
+            "env",
+            "out_string",
+            |mut caller: Caller<'_, Host>, bytes: i32, length: i32| {
+                let mut buffer = vec![0; length as usize];
+                memory(&caller)
+                    .read(&caller, bytes as usize, &mut buffer)
+                    .unwrap();
+                caller.data_mut().stdout.extend(buffer);
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "out_int",
+            |mut caller: Caller<'_, Host>, value: i32| {
+                let printed = value.to_string();
+                caller.data_mut().stdout.extend(printed.bytes());
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "in_string",
+            |mut caller: Caller<'_, Host>, buffer: i32, capacity: i32| {
+                let mut line = caller.data_mut().read_line();
+                line.truncate(capacity as usize);
+                memory(&caller)
+                    .write(&mut caller, buffer as usize, &line)
+                    .unwrap();
+                line.len() as i32
+            },
+        )
+        .unwrap()
+        .func_wrap("env", "in_int", |mut caller: Caller<'_, Host>| {
+            let line = String::from_utf8_lossy(&caller.data_mut().read_line()).into_owned();
+            let line = line.trim_start();
+            let (negative, digits) = match line.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, line.strip_prefix('+').unwrap_or(line)),
+            };
+            let value = digits
+                .bytes()
+                .take_while(u8::is_ascii_digit)
+                .fold(0i32, |value, digit| {
+                    value.wrapping_mul(10).wrapping_add(i32::from(digit - b'0'))
+                });
+            if negative {
+                value.wrapping_neg()
+            } else {
+                value
+            }
+        })
+        .unwrap()
+        .func_wrap(
+            "env",
+            "error",
+            |mut caller: Caller<'_, Host>, bytes: i32, length: i32| -> Result<(), Error> {
+                let mut buffer = vec![0; length as usize];
+                memory(&caller)
+                    .read(&caller, bytes as usize, &mut buffer)
+                    .unwrap();
+                let stderr = &mut caller.data_mut().stderr;
+                stderr.extend(buffer);
+                stderr.push(b'\n');
+                Err(Error::new("Runtime error"))
+            },
+        )
+        .unwrap();
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    // Runtime errors end the program by trapping, after the host printed them
+    let _ = main.call(&mut store, ());
+
+    let host = store.into_data();
+    let mut printed = String::from_utf8(host.stdout).unwrap();
+    printed.push_str(&String::from_utf8(host.stderr).unwrap());
+
+    printed
+}
+
+#[test]
+fn test_programs() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut paths: Vec<_> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cl"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    let gcc = succeeds(Command::new("gcc").arg("--version"));
+    let llc_args = llc_args().filter(|_| gcc);
+    let native = cfg!(all(target_arch = "x86_64", target_os = "linux"))
+        && succeeds(Command::new("as").arg("--version"))
+        && succeeds(Command::new("ld").arg("--version"));
+
+    let mut failed = vec![];
+    for path in paths {
+        let output = run(&directory, &path, &[]);
+        let expected_path = path.with_extension("out");
+
+        if bless {
+            fs::write(&expected_path, &output).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path).ok();
+        if expected.as_deref() != Some(output.as_str()) {
+            failed.push(path.display().to_string());
+        }
+        if let Some(llc_args) = &llc_args {
+            if expected.as_deref() != Some(run_llvm(&directory, &path, llc_args, &[]).as_str()) {
+                failed.push(format!("{} (llvm)", path.display()));
+            }
+            let output = run_llvm(&directory, &path, llc_args, &["--gc-stress"]);
+            if expected.as_deref() != Some(output.as_str()) {
+                failed.push(format!("{} (llvm gc stress)", path.display()));
+            }
+        }
+        if gcc && expected.as_deref() != Some(run_c(&directory, &path, &[]).as_str()) {
+            failed.push(format!("{} (c)", path.display()));
+        }
+        if gcc && expected.as_deref() != Some(run_c(&directory, &path, &["--gc-stress"]).as_str()) {
+            failed.push(format!("{} (c gc stress)", path.display()));
+        }
+        if expected.as_deref() != Some(run(&directory, &path, &["--gc-stress"]).as_str()) {
+            failed.push(format!("{} (gc stress)", path.display()));
+        }
+        if native {
+            for (options, args, name) in &[
+                (&[][..], &[][..], "x86_64"),
+                (&["-g"], &[], "x86_64 gc"),
+                (&["-g"], &["--gc-stress"], "x86_64 gc stress"),
+                (&["-g", "-t"], &[], "x86_64 gc test"),
+            ] {
+                let output = run_x86_64(&directory, &path, options, args);
+                if expected.as_deref() != Some(output.as_str()) {
+                    failed.push(format!("{} ({})", path.display(), name));
+                }
+            }
+        }
+        if expected.as_deref() != Some(run_wasm(&directory, &path).as_str()) {
+            failed.push(format!("{} (wasm)", path.display()));
+        }
+    }
+
+    assert!(failed.is_empty(), "Unexpected output for {:?}", failed);
+}
+
+#[test]
+fn test_c_runtime_collects_garbage() {
+    // A program of its own, so its build doesn't race with `test_programs`
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("gc_stats");
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("gc_stats.cl");
+    fs::write(
+        &path,
+        "class Main { main() : Object { let i : Int <- 0 in while i < 100 loop i <- i + 1 pool }; };\n",
+    )
+    .unwrap();
+    // `Main` and the 100 Ints of `i`, of which the last collection keeps the one `i` holds
+    let stats = "GC: 101 collections, 101 objects allocated, 98 freed, 3 live\n";
+
+    if succeeds(Command::new("gcc").arg("--version")) {
+        assert_eq!(
+            run_c(&directory, &path, &["--gc-stress", "--gc-stats"]),
+            stats
+        );
+
+        if let Some(llc_args) = llc_args() {
+            assert_eq!(
+                run_llvm(&directory, &path, &llc_args, &["--gc-stress", "--gc-stats"]),
+                stats
+            );
+        }
+    }
+}
+
+#[test]
+fn test_x86_64_builds_executables() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux"))
+        || !succeeds(Command::new("as").arg("--version"))
+        || !succeeds(Command::new("ld").arg("--version"))
+    {
+        return;
+    }
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("x86_64_output");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(
+        directory.join("hello.cl"),
+        "class Main inherits IO { main() : Object { out_string(\"hello\\n\") }; };\n",
+    )
+    .unwrap();
+    let build = |options: &[&str]| {
+        succeeds(
+            Command::new(env!("CARGO_BIN_EXE_cool"))
+                .args(["build", "--target", "x86_64"])
+                .args(options)
+                .arg("hello.cl")
+                .current_dir(&directory),
+        )
+    };
+
+    // -S stops at the assembly, which is written next to the program like for the other targets
+    assert!(build(&["-S"]));
+    let assembly = fs::read_to_string(directory.join("hello.s")).unwrap();
+    assert!(assembly.contains("_MemMgr_INITIALIZER"));
+
+    // Otherwise the executable is named after the program
+    assert!(build(&[]));
+    let hello = Command::new(directory.join("hello")).output().unwrap();
+    assert!(hello.status.success());
+    assert_eq!(hello.stdout, b"hello\n");
+
+    assert!(!build(&["-o", "-"]));
+}
@@ -0,0 +1,25 @@
+class Main inherits IO {
+  fact(n : Int) : Int { if n = 0 then 1 else n * fact(n - 1) fi };
+
+  fib(n : Int) : Int {
+    let a : Int <- 0, b : Int <- 1, i : Int in {
+      while i < n loop {
+        b <- a + b;
+        a <- b - a;
+        i <- i + 1;
+      } pool;
+      a;
+    }
+  };
+
+  main() : Object {
+    {
+      out_int(fact(10)).out_string("\n");
+      out_int(fib(30)).out_string("\n");
+      out_int(~7 / 2).out_string(" ").out_int(7 / ~2).out_string("\n");
+      out_int(2147483647 + 1).out_string("\n");
+      if 3 <= 3 then out_string("le\n") else out_string("gt\n") fi;
+      if not (2 < 1) then out_string("not lt\n") else abort() fi;
+    }
+  };
+};
@@ -0,0 +1,52 @@
+class A inherits IO {
+  x : Int <- 3;
+  s : String;
+  b : Bool;
+  me() : SELF_TYPE { self };
+  clone() : SELF_TYPE { new SELF_TYPE };
+  show() : Object { { out_int(x); out_string(" "); out_string(type_name()); out_string(s); if b then out_string("t\n") else out_string("f\n") fi; } };
+  setx(v : Int) : SELF_TYPE { { x <- v; self; } };
+  -- Named like the tables the backends generate for every class
+  name() : String { "A" };
+  vtable() : Int { 1 };
+  proto() : Int { 2 };
+};
+class B inherits A {
+  y : Int <- x + 1;
+  show() : Object { { out_string("B "); out_int(y); out_string(" "); self@A.show(); } };
+};
+class Main inherits IO {
+  -- More arguments than fit in registers
+  sum6(a : Int, b : Int, c : Int, d : Int, e : Int, f : Int) : Int { a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 };
+  sum7(a : Int, b : Int, c : Int, d : Int, e : Int, f : Int, g : Int) : Int { sum6(a, b, c, d, e, f) - g * 7 };
+  describe(o : Object) : String {
+    case o of
+      i : Int => "int";
+      s : String => "string";
+      a : A => a.type_name();
+      o : Object => "object";
+    esac
+  };
+  main() : Object {
+    let a : A <- new B, c : A, i : Int <- 0, total : Int in {
+      a.show();
+      c <- a.clone().setx(10);
+      c.show();
+      a.copy().setx(7).show();
+      a.show();
+      while i < 100000 loop { total <- total + i / 3 - (i * 2) / 7; i <- i + 1; } pool;
+      out_int(total); out_string("\n");
+      out_string(describe(3)).out_string(describe("x")).out_string(describe(a)).out_string(describe(new IO)).out_string(describe(true)).out_string("\n");
+      out_string(if "ab" = "a".concat("b") then "eq\n" else "ne\n" fi);
+      out_string(if 5 = 2 + 3 then "eq\n" else "ne\n" fi);
+      out_string(if a = c then "eq\n" else "ne\n" fi);
+      out_string(if isvoid total then "void\n" else "nonvoid\n" fi);
+      out_int(~(2147483647 + 1) ); out_string("\n");
+      out_string("tab\there \"quoted\"\n");
+      out_int("hello".length()); out_string("hello".substr(1,3)); out_string("\n");
+      out_string(a.name()).out_int(a.vtable() + a.proto()).out_string("\n");
+      out_int(sum6(1, 2, 3, 4, 5, 6)).out_string(" ").out_int(sum7(1, 2, 3, 4, 5, 6, 7)).out_string("\n");
+      (let x : Int <- 1 in (let x : Int <- x + 1 in out_int(x))).out_string("\n");
+    }
+  };
+};
@@ -0,0 +1,52 @@
+-- Allocates much more than stays live, so the garbage collector has to run and keep the live
+-- objects intact while it moves them.
+class Node {
+  value : Int;
+  label : String;
+  next : Node;
+  init(v : Int, l : String, n : Node) : Node { { value <- v; label <- l; next <- n; self; } };
+  value() : Int { value };
+  label() : String { label };
+  next() : Node { next };
+};
+
+class Main inherits IO {
+  kept : Node;
+
+  -- A list of n nodes, labelled with their values
+  build(n : Int) : Node {
+    let list : Node, i : Int in {
+      while i < n loop {
+        list <- new Node.init(i, "n".concat(digit(i)), list);
+        i <- i + 1;
+      } pool;
+      list;
+    }
+  };
+
+  digit(i : Int) : String { "0123456789".substr(i - i / 10 * 10, 1) };
+
+  sum(list : Node) : Int {
+    let total : Int in {
+      while not isvoid list loop { total <- total + list.value(); list <- list.next(); } pool;
+      total;
+    }
+  };
+
+  labels(list : Node, count : Int) : String {
+    if count = 0 then "" else list.label().concat(labels(list.next(), count - 1)) fi
+  };
+
+  main() : Object {
+    let round : Int, garbage : Int in {
+      kept <- build(100);
+      while round < 1000 loop {
+        garbage <- garbage + sum(build(20));
+        round <- round + 1;
+      } pool;
+      out_int(garbage).out_string("\n");
+      out_int(sum(kept)).out_string(" ").out_string(labels(kept, 12)).out_string("\n");
+      out_int(sum(kept.copy())).out_string(" ").out_string(labels(kept.next().copy(), 3)).out_string("\n");
+    }
+  };
+};
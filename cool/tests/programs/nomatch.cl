@@ -0,0 +1,7 @@
+class A { };
+class B inherits A { };
+class Main {
+  main() : Object {
+    case new A of b : B => b; i : Int => i; esac
+  };
+};
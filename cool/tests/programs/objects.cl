@@ -0,0 +1,53 @@
+class Animal inherits IO {
+  name : String <- "animal";
+  legs : Int <- count();
+  count() : Int { 4 };
+  legs() : Int { legs };
+  speak() : SELF_TYPE { out_string(name.concat(" says ").concat(sound()).concat("\n")) };
+  sound() : String { "..." };
+  rename(n : String) : SELF_TYPE { { name <- n; self; } };
+  clone() : SELF_TYPE { new SELF_TYPE };
+};
+
+class Dog inherits Animal {
+  sound() : String { "woof" };
+};
+
+class Bird inherits Animal {
+  count() : Int { 2 };
+  sound() : String { "tweet" };
+};
+
+class Main inherits IO {
+  describe(a : Animal) : SELF_TYPE {
+    {
+      case a of
+        d : Dog => out_string("a dog with ");
+        b : Bird => out_string("a bird with ");
+        o : Object => out_string("something with ");
+      esac;
+      self;
+    }
+  };
+
+  main() : Object {
+    let dog : Dog <- new Dog, bird : Animal <- new Bird, copy : Animal in {
+      dog.speak();
+      dog.rename("rex").speak();
+      bird.speak()@Animal.sound();
+      out_string(bird@Animal.sound()).out_string("\n");
+      describe(bird).out_int(bird.legs()).out_string(" legs\n");
+      describe(new Animal).out_string(bird.clone().type_name()).out_string("\n");
+      copy <- dog.copy();
+      copy.rename("copy");
+      dog.speak();
+      copy.speak();
+      out_string(1.type_name()).out_string(" ").out_string(true.type_name()).out_string(" ");
+      out_string("s".type_name()).out_string(" ").out_string(self.type_name()).out_string("\n");
+      if dog = dog then out_string("same\n") else abort() fi;
+      if dog = copy then abort() else out_string("different\n") fi;
+      if "a".concat("b") = "ab" then out_string("equal strings\n") else abort() fi;
+      if isvoid copy then abort() else out_string("not void\n") fi;
+    }
+  };
+};
@@ -0,0 +1,19 @@
+class Main inherits IO {
+  reverse(s : String) : String {
+    if s.length() = 0 then "" else reverse(s.substr(1, s.length() - 1)).concat(s.substr(0, 1)) fi
+  };
+
+  main() : Object {
+    let name : String, age : Int in {
+      out_string("Name? ");
+      name <- in_string();
+      out_string("Age? ");
+      age <- in_int();
+      out_string("Hello ").out_string(name).out_string(", next year you'll be ");
+      out_int(age + 1).out_string(".\n");
+      out_string(reverse(name)).out_string("\n");
+      out_int(name.length()).out_string("\n");
+      out_string(in_string()).out_string("|\n");
+    }
+  };
+};
@@ -0,0 +1,10 @@
+class Main inherits IO {
+  a : Main;
+  main() : Object {
+    {
+      out_string("before\n");
+      a.main();
+      out_string("after\n");
+    }
+  };
+};
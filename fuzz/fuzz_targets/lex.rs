@@ -0,0 +1,36 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use lexer::{cool, Lexer};
+
+thread_local! {
+    static LEXER: Lexer = Lexer::new(cool::rules());
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        LEXER.with(|lexer| {
+            let tokens = lexer
+                .lex(source)
+                .expect("The COOL rules should always make progress");
+
+            let mut consumed = 0;
+            let mut line_number = 1;
+
+            for (token, context) in tokens {
+                let end = consumed + token.length;
+
+                assert!(source.is_char_boundary(end));
+                assert_eq!(token.as_str(), &source[consumed..end]);
+                assert!(context.line_number >= line_number);
+
+                let _ = token.to_string();
+
+                consumed = end;
+                line_number = context.line_number;
+            }
+
+            assert_eq!(consumed, source.len());
+        });
+    }
+});
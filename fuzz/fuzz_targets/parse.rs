@@ -0,0 +1,39 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use common::Symbol;
+use lexer::{cool, Lexer};
+use parser::Parser;
+
+thread_local! {
+    static LEXER: Lexer = Lexer::new(cool::rules());
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        LEXER.with(|lexer| {
+            let tokens = lexer
+                .lex(source)
+                .expect("The COOL rules should always make progress");
+            let last_line = tokens
+                .last()
+                .map(|(_, context)| context.line_number)
+                .unwrap_or(1);
+
+            match Parser::new(&tokens, Symbol::intern("fuzz.cl")).parse_program() {
+                Ok(program) => {
+                    assert!(!program.classes.is_empty());
+                    assert!(program.classes.iter().all(|class| class.line <= last_line));
+                }
+                Err(errors) => {
+                    assert!(!errors.is_empty());
+                    assert!(errors.iter().all(|error| error.line <= last_line));
+
+                    for error in errors {
+                        let _ = error.to_string();
+                    }
+                }
+            }
+        });
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use lexer::{LexerContext, Rule, StringRule};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let rule = StringRule::default();
+        let mut context = LexerContext::default();
+
+        match rule.try_match(source) {
+            Some(token) => {
+                assert!(source.starts_with('"'));
+                assert!(token.length > 0);
+                assert!(source.is_char_boundary(token.length));
+
+                let rest = rule.accept(&token, &mut context, source);
+
+                assert_eq!(rest.len(), source.len() - token.length);
+                assert_eq!(context.line_number, 1 + token.as_str().matches('\n').count());
+
+                let _ = token.to_string();
+            }
+            None => assert!(!source.starts_with('"')),
+        }
+    }
+});
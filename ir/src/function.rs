@@ -0,0 +1,563 @@
+//! The instructions, blocks and functions of the IR, and their textual dump.
+
+use std::fmt;
+
+use common::Symbol;
+
+/// An SSA value, defined once by a parameter or an instruction of a function.
+///
+/// `self` is always the first value of a function and its parameters are the next ones.
+#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Copy, Clone)]
+pub struct Value(pub u32);
+
+impl Value {
+    pub const SELF: Self = Self(0);
+
+    /// The value of the parameter at `index`.
+    pub fn param(index: usize) -> Self {
+        Self(index as u32 + 1)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+/// The index of a block in [`Function::blocks`].
+#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Copy, Clone)]
+pub struct BlockId(pub u32);
+
+impl BlockId {
+    /// The block a function starts in, which no other block jumps to.
+    pub const ENTRY: Self = Self(0);
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i32),
+    String(Symbol),
+    Bool(bool),
+    Void,
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "int {}", value),
+            Self::String(value) => write!(f, "string {:?}", value.as_str()),
+            Self::Bool(value) => write!(f, "bool {}", value),
+            Self::Void => write!(f, "void"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompareOp {
+    Lt,
+    Le,
+    /// Equality of COOL's `=`: by value for `Int`s, `String`s and `Bool`s, by identity otherwise.
+    Eq,
+}
+
+/// What a [`InstructionKind::CheckVoid`] guards, which decides the runtime error it reports.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VoidCheck {
+    Dispatch,
+    Case,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionKind {
+    Const(Constant),
+    /// The value coming from whichever of these predecessors the block was entered from.
+    Phi(Vec<(BlockId, Value)>),
+    /// Load an attribute of `self`.
+    GetAttribute(Symbol),
+    /// Store a value in an attribute of `self`.
+    SetAttribute(Symbol, Value),
+    /// Integer arithmetic, which wraps. Division by zero is a runtime error at `line`.
+    Arith {
+        op: ArithOp,
+        lhs: Value,
+        rhs: Value,
+        line: usize,
+    },
+    Compare {
+        op: CompareOp,
+        lhs: Value,
+        rhs: Value,
+    },
+    Neg(Value),
+    Not(Value),
+    IsVoid(Value),
+    /// Stop with a runtime error at `line` if the value is void.
+    CheckVoid {
+        value: Value,
+        check: VoidCheck,
+        line: usize,
+    },
+    /// Call the method looked up in the vtable of `receiver`, whose static type is `class`.
+    Dispatch {
+        receiver: Value,
+        class: Symbol,
+        method: Symbol,
+        args: Vec<Value>,
+    },
+    /// Call the method `class` has, whatever the class of `receiver`.
+    StaticDispatch {
+        receiver: Value,
+        class: Symbol,
+        method: Symbol,
+        args: Vec<Value>,
+    },
+    /// Create an object of the class and run its initializer. `SELF_TYPE` creates an object of
+    /// the class of `self`.
+    New(Symbol),
+    /// Run the initializer of the class on `self`, which initializers use for their parent's.
+    Init(Symbol),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// The value the instruction defines, if it has a result.
+    pub result: Option<Value>,
+    pub kind: InstructionKind,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(result) = self.result {
+            write!(f, "{} = ", result)?;
+        }
+
+        let args = |args: &[Value]| {
+            args.iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &self.kind {
+            InstructionKind::Const(constant) => write!(f, "const {}", constant),
+            InstructionKind::Phi(incoming) => {
+                let incoming: Vec<_> = incoming
+                    .iter()
+                    .map(|(block, value)| format!("[{}, {}]", block, value))
+                    .collect();
+                write!(f, "phi {}", incoming.join(", "))
+            }
+            InstructionKind::GetAttribute(name) => write!(f, "get_attr {}", name),
+            InstructionKind::SetAttribute(name, value) => {
+                write!(f, "set_attr {}, {}", name, value)
+            }
+            InstructionKind::Arith { op, lhs, rhs, line } => match op {
+                ArithOp::Add => write!(f, "add {}, {}", lhs, rhs),
+                ArithOp::Sub => write!(f, "sub {}, {}", lhs, rhs),
+                ArithOp::Mul => write!(f, "mul {}, {}", lhs, rhs),
+                ArithOp::Div => write!(f, "div {}, {}, line {}", lhs, rhs, line),
+            },
+            InstructionKind::Compare { op, lhs, rhs } => {
+                let name = match op {
+                    CompareOp::Lt => "lt",
+                    CompareOp::Le => "le",
+                    CompareOp::Eq => "eq",
+                };
+                write!(f, "{} {}, {}", name, lhs, rhs)
+            }
+            InstructionKind::Neg(value) => write!(f, "neg {}", value),
+            InstructionKind::Not(value) => write!(f, "not {}", value),
+            InstructionKind::IsVoid(value) => write!(f, "isvoid {}", value),
+            InstructionKind::CheckVoid { value, check, line } => {
+                let check = match check {
+                    VoidCheck::Dispatch => "dispatch",
+                    VoidCheck::Case => "case",
+                };
+                write!(f, "check_void {} {}, line {}", check, value, line)
+            }
+            InstructionKind::Dispatch {
+                receiver,
+                class,
+                method,
+                args: values,
+            } => write!(
+                f,
+                "dispatch {} {}.{}({})",
+                receiver,
+                class,
+                method,
+                args(values)
+            ),
+            InstructionKind::StaticDispatch {
+                receiver,
+                class,
+                method,
+                args: values,
+            } => write!(
+                f,
+                "static_dispatch {} {}.{}({})",
+                receiver,
+                class,
+                method,
+                args(values)
+            ),
+            InstructionKind::New(class) => write!(f, "new {}", class),
+            InstructionKind::Init(class) => write!(f, "init {}", class),
+        }
+    }
+}
+
+/// How a block ends.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Jump to `then_block` if the `Bool` is true and to `else_block` otherwise.
+    Branch {
+        condition: Value,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    /// Jump to the branch for the closest ancestor of the class of the value, which mustn't be
+    /// void. It's a runtime error if no branch matches.
+    Case {
+        value: Value,
+        branches: Vec<(Symbol, BlockId)>,
+    },
+    Return(Value),
+}
+
+impl Terminator {
+    /// The blocks this terminator can jump to.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Self::Jump(target) => vec![*target],
+            Self::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![*then_block, *else_block],
+            Self::Case { branches, .. } => branches.iter().map(|&(_, block)| block).collect(),
+            Self::Return(_) => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jump(target) => write!(f, "jump {}", target),
+            Self::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => write!(f, "branch {}, {}, {}", condition, then_block, else_block),
+            Self::Case { value, branches } => {
+                let branches: Vec<_> = branches
+                    .iter()
+                    .map(|(class, block)| format!("{}: {}", class, block))
+                    .collect();
+                write!(f, "case {} [{}]", value, branches.join(", "))
+            }
+            Self::Return(value) => write!(f, "return {}", value),
+        }
+    }
+}
+
+/// A basic block. Its phis, if it has any, come first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FunctionKind {
+    Method(Symbol),
+    /// The initializer of a class, which runs its parent's initializer and then the initializers
+    /// of the attributes the class declares.
+    Init,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub class: Symbol,
+    pub kind: FunctionKind,
+    /// The names of the parameters, after `self`.
+    pub params: Vec<Symbol>,
+    pub blocks: Vec<Block>,
+    /// How many values the function defines, parameters included. Values are numbered in the
+    /// order of the blocks.
+    pub values: u32,
+}
+
+impl Function {
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0 as usize]
+    }
+
+    /// The ids of the blocks, in order.
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len() as u32).map(BlockId)
+    }
+}
+
+impl fmt::Display for FunctionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Method(name) => write!(f, "{}", name),
+            Self::Init => write!(f, "_init"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<_> = std::iter::once(format!("{} self", Value::SELF))
+            .chain(
+                self.params
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| format!("{} {}", Value::param(index), name)),
+            )
+            .collect();
+        writeln!(
+            f,
+            "function {}.{}({}) {{",
+            self.class,
+            self.kind,
+            params.join(", ")
+        )?;
+
+        for (id, block) in self.block_ids().zip(&self.blocks) {
+            writeln!(f, "{}:", id)?;
+            for instruction in &block.instructions {
+                writeln!(f, "  {}", instruction)?;
+            }
+            writeln!(f, "  {}", block.terminator)?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+/// A whole program: the initializer of every class, the basic ones included, and every method of
+/// the classes the program defines. The methods of the basic classes are left to the runtime.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub functions: Vec<Function>,
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(result: u32, kind: InstructionKind) -> Instruction {
+        Instruction {
+            result: Some(Value(result)),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_dump_instructions() {
+        let dumped: Vec<_> = vec![
+            instruction(2, InstructionKind::Const(Constant::Int(-3))),
+            instruction(
+                2,
+                InstructionKind::Const(Constant::String("a\n\"b\"".into())),
+            ),
+            instruction(2, InstructionKind::Const(Constant::Bool(true))),
+            instruction(2, InstructionKind::Const(Constant::Void)),
+            instruction(
+                2,
+                InstructionKind::Phi(vec![(BlockId(1), Value(3)), (BlockId(4), Value(5))]),
+            ),
+            instruction(2, InstructionKind::GetAttribute("a".into())),
+            Instruction {
+                result: None,
+                kind: InstructionKind::SetAttribute("a".into(), Value(1)),
+            },
+            instruction(
+                2,
+                InstructionKind::Arith {
+                    op: ArithOp::Sub,
+                    lhs: Value(0),
+                    rhs: Value(1),
+                    line: 7,
+                },
+            ),
+            instruction(
+                2,
+                InstructionKind::Arith {
+                    op: ArithOp::Div,
+                    lhs: Value(0),
+                    rhs: Value(1),
+                    line: 7,
+                },
+            ),
+            instruction(
+                2,
+                InstructionKind::Compare {
+                    op: CompareOp::Le,
+                    lhs: Value(0),
+                    rhs: Value(1),
+                },
+            ),
+            instruction(2, InstructionKind::IsVoid(Value(1))),
+            Instruction {
+                result: None,
+                kind: InstructionKind::CheckVoid {
+                    value: Value(1),
+                    check: VoidCheck::Case,
+                    line: 9,
+                },
+            },
+            instruction(
+                2,
+                InstructionKind::Dispatch {
+                    receiver: Value(0),
+                    class: "A".into(),
+                    method: "f".into(),
+                    args: vec![Value(1), Value(1)],
+                },
+            ),
+            instruction(
+                2,
+                InstructionKind::StaticDispatch {
+                    receiver: Value(1),
+                    class: "B".into(),
+                    method: "g".into(),
+                    args: vec![],
+                },
+            ),
+            instruction(2, InstructionKind::New("SELF_TYPE".into())),
+            instruction(2, InstructionKind::Init("IO".into())),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+        assert_eq!(
+            dumped,
+            vec![
+                "%2 = const int -3",
+                "%2 = const string \"a\\n\\\"b\\\"\"",
+                "%2 = const bool true",
+                "%2 = const void",
+                "%2 = phi [b1, %3], [b4, %5]",
+                "%2 = get_attr a",
+                "set_attr a, %1",
+                "%2 = sub %0, %1",
+                "%2 = div %0, %1, line 7",
+                "%2 = le %0, %1",
+                "%2 = isvoid %1",
+                "check_void case %1, line 9",
+                "%2 = dispatch %0 A.f(%1, %1)",
+                "%2 = static_dispatch %1 B.g()",
+                "%2 = new SELF_TYPE",
+                "%2 = init IO",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dump_module() {
+        let method = Function {
+            class: "Main".into(),
+            kind: FunctionKind::Method("f".into()),
+            params: vec!["x".into()],
+            blocks: vec![
+                Block {
+                    instructions: vec![],
+                    terminator: Terminator::Branch {
+                        condition: Value(1),
+                        then_block: BlockId(1),
+                        else_block: BlockId(2),
+                    },
+                },
+                Block {
+                    instructions: vec![],
+                    terminator: Terminator::Case {
+                        value: Value(0),
+                        branches: vec![("Main".into(), BlockId(2)), ("Object".into(), BlockId(2))],
+                    },
+                },
+                Block {
+                    instructions: vec![],
+                    terminator: Terminator::Jump(BlockId(3)),
+                },
+                Block {
+                    instructions: vec![],
+                    terminator: Terminator::Return(Value(1)),
+                },
+            ],
+            values: 2,
+        };
+        let init = Function {
+            class: "Main".into(),
+            kind: FunctionKind::Init,
+            params: vec![],
+            blocks: vec![Block {
+                instructions: vec![],
+                terminator: Terminator::Return(Value::SELF),
+            }],
+            values: 1,
+        };
+        let module = Module {
+            functions: vec![init, method],
+        };
+
+        assert_eq!(
+            module.to_string(),
+            "function Main._init(%0 self) {\n\
+             b0:\n  \
+             return %0\n\
+             }\n\
+             \n\
+             function Main.f(%0 self, %1 x) {\n\
+             b0:\n  \
+             branch %1, b1, b2\n\
+             b1:\n  \
+             case %0 [Main: b2, Object: b2]\n\
+             b2:\n  \
+             jump b3\n\
+             b3:\n  \
+             return %1\n\
+             }\n"
+        );
+        assert_eq!(
+            module.functions[1]
+                .block(BlockId(1))
+                .terminator
+                .successors(),
+            vec![BlockId(2), BlockId(2)]
+        );
+    }
+}
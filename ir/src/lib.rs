@@ -0,0 +1,22 @@
+//! A mid-level intermediate representation of COOL programs in SSA form.
+//!
+//! [`lower`] turns a type checked program into a [`Module`] with a [`Function`] for every method
+//! and class initializer. Functions are made of basic blocks of instructions that each define at
+//! most one [`Value`], and the places where the AST only implies work, like looking up a method
+//! in a vtable, checking a receiver for void or picking a `case` branch, are explicit
+//! instructions. Locals are SSA values joined by phis, attributes are loaded from and stored to
+//! `self`. A module prints as a textual dump that `cool build --target ir` writes.
+//!
+//! Backends lower from a module instead of walking the AST themselves, the LLVM backend in
+//! `codegen` does.
+
+pub mod function;
+mod lower;
+
+pub use crate::function::{Block, Function, Module, Value};
+pub use crate::lower::lower;
+
+pub mod prelude {
+    pub use crate::function::*;
+    pub use crate::lower;
+}
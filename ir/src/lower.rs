@@ -0,0 +1,971 @@
+//! Lowering the typed AST to the IR.
+//!
+//! Locals are put in SSA form while the blocks are built, with the algorithm of Braun et al.,
+//! "Simple and Efficient Construction of Static Single Assignment Form": the value of a local in
+//! a block is looked up in its predecessors, and blocks whose predecessors aren't all known yet,
+//! like loop headers, get phis that are completed once they are. Phis that turn out to choose
+//! between a single value are replaced by that value.
+
+use std::collections::HashMap;
+use std::mem;
+
+use common::ast::*;
+use common::visit::{self, Visitor};
+use common::{sym, Symbol, SymbolTable};
+use semant::{ClassInfo, ClassTable};
+
+use crate::function::{self, *};
+
+/// Lower `program`, which must have been type checked with `table` as its class table.
+pub fn lower(program: &Program, table: &ClassTable) -> Module {
+    let classes: HashMap<_, _> = program
+        .classes
+        .iter()
+        .map(|class| (class.name, class))
+        .collect();
+
+    let mut functions: Vec<_> = table
+        .classes()
+        .map(|info| init(table, info, classes.get(&info.name).copied()))
+        .collect();
+    for class in &program.classes {
+        for feature in &class.features {
+            if let Feature::Method(method) = feature {
+                functions.push(self::method(table, class, method));
+            }
+        }
+    }
+
+    Module { functions }
+}
+
+fn init(table: &ClassTable, info: &ClassInfo, class: Option<&Class>) -> Function {
+    let mut builder = Builder::new(table, info.name);
+    if let Some(parent) = info.parent {
+        builder.instruction(InstructionKind::Init(parent));
+    }
+
+    let attributes = class.into_iter().flat_map(|class| {
+        class.features.iter().filter_map(|feature| match feature {
+            Feature::Attribute(attribute) => Some(attribute),
+            Feature::Method(_) => None,
+        })
+    });
+    for attribute in attributes {
+        if !attribute.init.is_no_expr() {
+            let value = builder.expr(&attribute.init);
+            builder.instruction(InstructionKind::SetAttribute(attribute.name, value));
+        }
+    }
+
+    builder.terminate(Terminator::Return(Value::SELF));
+    builder.finish(FunctionKind::Init, vec![])
+}
+
+fn method(table: &ClassTable, class: &Class, method: &Method) -> Function {
+    let mut builder = Builder::new(table, class.name);
+    builder.scope.enter_scope();
+    for (index, formal) in method.formals.iter().enumerate() {
+        let local = builder.local(formal.name);
+        builder.write_local(local, BlockId::ENTRY, Value::param(index));
+    }
+    builder.next_value += method.formals.len() as u32;
+
+    let value = builder.expr(&method.body);
+    builder.terminate(Terminator::Return(value));
+    builder.finish(
+        FunctionKind::Method(method.name),
+        method.formals.iter().map(|formal| formal.name).collect(),
+    )
+}
+
+/// A local variable, numbered apart from the others that share its name.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+struct Local(u32);
+
+#[derive(Debug, Copy, Clone)]
+enum Variable {
+    Local(Local),
+    Attribute,
+}
+
+/// A block while it's being built.
+#[derive(Debug, Default)]
+struct BlockData {
+    phis: Vec<(Value, Vec<(BlockId, Value)>)>,
+    instructions: Vec<Instruction>,
+    terminator: Option<Terminator>,
+    predecessors: Vec<BlockId>,
+    /// Whether all predecessors are known.
+    sealed: bool,
+    /// The phis created before the block was sealed, which still need their operands.
+    incomplete: Vec<(Local, Value)>,
+}
+
+/// Builds the blocks of one function.
+struct Builder {
+    class: Symbol,
+    scope: SymbolTable<Variable>,
+    blocks: Vec<BlockData>,
+    current: BlockId,
+    next_value: u32,
+    next_local: u32,
+    /// The value of each local at the end of the blocks that assign it.
+    definitions: HashMap<(Local, BlockId), Value>,
+    /// Trivial phis and the values they were replaced by.
+    replaced: HashMap<Value, Value>,
+    /// The line of the expression being lowered, for runtime errors.
+    line: usize,
+    /// The value of the expression lowered last.
+    value: Value,
+}
+
+impl Builder {
+    /// A builder for a function of `class`, with the attributes of the class in scope.
+    fn new(table: &ClassTable, class: Symbol) -> Self {
+        let mut scope = SymbolTable::new();
+        scope.enter_scope();
+        for info in table.ancestors(class) {
+            for attribute in &info.attributes {
+                scope.add(attribute.name, Variable::Attribute);
+            }
+        }
+
+        let mut builder = Self {
+            class,
+            scope,
+            blocks: vec![],
+            current: BlockId::ENTRY,
+            next_value: 1,
+            next_local: 0,
+            definitions: HashMap::new(),
+            replaced: HashMap::new(),
+            line: 0,
+            value: Value::SELF,
+        };
+        let entry = builder.block();
+        builder.seal(entry);
+
+        builder
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push(BlockData::default());
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+
+    fn data(&mut self, block: BlockId) -> &mut BlockData {
+        &mut self.blocks[block.0 as usize]
+    }
+
+    fn value(&mut self) -> Value {
+        self.next_value += 1;
+        Value(self.next_value - 1)
+    }
+
+    fn instruction(&mut self, kind: InstructionKind) {
+        let current = self.current;
+        self.data(current)
+            .instructions
+            .push(Instruction { result: None, kind });
+    }
+
+    /// Add an instruction with a result to the current block and return the result.
+    fn define(&mut self, kind: InstructionKind) -> Value {
+        let result = self.value();
+        let current = self.current;
+        self.data(current).instructions.push(Instruction {
+            result: Some(result),
+            kind,
+        });
+
+        result
+    }
+
+    fn constant(&mut self, constant: Constant) -> Value {
+        self.define(InstructionKind::Const(constant))
+    }
+
+    /// End the current block, which makes it a predecessor of the blocks it jumps to.
+    fn terminate(&mut self, terminator: Terminator) {
+        let current = self.current;
+        for successor in terminator.successors() {
+            self.data(successor).predecessors.push(current);
+        }
+        self.data(current).terminator = Some(terminator);
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    fn local(&mut self, name: Symbol) -> Local {
+        let local = Local(self.next_local);
+        self.next_local += 1;
+        self.scope.add(name, Variable::Local(local));
+
+        local
+    }
+
+    fn write_local(&mut self, local: Local, block: BlockId, value: Value) {
+        self.definitions.insert((local, block), value);
+    }
+
+    fn read_local(&mut self, local: Local, block: BlockId) -> Value {
+        match self.definitions.get(&(local, block)) {
+            Some(&value) => self.resolve(value),
+            None => self.read_local_recursive(local, block),
+        }
+    }
+
+    fn read_local_recursive(&mut self, local: Local, block: BlockId) -> Value {
+        let data = self.data(block);
+        let value = if !data.sealed {
+            let phi = self.phi(block);
+            self.data(block).incomplete.push((local, phi));
+            phi
+        } else if let [predecessor] = data.predecessors[..] {
+            self.read_local(local, predecessor)
+        } else {
+            // Break cycles through loops by defining the local as the phi before looking it up
+            // in the predecessors
+            let phi = self.phi(block);
+            self.write_local(local, block, phi);
+            self.add_phi_operands(local, block, phi)
+        };
+        self.write_local(local, block, value);
+
+        value
+    }
+
+    /// A phi without operands at the start of `block`.
+    fn phi(&mut self, block: BlockId) -> Value {
+        let phi = self.value();
+        self.data(block).phis.push((phi, vec![]));
+
+        phi
+    }
+
+    fn add_phi_operands(&mut self, local: Local, block: BlockId, phi: Value) -> Value {
+        for predecessor in self.data(block).predecessors.clone() {
+            let value = self.read_local(local, predecessor);
+            self.phi_operands(block, phi).push((predecessor, value));
+        }
+
+        self.remove_trivial_phi(block, phi)
+    }
+
+    fn phi_operands(&mut self, block: BlockId, phi: Value) -> &mut Vec<(BlockId, Value)> {
+        let (_, operands) = self
+            .data(block)
+            .phis
+            .iter_mut()
+            .find(|(value, _)| *value == phi)
+            .expect("Unknown phi");
+
+        operands
+    }
+
+    /// Replace `phi` by its only operand other than itself, if it has one.
+    fn remove_trivial_phi(&mut self, block: BlockId, phi: Value) -> Value {
+        let mut same = None;
+        for (_, operand) in self.phi_operands(block, phi).clone() {
+            let operand = self.resolve(operand);
+            if Some(operand) == same || operand == phi {
+                continue;
+            }
+            if same.is_some() {
+                return phi;
+            }
+            same = Some(operand);
+        }
+
+        // Every local is defined before it's used, so some operand comes from that definition
+        let same = same.expect("Phi of an undefined local");
+        self.data(block).phis.retain(|(value, _)| *value != phi);
+        self.replaced.insert(phi, same);
+
+        same
+    }
+
+    fn resolve(&self, mut value: Value) -> Value {
+        while let Some(&replacement) = self.replaced.get(&value) {
+            value = replacement;
+        }
+
+        value
+    }
+
+    /// Mark that all predecessors of `block` are known and complete its phis.
+    fn seal(&mut self, block: BlockId) {
+        for (local, phi) in mem::take(&mut self.data(block).incomplete) {
+            self.add_phi_operands(local, block, phi);
+        }
+        self.data(block).sealed = true;
+    }
+
+    /// A phi of the values `incoming` has in each of the predecessors of `block`, which must be
+    /// sealed.
+    fn join(&mut self, block: BlockId, incoming: Vec<(BlockId, Value)>) -> Value {
+        let phi = self.phi(block);
+        *self.phi_operands(block, phi) = incoming;
+
+        self.remove_trivial_phi(block, phi)
+    }
+
+    /// The function built, with its values renumbered in the order of the blocks.
+    fn finish(self, kind: FunctionKind, params: Vec<Symbol>) -> Function {
+        let mut numbers = HashMap::new();
+        for value in 0..=params.len() as u32 {
+            numbers.insert(Value(value), Value(value));
+        }
+        for block in &self.blocks {
+            let results = block.phis.iter().map(|&(phi, _)| phi).chain(
+                block
+                    .instructions
+                    .iter()
+                    .filter_map(|instruction| instruction.result),
+            );
+            for result in results {
+                numbers.insert(result, Value(numbers.len() as u32));
+            }
+        }
+        let values = numbers.len() as u32;
+        let number = |value: Value| numbers[&self.resolve(value)];
+
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let phis = block.phis.iter().map(|(phi, incoming)| Instruction {
+                    result: Some(number(*phi)),
+                    kind: InstructionKind::Phi(
+                        incoming
+                            .iter()
+                            .map(|&(block, value)| (block, number(value)))
+                            .collect(),
+                    ),
+                });
+                let instructions = block.instructions.iter().map(|instruction| Instruction {
+                    result: instruction.result.map(number),
+                    kind: renumber(&instruction.kind, number),
+                });
+                let terminator = match block.terminator.clone().expect("Unterminated block") {
+                    Terminator::Branch {
+                        condition,
+                        then_block,
+                        else_block,
+                    } => Terminator::Branch {
+                        condition: number(condition),
+                        then_block,
+                        else_block,
+                    },
+                    Terminator::Case { value, branches } => Terminator::Case {
+                        value: number(value),
+                        branches,
+                    },
+                    Terminator::Return(value) => Terminator::Return(number(value)),
+                    jump @ Terminator::Jump(_) => jump,
+                };
+
+                function::Block {
+                    instructions: phis.chain(instructions).collect(),
+                    terminator,
+                }
+            })
+            .collect();
+
+        Function {
+            class: self.class,
+            kind,
+            params,
+            blocks,
+            values,
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) -> Vec<Value> {
+        args.iter().map(|arg| self.expr(arg)).collect()
+    }
+
+    /// Stop with a runtime error at the current line if `value` is void. `self` never is.
+    fn check_void(&mut self, value: Value, check: VoidCheck) {
+        if value != Value::SELF {
+            let line = self.line;
+            self.instruction(InstructionKind::CheckVoid { value, check, line });
+        }
+    }
+
+    /// The default value of a variable of type `ty`.
+    fn default(&mut self, ty: Symbol) -> Value {
+        let constant = match ty {
+            sym::INT => Constant::Int(0),
+            sym::STRING => Constant::String(Symbol::intern("")),
+            sym::BOOL => Constant::Bool(false),
+            _ => Constant::Void,
+        };
+
+        self.constant(constant)
+    }
+
+    /// Lower `body` with `name` bound to a new local holding `value`.
+    fn bind(&mut self, name: Symbol, value: Value, body: &Expr) -> Value {
+        self.scope.enter_scope();
+        let local = self.local(name);
+        self.write_local(local, self.current, value);
+        let result = self.expr(body);
+        self.scope.exit_scope();
+
+        result
+    }
+
+    /// Lower `expr` into the current block and return its value.
+    fn expr(&mut self, expr: &Expr) -> Value {
+        self.visit_expr(expr);
+
+        self.value
+    }
+}
+
+impl Visitor for Builder {
+    fn visit_expr(&mut self, expr: &Expr) {
+        let line = mem::replace(&mut self.line, expr.line);
+        visit::walk_expr(self, expr);
+        self.line = line;
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        let value = self.expr(&assign.expr);
+        match self.scope.lookup(assign.name).copied() {
+            Some(Variable::Local(local)) => self.write_local(local, self.current, value),
+            Some(Variable::Attribute) => {
+                self.instruction(InstructionKind::SetAttribute(assign.name, value))
+            }
+            None => panic!("Assignment to undeclared {}", assign.name),
+        }
+
+        self.value = value;
+    }
+
+    fn visit_static_dispatch(&mut self, dispatch: &StaticDispatch) {
+        // Like in the reference compiler, the arguments are evaluated before the receiver
+        let args = self.args(&dispatch.args);
+        let receiver = self.expr(&dispatch.expr);
+        self.check_void(receiver, VoidCheck::Dispatch);
+
+        self.value = self.define(InstructionKind::StaticDispatch {
+            receiver,
+            class: dispatch.type_name,
+            method: dispatch.name,
+            args,
+        });
+    }
+
+    fn visit_dispatch(&mut self, dispatch: &Dispatch) {
+        let args = self.args(&dispatch.args);
+        let receiver = self.expr(&dispatch.expr);
+        self.check_void(receiver, VoidCheck::Dispatch);
+
+        let class = match dispatch.expr.ty.expect("Untyped receiver") {
+            sym::SELF_TYPE => self.class,
+            ty => ty,
+        };
+        self.value = self.define(InstructionKind::Dispatch {
+            receiver,
+            class,
+            method: dispatch.name,
+            args,
+        });
+    }
+
+    fn visit_cond(&mut self, cond: &Cond) {
+        let condition = self.expr(&cond.pred);
+        let (then_block, else_block) = (self.block(), self.block());
+        self.terminate(Terminator::Branch {
+            condition,
+            then_block,
+            else_block,
+        });
+        self.seal(then_block);
+        self.seal(else_block);
+
+        self.switch_to(then_block);
+        let then_value = self.expr(&cond.then_expr);
+        let then_end = self.current;
+
+        self.switch_to(else_block);
+        let else_value = self.expr(&cond.else_expr);
+        let else_end = self.current;
+
+        let end = self.block();
+        self.switch_to(then_end);
+        self.terminate(Terminator::Jump(end));
+        self.switch_to(else_end);
+        self.terminate(Terminator::Jump(end));
+        self.seal(end);
+
+        self.switch_to(end);
+        self.value = self.join(end, vec![(then_end, then_value), (else_end, else_value)]);
+    }
+
+    fn visit_loop(&mut self, loop_: &Loop) {
+        let start = self.block();
+        self.terminate(Terminator::Jump(start));
+
+        self.switch_to(start);
+        let condition = self.expr(&loop_.pred);
+        let (body, end) = (self.block(), self.block());
+        self.terminate(Terminator::Branch {
+            condition,
+            then_block: body,
+            else_block: end,
+        });
+        self.seal(body);
+        self.seal(end);
+
+        self.switch_to(body);
+        self.expr(&loop_.body);
+        self.terminate(Terminator::Jump(start));
+        self.seal(start);
+
+        self.switch_to(end);
+        self.value = self.constant(Constant::Void);
+    }
+
+    fn visit_case(&mut self, case: &Case) {
+        let value = self.expr(&case.expr);
+        self.check_void(value, VoidCheck::Case);
+
+        let branches: Vec<_> = case
+            .branches
+            .iter()
+            .map(|branch| (branch.type_decl, self.block()))
+            .collect();
+        self.terminate(Terminator::Case {
+            value,
+            branches: branches.clone(),
+        });
+
+        let mut incoming = vec![];
+        for (branch, &(_, block)) in case.branches.iter().zip(&branches) {
+            self.seal(block);
+            self.switch_to(block);
+            let result = self.bind(branch.name, value, &branch.expr);
+            incoming.push((self.current, result));
+        }
+
+        let end = self.block();
+        for &(block, _) in &incoming {
+            self.switch_to(block);
+            self.terminate(Terminator::Jump(end));
+        }
+        self.seal(end);
+
+        self.switch_to(end);
+        self.value = self.join(end, incoming);
+    }
+
+    fn visit_block(&mut self, block: &common::ast::Block) {
+        let mut value = None;
+        for expr in &block.body {
+            value = Some(self.expr(expr));
+        }
+
+        self.value = value.unwrap_or_else(|| self.constant(Constant::Void));
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        let value = if let_.init.is_no_expr() {
+            self.default(let_.type_decl)
+        } else {
+            self.expr(&let_.init)
+        };
+
+        self.value = self.bind(let_.name, value, &let_.body);
+    }
+
+    fn visit_binary(&mut self, binary: &Binary) {
+        let lhs = self.expr(&binary.lhs);
+        let rhs = self.expr(&binary.rhs);
+
+        let line = self.line;
+        let arith = |op| InstructionKind::Arith { op, lhs, rhs, line };
+        let compare = |op| InstructionKind::Compare { op, lhs, rhs };
+        self.value = self.define(match binary.op {
+            BinaryOp::Plus => arith(ArithOp::Add),
+            BinaryOp::Sub => arith(ArithOp::Sub),
+            BinaryOp::Mul => arith(ArithOp::Mul),
+            BinaryOp::Divide => arith(ArithOp::Div),
+            BinaryOp::Lt => compare(CompareOp::Lt),
+            BinaryOp::Leq => compare(CompareOp::Le),
+            BinaryOp::Eq => compare(CompareOp::Eq),
+        });
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) {
+        let value = self.expr(&unary.expr);
+        self.value = self.define(match unary.op {
+            UnaryOp::Neg => InstructionKind::Neg(value),
+            UnaryOp::Comp => InstructionKind::Not(value),
+            UnaryOp::IsVoid => InstructionKind::IsVoid(value),
+        });
+    }
+
+    fn visit_int_const(&mut self, digits: Symbol) {
+        let value = digits.as_str().bytes().fold(0i32, |value, digit| {
+            value.wrapping_mul(10).wrapping_add(i32::from(digit - b'0'))
+        });
+        self.value = self.constant(Constant::Int(value));
+    }
+
+    fn visit_bool_const(&mut self, value: bool) {
+        self.value = self.constant(Constant::Bool(value));
+    }
+
+    fn visit_string_const(&mut self, value: Symbol) {
+        self.value = self.constant(Constant::String(value));
+    }
+
+    fn visit_new(&mut self, type_name: Symbol) {
+        self.value = self.define(InstructionKind::New(type_name));
+    }
+
+    fn visit_no_expr(&mut self) {
+        self.value = self.constant(Constant::Void);
+    }
+
+    fn visit_object(&mut self, name: Symbol) {
+        self.value = if name == sym::SELF {
+            Value::SELF
+        } else {
+            match self.scope.lookup(name).copied() {
+                Some(Variable::Local(local)) => self.read_local(local, self.current),
+                Some(Variable::Attribute) => self.define(InstructionKind::GetAttribute(name)),
+                None => panic!("Undeclared identifier {}", name),
+            }
+        };
+    }
+}
+
+/// `kind` with its operands renumbered by `number`.
+fn renumber(kind: &InstructionKind, number: impl Fn(Value) -> Value) -> InstructionKind {
+    let args = |args: &[Value]| args.iter().copied().map(&number).collect();
+
+    match kind {
+        InstructionKind::Const(_)
+        | InstructionKind::GetAttribute(_)
+        | InstructionKind::New(_)
+        | InstructionKind::Init(_) => kind.clone(),
+        InstructionKind::Phi(incoming) => InstructionKind::Phi(
+            incoming
+                .iter()
+                .map(|&(block, value)| (block, number(value)))
+                .collect(),
+        ),
+        InstructionKind::SetAttribute(name, value) => {
+            InstructionKind::SetAttribute(*name, number(*value))
+        }
+        InstructionKind::Arith { op, lhs, rhs, line } => InstructionKind::Arith {
+            op: *op,
+            lhs: number(*lhs),
+            rhs: number(*rhs),
+            line: *line,
+        },
+        InstructionKind::Compare { op, lhs, rhs } => InstructionKind::Compare {
+            op: *op,
+            lhs: number(*lhs),
+            rhs: number(*rhs),
+        },
+        InstructionKind::Neg(value) => InstructionKind::Neg(number(*value)),
+        InstructionKind::Not(value) => InstructionKind::Not(number(*value)),
+        InstructionKind::IsVoid(value) => InstructionKind::IsVoid(number(*value)),
+        InstructionKind::CheckVoid { value, check, line } => InstructionKind::CheckVoid {
+            value: number(*value),
+            check: *check,
+            line: *line,
+        },
+        InstructionKind::Dispatch {
+            receiver,
+            class,
+            method,
+            args: values,
+        } => InstructionKind::Dispatch {
+            receiver: number(*receiver),
+            class: *class,
+            method: *method,
+            args: args(values),
+        },
+        InstructionKind::StaticDispatch {
+            receiver,
+            class,
+            method,
+            args: values,
+        } => InstructionKind::StaticDispatch {
+            receiver: number(*receiver),
+            class: *class,
+            method: *method,
+            args: args(values),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn lower_program(input: &str) -> Module {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        lower(&program, &table)
+    }
+
+    fn function(module: &Module, class: &str, kind: FunctionKind) -> String {
+        module
+            .functions
+            .iter()
+            .find(|function| function.class.as_str() == class && function.kind == kind)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_lower_init() {
+        let module = lower_program(
+            "class A { a : Int <- 1; b : A; };\n\
+             class Main inherits A { main() : Object { a }; };\n",
+        );
+
+        assert_eq!(
+            function(&module, "A", FunctionKind::Init),
+            "function A._init(%0 self) {\n\
+             b0:\n  \
+             init Object\n  \
+             %1 = const int 1\n  \
+             set_attr a, %1\n  \
+             return %0\n\
+             }\n"
+        );
+        assert_eq!(
+            function(&module, "Object", FunctionKind::Init),
+            "function Object._init(%0 self) {\nb0:\n  return %0\n}\n"
+        );
+        assert_eq!(
+            function(&module, "Main", FunctionKind::Method(sym::MAIN_METHOD)),
+            "function Main.main(%0 self) {\nb0:\n  %1 = get_attr a\n  return %1\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_lower_loop() {
+        let module = lower_program(
+            "class Main {\n\
+             main() : Object { 0 };\n\
+             sum(n : Int) : Int { let s : Int in { while 0 < n loop { s <- s + n; n <- n - 1; } pool; s; } };\n\
+             };\n",
+        );
+
+        // The sum and the counter are phis in the loop header, the loop's condition only reads
+        // them
+        assert_eq!(
+            function(&module, "Main", FunctionKind::Method(Symbol::intern("sum"))),
+            "function Main.sum(%0 self, %1 n) {\n\
+             b0:\n  \
+             %2 = const int 0\n  \
+             jump b1\n\
+             b1:\n  \
+             %3 = phi [b0, %1], [b2, %9]\n  \
+             %4 = phi [b0, %2], [b2, %7]\n  \
+             %5 = const int 0\n  \
+             %6 = lt %5, %3\n  \
+             branch %6, b2, b3\n\
+             b2:\n  \
+             %7 = add %4, %3\n  \
+             %8 = const int 1\n  \
+             %9 = sub %3, %8\n  \
+             jump b1\n\
+             b3:\n  \
+             %10 = const void\n  \
+             return %4\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_lower_cond_and_case() {
+        let module = lower_program(
+            "class Main {\n\
+             main() : Object { 0 };\n\
+             f(x : Object, b : Bool) : Object {\n\
+             { if b then x <- 1 else b fi; case x of i : Int => i; o : Object => x.copy(); esac; }\n\
+             };\n\
+             };\n",
+        );
+
+        assert_eq!(
+            function(&module, "Main", FunctionKind::Method(Symbol::intern("f"))),
+            "function Main.f(%0 self, %1 x, %2 b) {\n\
+             b0:\n  \
+             branch %2, b1, b2\n\
+             b1:\n  \
+             %3 = const int 1\n  \
+             jump b3\n\
+             b2:\n  \
+             jump b3\n\
+             b3:\n  \
+             %4 = phi [b1, %3], [b2, %2]\n  \
+             %5 = phi [b1, %3], [b2, %1]\n  \
+             check_void case %5, line 4\n  \
+             case %5 [Int: b4, Object: b5]\n\
+             b4:\n  \
+             jump b6\n\
+             b5:\n  \
+             check_void dispatch %5, line 4\n  \
+             %6 = dispatch %5 Object.copy()\n  \
+             jump b6\n\
+             b6:\n  \
+             %7 = phi [b4, %5], [b5, %6]\n  \
+             return %7\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_lower_nested_loops() {
+        let module = lower_program(
+            "class Main {\n\
+             main() : Object { 0 };\n\
+             f(n : Int, m : Int) : Int { let i : Int, s : Int in {\n\
+             while i < n loop { let j : Int in while j < m loop { s <- s + j; j <- j + 1; } pool; i <- i + 1; } pool;\n\
+             s;\n\
+             } };\n\
+             };\n",
+        );
+
+        // Only the inner loop changes `j`, so it has no phi in the outer loop's header, and `i` has
+        // none in the inner loop's header since only the outer loop changes it
+        assert_eq!(
+            function(&module, "Main", FunctionKind::Method(Symbol::intern("f"))),
+            "function Main.f(%0 self, %1 n, %2 m) {\n\
+             b0:\n  \
+             %3 = const int 0\n  \
+             %4 = const int 0\n  \
+             jump b1\n\
+             b1:\n  \
+             %5 = phi [b0, %3], [b6, %18]\n  \
+             %6 = phi [b0, %4], [b6, %11]\n  \
+             %7 = lt %5, %1\n  \
+             branch %7, b2, b3\n\
+             b2:\n  \
+             %8 = const int 0\n  \
+             jump b4\n\
+             b3:\n  \
+             %9 = const void\n  \
+             return %6\n\
+             b4:\n  \
+             %10 = phi [b2, %8], [b5, %15]\n  \
+             %11 = phi [b2, %6], [b5, %13]\n  \
+             %12 = lt %10, %2\n  \
+             branch %12, b5, b6\n\
+             b5:\n  \
+             %13 = add %11, %10\n  \
+             %14 = const int 1\n  \
+             %15 = add %10, %14\n  \
+             jump b4\n\
+             b6:\n  \
+             %16 = const void\n  \
+             %17 = const int 1\n  \
+             %18 = add %5, %17\n  \
+             jump b1\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_lower_let_shadowing() {
+        let module = lower_program(
+            "class Main {\n\
+             main() : Object { 0 };\n\
+             f(x : Int, b : Bool) : Int { {\n\
+             if b then let x : Int <- 2 in x <- x + 1 else x fi;\n\
+             while b loop let x : Int in { x <- 3; b <- false; } pool;\n\
+             x;\n\
+             } };\n\
+             };\n",
+        );
+
+        // Assigning the `x`s bound by the lets leaves the formal alone, so it needs no phis and is
+        // returned as is. The phi after the `if` is the value of the `if`.
+        assert_eq!(
+            function(&module, "Main", FunctionKind::Method(Symbol::intern("f"))),
+            "function Main.f(%0 self, %1 x, %2 b) {\n\
+             b0:\n  \
+             branch %2, b1, b2\n\
+             b1:\n  \
+             %3 = const int 2\n  \
+             %4 = const int 1\n  \
+             %5 = add %3, %4\n  \
+             jump b3\n\
+             b2:\n  \
+             jump b3\n\
+             b3:\n  \
+             %6 = phi [b1, %5], [b2, %1]\n  \
+             jump b4\n\
+             b4:\n  \
+             %7 = phi [b3, %2], [b5, %10]\n  \
+             branch %7, b5, b6\n\
+             b5:\n  \
+             %8 = const int 0\n  \
+             %9 = const int 3\n  \
+             %10 = const bool false\n  \
+             jump b4\n\
+             b6:\n  \
+             %11 = const void\n  \
+             return %1\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_lower_case_and_void_checks() {
+        let module = lower_program(
+            "class A { g() : Int { 1 }; };\n\
+             class Main {\n\
+             a : A;\n\
+             main() : Object { 0 };\n\
+             f(x : Object) : Int { case x of x : A => x.g(); a : Main => a@Main.f(x); o : Object => 0; esac };\n\
+             };\n",
+        );
+
+        // The branches bind the value being matched itself, shadowing the formal and the
+        // attribute, and every dispatch on them checks for void first
+        assert_eq!(
+            function(&module, "Main", FunctionKind::Method(Symbol::intern("f"))),
+            "function Main.f(%0 self, %1 x) {\n\
+             b0:\n  \
+             check_void case %1, line 5\n  \
+             case %1 [A: b1, Main: b2, Object: b3]\n\
+             b1:\n  \
+             check_void dispatch %1, line 5\n  \
+             %2 = dispatch %1 A.g()\n  \
+             jump b4\n\
+             b2:\n  \
+             check_void dispatch %1, line 5\n  \
+             %3 = static_dispatch %1 Main.f(%1)\n  \
+             jump b4\n\
+             b3:\n  \
+             %4 = const int 0\n  \
+             jump b4\n\
+             b4:\n  \
+             %5 = phi [b1, %2], [b2, %3], [b3, %4]\n  \
+             return %5\n\
+             }\n"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use common::{KeywordKind, Symbol, TokenKind};
+use lexer::{cool, BlockCommentRule, KeywordRule, Lexer, LiteralRule, RegexRule, Rule, StringRule};
+
+mod programs;
+
+use programs::program;
+
+fn string_heavy(strings: usize) -> String {
+    (0..strings)
+        .map(|i| {
+            format!(
+                "\"string {} with \\\"escapes\\\"\\n\\t and a \\\nline break\"\n",
+                i
+            )
+        })
+        .collect()
+}
+
+fn comment_heavy(comments: usize) -> String {
+    (0..comments)
+        .map(|i| {
+            format!(
+                "(* comment {} (* nested *)\n spanning lines *)\n-- line comment\n",
+                i
+            )
+        })
+        .collect()
+}
+
+fn bench_lex(c: &mut Criterion) {
+    let lexer = Lexer::new(cool::rules());
+    let inputs = [
+        ("small", program(1)),
+        ("large", program(200)),
+        ("strings", string_heavy(1000)),
+        ("comments", comment_heavy(1000)),
+    ];
+
+    let mut group = c.benchmark_group("lex");
+    for (name, source) in inputs.iter() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), source, |b, source| {
+            b.iter(|| lexer.lex(source).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_rules(c: &mut Criterion) {
+    let object_id = RegexRule::refined(
+        "(self|[a-z][A-Za-z0-9_]*)",
+        Box::new(|mat| Some(TokenKind::ObjectId(Symbol::intern(mat.as_str())))),
+    )
+    .unwrap();
+    let keywords = KeywordRule::new(vec![
+        ("class", KeywordKind::Class),
+        ("inherits", KeywordKind::Inherits),
+        ("in", KeywordKind::In),
+        ("isvoid", KeywordKind::IsVoid),
+        ("while", KeywordKind::While),
+    ]);
+    let literal = LiteralRule::new("<-", TokenKind::Assign);
+    let string = StringRule::default();
+    let block_comment = BlockCommentRule::default();
+
+    let rules: [(&str, &dyn Rule, String); 5] = [
+        (
+            "regex",
+            &object_id,
+            "identifier_with_some_length <- 1".into(),
+        ),
+        ("keyword", &keywords, "inherits IO".into()),
+        ("literal", &literal, "<- 10".into()),
+        ("string", &string, string_heavy(1)),
+        ("block_comment", &block_comment, comment_heavy(1)),
+    ];
+
+    let mut group = c.benchmark_group("try_match");
+    for (name, rule, source) in rules.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), source, |b, source| {
+            b.iter(|| rule.try_match(source).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lex, bench_rules);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! The programs the benchmarks of every phase of the compiler run on, included by the benchmarks
+//! of the other crates.
+
+use std::fmt::Write;
+
+/// A synthetic COOL program with `classes` classes that uses most of the language, and a `Main`
+/// class so it also type checks.
+pub fn program(classes: usize) -> String {
+    let mut source = String::new();
+
+    for i in 0..classes {
+        write!(
+            source,
+            r#"(* Class number {i} *)
+class C{i} inherits IO {{
+    count : Int <- {i};
+    name : String <- "C{i}\tclass";
+
+    -- Count down to zero
+    run(n : Int, verbose : Bool) : SELF_TYPE {{
+        {{
+            while 0 < n loop
+                {{
+                    n <- n - 1;
+                    if verbose then out_string(name.concat("\n")) else self fi;
+                    count <- count + n * 2 / 1;
+                }}
+            pool;
+            let c : C{i} <- new C{i} in case c of x : C{i} => x; o : Object => self; esac;
+            isvoid self;
+            not true = false;
+            ~count <= 10;
+            self@IO.out_int(count);
+        }}
+    }};
+}};
+"#,
+            i = i
+        )
+        .unwrap();
+    }
+    source.push_str("class Main {\n    main() : Object { (new C0).run(3, false) };\n};\n");
+
+    source
+}
@@ -0,0 +1,154 @@
+//! A compact binary format for persisting lexed token streams.
+//!
+//! A cache file starts with the magic bytes `COOLTOK`, followed by the format version and the
+//! tokens encoded with bincode.
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use common::{Token, TokenKind};
+
+use crate::LexerContext;
+
+const MAGIC: &[u8; 7] = b"COOLTOK";
+const VERSION: u8 = 1;
+/// The most bytes `read_tokens` decodes, a corrupt length can't make it allocate more than this.
+const SIZE_LIMIT: u64 = 1 << 30;
+
+/// The bincode configuration of the format, the same as `bincode::serialize` uses.
+fn options() -> impl Options {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+/// A token without a reference to the source text it was lexed from.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CachedToken {
+    pub kind: TokenKind,
+    pub length: usize,
+    pub context: LexerContext,
+}
+
+impl<'s> From<&(Token<'s>, LexerContext)> for CachedToken {
+    fn from((token, context): &(Token<'s>, LexerContext)) -> Self {
+        Self {
+            kind: token.kind.clone(),
+            length: token.length,
+            context: context.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    /// The input isn't a token cache.
+    BadMagic,
+    /// The cache was written by an incompatible version.
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Encoding(err) => write!(f, "{}", err),
+            Self::BadMagic => write!(f, "not a token cache"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported token cache version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<io::Error> for CacheError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<bincode::Error> for CacheError {
+    fn from(err: bincode::Error) -> Self {
+        Self::Encoding(err)
+    }
+}
+
+pub fn write_tokens<'s, W: Write>(
+    mut writer: W,
+    tokens: &[(Token<'s>, LexerContext)],
+) -> Result<(), CacheError> {
+    let tokens: Vec<CachedToken> = tokens.iter().map(CachedToken::from).collect();
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    options().serialize_into(writer, &tokens)?;
+
+    Ok(())
+}
+
+pub fn read_tokens<R: Read>(mut reader: R) -> Result<Vec<CachedToken>, CacheError> {
+    let mut header = [0; MAGIC.len() + 1];
+    reader.read_exact(&mut header)?;
+
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(CacheError::BadMagic);
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(CacheError::UnsupportedVersion(header[MAGIC.len()]));
+    }
+
+    Ok(options().with_limit(SIZE_LIMIT).deserialize_from(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cool, Lexer};
+
+    #[test]
+    fn test_round_trip() {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer
+            .lex("class Main {\n  s : String <- \"\u{e9}\";\n  (* *) x };")
+            .unwrap();
+        let mut buffer = vec![];
+
+        write_tokens(&mut buffer, &tokens).unwrap();
+        let cached = read_tokens(&buffer[..]).unwrap();
+
+        assert_eq!(
+            cached,
+            tokens.iter().map(CachedToken::from).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_corrupt_length_is_an_error() {
+        let mut buffer = b"COOLTOK\x01".to_vec();
+        buffer.extend_from_slice(&u64::MAX.to_le_bytes());
+
+        assert!(matches!(
+            read_tokens(&buffer[..]),
+            Err(CacheError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_header() {
+        assert!(matches!(
+            read_tokens(&b"COOLTOK\x09"[..]),
+            Err(CacheError::UnsupportedVersion(9))
+        ));
+        assert!(matches!(
+            read_tokens(&b"NOTCOOL\x01"[..]),
+            Err(CacheError::BadMagic)
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+//! The lexical rules of the COOL language.
+
+use regex::Match;
+
+use common::{KeywordKind, Symbol, TokenKind};
+
+use crate::rule::{BlockCommentRule, KeywordRule, LiteralRule, RegexRule, Rule, StringRule};
+
+fn re_rule(pattern: &str, token: TokenKind, desc: &str) -> Box<RegexRule> {
+    Box::new(
+        RegexRule::new(pattern, token)
+            .unwrap_or_else(|_| panic!("Should be able to build regex rule for {}", desc)),
+    )
+}
+
+fn refined_re_rule<F>(pattern: &str, refinement: F, desc: &str) -> Box<RegexRule>
+where
+    F: Fn(Match) -> Option<TokenKind> + Send + Sync + 'static,
+{
+    Box::new(
+        RegexRule::refined(pattern, Box::new(refinement))
+            .unwrap_or_else(|_| panic!("Should be able to build regex rule for {}", desc)),
+    )
+}
+
+fn lit_rule(lit: &'static str, token: TokenKind) -> Box<dyn Rule> {
+    Box::new(LiteralRule::new(lit, token))
+}
+
+fn refine_type_id(mat: Match) -> Option<TokenKind> {
+    Some(TokenKind::TypeId(Symbol::intern(mat.as_str())))
+}
+
+fn refine_object_id(mat: Match) -> Option<TokenKind> {
+    Some(TokenKind::ObjectId(Symbol::intern(mat.as_str())))
+}
+
+fn refine_int(mat: Match) -> Option<TokenKind> {
+    Some(TokenKind::Int(Symbol::intern(mat.as_str())))
+}
+
+fn refine_error(mat: Match) -> Option<TokenKind> {
+    Some(TokenKind::Error(mat.as_str().into()))
+}
+
+/// Priorities used to break ties between rules that consume the same number of characters.
+mod priority {
+    /// Keywords and boolean constants win over identifiers.
+    pub const KEYWORD: i32 = 1;
+    /// Only used when no other rule matches.
+    pub const CATCH_ALL: i32 = -1;
+}
+
+/// The rules for lexing COOL source code.
+///
+/// COOL source is ASCII. Non-ASCII characters are accepted inside string constants and comments,
+/// anywhere else each non-ASCII character becomes an error token. Like every other string, the
+/// error is printed with one octal escape per byte, matching the byte based reference lexer.
+pub fn rules() -> Vec<Box<dyn Rule>> {
+    // Lexical analysis rules
+    // We use max munch, when two rules consume the same number of characters the one with the
+    // highest priority wins. Use `Lexer::validate` to check for conflicts after changing these.
+    vec![
+        // Keywords
+        Box::new(
+            KeywordRule::new(vec![
+                ("class", KeywordKind::Class),
+                ("else", KeywordKind::Else),
+                ("fi", KeywordKind::Fi),
+                ("if", KeywordKind::If),
+                ("in", KeywordKind::In),
+                ("inherits", KeywordKind::Inherits),
+                ("isvoid", KeywordKind::IsVoid),
+                ("let", KeywordKind::Let),
+                ("loop", KeywordKind::Loop),
+                ("pool", KeywordKind::Pool),
+                ("then", KeywordKind::Then),
+                ("while", KeywordKind::While),
+                ("case", KeywordKind::Case),
+                ("esac", KeywordKind::Esac),
+                ("new", KeywordKind::New),
+                ("of", KeywordKind::Of),
+                ("not", KeywordKind::Not),
+            ])
+            .with_priority(priority::KEYWORD),
+        ),
+        lit_rule("<=", TokenKind::Le),
+        lit_rule("=>", TokenKind::DArrow),
+        lit_rule("<-", TokenKind::Assign),
+        // Comments
+        Box::new(BlockCommentRule::default()),
+        Box::new(
+            re_rule(r"--[^\n]*\n?", TokenKind::LineComment, "Line Comment").with_accepting_fn(
+                Box::new(|token, lexer, source| {
+                    // The newline is part of the comment unless the comment ends at EOF
+                    if token.as_str().ends_with('\n') {
+                        lexer.line_number += 1;
+                    }
+
+                    &source[token.length..]
+                }),
+            ),
+        ),
+        // Strings
+        Box::new(StringRule::default()),
+        // Single characters
+        lit_rule("{", TokenKind::OpenBrace),
+        lit_rule("}", TokenKind::CloseBrace),
+        lit_rule("(", TokenKind::OpenParen),
+        lit_rule(")", TokenKind::CloseParen),
+        lit_rule(":", TokenKind::Colon),
+        lit_rule(";", TokenKind::SemiColon),
+        lit_rule("@", TokenKind::At),
+        lit_rule(".", TokenKind::Dot),
+        lit_rule(",", TokenKind::Comma),
+        lit_rule("=", TokenKind::Equal),
+        lit_rule("~", TokenKind::Tilde),
+        // Operators
+        lit_rule("+", TokenKind::Plus),
+        lit_rule("-", TokenKind::Minus),
+        lit_rule("*", TokenKind::Star),
+        lit_rule("/", TokenKind::Slash),
+        lit_rule("<", TokenKind::Lt),
+        // True and False get special rules due to their behaviour
+        Box::new(
+            re_rule("t(?i:rue)", TokenKind::Bool(true), "true").with_priority(priority::KEYWORD),
+        ),
+        Box::new(
+            re_rule("f(?i:alse)", TokenKind::Bool(false), "false").with_priority(priority::KEYWORD),
+        ),
+        // Int
+        refined_re_rule(r"[0-9]+", refine_int, "Int"),
+        // Type ID
+        refined_re_rule(r"(SELF_TYPE|[A-Z][A-Za-z0-9_]*)", refine_type_id, "Type ID"),
+        // Object ID
+        refined_re_rule(r"(self|[a-z][A-Za-z0-9_]*)", refine_object_id, "Object ID"),
+        // Newlines, to count line number
+        Box::new(
+            re_rule(r"\n", TokenKind::Whitespace, "whitespace").with_accepting_fn(Box::new(
+                |_, lexer, source| {
+                    lexer.line_number += 1;
+                    // Eat it
+                    &source[1..]
+                },
+            )),
+        ),
+        // Whitespace
+        re_rule(r"[ \t\r\f\v]+", TokenKind::Whitespace, "whitespace"),
+        // Error catch all
+        Box::new(
+            refined_re_rule(r".", refine_error, "catch-all").with_priority(priority::CATCH_ALL),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+
+    #[test]
+    fn test_rules_have_no_conflicts() {
+        let lexer = Lexer::new(rules());
+
+        assert_eq!(lexer.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_line_comment_consumes_newline() {
+        let lexer = Lexer::new(rules());
+
+        let tokens = lexer.lex("-- one\n-- two\n1 -- three").unwrap();
+
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter(|(token, _)| token.kind == TokenKind::LineComment)
+            .map(|(token, context)| (token.as_str(), context.line_number))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![("-- one\n", 2), ("-- two\n", 3), ("-- three", 3)]
+        );
+    }
+
+    #[test]
+    fn test_null_in_string_skips_to_end_of_string() {
+        let lexer = Lexer::new(rules());
+
+        let tokens = lexer.lex("\"a\0b\" 1\n\"c\0d\n2").unwrap();
+
+        let tokens: Vec<_> = tokens
+            .iter()
+            .filter(|(token, _)| token.kind != TokenKind::Whitespace)
+            .map(|(token, context)| (token.as_str(), context.line_number))
+            .collect();
+        // The rest of the string is part of the error token, up to the closing quote or the end
+        // of the line, whose newline is counted
+        assert_eq!(
+            tokens,
+            vec![("\"a\0b\"", 1), ("1", 1), ("\"c\0d\n", 3), ("2", 3)]
+        );
+    }
+
+    #[test]
+    fn test_non_ascii() {
+        let lexer = Lexer::new(rules());
+        let source = "(* \u{e5} *) \"\u{e4}\" -- \u{f6}\nx\u{1f600}y";
+
+        let tokens: Vec<_> = lexer
+            .lex(source)
+            .unwrap()
+            .into_iter()
+            .map(|(t, context)| (context.line_number, t.to_string()))
+            .filter(|(_, t)| !t.is_empty())
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (1, "STR_CONST \"\\303\\244\"".to_owned()),
+                (2, "OBJECTID x".to_owned()),
+                (2, "ERROR \"\\360\\237\\230\\200\"".to_owned()),
+                (2, "OBJECTID y".to_owned()),
+            ]
+        );
+    }
+}
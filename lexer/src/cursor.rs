@@ -20,14 +20,12 @@ impl<'s> Cursor<'s> {
         self.chars().nth(1)
     }
 
+    /// Peek at the next `n` characters, or fewer if EOF is reached first.
     pub fn peek_many(&mut self, n: usize) -> &str {
-        let s = &self.chars().as_str();
+        let s = self.chars.as_str();
+        let end = s.char_indices().nth(n).map(|(i, _)| i).unwrap_or(s.len());
 
-        if s.len() >= n {
-            &s[0..n]
-        } else {
-            &s[0..s.len()]
-        }
+        &s[..end]
     }
 
     pub fn next_is_null(&mut self) -> bool {
@@ -46,11 +44,14 @@ impl<'s> Cursor<'s> {
         self.initial_len - self.chars.as_str().len()
     }
 
+    /// The length in bytes up to and including the first of `chars`, or until EOF.
     pub fn length_including(&self, chars: &[char]) -> usize {
-        self.chars()
-            .position(|c| chars.contains(&c))
-            .map(|p| p + 1)
-            .unwrap_or_else(|| self.chars().count())
+        let s = self.chars.as_str();
+
+        s.char_indices()
+            .find(|(_, c)| chars.contains(c))
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(s.len())
     }
 
     pub fn chars(&self) -> Chars<'s> {
@@ -81,4 +82,13 @@ mod tests {
         assert_eq!(cursor.bump(), Some('l'));
         assert_eq!(cursor.bump(), Some('l'));
     }
+
+    #[test]
+    fn test_cursor_multibyte() {
+        let mut cursor: Cursor = "\u{e5}\u{e4}\"\u{f6}".into();
+
+        assert_eq!(cursor.peek_many(2), "\u{e5}\u{e4}");
+        assert_eq!(cursor.peek_many(10), "\u{e5}\u{e4}\"\u{f6}");
+        assert_eq!(cursor.length_including(&['"']), 5);
+    }
 }
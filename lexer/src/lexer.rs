@@ -1,8 +1,18 @@
-use common::Token;
+use std::collections::HashSet;
+use std::fmt;
 
-use crate::rule::Rule;
+use regex_automata::dfa::dense::{self, DFA};
+use regex_automata::dfa::{Automaton, StartKind};
+use regex_automata::util::{start, syntax};
+use regex_automata::Anchored;
 
-#[derive(Clone)]
+use common::{Token, TokenKind};
+
+use crate::rule::{Rule, RuleDescription};
+use crate::source::Source;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Context maintained by `Lexer` as it lexes the source code.
 pub struct LexerContext {
     /// The current line number.
@@ -15,6 +25,91 @@ impl Default for LexerContext {
     }
 }
 
+/// A problem with a set of lexer rules found by `Lexer::validate`.
+///
+/// Rules are referred to by their index in the rule set the lexer was created with.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RuleConflict {
+    /// The rule can never produce a token because `by` wins every match it makes.
+    Shadowed { rule: usize, by: usize },
+    /// The same keyword, ignoring case, occurs more than once in a keyword rule.
+    DuplicateKeyword { rule: usize, keyword: &'static str },
+    /// `literal` is always matched by `by` instead of `rule`.
+    CoveredLiteral {
+        rule: usize,
+        literal: &'static str,
+        by: usize,
+    },
+    /// The regex of the rule can match the empty string, lexing with it might never make progress.
+    EmptyMatch { rule: usize },
+}
+
+impl fmt::Display for RuleConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shadowed { rule, by } => {
+                write!(f, "rule {} is shadowed by rule {}", rule, by)
+            }
+            Self::DuplicateKeyword { rule, keyword } => {
+                write!(
+                    f,
+                    "rule {} contains the keyword {:?} more than once",
+                    rule, keyword
+                )
+            }
+            Self::CoveredLiteral { rule, literal, by } => write!(
+                f,
+                "{:?} in rule {} is always matched by rule {}",
+                literal, rule, by
+            ),
+            Self::EmptyMatch { rule } => {
+                write!(f, "rule {} can match the empty string", rule)
+            }
+        }
+    }
+}
+
+/// An error that stops the lexer.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LexError {
+    /// None of the rules matched the input at `offset`.
+    NoMatch { line_number: usize, offset: usize },
+    /// The rule at index `rule` matched but didn't consume any input at `offset`.
+    NoProgress {
+        rule: usize,
+        line_number: usize,
+        offset: usize,
+    },
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoMatch {
+                line_number,
+                offset,
+            } => write!(
+                f,
+                "line {}: no rule matched at byte {}",
+                line_number, offset
+            ),
+            Self::NoProgress {
+                rule,
+                line_number,
+                offset,
+            } => write!(
+                f,
+                "line {}: rule {} matched without consuming input at byte {}",
+                line_number, rule, offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
 pub struct Lexer {
     rules: Vec<Box<dyn Rule>>,
 }
@@ -24,32 +119,396 @@ impl Lexer {
         Self { rules }
     }
 
-    pub fn lex<'a, 'b>(&'a mut self, input: &'b str) -> Vec<(Token<'b>, LexerContext)> {
+    /// Lex `input` into tokens.
+    ///
+    /// Each token is paired with the context as it was after the token was accepted. Malformed
+    /// source code is reported with `TokenKind::Error` tokens, an `Err` is only returned when the
+    /// rules themselves can't make progress.
+    pub fn lex<'b>(&self, input: &'b str) -> Result<Vec<(Token<'b>, LexerContext)>, LexError> {
         let mut current = input;
         let mut context = LexerContext::default();
         let mut result = vec![];
 
         while !current.is_empty() {
-            let mut current_match: Option<(usize, &mut dyn Rule, Token)> = None;
+            let offset = input.len() - current.len();
+            let mut current_match: Option<((usize, i32), usize, Token)> = None;
 
-            for rule in self.rules.iter_mut() {
+            for (index, rule) in self.rules.iter().enumerate() {
                 if let Some(token) = rule.try_match(current) {
-                    if current_match
-                        .as_ref()
-                        .map(|m| token.length > m.0)
-                        .unwrap_or(true)
-                    {
-                        current_match = Some((token.length, rule.as_mut(), token));
+                    // Longest match wins, ties are broken by priority and then by rule order.
+                    let key = (token.length, rule.priority());
+                    if current_match.as_ref().map(|m| key > m.0).unwrap_or(true) {
+                        current_match = Some((key, index, token));
                     }
                 }
             }
 
-            let mat = current_match.expect("Should have had at least one match");
-            current = mat.1.accept(&mat.2, &mut context, current);
+            let (_, rule, token) = current_match.ok_or(LexError::NoMatch {
+                line_number: context.line_number,
+                offset,
+            })?;
+            let no_progress = LexError::NoProgress {
+                rule,
+                line_number: context.line_number,
+                offset,
+            };
 
-            result.push((mat.2, context.clone()));
+            if token.length == 0 {
+                return Err(no_progress);
+            }
+
+            let rest = self.rules[rule].accept(&token, &mut context, current);
+            if rest.len() >= current.len() {
+                return Err(no_progress);
+            }
+            current = rest;
+
+            result.push((token, context.clone()));
         }
 
-        result
+        Ok(result)
+    }
+
+    /// Lex decoded `source`.
+    ///
+    /// Like `lex`, but tokens that contain invalid UTF-8 from the original input are turned into
+    /// error tokens. Comments may contain invalid UTF-8.
+    pub fn lex_source<'b>(
+        &self,
+        source: &'b Source,
+    ) -> Result<Vec<(Token<'b>, LexerContext)>, LexError> {
+        let mut tokens = self.lex(source.as_str())?;
+        let mut invalid = source.invalid_ranges().iter().peekable();
+
+        for (token, _) in tokens.iter_mut() {
+            let start = token
+                .offset_in(source.as_str())
+                .expect("Tokens should point into the source they were lexed from");
+            let range = start..start + token.length;
+
+            while invalid.next_if(|r| r.end <= range.start).is_some() {}
+            if !invalid.peek().map(|r| r.start < range.end).unwrap_or(false) {
+                continue;
+            }
+
+            match token.kind {
+                TokenKind::LineComment | TokenKind::BlockComment => {}
+                TokenKind::String(_) => {
+                    token.kind = TokenKind::Error("String contains invalid UTF-8.".into())
+                }
+                TokenKind::Error(_) if token.as_str() != "\u{fffd}" => {}
+                _ => token.kind = TokenKind::Error("Invalid UTF-8.".into()),
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Analyse the rules of this lexer for conflicts.
+    ///
+    /// Returns every conflict found, an empty result means the rule set is consistent.
+    pub fn validate(&self) -> Vec<RuleConflict> {
+        let mut conflicts = vec![];
+        let languages: Vec<_> = self
+            .rules
+            .iter()
+            .map(|rule| language(rule.as_ref()))
+            .collect();
+
+        for rule in 0..self.rules.len() {
+            match self.rules[rule].describe() {
+                RuleDescription::Literal(literal) => {
+                    if let Some(by) = self.covering_rule(rule, literal) {
+                        conflicts.push(RuleConflict::CoveredLiteral { rule, literal, by });
+                    }
+                }
+                RuleDescription::Keywords(keywords) => {
+                    let keywords = keywords.to_vec();
+
+                    for (i, &(keyword, _)) in keywords.iter().enumerate() {
+                        if keywords[..i]
+                            .iter()
+                            .any(|(other, _)| other.eq_ignore_ascii_case(keyword))
+                        {
+                            conflicts.push(RuleConflict::DuplicateKeyword { rule, keyword });
+                        }
+                    }
+
+                    let covered: Vec<_> = keywords
+                        .iter()
+                        .filter_map(|(keyword, _)| {
+                            self.covering_rule(rule, keyword).map(|by| (*keyword, by))
+                        })
+                        .collect();
+
+                    match covered.first() {
+                        Some(&(_, by)) if covered.len() == keywords.len() => {
+                            conflicts.push(RuleConflict::Shadowed { rule, by })
+                        }
+                        _ => conflicts.extend(covered.into_iter().map(|(literal, by)| {
+                            RuleConflict::CoveredLiteral { rule, literal, by }
+                        })),
+                    }
+                }
+                RuleDescription::Regex(regex) => {
+                    if can_match_empty(regex.as_str()) {
+                        conflicts.push(RuleConflict::EmptyMatch { rule });
+                    }
+
+                    if let Some(by) = self.shadowing_rule(rule, &languages) {
+                        conflicts.push(RuleConflict::Shadowed { rule, by });
+                    }
+                }
+                RuleDescription::Custom(_) => {}
+            }
+        }
+
+        conflicts
+    }
+
+    /// Whether the rule at index `a` wins over the rule at index `b` when both match the same
+    /// number of characters.
+    fn beats(&self, a: usize, b: usize) -> bool {
+        let (pa, pb) = (self.rules[a].priority(), self.rules[b].priority());
+
+        pa > pb || (pa == pb && a < b)
+    }
+
+    /// Find a rule that wins over `rule` on every text `rule` matches.
+    ///
+    /// Only literal, keyword and regex rules are compared, the language of a custom rule is
+    /// unknown.
+    fn shadowing_rule(&self, rule: usize, languages: &[Option<DFA<Vec<u32>>>]) -> Option<usize> {
+        let language = languages[rule].as_ref()?;
+
+        (0..self.rules.len()).find(|&other| {
+            other != rule
+                && self.beats(other, rule)
+                && languages[other]
+                    .as_ref()
+                    .map(|by| is_subset(language, by).unwrap_or(false))
+                    .unwrap_or(false)
+        })
+    }
+
+    /// Find a rule that wins over `rule` when lexing exactly `text`.
+    fn covering_rule(&self, rule: usize, text: &str) -> Option<usize> {
+        (0..self.rules.len()).find(|&other| {
+            other != rule
+                && self.beats(other, rule)
+                && self.rules[other]
+                    .try_match(text)
+                    .map(|token| token.length >= text.len())
+                    .unwrap_or(false)
+        })
+    }
+}
+
+/// Build an anchored DFA for the texts `rule` matches.
+///
+/// Regexes are built with the same flags as `RegexRule` and keywords ignore ASCII case like
+/// `KeywordRule`. Returns `None` for custom rules and for regexes the DFA can't represent.
+fn language(rule: &dyn Rule) -> Option<DFA<Vec<u32>>> {
+    let pattern = match rule.describe() {
+        RuleDescription::Literal(literal) => regex_syntax::escape(literal),
+        RuleDescription::Keywords(keywords) => {
+            let keywords: Vec<_> = keywords
+                .iter()
+                .map(|(keyword, _)| regex_syntax::escape(keyword))
+                .collect();
+
+            format!("(?i-u:{})", keywords.join("|"))
+        }
+        RuleDescription::Regex(regex) => regex.as_str().to_owned(),
+        RuleDescription::Custom(_) => return None,
+    };
+
+    dense::Builder::new()
+        .syntax(
+            syntax::Config::new()
+                .multi_line(true)
+                .dot_matches_new_line(true),
+        )
+        .configure(dense::Config::new().start_kind(StartKind::Anchored))
+        .build(&pattern)
+        .ok()
+}
+
+/// Whether every text matched in full by `a` is also matched in full by `b`.
+///
+/// Walks the product of both automata looking for a text accepted by `a` but not by `b`. Gives up
+/// with `None` once too many states have been visited.
+fn is_subset(a: &DFA<Vec<u32>>, b: &DFA<Vec<u32>>) -> Option<bool> {
+    const STATE_LIMIT: usize = 100_000;
+
+    let config = start::Config::new().anchored(Anchored::Yes);
+    let start = (a.start_state(&config).ok()?, b.start_state(&config).ok()?);
+    let mut seen = HashSet::from([start]);
+    let mut pending = vec![start];
+
+    while let Some((sa, sb)) = pending.pop() {
+        if a.is_match_state(a.next_eoi_state(sa)) && !b.is_match_state(b.next_eoi_state(sb)) {
+            return Some(false);
+        }
+
+        for byte in 0..=u8::MAX {
+            let next = (a.next_state(sa, byte), b.next_state(sb, byte));
+
+            if !a.is_dead_state(next.0) && seen.insert(next) {
+                if seen.len() > STATE_LIMIT {
+                    return None;
+                }
+                pending.push(next);
+            }
+        }
+    }
+
+    Some(true)
+}
+
+fn can_match_empty(pattern: &str) -> bool {
+    regex_syntax::Parser::new()
+        .parse(pattern)
+        .map(|hir| hir.properties().minimum_len() == Some(0))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::{KeywordRule, LiteralRule, RegexRule};
+    use common::{KeywordKind, TokenKind};
+
+    #[test]
+    fn test_lex_source_with_invalid_utf8() {
+        let lexer = Lexer::new(crate::cool::rules());
+        let source = Source::from_bytes(b"(* \xff *) \"a\xffb\"\n\xfe\xff x \"\xff");
+
+        let tokens: Vec<_> = lexer
+            .lex_source(&source)
+            .unwrap()
+            .into_iter()
+            .map(|(t, context)| (context.line_number, t.to_string()))
+            .filter(|(_, t)| !t.is_empty())
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (1, "ERROR \"String contains invalid UTF-8.\"".to_owned()),
+                (2, "ERROR \"Invalid UTF-8.\"".to_owned()),
+                (2, "ERROR \"Invalid UTF-8.\"".to_owned()),
+                (2, "OBJECTID x".to_owned()),
+                (2, "ERROR \"EOF in string constant.\"".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_without_match() {
+        let lexer = Lexer::new(vec![Box::new(LiteralRule::new("a", TokenKind::Plus))]);
+
+        assert_eq!(
+            lexer.lex("ab").unwrap_err(),
+            LexError::NoMatch {
+                line_number: 1,
+                offset: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_lex_without_match_after_multibyte() {
+        let lexer = Lexer::new(vec![Box::new(LiteralRule::new("a", TokenKind::Plus))]);
+
+        // Rules mustn't slice the input in the middle of a character
+        assert_eq!(
+            lexer.lex("a\u{e9}").unwrap_err(),
+            LexError::NoMatch {
+                line_number: 1,
+                offset: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_lex_without_progress() {
+        let lexer = Lexer::new(vec![Box::new(
+            RegexRule::new("a*", TokenKind::Whitespace).unwrap(),
+        )]);
+
+        assert_eq!(
+            lexer.lex("b").unwrap_err(),
+            LexError::NoProgress {
+                rule: 0,
+                line_number: 1,
+                offset: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_finds_conflicts() {
+        let lexer = Lexer::new(vec![
+            Box::new(KeywordRule::new(vec![
+                ("not", KeywordKind::Not),
+                ("NOT", KeywordKind::Not),
+            ])),
+            Box::new(RegexRule::new("[a-z]*", TokenKind::Whitespace).unwrap()),
+            Box::new(LiteralRule::new("new", TokenKind::Whitespace)),
+            Box::new(RegexRule::new("[a-z]*", TokenKind::Whitespace).unwrap()),
+        ]);
+
+        assert_eq!(
+            lexer.validate(),
+            vec![
+                RuleConflict::DuplicateKeyword {
+                    rule: 0,
+                    keyword: "NOT"
+                },
+                RuleConflict::EmptyMatch { rule: 1 },
+                RuleConflict::CoveredLiteral {
+                    rule: 2,
+                    literal: "new",
+                    by: 1
+                },
+                RuleConflict::EmptyMatch { rule: 3 },
+                RuleConflict::Shadowed { rule: 3, by: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_finds_shadowing_by_language() {
+        let lexer = Lexer::new(vec![
+            Box::new(KeywordRule::new(vec![("if", KeywordKind::If)]).with_priority(1)),
+            Box::new(RegexRule::new("[a-z][a-z0-9]*", TokenKind::Whitespace).unwrap()),
+            Box::new(RegexRule::new("[a-z]+", TokenKind::Whitespace).unwrap()),
+            Box::new(RegexRule::new("IF|If", TokenKind::Whitespace).unwrap()),
+            Box::new(RegexRule::new("[a-z0-9]+", TokenKind::Whitespace).unwrap()),
+            Box::new(LiteralRule::new("=>", TokenKind::DArrow)),
+            Box::new(RegexRule::new("=>?", TokenKind::Whitespace).unwrap()),
+        ]);
+
+        assert_eq!(
+            lexer.validate(),
+            vec![
+                RuleConflict::Shadowed { rule: 2, by: 1 },
+                RuleConflict::Shadowed { rule: 3, by: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_priority_breaks_ties() {
+        let lexer = Lexer::new(vec![
+            Box::new(RegexRule::new("[a-z]+", TokenKind::Whitespace).unwrap()),
+            Box::new(KeywordRule::new(vec![("fi", KeywordKind::Fi)]).with_priority(1)),
+        ]);
+
+        assert_eq!(lexer.validate(), vec![]);
+
+        let tokens = lexer.lex("fi").unwrap();
+        assert_eq!(tokens[0].0.kind, TokenKind::Keyword(KeywordKind::Fi));
     }
 }
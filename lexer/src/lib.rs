@@ -1,14 +1,22 @@
+#[cfg(feature = "serde")]
+pub mod cache;
+pub mod cool;
 mod cursor;
 mod lexer;
 mod rule;
+mod source;
 
 use crate::cursor::Cursor;
-pub use crate::lexer::{Lexer, LexerContext};
-pub use crate::rule::{BlockCommentRule, KeywordRule, LiteralRule, RegexRule, Rule, StringRule};
+pub use crate::lexer::{LexError, Lexer, LexerContext, RuleConflict};
+pub use crate::rule::{
+    BlockCommentRule, KeywordRule, LiteralRule, RegexRule, Rule, RuleDescription, StringRule,
+};
+pub use crate::source::Source;
 
 pub mod prelude {
-    pub use crate::lexer::{Lexer, LexerContext};
+    pub use crate::lexer::{LexError, Lexer, LexerContext, RuleConflict};
     pub use crate::rule::{
-        BlockCommentRule, KeywordRule, LiteralRule, RegexRule, Rule, StringRule,
+        BlockCommentRule, KeywordRule, LiteralRule, RegexRule, Rule, RuleDescription, StringRule,
     };
+    pub use crate::source::Source;
 }
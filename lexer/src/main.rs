@@ -1,149 +1,127 @@
 use clap::{crate_authors, crate_version, App, Arg};
-use regex::Match;
+use memmap2::Mmap;
 
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::fs::File;
-use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
-use common::prelude::*;
+use common::PassTimings;
+use lexer::cool;
 use lexer::prelude::*;
 
-fn re_rule(pattern: &str, token: TokenKind, desc: &str) -> Box<RegexRule> {
-    Box::new(
-        RegexRule::new(pattern, token)
-            .unwrap_or_else(|_| panic!("Should be able to build regex rule for {}", desc)),
-    )
-}
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+fn lex_file(lexer: &Lexer, path: &str, timings: &PassTimings) -> Result<String, Error> {
+    let (file, mmap) = timings.time("read", || -> Result<_, Error> {
+        let file = File::open(path)?;
+        // Pipes, FIFOs and devices can't be mapped, and mapping can fail for other reasons too
+        let mmap = if file.metadata()?.is_file() {
+            // SAFETY: The mapping is only read. If another process truncates the file while it's
+            // being lexed we read garbage or crash, which is acceptable for a command line tool.
+            unsafe { Mmap::map(&file) }.ok()
+        } else {
+            None
+        };
+
+        Ok((file, mmap))
+    })?;
+    let source = match &mmap {
+        Some(mmap) => timings.time("decode", || Source::from_bytes(mmap)),
+        None => timings.time("read", || Source::read(&file))?,
+    };
+
+    let tokens = timings
+        .time("lex", || lexer.lex_source(&source))
+        .map_err(|err| format!("{}: {}", path, err))?;
+
+    timings.time("format", || {
+        let mut output = String::new();
+        writeln!(output, "#name \"{}\"", path)?;
 
-fn refined_re_rule<F>(pattern: &str, refinement: F, desc: &str) -> Box<dyn Rule>
-where
-    F: FnMut(Match) -> Option<TokenKind> + 'static,
-{
-    Box::new(
-        RegexRule::refined(pattern, Box::new(refinement))
-            .unwrap_or_else(|_| panic!("Should be able to build regex rule for {}", desc)),
-    )
-}
+        for (t, context) in tokens {
+            let string_token = format!("{}", t);
 
-fn lit_rule(lit: &'static str, token: TokenKind) -> Box<dyn Rule> {
-    Box::new(LiteralRule::new(lit, token))
-}
+            // dbg!(&t, &t.as_str());
+            if string_token.is_empty() {
+                continue;
+            }
 
-fn refine_type_id(mat: Match) -> Option<TokenKind> {
-    Some(TokenKind::TypeId(mat.as_str().into()))
-}
+            writeln!(output, "#{} {}", context.line_number, string_token)?;
+        }
 
-fn refine_object_id(mat: Match) -> Option<TokenKind> {
-    Some(TokenKind::ObjectId(mat.as_str().into()))
+        Ok(output)
+    })
 }
 
-fn refine_int(mat: Match) -> Option<TokenKind> {
-    Some(TokenKind::Int(mat.as_str().into()))
-}
+/// Lex `paths` using up to `jobs` threads.
+///
+/// The output is printed in the order the files were given, no matter which file finishes first.
+fn lex_files(
+    lexer: &Lexer,
+    paths: &[&str],
+    jobs: usize,
+    timings: &PassTimings,
+) -> Result<(), Error> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(paths.len()) {
+            let sender = sender.clone();
+            let next = &next;
+
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= paths.len() {
+                    break;
+                }
+
+                // The receiver hangs up after an error, stop working when that happens
+                let output = lex_file(lexer, paths[index], timings);
+                if sender.send((index, output)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
 
-fn refine_error(mat: Match) -> Option<TokenKind> {
-    Some(TokenKind::Error(mat.as_str().into()))
-}
+        let mut finished = BTreeMap::new();
+        let mut next_to_print = 0;
+
+        for (index, output) in receiver {
+            finished.insert(index, output);
+
+            while let Some(output) = finished.remove(&next_to_print) {
+                print!("{}", output?);
+                next_to_print += 1;
+            }
+        }
 
-fn rules() -> Vec<Box<dyn Rule>> {
-    // Lexical analysis rules
-    // Order matter heres, we ues max munch and when two rules consume the same
-    // number of characters the first one wins.
-    vec![
-        // Keywords
-        Box::new(KeywordRule::new(
-            vec![
-                ("class", KeywordKind::Class),
-                ("else", KeywordKind::Else),
-                ("fi", KeywordKind::Fi),
-                ("if", KeywordKind::If),
-                ("in", KeywordKind::In),
-                ("inherits", KeywordKind::Inherits),
-                ("isvoid", KeywordKind::IsVoid),
-                ("let", KeywordKind::Let),
-                ("loop", KeywordKind::Loop),
-                ("pool", KeywordKind::Pool),
-                ("then", KeywordKind::Then),
-                ("while", KeywordKind::While),
-                ("case", KeywordKind::Case),
-                ("esac", KeywordKind::Esac),
-                ("new", KeywordKind::New),
-                ("of", KeywordKind::Of),
-                ("not", KeywordKind::Not),
-                ("not", KeywordKind::Not),
-            ]
-            .into_iter()
-            .collect(),
-        )),
-        lit_rule("<=", TokenKind::Le),
-        lit_rule("=>", TokenKind::DArrow),
-        lit_rule("<-", TokenKind::Assign),
-        // Comments
-        Box::new(BlockCommentRule::default()),
-        Box::new(
-            re_rule(r"--[^\n]*$", TokenKind::LineComment, "Line Comment").with_accepting_fn(
-                Box::new(|token, lexer, source| {
-                    if token.length >= source.len() {
-                        // Reached EOF
-                        ""
-                    } else {
-                        lexer.line_number += 1;
-                        // `$` in regex does not consume the newline, eat it manually
-                        &source[token.length + 1..]
-                    }
-                }),
-            ),
-        ),
-        // Strings
-        Box::new(StringRule::default()),
-        // Single characters
-        lit_rule("{", TokenKind::OpenBrace),
-        lit_rule("}", TokenKind::CloseBrace),
-        lit_rule("(", TokenKind::OpenParen),
-        lit_rule(")", TokenKind::CloseParen),
-        lit_rule(":", TokenKind::Colon),
-        lit_rule(";", TokenKind::SemiColon),
-        lit_rule("@", TokenKind::At),
-        lit_rule(".", TokenKind::Dot),
-        lit_rule(",", TokenKind::Comma),
-        lit_rule("=", TokenKind::Equal),
-        lit_rule("~", TokenKind::Tilde),
-        // Operators
-        lit_rule("+", TokenKind::Plus),
-        lit_rule("-", TokenKind::Minus),
-        lit_rule("*", TokenKind::Star),
-        lit_rule("/", TokenKind::Slash),
-        lit_rule("<", TokenKind::Lt),
-        // True and False get special rules due to their behaviour
-        re_rule("t(?i:rue)", TokenKind::Bool(true), "true"),
-        re_rule("f(?i:alse)", TokenKind::Bool(false), "false"),
-        // Int
-        refined_re_rule(r"[0-9]+", refine_int, "Int"),
-        // Type ID
-        refined_re_rule(r"(SELF_TYPE|[A-Z][A-Za-z0-9_]*)", refine_type_id, "Type ID"),
-        // Object ID
-        refined_re_rule(r"(self|[a-z][A-Za-z0-9_]*)", refine_object_id, "Object ID"),
-        // Newlines, to count line number
-        Box::new(
-            re_rule(r"\n", TokenKind::Whitespace, "whitespace").with_accepting_fn(Box::new(
-                |_, lexer, source| {
-                    lexer.line_number += 1;
-                    // Eat it
-                    &source[1..]
-                },
-            )),
-        ),
-        // Whitespace
-        re_rule(r"[ \t\r\f\v]+", TokenKind::Whitespace, "whitespace"),
-        // Error catch all
-        refined_re_rule(r".", refine_error, "catch-all"),
-    ]
+        Ok(())
+    })
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+fn main() -> Result<(), Error> {
     let matches = App::new("lexer")
         .version(crate_version!())
         .author(crate_authors!())
         .about("A lexer for the COOL language")
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .value_name("N")
+                .takes_value(true)
+                .help("Lex up to N files concurrently"),
+        )
+        .arg(
+            Arg::with_name("time-passes")
+                .long("time-passes")
+                .help("Report the time spent in each pass on stderr"),
+        )
         .arg(
             Arg::with_name("FILES")
                 .multiple(true)
@@ -152,29 +130,21 @@ fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
         )
         .get_matches();
 
-    let mut lexer = Lexer::new(rules());
-    let mut buffer = String::default();
-
-    for path in matches.values_of("FILES").unwrap() {
-        let mut file = File::open(path)?;
-        file.read_to_string(&mut buffer)?;
-
-        println!("#name \"{}\"", path);
-
-        let tokens = lexer.lex(&buffer);
-
-        for (t, context) in tokens {
-            let string_token = format!("{}", t);
-
-            // dbg!(&t, &t.as_str());
-            if string_token.is_empty() {
-                continue;
-            }
-
-            println!("#{} {}", context.line_number, string_token);
-        }
-
-        buffer.clear()
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => jobs
+            .parse::<usize>()
+            .map_err(|err| format!("Invalid number of jobs {:?}: {}", jobs, err))?
+            .max(1),
+        None => 1,
+    };
+    let paths: Vec<_> = matches.values_of("FILES").unwrap().collect();
+    let timings = PassTimings::default();
+    let lexer = timings.time("rules", || Lexer::new(cool::rules()));
+
+    lex_files(&lexer, &paths, jobs, &timings)?;
+
+    if matches.is_present("time-passes") {
+        eprintln!("{}", timings);
     }
 
     Ok(())
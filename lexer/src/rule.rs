@@ -2,9 +2,9 @@ use either::Either;
 use regex::{Match, Regex, RegexBuilder};
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::fmt;
 
-use common::{KeywordKind, Token, TokenKind};
+use common::{KeywordKind, Symbol, Token, TokenKind};
 
 use crate::{Cursor, LexerContext};
 
@@ -19,13 +19,40 @@ impl From<regex::Error> for RuleError {
     }
 }
 
-pub trait Rule {
+/// A description of what a rule matches, used by `Lexer::validate` to analyse a rule set.
+pub enum RuleDescription<'r> {
+    /// The rule matches exactly this text.
+    Literal(&'static str),
+    /// The rule matches any of these keywords, ignoring case.
+    Keywords(&'r [(&'static str, KeywordKind)]),
+    /// The rule matches this regex.
+    Regex(&'r Regex),
+    /// The rule is implemented by hand and can only be analysed by calling `try_match`.
+    Custom(&'static str),
+}
+
+impl<'r> fmt::Display for RuleDescription<'r> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Literal(lit) => write!(f, "literal {:?}", lit),
+            Self::Keywords(keywords) => write!(f, "{} keywords", keywords.len()),
+            Self::Regex(regex) => write!(f, "regex {:?}", regex.as_str()),
+            Self::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A rule for lexing one kind of token.
+///
+/// Rules don't keep state between matches, anything that has to be tracked while lexing lives in
+/// the `LexerContext`. This lets a single `Lexer` be shared between threads.
+pub trait Rule: Send + Sync {
     /// Try to match the given rule.
     ///
     /// If the rule matches return it should return a token.
     /// A match doesn't mean this rule is accepted, another rule might produce a
     /// longer match or have higher precedence.
-    fn try_match<'a, 'b>(&'a mut self, source: &'b str) -> Option<Token<'b>>;
+    fn try_match<'b>(&self, source: &'b str) -> Option<Token<'b>>;
 
     /// Accept a token that has been matched by this rule.
     ///
@@ -34,19 +61,29 @@ pub trait Rule {
     /// consuming the match.
     /// `accept` can also modify the lexer context, for example incrementing the line count.
     fn accept<'s>(
-        &'_ mut self,
+        &'_ self,
         token: &Token<'s>,
         context: &mut LexerContext,
         source: &'s str,
     ) -> &'s str;
+
+    /// The priority of this rule.
+    ///
+    /// When two rules match the same number of characters the one with the highest priority wins,
+    /// if the priorities are also equal the rule that comes first wins.
+    fn priority(&self) -> i32;
+
+    /// Describe what this rule matches.
+    fn describe(&self) -> RuleDescription<'_>;
 }
 
-type RefinementFn = Box<dyn FnMut(Match) -> Option<TokenKind>>;
-type AcceptingFn = Box<dyn for<'s> FnMut(&Token, &mut LexerContext, &'s str) -> &'s str>;
+type RefinementFn = Box<dyn Fn(Match) -> Option<TokenKind> + Send + Sync>;
+type AcceptingFn = Box<dyn for<'s> Fn(&Token, &mut LexerContext, &'s str) -> &'s str + Send + Sync>;
 pub struct RegexRule {
     regex: Regex,
     token_kind: Either<TokenKind, RefinementFn>,
     accepting_fn: Option<AcceptingFn>,
+    priority: i32,
 }
 
 impl RegexRule {
@@ -60,6 +97,7 @@ impl RegexRule {
             regex,
             token_kind: Either::Left(token_kind),
             accepting_fn: None,
+            priority: 0,
         })
     }
 
@@ -68,13 +106,11 @@ impl RegexRule {
             regex,
             token_kind: Either::Left(token_kind),
             accepting_fn: None,
+            priority: 0,
         }
     }
 
-    pub fn refined(
-        pattern: &str,
-        refinement: Box<dyn FnMut(Match) -> Option<TokenKind>>,
-    ) -> Result<Self, RuleError> {
+    pub fn refined(pattern: &str, refinement: RefinementFn) -> Result<Self, RuleError> {
         let regex = RegexBuilder::new(&format!("\\A(?:{})", &pattern))
             .multi_line(true)
             .dot_matches_new_line(true)
@@ -84,23 +120,27 @@ impl RegexRule {
             regex,
             token_kind: Either::Right(refinement),
             accepting_fn: None,
+            priority: 0,
         })
     }
 
     pub fn with_accepting_fn(self, accepting_fn: AcceptingFn) -> Self {
         Self {
-            regex: self.regex,
-            token_kind: self.token_kind,
             accepting_fn: Some(accepting_fn),
+            ..self
         }
     }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
+    }
 }
 
 impl Rule for RegexRule {
-    fn try_match<'a, 'b>(&'a mut self, source: &'b str) -> Option<Token<'b>> {
+    fn try_match<'b>(&self, source: &'b str) -> Option<Token<'b>> {
         self.regex
             .find(source)
-            .and_then(|mat| match self.token_kind.as_mut() {
+            .and_then(|mat| match self.token_kind.as_ref() {
                 Either::Left(token_kind) => Some(Token::new(
                     token_kind.clone(),
                     mat.end() - mat.start(),
@@ -112,39 +152,57 @@ impl Rule for RegexRule {
     }
 
     fn accept<'s>(
-        &mut self,
+        &self,
         token: &Token<'s>,
         context: &mut LexerContext,
         source: &'s str,
     ) -> &'s str {
-        match &mut self.accepting_fn {
+        match &self.accepting_fn {
             Some(afn) => afn(token, context, source),
-            _ => (&source[token.length..]),
+            _ => &source[token.length..],
         }
     }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn describe(&self) -> RuleDescription<'_> {
+        RuleDescription::Regex(&self.regex)
+    }
 }
 
 pub struct KeywordRule {
-    mapping: HashMap<&'static str, KeywordKind>,
+    mapping: Vec<(&'static str, KeywordKind)>,
+    priority: i32,
 }
 
 impl KeywordRule {
-    pub fn new(mapping: HashMap<&'static str, KeywordKind>) -> Self {
-        Self { mapping }
+    pub fn new(mapping: Vec<(&'static str, KeywordKind)>) -> Self {
+        Self {
+            mapping,
+            priority: 0,
+        }
+    }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
     }
 }
 
 impl Rule for KeywordRule {
-    fn try_match<'a, 'b>(&'a mut self, source: &'b str) -> Option<Token<'b>> {
+    fn try_match<'b>(&self, source: &'b str) -> Option<Token<'b>> {
         let mat = {
             let mut longest_match_length = 0;
             self.mapping
                 .iter()
-                .filter(|(&key, _)| {
+                .filter(|(key, _)| {
                     if longest_match_length > key.len() {
                         false
-                    } else if source.len() >= key.len()
-                        && source[..key.len()].to_lowercase() == key.to_lowercase()
+                    } else if source
+                        .get(..key.len())
+                        .map(|prefix| prefix.eq_ignore_ascii_case(key))
+                        .unwrap_or(false)
                     {
                         longest_match_length = key.len();
                         true
@@ -155,76 +213,84 @@ impl Rule for KeywordRule {
                 .last()
         };
 
-        mat.map(|(k, &kind)| Token::new(TokenKind::Keyword(kind), k.len(), source))
+        mat.map(|(k, kind)| Token::new(TokenKind::Keyword(*kind), k.len(), source))
     }
 
     fn accept<'s>(
-        &mut self,
+        &self,
         token: &Token<'s>,
         _context: &mut LexerContext,
         source: &'s str,
     ) -> &'s str {
         &source[token.length..]
     }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn describe(&self) -> RuleDescription<'_> {
+        RuleDescription::Keywords(&self.mapping)
+    }
 }
 
 pub struct LiteralRule {
     lit: &'static str,
     token_kind: TokenKind,
+    priority: i32,
 }
 
 impl LiteralRule {
     pub fn new(lit: &'static str, token_kind: TokenKind) -> Self {
-        Self { lit, token_kind }
+        Self {
+            lit,
+            token_kind,
+            priority: 0,
+        }
+    }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
     }
 }
 
 impl Rule for LiteralRule {
-    fn try_match<'a, 'b>(&'a mut self, source: &'b str) -> Option<Token<'b>> {
-        if source.len() >= self.lit.len() {
-            (&source[..self.lit.len()] == self.lit)
-                .then(|| Token::new(self.token_kind.clone(), self.lit.len(), source))
-        } else {
-            None
-        }
+    fn try_match<'b>(&self, source: &'b str) -> Option<Token<'b>> {
+        source
+            .starts_with(self.lit)
+            .then(|| Token::new(self.token_kind.clone(), self.lit.len(), source))
     }
 
     fn accept<'s>(
-        &mut self,
+        &self,
         token: &Token<'s>,
         _context: &mut LexerContext,
         source: &'s str,
     ) -> &'s str {
         &source[token.length..]
     }
-}
 
-pub struct StringRule {
-    buffer: String,
-    number_of_lines: usize,
-    recovery_consume: Option<usize>,
-}
+    fn priority(&self) -> i32 {
+        self.priority
+    }
 
-impl Default for StringRule {
-    fn default() -> Self {
-        Self {
-            buffer: String::with_capacity(1024),
-            number_of_lines: 0,
-            recovery_consume: None,
-        }
+    fn describe(&self) -> RuleDescription<'_> {
+        RuleDescription::Literal(self.lit)
     }
 }
 
+#[derive(Default)]
+pub struct StringRule {
+    priority: i32,
+}
+
 impl StringRule {
-    fn reset(&mut self) {
-        self.number_of_lines = 0;
-        self.buffer.clear();
-        self.recovery_consume = None;
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority }
     }
 
-    fn consume_string(&mut self, mut cursor: Cursor) -> Result<(usize, String), (usize, String)> {
-        self.reset();
-        let result: &mut String = &mut self.buffer;
+    fn consume_string(&self, mut cursor: Cursor) -> Result<(usize, Symbol), (usize, String)> {
+        let mut result = String::new();
 
         loop {
             if cursor.is_eof() {
@@ -233,18 +299,15 @@ impl StringRule {
             } else if cursor.next_is_null() {
                 // Null in string
 
-                // Consume until a stable state
-                self.recovery_consume = Some(cursor.length_including(&['\n', '\"']));
+                // Consume until a stable state, the skipped text is part of the error token
+                let recovery = cursor.length_including(&['\n', '\"']);
 
                 return Err((
-                    cursor.consumed_len(),
+                    cursor.consumed_len() + recovery,
                     "String contains null character.".into(),
                 ));
             } else if cursor.next_is_newline() {
-                // Unescaped newline
-                self.number_of_lines += 1;
-
-                // Eat newline
+                // Unescaped newline, eat it
                 let _ = cursor.bump();
                 return Err((
                     cursor.consumed_len(),
@@ -259,15 +322,12 @@ impl StringRule {
                     't' => result.push('\t'),
                     'n' => result.push('\n'),
                     'f' => result.push('\x0C'),
-                    '\n' => {
-                        result.push('\n');
-                        self.number_of_lines += 1;
-                    }
+                    '\n' => result.push('\n'),
                     '\0' => {
-                        // Consume until a stable state
-                        self.recovery_consume = Some(cursor.length_including(&['\n', '\"']));
+                        // Consume until a stable state, the skipped text is part of the error token
+                        let recovery = cursor.length_including(&['\n', '\"']);
                         return Err((
-                            cursor.consumed_len(),
+                            cursor.consumed_len() + recovery,
                             "String contains escaped null character.".into(),
                         ));
                     }
@@ -275,7 +335,7 @@ impl StringRule {
                 }
             } else if cursor.peek().map(|c| c == '\"').unwrap_or(false) {
                 let _ = cursor.bump();
-                return Ok((cursor.consumed_len(), result.clone()));
+                return Ok((cursor.consumed_len(), Symbol::intern(&result)));
             } else {
                 match cursor.bump() {
                     Some(c) => result.push(c),
@@ -287,7 +347,7 @@ impl StringRule {
 }
 
 impl Rule for StringRule {
-    fn try_match<'a, 'b>(&'a mut self, source: &'b str) -> Option<Token<'b>> {
+    fn try_match<'b>(&self, source: &'b str) -> Option<Token<'b>> {
         let mut cursor: Cursor = source.into();
         if cursor.bump().map(|c| c != '\"').unwrap_or(true) {
             return None;
@@ -308,43 +368,49 @@ impl Rule for StringRule {
     }
 
     fn accept<'s>(
-        &mut self,
+        &self,
         token: &Token<'s>,
         context: &mut LexerContext,
         source: &'s str,
     ) -> &'s str {
-        context.line_number += self.number_of_lines;
+        context.line_number += token.as_str().matches('\n').count();
+        &source[token.length..]
+    }
 
-        match self.recovery_consume {
-            Some(recovery) => &source[token.length + recovery..],
-            None => &source[token.length..],
-        }
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn describe(&self) -> RuleDescription<'_> {
+        RuleDescription::Custom("string")
     }
 }
 
 #[derive(Default)]
 pub struct BlockCommentRule {
-    depth: i64,
-    number_of_lines: usize,
+    priority: i32,
 }
 
 impl BlockCommentRule {
-    fn consume_comment(&mut self, mut cursor: Cursor) -> Result<usize, (usize, String)> {
-        self.depth = 0;
-        self.number_of_lines = 0;
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority }
+    }
+
+    fn consume_comment(&self, mut cursor: Cursor) -> Result<usize, (usize, String)> {
+        let mut depth: i64 = 0;
 
         loop {
             match cursor.bump() {
                 // New comment
                 Some(c) if c == '(' && cursor.peek() == Some('*') => {
                     cursor.bump();
-                    self.depth += 1;
+                    depth += 1;
                 }
                 Some(c) if c == '*' && cursor.peek() == Some(')') => {
                     cursor.bump();
-                    self.depth -= 1;
+                    depth -= 1;
 
-                    match self.depth.cmp(&0) {
+                    match depth.cmp(&0) {
                         Ordering::Equal => {
                             return Ok(cursor.consumed_len());
                         }
@@ -354,9 +420,6 @@ impl BlockCommentRule {
                         _ => (),
                     }
                 }
-                Some(c) if c == '\n' => {
-                    self.number_of_lines += 1;
-                }
                 Some(_) => (),
                 None => {
                     return Err((cursor.consumed_len(), "EOF in comment".into()));
@@ -367,7 +430,7 @@ impl BlockCommentRule {
 }
 
 impl Rule for BlockCommentRule {
-    fn try_match<'a, 'b>(&'a mut self, source: &'b str) -> Option<Token<'b>> {
+    fn try_match<'b>(&self, source: &'b str) -> Option<Token<'b>> {
         let mut cursor: Cursor = source.into();
         let first_two = cursor.peek_many(2);
 
@@ -396,14 +459,22 @@ impl Rule for BlockCommentRule {
     }
 
     fn accept<'s>(
-        &mut self,
+        &self,
         token: &Token<'s>,
         context: &mut LexerContext,
         source: &'s str,
     ) -> &'s str {
-        context.line_number += self.number_of_lines;
+        context.line_number += token.as_str().matches('\n').count();
         &source[token.length..]
     }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn describe(&self) -> RuleDescription<'_> {
+        RuleDescription::Custom("block comment")
+    }
 }
 
 #[cfg(test)]
@@ -411,7 +482,7 @@ mod tests {
     use super::*;
 
     fn int_rule() -> impl Rule {
-        RegexRule::new("[0-9]+", TokenKind::Int("0".into())).unwrap()
+        RegexRule::new("[0-9]+", TokenKind::Int(Symbol::intern("0"))).unwrap()
     }
 
     fn string_rule() -> impl Rule {
@@ -430,14 +501,14 @@ mod tests {
 
     #[test]
     fn test_int_rule() {
-        let rule = RegexRule::new("[0-9]+", TokenKind::Int("0".into()));
+        let rule = RegexRule::new("[0-9]+", TokenKind::Int(Symbol::intern("0")));
 
         assert!(rule.is_ok());
     }
 
     #[test]
     fn test_try_match_matches() {
-        let mut rule = int_rule();
+        let rule = int_rule();
 
         let token = rule.try_match("12313\n\tlet a <- 10");
 
@@ -450,11 +521,10 @@ mod tests {
 
     #[test]
     fn test_keyword_rule() {
-        let mut keyword_rule = KeywordRule::new(
-            vec![("InheRits", KeywordKind::Inherits), ("in", KeywordKind::In)]
-                .into_iter()
-                .collect(),
-        );
+        let keyword_rule = KeywordRule::new(vec![
+            ("InheRits", KeywordKind::Inherits),
+            ("in", KeywordKind::In),
+        ]);
 
         let token = keyword_rule.try_match("inherits A");
 
@@ -467,7 +537,7 @@ mod tests {
 
     #[test]
     fn test_eof_in_line_comment() {
-        let mut rule = line_comment_rule();
+        let rule = line_comment_rule();
 
         let token = rule.try_match("-- can they handle EOF in \"--\" state?");
 
@@ -476,7 +546,7 @@ mod tests {
 
     #[test]
     fn test_string_rule() {
-        let mut rule = string_rule();
+        let rule = string_rule();
 
         let token = rule.try_match("\"Hello World\" class");
 
@@ -490,7 +560,7 @@ mod tests {
 
     #[test]
     fn test_string_rule_2() {
-        let mut rule = string_rule();
+        let rule = string_rule();
 
         let token = rule.try_match("\"\\n\\tTo add a number to \");");
 
@@ -498,8 +568,8 @@ mod tests {
         let token = token.unwrap();
 
         match &token.kind {
-            TokenKind::String(s) => assert_eq!(s, "\n\tTo add a number to "),
-            _ => assert!(false, "Token kind should be String"),
+            TokenKind::String(s) => assert_eq!(s.as_str(), "\n\tTo add a number to "),
+            _ => panic!("Token kind should be String"),
         };
 
         assert_eq!(token.as_str(), "\"\\n\\tTo add a number to \"");
@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::ops::Range;
+
+/// Source text decoded from bytes that aren't necessarily valid UTF-8.
+///
+/// Valid input is borrowed as is. Invalid UTF-8 sequences are replaced with U+FFFD and their
+/// location is recorded so `Lexer::lex_source` can report them as errors instead of giving up on
+/// the whole input.
+pub struct Source<'b> {
+    text: Cow<'b, str>,
+    invalid: Vec<Range<usize>>,
+}
+
+impl<'b> Source<'b> {
+    pub fn from_bytes(bytes: &'b [u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self {
+                text: Cow::Borrowed(text),
+                invalid: vec![],
+            },
+            Err(_) => Self::decode_lossy(bytes),
+        }
+    }
+
+    /// The decoded text.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The byte ranges in `as_str` that replace invalid UTF-8 in the input.
+    pub fn invalid_ranges(&self) -> &[Range<usize>] {
+        &self.invalid
+    }
+
+    fn decode_lossy(bytes: &[u8]) -> Self {
+        let mut text = String::with_capacity(bytes.len());
+        let mut invalid = vec![];
+
+        for chunk in bytes.utf8_chunks() {
+            text.push_str(chunk.valid());
+
+            if !chunk.invalid().is_empty() {
+                let start = text.len();
+                text.push(char::REPLACEMENT_CHARACTER);
+                invalid.push(start..text.len());
+            }
+        }
+
+        Self {
+            text: Cow::Owned(text),
+            invalid,
+        }
+    }
+}
+
+impl Source<'static> {
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self {
+                text: Cow::Owned(text),
+                invalid: vec![],
+            },
+            Err(err) => Self::decode_lossy(err.as_bytes()),
+        }
+    }
+
+    /// Read all of `reader` and decode it.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+
+        Ok(Self::from_vec(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_source_is_borrowed() {
+        let source = Source::from_bytes("class Main {};".as_bytes());
+
+        assert!(matches!(source.text, Cow::Borrowed(_)));
+        assert!(source.invalid_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_source() {
+        let source = Source::read(&b"a\xff\xfeb\xe2\x82"[..]).unwrap();
+
+        assert_eq!(source.as_str(), "a\u{fffd}\u{fffd}b\u{fffd}");
+        assert_eq!(source.invalid_ranges(), &[1..4, 4..7, 8..11]);
+    }
+}
@@ -0,0 +1,34 @@
+//! Runs the `lexer` binary on inputs that aren't regular files.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[cfg(unix)]
+#[test]
+fn test_lex_from_pipe() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lexer"))
+        .arg("/dev/stdin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"class A {};\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#name \"/dev/stdin\"\n#1 CLASS\n#1 TYPEID A\n#1 '{'\n#1 '}'\n#1 ';'\n"
+    );
+}
@@ -0,0 +1,106 @@
+//! Property tests checking that COOL string literals round-trip through the lexer and the
+//! `STR_CONST` output format.
+
+use proptest::prelude::*;
+
+use common::TokenKind;
+use lexer::{cool, Lexer};
+
+/// Render `chars` as a COOL string literal.
+///
+/// Characters paired with `true` are written with an escape sequence when COOL has one for them,
+/// otherwise they are written as is.
+fn render_literal(chars: &[(char, bool)]) -> String {
+    let mut literal = String::from("\"");
+
+    for &(c, escape) in chars {
+        match (c, escape) {
+            ('\\', _) => literal.push_str("\\\\"),
+            ('"', _) => literal.push_str("\\\""),
+            ('\n', true) => literal.push_str("\\\n"),
+            ('\n', false) => literal.push_str("\\n"),
+            ('\t', true) => literal.push_str("\\t"),
+            ('\x08', true) => literal.push_str("\\b"),
+            ('\x0C', true) => literal.push_str("\\f"),
+            // These would turn into control characters when escaped
+            ('b' | 't' | 'n' | 'f', _) => literal.push(c),
+            (c, true) => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            (c, false) => literal.push(c),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
+
+/// Invert the escaping used when printing `STR_CONST` tokens.
+fn unescape_output(s: &str) -> String {
+    let mut result = vec![];
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c as u8);
+            continue;
+        }
+
+        match chars.next().expect("Dangling escape in output") {
+            'n' => result.push(b'\n'),
+            't' => result.push(b'\t'),
+            'b' => result.push(b'\x08'),
+            'f' => result.push(b'\x0C'),
+            d @ '0'..='7' => {
+                let digits: String = std::iter::once(d).chain(chars.by_ref().take(2)).collect();
+                result.push(u8::from_str_radix(&digits, 8).expect("Invalid octal escape"));
+            }
+            other => result.push(other as u8),
+        }
+    }
+
+    String::from_utf8(result).expect("Octal escapes should form valid UTF-8")
+}
+
+fn string_char() -> impl Strategy<Value = char> {
+    prop_oneof![
+        // Characters with special meaning in literals or in the output
+        prop::sample::select(vec![
+            '\\', '"', '\n', '\t', '\x08', '\x0C', 'b', 't', 'n', 'f'
+        ]),
+        // Control characters, printed as octal escapes
+        prop::char::range('\u{1}', '\u{1f}'),
+        prop::char::range(' ', '~'),
+        // Non-ASCII, printed as one octal escape per UTF-8 byte
+        prop::char::range('\u{7f}', '\u{ff}'),
+        any::<char>().prop_filter("COOL strings can't contain null", |&c| c != '\0'),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn test_string_literal_round_trip(chars in prop::collection::vec((string_char(), any::<bool>()), 0..64)) {
+        let expected: String = chars.iter().map(|&(c, _)| c).collect();
+        let literal = render_literal(&chars);
+        let escaped_newlines = chars.iter().filter(|&&(c, escape)| c == '\n' && escape).count();
+
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(&literal).unwrap();
+
+        prop_assert_eq!(tokens.len(), 1);
+        let (token, context) = &tokens[0];
+
+        prop_assert_eq!(token.length, literal.len());
+        prop_assert_eq!(context.line_number, 1 + escaped_newlines);
+        match token.kind {
+            TokenKind::String(s) => prop_assert_eq!(s.as_str(), expected.as_str()),
+            ref other => prop_assert!(false, "Expected a string token, got {:?}", other),
+        }
+
+        let printed = token.to_string();
+        prop_assert!(printed.starts_with("STR_CONST \"") && printed.ends_with('"'));
+        prop_assert!(printed.chars().all(|c| c.is_ascii() && !c.is_ascii_control()));
+        prop_assert_eq!(unescape_output(&printed[11..printed.len() - 1]), expected);
+    }
+}
@@ -0,0 +1,29 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use common::Symbol;
+use lexer::{cool, Lexer};
+use parser::Parser;
+
+#[path = "../../lexer/benches/programs/mod.rs"]
+mod programs;
+
+use programs::program;
+
+fn bench_parse(c: &mut Criterion) {
+    let lexer = Lexer::new(cool::rules());
+    let inputs = [("small", program(1)), ("large", program(200))];
+    let file = Symbol::intern("bench.cl");
+
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in inputs.iter() {
+        let tokens = lexer.lex(source).unwrap();
+        group.throughput(Throughput::Elements(tokens.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &tokens, |b, tokens| {
+            b.iter(|| Parser::new(tokens, file).parse_program().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
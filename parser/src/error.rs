@@ -0,0 +1,96 @@
+use std::fmt;
+
+use common::{Symbol, TokenKind};
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParseErrorKind {
+    /// A token that can't appear where it was found.
+    UnexpectedToken(TokenKind),
+    /// The input ended in the middle of a class.
+    UnexpectedEof,
+    /// A comparison used as an operand of another comparison, e.g. the second `<` in `a < b < c`.
+    /// Comparisons are non-associative in COOL.
+    ChainedComparison(TokenKind),
+    /// Expressions are nested deeper than the parser supports.
+    TooDeep,
+}
+
+/// A syntax error.
+///
+/// Displayed the way the reference parser reports syntax errors:
+///
+/// ```text
+/// "test.cl", line 3: syntax error at or near '<'
+/// ```
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseError {
+    pub file_name: Symbol,
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
+
+/// Print `kind` the way the reference parser refers to tokens in syntax errors.
+fn describe_token(f: &mut fmt::Formatter<'_>, kind: &TokenKind) -> fmt::Result {
+    match kind {
+        TokenKind::ObjectId(_)
+        | TokenKind::TypeId(_)
+        | TokenKind::Int(_)
+        | TokenKind::String(_)
+        | TokenKind::Bool(_)
+        | TokenKind::Error(_) => {
+            // These are printed as e.g. `OBJECTID = x` rather than `OBJECTID x`
+            let printed = kind.to_string();
+            f.write_str(&printed.replacen(' ', " = ", 1))
+        }
+        _ => fmt::Display::fmt(kind, f),
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\", line {}: ", self.file_name, self.line)?;
+
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken(kind) | ParseErrorKind::ChainedComparison(kind) => {
+                write!(f, "syntax error at or near ")?;
+                describe_token(f, kind)
+            }
+            ParseErrorKind::UnexpectedEof => write!(f, "syntax error at or near EOF"),
+            ParseErrorKind::TooDeep => write!(f, "expression nested too deeply"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = |kind| ParseError {
+            file_name: Symbol::intern("test.cl"),
+            line: 3,
+            kind,
+        };
+
+        assert_eq!(
+            error(ParseErrorKind::ChainedComparison(TokenKind::Lt)).to_string(),
+            "\"test.cl\", line 3: syntax error at or near '<'"
+        );
+        assert_eq!(
+            error(ParseErrorKind::UnexpectedToken(TokenKind::ObjectId(
+                Symbol::intern("x")
+            )))
+            .to_string(),
+            "\"test.cl\", line 3: syntax error at or near OBJECTID = x"
+        );
+        assert_eq!(
+            error(ParseErrorKind::UnexpectedEof).to_string(),
+            "\"test.cl\", line 3: syntax error at or near EOF"
+        );
+    }
+}
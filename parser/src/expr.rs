@@ -0,0 +1,619 @@
+//! Expression parsing.
+//!
+//! Operators are parsed with a Pratt parser driven by the tables below. From loosest to tightest
+//! COOL's operators are:
+//!
+//! | Operator         | Associativity  |
+//! |------------------|----------------|
+//! | `<-`             | right          |
+//! | `not`            | prefix         |
+//! | `<=` `<` `=`     | non-associative|
+//! | `+` `-`          | left           |
+//! | `*` `/`          | left           |
+//! | `isvoid`         | prefix         |
+//! | `~`              | prefix         |
+//! | `@`              | left           |
+//! | `.`              | left           |
+//!
+//! The left hand side of `<-` is always a single identifier, so like the reference parser we
+//! parse assignments when we see an identifier followed by `<-`, wherever that is. This makes
+//! `a + b <- c` mean `a + (b <- c)`.
+
+use common::ast::*;
+use common::{sym, KeywordKind, TokenKind};
+
+use crate::error::ParseErrorKind;
+use crate::parser::{Parser, Result};
+
+/// How deeply expressions can be nested before the parser gives up, to avoid overflowing the
+/// stack on pathological input.
+pub const MAX_DEPTH: usize = 256;
+
+/// The stack a thread needs to parse expressions nested up to `MAX_DEPTH` deep. Every level takes
+/// up to 16 KiB in debug builds, this leaves room for the rest of the thread.
+pub const STACK_SIZE: usize = MAX_DEPTH * 32 * 1024;
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+enum Precedence {
+    Lowest,
+    Not,
+    Comparison,
+    Additive,
+    Multiplicative,
+    IsVoid,
+    Neg,
+    At,
+    Dot,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Associativity {
+    Left,
+    NonAssociative,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Infix {
+    Binary(BinaryOp),
+    /// `@Type.method(args)`
+    StaticDispatch,
+    /// `.method(args)`
+    Dispatch,
+}
+
+struct InfixOperator {
+    token: TokenKind,
+    precedence: Precedence,
+    associativity: Associativity,
+    infix: Infix,
+}
+
+struct PrefixOperator {
+    token: TokenKind,
+    precedence: Precedence,
+    op: UnaryOp,
+}
+
+const INFIX_OPERATORS: [InfixOperator; 9] = [
+    InfixOperator {
+        token: TokenKind::Le,
+        precedence: Precedence::Comparison,
+        associativity: Associativity::NonAssociative,
+        infix: Infix::Binary(BinaryOp::Leq),
+    },
+    InfixOperator {
+        token: TokenKind::Lt,
+        precedence: Precedence::Comparison,
+        associativity: Associativity::NonAssociative,
+        infix: Infix::Binary(BinaryOp::Lt),
+    },
+    InfixOperator {
+        token: TokenKind::Equal,
+        precedence: Precedence::Comparison,
+        associativity: Associativity::NonAssociative,
+        infix: Infix::Binary(BinaryOp::Eq),
+    },
+    InfixOperator {
+        token: TokenKind::Plus,
+        precedence: Precedence::Additive,
+        associativity: Associativity::Left,
+        infix: Infix::Binary(BinaryOp::Plus),
+    },
+    InfixOperator {
+        token: TokenKind::Minus,
+        precedence: Precedence::Additive,
+        associativity: Associativity::Left,
+        infix: Infix::Binary(BinaryOp::Sub),
+    },
+    InfixOperator {
+        token: TokenKind::Star,
+        precedence: Precedence::Multiplicative,
+        associativity: Associativity::Left,
+        infix: Infix::Binary(BinaryOp::Mul),
+    },
+    InfixOperator {
+        token: TokenKind::Slash,
+        precedence: Precedence::Multiplicative,
+        associativity: Associativity::Left,
+        infix: Infix::Binary(BinaryOp::Divide),
+    },
+    InfixOperator {
+        token: TokenKind::At,
+        precedence: Precedence::At,
+        associativity: Associativity::Left,
+        infix: Infix::StaticDispatch,
+    },
+    InfixOperator {
+        token: TokenKind::Dot,
+        precedence: Precedence::Dot,
+        associativity: Associativity::Left,
+        infix: Infix::Dispatch,
+    },
+];
+
+const PREFIX_OPERATORS: [PrefixOperator; 3] = [
+    PrefixOperator {
+        token: TokenKind::Keyword(KeywordKind::Not),
+        precedence: Precedence::Not,
+        op: UnaryOp::Comp,
+    },
+    PrefixOperator {
+        token: TokenKind::Keyword(KeywordKind::IsVoid),
+        precedence: Precedence::IsVoid,
+        op: UnaryOp::IsVoid,
+    },
+    PrefixOperator {
+        token: TokenKind::Tilde,
+        precedence: Precedence::Neg,
+        op: UnaryOp::Neg,
+    },
+];
+
+fn infix_operator(kind: &TokenKind) -> Option<&'static InfixOperator> {
+    INFIX_OPERATORS
+        .iter()
+        .find(|operator| operator.token == *kind)
+}
+
+fn prefix_operator(kind: &TokenKind) -> Option<&'static PrefixOperator> {
+    PREFIX_OPERATORS
+        .iter()
+        .find(|operator| operator.token == *kind)
+}
+
+impl Parser {
+    pub(crate) fn expr(&mut self) -> Result<Expr> {
+        self.expr_with_precedence(Precedence::Lowest)
+    }
+
+    /// Parse an expression containing only operators that bind tighter than `min`.
+    fn expr_with_precedence(&mut self, min: Precedence) -> Result<Expr> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(ParseErrorKind::TooDeep));
+        }
+
+        self.depth += 1;
+        let result = self.operators(min);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn operators(&mut self, min: Precedence) -> Result<Expr> {
+        let mut lhs = self.prefix()?;
+        // The precedence of the last non-associative operator applied to `lhs`
+        let mut non_associative = None;
+
+        while let Some(operator) = self.peek().and_then(infix_operator) {
+            if operator.precedence <= min {
+                break;
+            }
+            if non_associative == Some(operator.precedence) {
+                return Err(self.error(ParseErrorKind::ChainedComparison(operator.token.clone())));
+            }
+
+            self.advance();
+            let line = lhs.line;
+            let kind = match operator.infix {
+                Infix::Binary(op) => {
+                    let rhs = self.expr_with_precedence(operator.precedence)?;
+
+                    ExprKind::Binary(Binary {
+                        op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    })
+                }
+                Infix::StaticDispatch => {
+                    let (type_name, _) = self.expect_type_id()?;
+                    self.expect(&TokenKind::Dot)?;
+                    let (name, _) = self.expect_object_id()?;
+                    let args = self.args()?;
+
+                    ExprKind::StaticDispatch(StaticDispatch {
+                        expr: Box::new(lhs),
+                        type_name,
+                        name,
+                        args,
+                    })
+                }
+                Infix::Dispatch => {
+                    let (name, _) = self.expect_object_id()?;
+                    let args = self.args()?;
+
+                    ExprKind::Dispatch(Dispatch {
+                        expr: Box::new(lhs),
+                        name,
+                        args,
+                    })
+                }
+            };
+
+            lhs = Expr::new(kind, line);
+            non_associative = match operator.associativity {
+                Associativity::NonAssociative => Some(operator.precedence),
+                Associativity::Left => None,
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parse a prefix operator and its operand or a primary expression.
+    fn prefix(&mut self) -> Result<Expr> {
+        let line = self.line();
+
+        if let Some(operator) = self.peek().and_then(prefix_operator) {
+            self.advance();
+            let expr = self.expr_with_precedence(operator.precedence)?;
+
+            return Ok(Expr::new(
+                ExprKind::Unary(Unary {
+                    op: operator.op,
+                    expr: Box::new(expr),
+                }),
+                line,
+            ));
+        }
+
+        let kind = match self.advance() {
+            Some((TokenKind::ObjectId(name), _)) => match self.peek() {
+                Some(TokenKind::Assign) => {
+                    self.advance();
+                    // Assignment is right associative and the loosest operator
+                    let expr = self.expr_with_precedence(Precedence::Lowest)?;
+
+                    ExprKind::Assign(Assign {
+                        name,
+                        expr: Box::new(expr),
+                    })
+                }
+                Some(TokenKind::OpenParen) => {
+                    let args = self.args()?;
+
+                    ExprKind::Dispatch(Dispatch {
+                        expr: Box::new(Expr::new(ExprKind::Object(sym::SELF), line)),
+                        name,
+                        args,
+                    })
+                }
+                _ => ExprKind::Object(name),
+            },
+            Some((TokenKind::Int(value), _)) => ExprKind::IntConst(value),
+            Some((TokenKind::String(value), _)) => ExprKind::StringConst(value),
+            Some((TokenKind::Bool(value), _)) => ExprKind::BoolConst(value),
+            Some((TokenKind::OpenParen, _)) => {
+                let expr = self.expr()?;
+                self.expect(&TokenKind::CloseParen)?;
+
+                return Ok(expr);
+            }
+            Some((TokenKind::OpenBrace, _)) => {
+                let mut body = vec![];
+                loop {
+                    body.push(self.expr()?);
+                    self.expect(&TokenKind::SemiColon)?;
+                    if self.eat(&TokenKind::CloseBrace).is_some() {
+                        break;
+                    }
+                }
+
+                ExprKind::Block(Block { body })
+            }
+            Some((TokenKind::Keyword(KeywordKind::If), _)) => {
+                let pred = self.expr()?;
+                self.expect_keyword(KeywordKind::Then)?;
+                let then_expr = self.expr()?;
+                self.expect_keyword(KeywordKind::Else)?;
+                let else_expr = self.expr()?;
+                self.expect_keyword(KeywordKind::Fi)?;
+
+                ExprKind::Cond(Cond {
+                    pred: Box::new(pred),
+                    then_expr: Box::new(then_expr),
+                    else_expr: Box::new(else_expr),
+                })
+            }
+            Some((TokenKind::Keyword(KeywordKind::While), _)) => {
+                let pred = self.expr()?;
+                self.expect_keyword(KeywordKind::Loop)?;
+                let body = self.expr()?;
+                self.expect_keyword(KeywordKind::Pool)?;
+
+                ExprKind::Loop(Loop {
+                    pred: Box::new(pred),
+                    body: Box::new(body),
+                })
+            }
+            Some((TokenKind::Keyword(KeywordKind::Let), _)) => return self.let_(line),
+            Some((TokenKind::Keyword(KeywordKind::Case), _)) => {
+                let expr = self.expr()?;
+                self.expect_keyword(KeywordKind::Of)?;
+
+                let mut branches = vec![];
+                loop {
+                    let (name, line) = self.expect_object_id()?;
+                    self.expect(&TokenKind::Colon)?;
+                    let (type_decl, _) = self.expect_type_id()?;
+                    self.expect(&TokenKind::DArrow)?;
+                    let expr = self.expr()?;
+                    self.expect(&TokenKind::SemiColon)?;
+
+                    branches.push(CaseBranch {
+                        name,
+                        type_decl,
+                        expr,
+                        line,
+                    });
+
+                    if self.eat_keyword(KeywordKind::Esac).is_some() {
+                        break;
+                    }
+                }
+
+                ExprKind::Case(Case {
+                    expr: Box::new(expr),
+                    branches,
+                })
+            }
+            Some((TokenKind::Keyword(KeywordKind::New), _)) => {
+                ExprKind::New(self.expect_type_id()?.0)
+            }
+            Some(_) => {
+                // Leave the token for error recovery to look at
+                self.retreat();
+                return Err(self.unexpected());
+            }
+            None => return Err(self.unexpected()),
+        };
+
+        Ok(Expr::new(kind, line))
+    }
+
+    /// Parse the bindings and body of a `let`, after the `let` keyword.
+    ///
+    /// Each binding becomes a `Let` of its own, with the `Let` of the following binding as its
+    /// body. The body of the last binding extends as far to the right as possible.
+    fn let_(&mut self, line: usize) -> Result<Expr> {
+        let mut bindings = vec![];
+        loop {
+            let (name, name_line) = self.expect_object_id()?;
+            self.expect(&TokenKind::Colon)?;
+            let (type_decl, _) = self.expect_type_id()?;
+            let init = if self.eat(&TokenKind::Assign).is_some() {
+                self.expr()?
+            } else {
+                Expr::no_expr(name_line)
+            };
+
+            let line = if bindings.is_empty() { line } else { name_line };
+            bindings.push((name, type_decl, init, line));
+
+            if self.eat(&TokenKind::Comma).is_none() {
+                break;
+            }
+        }
+        self.expect_keyword(KeywordKind::In)?;
+
+        let mut body = self.expr()?;
+        for (name, type_decl, init, line) in bindings.into_iter().rev() {
+            body = Expr::new(
+                ExprKind::Let(Let {
+                    name,
+                    type_decl,
+                    init: Box::new(init),
+                    body: Box::new(body),
+                }),
+                line,
+            );
+        }
+
+        Ok(body)
+    }
+
+    /// Parse a parenthesized, comma separated list of arguments.
+    fn args(&mut self) -> Result<Vec<Expr>> {
+        self.expect(&TokenKind::OpenParen)?;
+
+        let mut args = vec![];
+        if self.eat(&TokenKind::CloseParen).is_some() {
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.expr()?);
+            if self.eat(&TokenKind::Comma).is_none() {
+                break;
+            }
+        }
+        self.expect(&TokenKind::CloseParen)?;
+
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseError;
+    use common::Symbol;
+    use lexer::{cool, Lexer};
+
+    fn parse(input: &str) -> std::result::Result<Expr, ParseError> {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+
+        Parser::new(&tokens, Symbol::intern("test.cl")).parse_expression()
+    }
+
+    /// Render `expr` fully parenthesized.
+    fn render(expr: &Expr) -> String {
+        let list = |exprs: &[Expr]| exprs.iter().map(render).collect::<Vec<_>>().join(", ");
+
+        match &expr.kind {
+            ExprKind::Assign(assign) => format!("({} <- {})", assign.name, render(&assign.expr)),
+            ExprKind::StaticDispatch(dispatch) => format!(
+                "{}@{}.{}({})",
+                render(&dispatch.expr),
+                dispatch.type_name,
+                dispatch.name,
+                list(&dispatch.args)
+            ),
+            ExprKind::Dispatch(dispatch) => format!(
+                "{}.{}({})",
+                render(&dispatch.expr),
+                dispatch.name,
+                list(&dispatch.args)
+            ),
+            ExprKind::Binary(binary) => {
+                let op = match binary.op {
+                    BinaryOp::Plus => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    BinaryOp::Divide => "/",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Eq => "=",
+                    BinaryOp::Leq => "<=",
+                };
+                format!("({} {} {})", render(&binary.lhs), op, render(&binary.rhs))
+            }
+            ExprKind::Unary(unary) => {
+                let op = match unary.op {
+                    UnaryOp::Neg => "~",
+                    UnaryOp::Comp => "not ",
+                    UnaryOp::IsVoid => "isvoid ",
+                };
+                format!("({}{})", op, render(&unary.expr))
+            }
+            ExprKind::Let(let_) => format!(
+                "(let {} : {} <- {} in {})",
+                let_.name,
+                let_.type_decl,
+                render(&let_.init),
+                render(&let_.body)
+            ),
+            ExprKind::Block(block) => format!("{{ {} }}", list(&block.body)),
+            ExprKind::IntConst(value) | ExprKind::Object(value) => value.to_string(),
+            ExprKind::NoExpr => "_".to_owned(),
+            other => format!("{:?}", other),
+        }
+    }
+
+    fn assert_parses_as(input: &str, expected: &str) {
+        match parse(input) {
+            Ok(expr) => assert_eq!(render(&expr), expected, "Parsing {:?}", input),
+            Err(error) => panic!("Failed to parse {:?}: {}", input, error),
+        }
+    }
+
+    fn assert_error(input: &str, expected: ParseErrorKind) {
+        match parse(input) {
+            Ok(expr) => panic!("Expected {:?} to fail, got {}", input, render(&expr)),
+            Err(error) => assert_eq!(error.kind, expected, "Parsing {:?}", input),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_parses_as("1 + 2 * 3", "(1 + (2 * 3))");
+        assert_parses_as("1 * 2 + 3", "((1 * 2) + 3)");
+        assert_parses_as("1 - 2 - 3", "((1 - 2) - 3)");
+        assert_parses_as("1 / 2 / 3", "((1 / 2) / 3)");
+        assert_parses_as("(1 + 2) * 3", "((1 + 2) * 3)");
+        assert_parses_as("~1 + ~2", "((~1) + (~2))");
+    }
+
+    #[test]
+    fn test_prefix_operators() {
+        assert_parses_as("not a < b", "(not (a < b))");
+        assert_parses_as("not a + b = c", "(not ((a + b) = c))");
+        assert_parses_as("isvoid a + b", "((isvoid a) + b)");
+        assert_parses_as("isvoid ~a", "(isvoid (~a))");
+        assert_parses_as("~a.f()", "(~a.f())");
+        assert_parses_as("a * not b + c", "(a * (not (b + c)))");
+    }
+
+    #[test]
+    fn test_comparisons_are_non_associative() {
+        assert_parses_as("a + 1 <= b * 2", "((a + 1) <= (b * 2))");
+        assert_parses_as("(a < b) = c", "((a < b) = c)");
+        assert_parses_as("a < not b < c", "(a < (not (b < c)))");
+
+        assert_error(
+            "a < b < c",
+            ParseErrorKind::ChainedComparison(TokenKind::Lt),
+        );
+        assert_error(
+            "a = b <= c",
+            ParseErrorKind::ChainedComparison(TokenKind::Le),
+        );
+        assert_error(
+            "a < b + c = d",
+            ParseErrorKind::ChainedComparison(TokenKind::Equal),
+        );
+        assert_error(
+            "not a <= b < c",
+            ParseErrorKind::ChainedComparison(TokenKind::Lt),
+        );
+    }
+
+    #[test]
+    fn test_assign() {
+        assert_parses_as("a <- b <- 1 + 2", "(a <- (b <- (1 + 2)))");
+        assert_parses_as("a + b <- c", "(a + (b <- c))");
+        assert_parses_as("not a <- b", "(not (a <- b))");
+        assert_error("1 <- 2", ParseErrorKind::UnexpectedToken(TokenKind::Assign));
+    }
+
+    #[test]
+    fn test_dispatch() {
+        assert_parses_as("f(1, 2)", "self.f(1, 2)");
+        assert_parses_as("a.f().g(b + 1)", "a.f().g((b + 1))");
+        assert_parses_as("a@A.f()", "a@A.f()");
+        assert_parses_as("a@A.f().g()", "a@A.f().g()");
+        assert_parses_as("1 + a.f() * 2", "(1 + (a.f() * 2))");
+        assert_parses_as("{ 1; a.f(); }.g()", "{ 1, a.f() }.g()");
+    }
+
+    #[test]
+    fn test_let_extends_to_the_right() {
+        assert_parses_as(
+            "let x : Int, y : Int <- 2 in x + y",
+            "(let x : Int <- _ in (let y : Int <- 2 in (x + y)))",
+        );
+        assert_parses_as(
+            "1 + let x : Int in x * 2",
+            "(1 + (let x : Int <- _ in (x * 2)))",
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let expr = parse("a\n+\nb.f(\nc)").unwrap();
+        assert_eq!(expr.line, 1);
+
+        match expr.kind {
+            ExprKind::Binary(binary) => {
+                assert_eq!(binary.rhs.line, 3);
+                match binary.rhs.kind {
+                    ExprKind::Dispatch(dispatch) => assert_eq!(dispatch.args[0].line, 4),
+                    other => panic!("Expected a dispatch, got {:?}", other),
+                }
+            }
+            other => panic!("Expected a binary expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let input = format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+
+        // Parsing up to `MAX_DEPTH` needs more stack than test threads get in debug builds
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || assert_error(&input, ParseErrorKind::TooDeep))
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
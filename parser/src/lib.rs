@@ -0,0 +1,12 @@
+mod error;
+mod expr;
+mod parser;
+
+pub use crate::error::{ParseError, ParseErrorKind};
+pub use crate::expr::{MAX_DEPTH, STACK_SIZE};
+pub use crate::parser::Parser;
+
+pub mod prelude {
+    pub use crate::error::{ParseError, ParseErrorKind};
+    pub use crate::parser::Parser;
+}
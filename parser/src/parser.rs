@@ -0,0 +1,401 @@
+use common::ast::*;
+use common::{sym, KeywordKind, Symbol, Token, TokenKind};
+use lexer::LexerContext;
+
+use crate::error::{ParseError, ParseErrorKind};
+
+pub(crate) type Result<T> = std::result::Result<T, ParseError>;
+
+/// A recursive descent parser for COOL.
+///
+/// Classes and features are parsed by recursive descent, expressions by the Pratt parser in
+/// `expr.rs`.
+///
+/// Like the reference parser, a syntax error in a feature skips to the end of that feature and a
+/// syntax error elsewhere in a class skips to the next class, so one run reports as many errors
+/// as possible.
+pub struct Parser {
+    /// The tokens to parse and the line each of them ends on, without whitespace and comments.
+    tokens: Vec<(TokenKind, usize)>,
+    position: usize,
+    file_name: Symbol,
+    errors: Vec<ParseError>,
+    /// How many expressions are currently being parsed, see `MAX_DEPTH`.
+    pub(crate) depth: usize,
+}
+
+impl Parser {
+    /// Create a parser for the tokens lexed from the file `file_name`.
+    pub fn new(tokens: &[(Token<'_>, LexerContext)], file_name: Symbol) -> Self {
+        let tokens = tokens
+            .iter()
+            .filter(|(token, _)| !is_trivia(&token.kind))
+            .map(|(token, context)| (token.kind.clone(), context.line_number))
+            .collect();
+
+        Self {
+            tokens,
+            position: 0,
+            file_name,
+            errors: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Parse the classes in the file.
+    pub fn parse_program(mut self) -> std::result::Result<Program, Vec<ParseError>> {
+        let mut classes = vec![];
+
+        while !self.at_end() {
+            match self.class() {
+                Ok(class) => classes.push(class),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.recover_class();
+                }
+            }
+        }
+
+        if classes.is_empty() && self.errors.is_empty() {
+            // A program consists of at least one class
+            self.errors.push(self.unexpected());
+        }
+
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+
+        let line = classes.first().map(|class| class.line).unwrap_or(0);
+        Ok(Program { classes, line })
+    }
+
+    /// Parse a single expression that makes up all of the input.
+    pub fn parse_expression(mut self) -> Result<Expr> {
+        let expr = self.expr()?;
+
+        if !self.at_end() {
+            return Err(self.unexpected());
+        }
+
+        Ok(expr)
+    }
+
+    fn class(&mut self) -> Result<Class> {
+        let line = self.expect_keyword(KeywordKind::Class)?;
+        let (name, _) = self.expect_type_id()?;
+        let parent = if self.eat_keyword(KeywordKind::Inherits).is_some() {
+            self.expect_type_id()?.0
+        } else {
+            sym::OBJECT
+        };
+        self.expect(&TokenKind::OpenBrace)?;
+
+        let mut features = vec![];
+        while !self.at_end() && !self.check(&TokenKind::CloseBrace) {
+            let start = self.position;
+            match self.feature() {
+                Ok(feature) => features.push(feature),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.recover_feature(start);
+                }
+            }
+        }
+
+        self.expect(&TokenKind::CloseBrace)?;
+        self.expect(&TokenKind::SemiColon)?;
+
+        Ok(Class {
+            name,
+            parent,
+            features,
+            file_name: self.file_name,
+            line,
+        })
+    }
+
+    fn feature(&mut self) -> Result<Feature> {
+        let (name, line) = self.expect_object_id()?;
+
+        if self.eat(&TokenKind::OpenParen).is_some() {
+            let mut formals = vec![];
+            if !self.check(&TokenKind::CloseParen) {
+                loop {
+                    formals.push(self.formal()?);
+                    if self.eat(&TokenKind::Comma).is_none() {
+                        break;
+                    }
+                }
+            }
+            self.expect(&TokenKind::CloseParen)?;
+            self.expect(&TokenKind::Colon)?;
+            let (return_type, _) = self.expect_type_id()?;
+            self.expect(&TokenKind::OpenBrace)?;
+            let body = self.expr()?;
+            self.expect(&TokenKind::CloseBrace)?;
+            self.expect(&TokenKind::SemiColon)?;
+
+            Ok(Feature::Method(Method {
+                name,
+                formals,
+                return_type,
+                body,
+                line,
+            }))
+        } else {
+            self.expect(&TokenKind::Colon)?;
+            let (type_decl, _) = self.expect_type_id()?;
+            let init = if self.eat(&TokenKind::Assign).is_some() {
+                self.expr()?
+            } else {
+                Expr::no_expr(line)
+            };
+            self.expect(&TokenKind::SemiColon)?;
+
+            Ok(Feature::Attribute(Attribute {
+                name,
+                type_decl,
+                init,
+                line,
+            }))
+        }
+    }
+
+    fn formal(&mut self) -> Result<Formal> {
+        let (name, line) = self.expect_object_id()?;
+        self.expect(&TokenKind::Colon)?;
+        let (type_decl, _) = self.expect_type_id()?;
+
+        Ok(Formal {
+            name,
+            type_decl,
+            line,
+        })
+    }
+
+    /// Skip to the end of the feature starting at `start`, the first `;` outside of braces, or to
+    /// the `}` that closes the class.
+    fn recover_feature(&mut self, start: usize) {
+        let mut depth = 0usize;
+
+        // Rescan the feature so braces opened before the error are counted
+        self.position = start;
+
+        while let Some(kind) = self.peek() {
+            match kind {
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => return,
+                TokenKind::CloseBrace => depth -= 1,
+                TokenKind::SemiColon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skip to the start of the next class.
+    fn recover_class(&mut self) {
+        while let Some(kind) = self.peek() {
+            if *kind == TokenKind::Keyword(KeywordKind::Class) {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.position).map(|(kind, _)| kind)
+    }
+
+    /// The line of the next token, or of the last token at the end of the input.
+    pub(crate) fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or_else(|| self.tokens.last())
+            .map(|&(_, line)| line)
+            .unwrap_or(1)
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    /// Consume the next token and return it and its line.
+    pub(crate) fn advance(&mut self) -> Option<(TokenKind, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+
+        token
+    }
+
+    /// Undo the last `advance`.
+    pub(crate) fn retreat(&mut self) {
+        self.position -= 1;
+    }
+
+    pub(crate) fn check(&self, kind: &TokenKind) -> bool {
+        self.peek() == Some(kind)
+    }
+
+    /// Consume the next token if it is `kind` and return its line.
+    pub(crate) fn eat(&mut self, kind: &TokenKind) -> Option<usize> {
+        if self.check(kind) {
+            self.advance().map(|(_, line)| line)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn eat_keyword(&mut self, keyword: KeywordKind) -> Option<usize> {
+        self.eat(&TokenKind::Keyword(keyword))
+    }
+
+    pub(crate) fn expect(&mut self, kind: &TokenKind) -> Result<usize> {
+        self.eat(kind).ok_or_else(|| self.unexpected())
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: KeywordKind) -> Result<usize> {
+        self.expect(&TokenKind::Keyword(keyword))
+    }
+
+    pub(crate) fn expect_object_id(&mut self) -> Result<(Symbol, usize)> {
+        match self.peek() {
+            Some(&TokenKind::ObjectId(name)) => Ok((name, self.advance().unwrap().1)),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    pub(crate) fn expect_type_id(&mut self) -> Result<(Symbol, usize)> {
+        match self.peek() {
+            Some(&TokenKind::TypeId(name)) => Ok((name, self.advance().unwrap().1)),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// An error for the next token.
+    pub(crate) fn unexpected(&self) -> ParseError {
+        let kind = match self.peek() {
+            Some(kind) => ParseErrorKind::UnexpectedToken(kind.clone()),
+            None => ParseErrorKind::UnexpectedEof,
+        };
+
+        self.error(kind)
+    }
+
+    /// An error at the next token.
+    pub(crate) fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            file_name: self.file_name,
+            line: self.line(),
+            kind,
+        }
+    }
+}
+
+fn is_trivia(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+
+    fn parse(input: &str) -> std::result::Result<Program, Vec<ParseError>> {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+
+        Parser::new(&tokens, Symbol::intern("test.cl")).parse_program()
+    }
+
+    #[test]
+    fn test_parse_classes() {
+        let program = parse(
+            "class Main inherits IO {\n\
+             \tx : Int <- 1;\n\
+             \ty : String;\n\
+             \tmain(a : Int, b : Bool) : Object { out_int(x) };\n\
+             };\n\
+             (* A second class *)\n\
+             class A { };\n",
+        )
+        .unwrap();
+
+        assert_eq!(program.classes.len(), 2);
+        let main = &program.classes[0];
+        assert_eq!(main.name.as_str(), "Main");
+        assert_eq!(main.parent.as_str(), "IO");
+        assert_eq!(main.file_name.as_str(), "test.cl");
+        assert_eq!(main.line, 1);
+        assert_eq!(main.features.len(), 3);
+
+        match &main.features[1] {
+            Feature::Attribute(attribute) => {
+                assert_eq!(attribute.name.as_str(), "y");
+                assert!(attribute.init.is_no_expr());
+                assert_eq!(attribute.line, 3);
+            }
+            other => panic!("Expected an attribute, got {:?}", other),
+        }
+
+        match &main.features[2] {
+            Feature::Method(method) => {
+                assert_eq!(method.formals.len(), 2);
+                assert_eq!(method.return_type.as_str(), "Object");
+                assert_eq!(method.line, 4);
+                match &method.body.kind {
+                    ExprKind::Dispatch(dispatch) => {
+                        assert_eq!(dispatch.name.as_str(), "out_int");
+                        assert_eq!(dispatch.expr.kind, ExprKind::Object(sym::SELF));
+                    }
+                    other => panic!("Expected a dispatch, got {:?}", other),
+                }
+            }
+            other => panic!("Expected a method, got {:?}", other),
+        }
+
+        assert_eq!(program.classes[1].parent.as_str(), "Object");
+        assert_eq!(program.classes[1].line, 7);
+    }
+
+    #[test]
+    fn test_empty_program() {
+        let errors = parse("-- nothing here\n").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_recovery() {
+        let errors = parse(
+            "class A {\n\
+             \tx : Int <- ;\n\
+             \tf() : Int { { 1; 2 } };\n\
+             \tg() : Int { 3 };\n\
+             };\n\
+             class B inherits { };\n\
+             class C { y : Int };\n",
+        )
+        .unwrap_err();
+
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "\"test.cl\", line 2: syntax error at or near ';'",
+                "\"test.cl\", line 3: syntax error at or near '}'",
+                "\"test.cl\", line 6: syntax error at or near '{'",
+                "\"test.cl\", line 7: syntax error at or near '}'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use common::Symbol;
+use lexer::{cool, Lexer};
+use parser::Parser;
+
+#[path = "../../lexer/benches/programs/mod.rs"]
+mod programs;
+
+use programs::program;
+
+fn bench_check(c: &mut Criterion) {
+    let lexer = Lexer::new(cool::rules());
+    let inputs = [("small", program(1)), ("large", program(200))];
+
+    let mut group = c.benchmark_group("semant");
+    for (name, source) in inputs.iter() {
+        let tokens = lexer.lex(source).unwrap();
+        let program = Parser::new(&tokens, Symbol::intern("bench.cl"))
+            .parse_program()
+            .unwrap();
+        // Type checking fills in the types, so every iteration checks a fresh copy
+        group.bench_with_input(BenchmarkId::from_parameter(name), &program, |b, program| {
+            b.iter_batched(
+                || program.clone(),
+                |mut program| semant::check(&mut program).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_check);
+criterion_main!(benches);
@@ -0,0 +1,446 @@
+use std::collections::{HashMap, HashSet};
+
+use common::ast::*;
+use common::{sym, Symbol};
+
+use crate::error::{ErrorKind, SemantError};
+
+#[derive(Debug, Clone)]
+pub struct AttributeInfo {
+    pub name: Symbol,
+    pub type_decl: Symbol,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodInfo {
+    pub name: Symbol,
+    pub formals: Vec<Formal>,
+    pub return_type: Symbol,
+    pub line: usize,
+}
+
+/// The signature of a class: its parent and the features it declares itself.
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    pub name: Symbol,
+    /// The parent class, `None` only for `Object`.
+    pub parent: Option<Symbol>,
+    pub attributes: Vec<AttributeInfo>,
+    pub methods: Vec<MethodInfo>,
+    pub file_name: Symbol,
+    pub line: usize,
+}
+
+impl ClassInfo {
+    fn new(class: &Class, parent: Option<Symbol>) -> Self {
+        let mut attributes = vec![];
+        let mut methods = vec![];
+
+        for feature in &class.features {
+            match feature {
+                Feature::Attribute(attribute) => attributes.push(AttributeInfo {
+                    name: attribute.name,
+                    type_decl: attribute.type_decl,
+                    line: attribute.line,
+                }),
+                Feature::Method(method) => methods.push(MethodInfo {
+                    name: method.name,
+                    formals: method.formals.clone(),
+                    return_type: method.return_type,
+                    line: method.line,
+                }),
+            }
+        }
+
+        Self {
+            name: class.name,
+            parent,
+            attributes,
+            methods,
+            file_name: class.file_name,
+            line: class.line,
+        }
+    }
+
+    /// Whether this is one of the classes every program starts with.
+    pub fn is_basic(&self) -> bool {
+        is_basic_class(self.name)
+    }
+
+    pub fn attribute(&self, name: Symbol) -> Option<&AttributeInfo> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    pub fn method(&self, name: Symbol) -> Option<&MethodInfo> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+}
+
+fn is_basic_class(name: Symbol) -> bool {
+    [sym::OBJECT, sym::IO, sym::INT, sym::STRING, sym::BOOL].contains(&name)
+}
+
+/// The file name of the basic classes, as in the reference compiler.
+const BASIC_CLASS_FILE: &str = "<basic class>";
+
+/// The classes every program starts with, like `install_basic_classes` in the reference compiler.
+///
+/// Their methods are implemented by the runtime, so their bodies are empty.
+pub fn basic_classes() -> Vec<Class> {
+    let file_name = Symbol::intern(BASIC_CLASS_FILE);
+    let method = |name, formals: &[(Symbol, Symbol)], return_type| {
+        Feature::Method(Method {
+            name,
+            formals: formals
+                .iter()
+                .map(|&(name, type_decl)| Formal {
+                    name,
+                    type_decl,
+                    line: 0,
+                })
+                .collect(),
+            return_type,
+            body: Expr::no_expr(0),
+            line: 0,
+        })
+    };
+    let class = |name, parent, features| Class {
+        name,
+        parent,
+        features,
+        file_name,
+        line: 0,
+    };
+
+    vec![
+        class(
+            sym::OBJECT,
+            sym::OBJECT,
+            vec![
+                method(sym::ABORT, &[], sym::OBJECT),
+                method(sym::TYPE_NAME, &[], sym::STRING),
+                method(sym::COPY, &[], sym::SELF_TYPE),
+            ],
+        ),
+        class(
+            sym::IO,
+            sym::OBJECT,
+            vec![
+                method(sym::OUT_STRING, &[(sym::ARG, sym::STRING)], sym::SELF_TYPE),
+                method(sym::OUT_INT, &[(sym::ARG, sym::INT)], sym::SELF_TYPE),
+                method(sym::IN_STRING, &[], sym::STRING),
+                method(sym::IN_INT, &[], sym::INT),
+            ],
+        ),
+        class(sym::INT, sym::OBJECT, vec![]),
+        class(
+            sym::STRING,
+            sym::OBJECT,
+            vec![
+                method(sym::LENGTH, &[], sym::INT),
+                method(sym::CONCAT, &[(sym::ARG, sym::STRING)], sym::STRING),
+                method(
+                    sym::SUBSTR,
+                    &[(sym::ARG, sym::INT), (sym::ARG2, sym::INT)],
+                    sym::STRING,
+                ),
+            ],
+        ),
+        class(sym::BOOL, sym::OBJECT, vec![]),
+    ]
+}
+
+/// All classes of a program, the basic classes followed by the program's own in the order they
+/// were defined.
+#[derive(Debug)]
+pub struct ClassTable {
+    classes: Vec<ClassInfo>,
+    index: HashMap<Symbol, usize>,
+}
+
+impl ClassTable {
+    /// Build the class table of `program` and check that its classes form a tree rooted at
+    /// `Object`.
+    pub fn new(program: &Program) -> Result<Self, Vec<SemantError>> {
+        let mut table = Self {
+            classes: vec![],
+            index: HashMap::new(),
+        };
+        let mut errors = vec![];
+        let error = |class: &Class, kind| SemantError::new(class.file_name, class.line, kind);
+
+        for class in basic_classes() {
+            let parent = if class.name == sym::OBJECT {
+                None
+            } else {
+                Some(class.parent)
+            };
+            table.insert(ClassInfo::new(&class, parent));
+        }
+
+        let mut defined = vec![];
+        for class in &program.classes {
+            if is_basic_class(class.name) || class.name == sym::SELF_TYPE {
+                errors.push(error(class, ErrorKind::RedefinedBasicClass(class.name)));
+            } else if table.contains(class.name) {
+                errors.push(error(class, ErrorKind::ClassRedefined(class.name)));
+            } else {
+                table.insert(ClassInfo::new(class, Some(class.parent)));
+                defined.push(class);
+            }
+        }
+
+        let mut valid_parents = true;
+        for class in &defined {
+            if [sym::INT, sym::STRING, sym::BOOL, sym::SELF_TYPE].contains(&class.parent) {
+                valid_parents = false;
+                errors.push(error(
+                    class,
+                    ErrorKind::CannotInherit {
+                        class: class.name,
+                        parent: class.parent,
+                    },
+                ));
+            } else if !table.contains(class.parent) {
+                valid_parents = false;
+                errors.push(error(
+                    class,
+                    ErrorKind::UndefinedParent {
+                        class: class.name,
+                        parent: class.parent,
+                    },
+                ));
+            }
+        }
+
+        // Cycles can only be found once every parent is known to exist
+        if valid_parents {
+            for class in &defined {
+                if let Some(ancestor) = table.ancestor_in_cycle(class.name) {
+                    errors.push(error(
+                        class,
+                        ErrorKind::InheritanceCycle {
+                            ancestor,
+                            class: class.name,
+                        },
+                    ));
+                }
+            }
+        }
+
+        if !table.contains(sym::MAIN) {
+            errors.push(SemantError::program(ErrorKind::NoMainClass));
+        }
+
+        if errors.is_empty() {
+            Ok(table)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn insert(&mut self, class: ClassInfo) {
+        self.index.insert(class.name, self.classes.len());
+        self.classes.push(class);
+    }
+
+    /// The first ancestor of `class` that is part of an inheritance cycle, if there is one.
+    fn ancestor_in_cycle(&self, class: Symbol) -> Option<Symbol> {
+        let mut path = vec![];
+        let mut seen = HashSet::new();
+        let mut current = class;
+
+        while seen.insert(current) {
+            path.push(current);
+            current = self.get(current)?.parent?;
+        }
+
+        // `current` is the first class seen twice, the cycle is the part of the path from it on
+        let start = path.iter().position(|&name| name == current).unwrap();
+        let cycle = &path[start..];
+
+        path[1..]
+            .iter()
+            .chain(std::iter::once(&current))
+            .find(|name| cycle.contains(name))
+            .copied()
+    }
+
+    pub fn contains(&self, class: Symbol) -> bool {
+        self.index.contains_key(&class)
+    }
+
+    pub fn get(&self, class: Symbol) -> Option<&ClassInfo> {
+        self.index.get(&class).map(|&index| &self.classes[index])
+    }
+
+    /// All classes, basic classes first.
+    pub fn classes(&self) -> impl Iterator<Item = &ClassInfo> {
+        self.classes.iter()
+    }
+
+    /// `class` followed by its parent, its parent's parent and so on up to `Object`.
+    pub fn ancestors(&self, class: Symbol) -> impl Iterator<Item = &ClassInfo> {
+        std::iter::successors(self.get(class), move |info| {
+            info.parent.and_then(|parent| self.get(parent))
+        })
+    }
+
+    /// Find the method `name` of `class`, either declared in the class itself or inherited.
+    pub fn method(&self, class: Symbol, name: Symbol) -> Option<&MethodInfo> {
+        self.ancestors(class).find_map(|info| info.method(name))
+    }
+
+    /// Find the attribute `name` of `class`, either declared in the class itself or inherited.
+    pub fn attribute(&self, class: Symbol, name: Symbol) -> Option<&AttributeInfo> {
+        self.ancestors(class).find_map(|info| info.attribute(name))
+    }
+
+    /// Whether `sub` conforms to `sup` in the class `current`, where `SELF_TYPE` stands for the
+    /// type of `self`.
+    ///
+    /// Undefined types conform to and are conformed to by every type, so an error about an
+    /// undefined class doesn't cause further errors.
+    pub fn conforms(&self, sub: Symbol, sup: Symbol, current: Symbol) -> bool {
+        if sub == sup {
+            return true;
+        }
+        if sup == sym::SELF_TYPE {
+            // Only SELF_TYPE conforms to SELF_TYPE, the class of self could be any subclass
+            return false;
+        }
+
+        let sub = if sub == sym::SELF_TYPE { current } else { sub };
+        if !self.contains(sub) || !self.contains(sup) {
+            return true;
+        }
+
+        self.ancestors(sub).any(|info| info.name == sup)
+    }
+
+    /// The least upper bound of `a` and `b` in the class `current`.
+    pub fn join(&self, a: Symbol, b: Symbol, current: Symbol) -> Symbol {
+        if a == b {
+            return a;
+        }
+
+        let a = if a == sym::SELF_TYPE { current } else { a };
+        let b = if b == sym::SELF_TYPE { current } else { b };
+        let b_ancestors: HashSet<_> = self.ancestors(b).map(|info| info.name).collect();
+
+        self.ancestors(a)
+            .map(|info| info.name)
+            .find(|name| b_ancestors.contains(name))
+            .unwrap_or(sym::OBJECT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(name: &str, parent: &str, line: usize) -> Class {
+        Class {
+            name: Symbol::intern(name),
+            parent: Symbol::intern(parent),
+            features: vec![],
+            file_name: Symbol::intern("test.cl"),
+            line,
+        }
+    }
+
+    fn table(classes: Vec<Class>) -> Result<ClassTable, Vec<String>> {
+        let program = Program { classes, line: 1 };
+
+        ClassTable::new(&program).map_err(|errors| errors.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let table = table(vec![
+            class("A", "IO", 1),
+            class("B", "A", 2),
+            class("C", "A", 3),
+            class("Main", "Object", 4),
+        ])
+        .unwrap();
+        let (a, b, c) = (
+            Symbol::intern("A"),
+            Symbol::intern("B"),
+            Symbol::intern("C"),
+        );
+
+        let ancestors: Vec<_> = table.ancestors(b).map(|info| info.name.as_str()).collect();
+        assert_eq!(ancestors, vec!["B", "A", "IO", "Object"]);
+
+        assert!(table.conforms(b, a, c));
+        assert!(!table.conforms(a, b, c));
+        assert!(table.conforms(sym::SELF_TYPE, a, b));
+        assert!(!table.conforms(b, sym::SELF_TYPE, b));
+        assert!(table.conforms(sym::SELF_TYPE, sym::SELF_TYPE, b));
+
+        assert_eq!(table.join(b, c, a), a);
+        assert_eq!(table.join(sym::SELF_TYPE, c, b), a);
+        assert_eq!(
+            table.join(sym::SELF_TYPE, sym::SELF_TYPE, b),
+            sym::SELF_TYPE
+        );
+        assert_eq!(table.join(sym::INT, b, a), sym::OBJECT);
+
+        assert_eq!(
+            table.method(b, sym::OUT_INT).unwrap().return_type,
+            sym::SELF_TYPE
+        );
+        assert!(table.method(b, sym::LENGTH).is_none());
+    }
+
+    #[test]
+    fn test_hierarchy_errors() {
+        let errors = table(vec![
+            class("Int", "Object", 1),
+            class("A", "Object", 2),
+            class("A", "Object", 3),
+            class("B", "String", 4),
+            class("C", "D", 5),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                "test.cl:1: Redefinition of basic class Int.",
+                "test.cl:3: Class A was previously defined.",
+                "test.cl:4: Class B cannot inherit class String.",
+                "test.cl:5: Class C inherits from an undefined class D.",
+                "Class Main is not defined.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inheritance_cycle() {
+        let errors = table(vec![
+            class("A", "B", 1),
+            class("B", "C", 2),
+            class("C", "A", 3),
+            class("D", "B", 4),
+            class("E", "E", 5),
+            class("Main", "Object", 6),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                "test.cl:1: Class B, an ancestor of A, is involved in an inheritance cycle.",
+                "test.cl:2: Class C, an ancestor of B, is involved in an inheritance cycle.",
+                "test.cl:3: Class A, an ancestor of C, is involved in an inheritance cycle.",
+                "test.cl:4: Class B, an ancestor of D, is involved in an inheritance cycle.",
+                "test.cl:5: Class E, an ancestor of E, is involved in an inheritance cycle.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+use std::fmt;
+
+use common::ast::BinaryOp;
+use common::Symbol;
+
+/// A static semantic error, worded like the reference semantic analyzer's.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    // The class hierarchy
+    RedefinedBasicClass(Symbol),
+    ClassRedefined(Symbol),
+    CannotInherit {
+        class: Symbol,
+        parent: Symbol,
+    },
+    UndefinedParent {
+        class: Symbol,
+        parent: Symbol,
+    },
+    /// `ancestor` is the first ancestor of `class` that is part of the cycle.
+    InheritanceCycle {
+        ancestor: Symbol,
+        class: Symbol,
+    },
+    NoMainClass,
+    NoMainMethod,
+    MainMethodArguments,
+
+    // Features
+    AttributeNamedSelf,
+    UndefinedAttributeType {
+        attribute: Symbol,
+        type_decl: Symbol,
+    },
+    AttributeInitType {
+        attribute: Symbol,
+        inferred: Symbol,
+        declared: Symbol,
+    },
+    UndefinedReturnType {
+        method: Symbol,
+        return_type: Symbol,
+    },
+    ReturnType {
+        method: Symbol,
+        inferred: Symbol,
+        declared: Symbol,
+    },
+    UndefinedFormalType {
+        formal: Symbol,
+        type_decl: Symbol,
+    },
+    FormalSelfType(Symbol),
+
+    // Redefined features
+    AttributeMultiplyDefined(Symbol),
+    InheritedAttribute(Symbol),
+    MethodMultiplyDefined(Symbol),
+    FormalMultiplyDefined(Symbol),
+    FormalNamedSelf,
+    OverrideArgumentCount(Symbol),
+    OverrideFormalType {
+        method: Symbol,
+        formal_type: Symbol,
+        original: Symbol,
+    },
+    OverrideReturnType {
+        method: Symbol,
+        return_type: Symbol,
+        original: Symbol,
+    },
+
+    // Expressions
+    UndeclaredIdentifier(Symbol),
+    AssignToSelf,
+    AssignToUndeclared(Symbol),
+    AssignType {
+        name: Symbol,
+        inferred: Symbol,
+        declared: Symbol,
+    },
+    DispatchOnUndefinedClass(Symbol),
+    UndefinedMethod(Symbol),
+    StaticDispatchUndefinedMethod(Symbol),
+    StaticDispatchUndefinedClass(Symbol),
+    StaticDispatchToSelfType,
+    StaticDispatchType {
+        inferred: Symbol,
+        declared: Symbol,
+    },
+    WrongArgumentCount(Symbol),
+    ArgumentType {
+        method: Symbol,
+        inferred: Symbol,
+        formal: Symbol,
+        declared: Symbol,
+    },
+    IfPredicate,
+    LoopPredicate,
+    CaseBindsSelf,
+    CaseSelfType(Symbol),
+    UndefinedCaseType(Symbol),
+    DuplicateCaseBranch(Symbol),
+    LetBindsSelf,
+    UndefinedLetType {
+        name: Symbol,
+        type_decl: Symbol,
+    },
+    LetInitType {
+        name: Symbol,
+        inferred: Symbol,
+        declared: Symbol,
+    },
+    NonIntArguments {
+        op: BinaryOp,
+        lhs: Symbol,
+        rhs: Symbol,
+    },
+    IllegalComparison,
+    NegArgument(Symbol),
+    NotArgument(Symbol),
+    UndefinedNewType(Symbol),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RedefinedBasicClass(class) => write!(f, "Redefinition of basic class {}.", class),
+            Self::ClassRedefined(class) => write!(f, "Class {} was previously defined.", class),
+            Self::CannotInherit { class, parent } => {
+                write!(f, "Class {} cannot inherit class {}.", class, parent)
+            }
+            Self::UndefinedParent { class, parent } => write!(
+                f,
+                "Class {} inherits from an undefined class {}.",
+                class, parent
+            ),
+            Self::InheritanceCycle { ancestor, class } => write!(
+                f,
+                "Class {}, an ancestor of {}, is involved in an inheritance cycle.",
+                ancestor, class
+            ),
+            Self::NoMainClass => write!(f, "Class Main is not defined."),
+            Self::NoMainMethod => write!(f, "No 'main' method in class Main."),
+            Self::MainMethodArguments => {
+                write!(f, "'main' method in class Main should have no arguments.")
+            }
+
+            Self::AttributeNamedSelf => write!(f, "'self' cannot be the name of an attribute."),
+            Self::UndefinedAttributeType {
+                attribute,
+                type_decl,
+            } => write!(
+                f,
+                "Class {} of attribute {} is undefined.",
+                type_decl, attribute
+            ),
+            Self::AttributeInitType {
+                attribute,
+                inferred,
+                declared,
+            } => write!(
+                f,
+                "Inferred type {} of initialization of attribute {} does not conform to declared type {}.",
+                inferred, attribute, declared
+            ),
+            Self::UndefinedReturnType {
+                method,
+                return_type,
+            } => write!(
+                f,
+                "Undefined return type {} in method {}.",
+                return_type, method
+            ),
+            Self::ReturnType {
+                method,
+                inferred,
+                declared,
+            } => write!(
+                f,
+                "Inferred return type {} of method {} does not conform to declared return type {}.",
+                inferred, method, declared
+            ),
+            Self::UndefinedFormalType { formal, type_decl } => write!(
+                f,
+                "Class {} of formal parameter {} is undefined.",
+                type_decl, formal
+            ),
+            Self::FormalSelfType(formal) => write!(
+                f,
+                "Formal parameter {} cannot have type SELF_TYPE.",
+                formal
+            ),
+
+            Self::AttributeMultiplyDefined(attribute) => {
+                write!(f, "Attribute {} is multiply defined in class.", attribute)
+            }
+            Self::InheritedAttribute(attribute) => write!(
+                f,
+                "Attribute {} is an attribute of an inherited class.",
+                attribute
+            ),
+            Self::MethodMultiplyDefined(method) => {
+                write!(f, "Method {} is multiply defined.", method)
+            }
+            Self::FormalMultiplyDefined(formal) => {
+                write!(f, "Formal parameter {} is multiply defined.", formal)
+            }
+            Self::FormalNamedSelf => {
+                write!(f, "'self' cannot be the name of a formal parameter.")
+            }
+            Self::OverrideArgumentCount(method) => write!(
+                f,
+                "Incompatible number of formal parameters in redefined method {}.",
+                method
+            ),
+            Self::OverrideFormalType {
+                method,
+                formal_type,
+                original,
+            } => write!(
+                f,
+                "In redefined method {}, parameter type {} is different from original type {}.",
+                method, formal_type, original
+            ),
+            Self::OverrideReturnType {
+                method,
+                return_type,
+                original,
+            } => write!(
+                f,
+                "In redefined method {}, return type {} is different from original return type {}.",
+                method, return_type, original
+            ),
+
+            Self::UndeclaredIdentifier(name) => write!(f, "Undeclared identifier {}.", name),
+            Self::AssignToSelf => write!(f, "Cannot assign to 'self'."),
+            Self::AssignToUndeclared(name) => {
+                write!(f, "Assignment to undeclared variable {}.", name)
+            }
+            Self::AssignType {
+                name,
+                inferred,
+                declared,
+            } => write!(
+                f,
+                "Type {} of assigned expression does not conform to declared type {} of identifier {}.",
+                inferred, declared, name
+            ),
+            Self::DispatchOnUndefinedClass(class) => {
+                write!(f, "Dispatch on undefined class {}.", class)
+            }
+            Self::UndefinedMethod(method) => write!(f, "Dispatch to undefined method {}.", method),
+            Self::StaticDispatchUndefinedMethod(method) => {
+                write!(f, "Static dispatch to undefined method {}.", method)
+            }
+            Self::StaticDispatchUndefinedClass(class) => {
+                write!(f, "Static dispatch to undefined class {}.", class)
+            }
+            Self::StaticDispatchToSelfType => write!(f, "Static dispatch to SELF_TYPE."),
+            Self::StaticDispatchType { inferred, declared } => write!(
+                f,
+                "Expression type {} does not conform to declared static dispatch type {}.",
+                inferred, declared
+            ),
+            Self::WrongArgumentCount(method) => write!(
+                f,
+                "Method {} called with wrong number of arguments.",
+                method
+            ),
+            Self::ArgumentType {
+                method,
+                inferred,
+                formal,
+                declared,
+            } => write!(
+                f,
+                "In call of method {}, type {} of parameter {} does not conform to declared type {}.",
+                method, inferred, formal, declared
+            ),
+            Self::IfPredicate => write!(f, "Predicate of 'if' does not have type Bool."),
+            Self::LoopPredicate => write!(f, "Loop condition does not have type Bool."),
+            Self::CaseBindsSelf => write!(f, "'self' bound in 'case'."),
+            Self::CaseSelfType(name) => write!(
+                f,
+                "Identifier {} declared with type SELF_TYPE in case branch.",
+                name
+            ),
+            Self::UndefinedCaseType(class) => {
+                write!(f, "Class {} of case branch is undefined.", class)
+            }
+            Self::DuplicateCaseBranch(class) => {
+                write!(f, "Duplicate branch {} in case statement.", class)
+            }
+            Self::LetBindsSelf => write!(f, "'self' cannot be bound in a 'let' expression."),
+            Self::UndefinedLetType { name, type_decl } => write!(
+                f,
+                "Class {} of let-bound identifier {} is undefined.",
+                type_decl, name
+            ),
+            Self::LetInitType {
+                name,
+                inferred,
+                declared,
+            } => write!(
+                f,
+                "Inferred type {} of initialization of {} does not conform to identifier's declared type {}.",
+                inferred, name, declared
+            ),
+            Self::NonIntArguments { op, lhs, rhs } => {
+                let op = match op {
+                    BinaryOp::Plus => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    BinaryOp::Divide => "/",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Eq => "=",
+                    BinaryOp::Leq => "<=",
+                };
+                write!(f, "non-Int arguments: {} {} {}", lhs, op, rhs)
+            }
+            Self::IllegalComparison => write!(f, "Illegal comparison with a basic type."),
+            Self::NegArgument(ty) => {
+                write!(f, "Argument of '~' has type {} instead of Int.", ty)
+            }
+            Self::NotArgument(ty) => {
+                write!(f, "Argument of 'not' has type {} instead of Bool.", ty)
+            }
+            Self::UndefinedNewType(class) => {
+                write!(f, "'new' used with undefined class {}.", class)
+            }
+        }
+    }
+}
+
+/// Where in the program a [`SemantError`] was found.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    pub file_name: Symbol,
+    pub line: usize,
+}
+
+/// A static semantic error.
+///
+/// Displayed as `file:line: message` like the reference semantic analyzer's errors. Errors about
+/// the program as a whole, such as a missing `Main` class, have no location and are displayed as
+/// just the message.
+///
+/// Errors about a redefined feature also point at the original declaration, see
+/// [`SemantError::note`].
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SemantError {
+    pub location: Option<Location>,
+    pub kind: ErrorKind,
+    /// The declaration the error is related to, e.g. the method an incorrect override overrides.
+    pub related: Option<Location>,
+}
+
+impl SemantError {
+    pub fn new(file_name: Symbol, line: usize, kind: ErrorKind) -> Self {
+        Self {
+            location: Some(Location { file_name, line }),
+            kind,
+            related: None,
+        }
+    }
+
+    /// Point at the declaration at `line` in `file_name` as well.
+    pub fn with_related(mut self, file_name: Symbol, line: usize) -> Self {
+        self.related = Some(Location { file_name, line });
+        self
+    }
+
+    /// A note pointing at the related declaration, if there is one.
+    ///
+    /// The note isn't part of the displayed error, which is kept identical to the reference
+    /// semantic analyzer's.
+    pub fn note(&self) -> Option<String> {
+        self.related.map(|Location { file_name, line }| {
+            format!(
+                "{}:{}: note: the original declaration is here",
+                file_name, line
+            )
+        })
+    }
+
+    /// An error about the program as a whole.
+    pub fn program(kind: ErrorKind) -> Self {
+        Self {
+            location: None,
+            kind,
+            related: None,
+        }
+    }
+}
+
+impl fmt::Display for SemantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(Location { file_name, line }) => {
+                write!(f, "{}:{}: {}", file_name, line, self.kind)
+            }
+            None => fmt::Display::fmt(&self.kind, f),
+        }
+    }
+}
+
+impl std::error::Error for SemantError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = SemantError::new(
+            Symbol::intern("bad.cl"),
+            7,
+            ErrorKind::AttributeInitType {
+                attribute: Symbol::intern("a"),
+                inferred: Symbol::intern("String"),
+                declared: Symbol::intern("Int"),
+            },
+        );
+        assert_eq!(
+            error.to_string(),
+            "bad.cl:7: Inferred type String of initialization of attribute a does not conform to declared type Int."
+        );
+        assert_eq!(
+            SemantError::program(ErrorKind::NoMainClass).to_string(),
+            "Class Main is not defined."
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Checks of the features of each class against each other and against the features it inherits.
+
+use common::{sym, SymbolTable};
+
+use crate::class_table::{AttributeInfo, ClassInfo, ClassTable, MethodInfo};
+use crate::error::{ErrorKind, SemantError};
+
+/// Check that no class redefines an attribute, that methods are overridden with the same
+/// signature and that every method has distinct formals.
+///
+/// The errors of each class are sorted by line.
+pub(crate) fn check_features(table: &ClassTable) -> Vec<SemantError> {
+    let mut errors = vec![];
+
+    for class in table.classes().filter(|class| !class.is_basic()) {
+        let start = errors.len();
+        check_attributes(table, class, &mut errors);
+        check_methods(table, class, &mut errors);
+
+        errors[start..].sort_by_key(|error| error.location.map(|location| location.line));
+    }
+
+    errors
+}
+
+/// An error at `line` in `class` about a feature that was first declared at `original` in
+/// `original_class`.
+fn redefinition(
+    class: &ClassInfo,
+    line: usize,
+    kind: ErrorKind,
+    original_class: &ClassInfo,
+    original: usize,
+) -> SemantError {
+    let error = SemantError::new(class.file_name, line, kind);
+
+    // The basic classes have no source to point at
+    if original_class.is_basic() {
+        error
+    } else {
+        error.with_related(original_class.file_name, original)
+    }
+}
+
+/// The ancestors of `class`, not including `class` itself.
+fn inherited<'t>(table: &'t ClassTable, class: &ClassInfo) -> impl Iterator<Item = &'t ClassInfo> {
+    class
+        .parent
+        .into_iter()
+        .flat_map(move |parent| table.ancestors(parent))
+}
+
+fn check_attributes(table: &ClassTable, class: &ClassInfo, errors: &mut Vec<SemantError>) {
+    let mut declared: SymbolTable<&AttributeInfo> = SymbolTable::new();
+    declared.enter_scope();
+
+    for attribute in &class.attributes {
+        if let Some(original) = declared.probe(attribute.name) {
+            errors.push(redefinition(
+                class,
+                attribute.line,
+                ErrorKind::AttributeMultiplyDefined(attribute.name),
+                class,
+                original.line,
+            ));
+            continue;
+        }
+        declared.add(attribute.name, attribute);
+
+        let original = inherited(table, class)
+            .find_map(|ancestor| Some((ancestor, ancestor.attribute(attribute.name)?)));
+        if let Some((ancestor, original)) = original {
+            errors.push(redefinition(
+                class,
+                attribute.line,
+                ErrorKind::InheritedAttribute(attribute.name),
+                ancestor,
+                original.line,
+            ));
+        }
+    }
+}
+
+fn check_methods(table: &ClassTable, class: &ClassInfo, errors: &mut Vec<SemantError>) {
+    let mut declared: SymbolTable<&MethodInfo> = SymbolTable::new();
+    declared.enter_scope();
+
+    for method in &class.methods {
+        check_formals(class, method, errors);
+
+        if let Some(original) = declared.probe(method.name) {
+            errors.push(redefinition(
+                class,
+                method.line,
+                ErrorKind::MethodMultiplyDefined(method.name),
+                class,
+                original.line,
+            ));
+            continue;
+        }
+        declared.add(method.name, method);
+
+        let original = inherited(table, class)
+            .find_map(|ancestor| Some((ancestor, ancestor.method(method.name)?)));
+        if let Some((ancestor, original)) = original {
+            if let Some(kind) = override_error(method, original) {
+                errors.push(redefinition(
+                    class,
+                    method.line,
+                    kind,
+                    ancestor,
+                    original.line,
+                ));
+            }
+        }
+    }
+}
+
+/// How `method` fails to have the same signature as the method it overrides, if it does.
+fn override_error(method: &MethodInfo, original: &MethodInfo) -> Option<ErrorKind> {
+    if method.formals.len() != original.formals.len() {
+        return Some(ErrorKind::OverrideArgumentCount(method.name));
+    }
+
+    let formal_types = method.formals.iter().map(|formal| formal.type_decl);
+    let original_types = original.formals.iter().map(|formal| formal.type_decl);
+    if let Some((formal_type, original)) = formal_types
+        .zip(original_types)
+        .find(|(formal_type, original)| formal_type != original)
+    {
+        return Some(ErrorKind::OverrideFormalType {
+            method: method.name,
+            formal_type,
+            original,
+        });
+    }
+
+    if method.return_type != original.return_type {
+        return Some(ErrorKind::OverrideReturnType {
+            method: method.name,
+            return_type: method.return_type,
+            original: original.return_type,
+        });
+    }
+
+    None
+}
+
+fn check_formals(class: &ClassInfo, method: &MethodInfo, errors: &mut Vec<SemantError>) {
+    let mut declared: SymbolTable<usize> = SymbolTable::new();
+    declared.enter_scope();
+
+    for formal in &method.formals {
+        if formal.name == sym::SELF {
+            errors.push(SemantError::new(
+                class.file_name,
+                formal.line,
+                ErrorKind::FormalNamedSelf,
+            ));
+        } else if let Some(&original) = declared.probe(formal.name) {
+            errors.push(redefinition(
+                class,
+                formal.line,
+                ErrorKind::FormalMultiplyDefined(formal.name),
+                class,
+                original,
+            ));
+        } else {
+            declared.add(formal.name, formal.line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ast::Program;
+    use common::Symbol;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn check(input: &str) -> Vec<String> {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let program: Program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = ClassTable::new(&program).unwrap();
+
+        check_features(&table)
+            .iter()
+            .map(|error| match error.note() {
+                Some(note) => format!("{}\n{}", error, note),
+                None => error.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_overrides() {
+        let errors = check(
+            "class Main inherits A {\n\
+             \tmain() : Object { 0 };\n\
+             \tf(x : Int) : Int { x };\n\
+             \tg(x : Bool, y : Int) : Object { x };\n\
+             \th(x : Int) : Bool { true };\n\
+             \ta : Int;\n\
+             \tabort() : Int { 0 };\n\
+             };\n\
+             class A {\n\
+             \ta : Int;\n\
+             \tf(x : Int, y : Int) : Int { x };\n\
+             \tg(x : Int, y : Int) : Object { x };\n\
+             \th(x : Int) : Int { x };\n\
+             };\n",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                "test.cl:3: Incompatible number of formal parameters in redefined method f.\n\
+                 test.cl:11: note: the original declaration is here",
+                "test.cl:4: In redefined method g, parameter type Bool is different from original type Int.\n\
+                 test.cl:12: note: the original declaration is here",
+                "test.cl:5: In redefined method h, return type Bool is different from original return type Int.\n\
+                 test.cl:13: note: the original declaration is here",
+                "test.cl:6: Attribute a is an attribute of an inherited class.\n\
+                 test.cl:10: note: the original declaration is here",
+                "test.cl:7: In redefined method abort, return type Int is different from original return type Object.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let errors = check(
+            "class Main {\n\
+             \ta : Int;\n\
+             \ta : String;\n\
+             \tmain() : Object { 0 };\n\
+             \tf(x : Int,\n\
+             \t\tx : Int, self : Int) : Object { 0 };\n\
+             \tmain() : Object { 1 };\n\
+             };\n",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                "test.cl:3: Attribute a is multiply defined in class.\n\
+                 test.cl:2: note: the original declaration is here",
+                "test.cl:6: Formal parameter x is multiply defined.\n\
+                 test.cl:5: note: the original declaration is here",
+                "test.cl:6: 'self' cannot be the name of a formal parameter.",
+                "test.cl:7: Method main is multiply defined.\n\
+                 test.cl:4: note: the original declaration is here",
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use common::ast::Program;
+use common::{PassTimings, Symbol};
+use lexer::cool;
+use lexer::prelude::*;
+use parser::{ParseError, Parser};
+
+use crate::class_table::ClassTable;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+type Parsed = Result<Result<Program, Vec<ParseError>>, Error>;
+
+fn parse_file(lexer: &Lexer, path: &str, timings: &PassTimings) -> Parsed {
+    let bytes = timings
+        .time("read", || fs::read(path))
+        .map_err(|err| format!("{}: {}", path, err))?;
+    let source = timings.time("decode", || Source::from_bytes(&bytes));
+    let tokens = timings
+        .time("lex", || lexer.lex_source(&source))
+        .map_err(|err| format!("{}: {}", path, err))?;
+
+    Ok(timings.time("parse", || {
+        Parser::new(&tokens, Symbol::intern(path)).parse_program()
+    }))
+}
+
+/// Lex and parse `paths` using up to `jobs` threads.
+///
+/// The results are in the order the files were given, no matter which file finishes first. The
+/// threads get enough stack to parse expressions nested as deep as the parser allows.
+fn parse_files(lexer: &Lexer, paths: &[&str], jobs: usize, timings: &PassTimings) -> Vec<Parsed> {
+    let next = AtomicUsize::new(0);
+    let results: Vec<_> = paths.iter().map(|_| Mutex::new(None)).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(paths.len()) {
+            thread::Builder::new()
+                .stack_size(parser::STACK_SIZE)
+                .spawn_scoped(scope, || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= paths.len() {
+                        break;
+                    }
+
+                    let parsed = parse_file(lexer, paths[index], timings);
+                    *results[index].lock().unwrap() = Some(parsed);
+                })
+                .expect("Failed to spawn a parser thread");
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| {
+            result
+                .into_inner()
+                .unwrap()
+                .expect("Every file should have been parsed")
+        })
+        .collect()
+}
+
+/// How `check_files` processes a program and reports its errors.
+#[derive(Debug, Clone, Copy)]
+pub struct CheckOptions {
+    /// Lex and parse up to this many files concurrently.
+    pub jobs: usize,
+    /// Follow errors about a redefinition with a note pointing at the original declaration.
+    pub notes: bool,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            notes: false,
+        }
+    }
+}
+
+/// Lex, parse and type check the files in `paths` as a single program.
+///
+/// Errors in the program are printed the way the reference compiler prints them, `None` is
+/// returned if there were any. An `Err` is only returned if a file can't be read or lexed.
+///
+/// Files are lexed and parsed concurrently, errors are still printed in the order the files were
+/// given. The time spent in each pass is added to `timings`.
+pub fn check_files(
+    paths: &[&str],
+    options: &CheckOptions,
+    timings: &PassTimings,
+) -> Result<Option<(Program, ClassTable)>, Error> {
+    let lexer = timings.time("rules", || Lexer::new(cool::rules()));
+    let mut classes = vec![];
+    let mut failed = false;
+
+    for parsed in parse_files(&lexer, paths, options.jobs, timings) {
+        match parsed? {
+            Ok(program) => classes.extend(program.classes),
+            Err(errors) => {
+                failed = true;
+                for error in errors {
+                    eprintln!("{}", error);
+                }
+            }
+        }
+    }
+
+    if failed {
+        eprintln!("Compilation halted due to lex and parse errors");
+        return Ok(None);
+    }
+
+    let line = classes.first().map(|class| class.line).unwrap_or(0);
+    let mut program = Program { classes, line };
+    match timings.time("semant", || crate::check(&mut program)) {
+        Ok(table) => Ok(Some((program, table))),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+                if let Some(note) = error.note().filter(|_| options.notes) {
+                    eprintln!("{}", note);
+                }
+            }
+            eprintln!("Compilation halted due to static semantic errors.");
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,39 @@
+mod class_table;
+mod error;
+mod features;
+mod files;
+mod typecheck;
+
+use common::ast::Program;
+use common::visit::VisitorMut;
+
+pub use crate::class_table::{basic_classes, AttributeInfo, ClassInfo, ClassTable, MethodInfo};
+pub use crate::error::{ErrorKind, Location, SemantError};
+use crate::features::check_features;
+pub use crate::files::{check_files, CheckOptions};
+use crate::typecheck::TypeChecker;
+
+/// Check the static semantics of `program` and annotate each of its expressions with its type.
+///
+/// Errors in the class hierarchy stop the analysis before any expression is checked, like in the
+/// reference compiler.
+pub fn check(program: &mut Program) -> Result<ClassTable, Vec<SemantError>> {
+    let table = ClassTable::new(program)?;
+    let mut errors = check_features(&table);
+
+    let mut checker = TypeChecker::new(&table);
+    checker.visit_program_mut(program);
+
+    errors.extend(checker.into_errors());
+    if errors.is_empty() {
+        Ok(table)
+    } else {
+        Err(errors)
+    }
+}
+
+pub mod prelude {
+    pub use crate::check;
+    pub use crate::class_table::{ClassInfo, ClassTable};
+    pub use crate::error::{ErrorKind, SemantError};
+}
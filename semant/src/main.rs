@@ -0,0 +1,69 @@
+use clap::{crate_authors, crate_version, App, Arg};
+
+use std::process;
+
+use common::dump::Dump;
+use common::PassTimings;
+use semant::CheckOptions;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+fn main() -> Result<(), Error> {
+    let matches = App::new("semant")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Type checks COOL programs and prints the annotated AST")
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .value_name("N")
+                .takes_value(true)
+                .help("Lex and parse up to N files concurrently"),
+        )
+        .arg(
+            Arg::with_name("notes")
+                .long("notes")
+                .help("Point at the original declaration after errors about redefinitions"),
+        )
+        .arg(
+            Arg::with_name("time-passes")
+                .long("time-passes")
+                .help("Report the time spent in each pass on stderr"),
+        )
+        .arg(
+            Arg::with_name("FILES")
+                .multiple(true)
+                .index(1)
+                .required(true),
+        )
+        .get_matches();
+
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => jobs
+            .parse::<usize>()
+            .map_err(|err| format!("Invalid number of jobs {:?}: {}", jobs, err))?
+            .max(1),
+        None => 1,
+    };
+    let paths: Vec<_> = matches.values_of("FILES").unwrap().collect();
+
+    let timings = PassTimings::default();
+    let options = CheckOptions {
+        jobs,
+        notes: matches.is_present("notes"),
+    };
+    let checked = semant::check_files(&paths, &options, &timings)?;
+
+    if matches.is_present("time-passes") {
+        eprintln!("{}", timings);
+    }
+    let (program, _) = match checked {
+        Some(checked) => checked,
+        None => process::exit(1),
+    };
+
+    print!("{}", Dump(&program));
+
+    Ok(())
+}
@@ -0,0 +1,638 @@
+use common::ast::*;
+use common::visit::VisitorMut;
+use common::{sym, Symbol, SymbolTable};
+
+use crate::class_table::ClassTable;
+use crate::error::{ErrorKind, SemantError};
+
+/// Infers the type of every expression in a program and checks them against the typing rules.
+pub(crate) struct TypeChecker<'t> {
+    table: &'t ClassTable,
+    /// The class being checked.
+    class: Symbol,
+    file_name: Symbol,
+    /// The types of the identifiers in scope.
+    objects: SymbolTable<Symbol>,
+    errors: Vec<SemantError>,
+}
+
+impl<'t> TypeChecker<'t> {
+    pub(crate) fn new(table: &'t ClassTable) -> Self {
+        Self {
+            table,
+            class: sym::OBJECT,
+            file_name: sym::OBJECT,
+            objects: SymbolTable::new(),
+            errors: vec![],
+        }
+    }
+
+    pub(crate) fn into_errors(self) -> Vec<SemantError> {
+        self.errors
+    }
+
+    fn error(&mut self, line: usize, kind: ErrorKind) {
+        self.errors
+            .push(SemantError::new(self.file_name, line, kind));
+    }
+
+    /// Whether `ty` names a class, or is `SELF_TYPE`.
+    fn is_defined(&self, ty: Symbol) -> bool {
+        ty == sym::SELF_TYPE || self.table.contains(ty)
+    }
+
+    fn conforms(&self, sub: Symbol, sup: Symbol) -> bool {
+        self.table.conforms(sub, sup, self.class)
+    }
+
+    fn join(&self, a: Symbol, b: Symbol) -> Symbol {
+        self.table.join(a, b, self.class)
+    }
+
+    /// Check `expr` and return its type.
+    fn check(&mut self, expr: &mut Expr) -> Symbol {
+        self.visit_expr_mut(expr);
+
+        expr.ty.unwrap_or(sym::OBJECT)
+    }
+
+    fn check_args(&mut self, args: &mut [Expr]) -> Vec<Symbol> {
+        args.iter_mut().map(|arg| self.check(arg)).collect()
+    }
+
+    /// Check the arguments of a call of `method` in `class` and return the type of the result,
+    /// given that the type of the receiver is `receiver`.
+    fn check_call(
+        &mut self,
+        line: usize,
+        class: Symbol,
+        method: Symbol,
+        receiver: Symbol,
+        arg_types: &[Symbol],
+        undefined: ErrorKind,
+    ) -> Symbol {
+        let info = match self.table.method(class, method) {
+            Some(info) => info,
+            None => {
+                self.error(line, undefined);
+                return sym::OBJECT;
+            }
+        };
+
+        if info.formals.len() != arg_types.len() {
+            self.error(line, ErrorKind::WrongArgumentCount(method));
+        } else {
+            for (formal, &inferred) in info.formals.iter().zip(arg_types) {
+                if !self.conforms(inferred, formal.type_decl) {
+                    self.error(
+                        line,
+                        ErrorKind::ArgumentType {
+                            method,
+                            inferred,
+                            formal: formal.name,
+                            declared: formal.type_decl,
+                        },
+                    );
+                }
+            }
+        }
+
+        if info.return_type == sym::SELF_TYPE {
+            receiver
+        } else {
+            info.return_type
+        }
+    }
+
+    fn check_expr(&mut self, expr: &mut Expr) -> Option<Symbol> {
+        let line = expr.line;
+
+        let ty = match &mut expr.kind {
+            ExprKind::Assign(assign) => {
+                let inferred = self.check(&mut assign.expr);
+
+                if assign.name == sym::SELF {
+                    self.error(line, ErrorKind::AssignToSelf);
+                } else {
+                    match self.objects.lookup(assign.name).copied() {
+                        None => self.error(line, ErrorKind::AssignToUndeclared(assign.name)),
+                        Some(declared) if !self.conforms(inferred, declared) => self.error(
+                            line,
+                            ErrorKind::AssignType {
+                                name: assign.name,
+                                inferred,
+                                declared,
+                            },
+                        ),
+                        Some(_) => {}
+                    }
+                }
+
+                inferred
+            }
+            ExprKind::StaticDispatch(dispatch) => {
+                let receiver = self.check(&mut dispatch.expr);
+                let arg_types = self.check_args(&mut dispatch.args);
+
+                if dispatch.type_name == sym::SELF_TYPE {
+                    self.error(line, ErrorKind::StaticDispatchToSelfType);
+                    sym::OBJECT
+                } else if !self.table.contains(dispatch.type_name) {
+                    self.error(
+                        line,
+                        ErrorKind::StaticDispatchUndefinedClass(dispatch.type_name),
+                    );
+                    sym::OBJECT
+                } else {
+                    if !self.conforms(receiver, dispatch.type_name) {
+                        self.error(
+                            line,
+                            ErrorKind::StaticDispatchType {
+                                inferred: receiver,
+                                declared: dispatch.type_name,
+                            },
+                        );
+                    }
+
+                    self.check_call(
+                        line,
+                        dispatch.type_name,
+                        dispatch.name,
+                        receiver,
+                        &arg_types,
+                        ErrorKind::StaticDispatchUndefinedMethod(dispatch.name),
+                    )
+                }
+            }
+            ExprKind::Dispatch(dispatch) => {
+                let receiver = self.check(&mut dispatch.expr);
+                let arg_types = self.check_args(&mut dispatch.args);
+                let class = if receiver == sym::SELF_TYPE {
+                    self.class
+                } else {
+                    receiver
+                };
+
+                if self.table.contains(class) {
+                    self.check_call(
+                        line,
+                        class,
+                        dispatch.name,
+                        receiver,
+                        &arg_types,
+                        ErrorKind::UndefinedMethod(dispatch.name),
+                    )
+                } else {
+                    self.error(line, ErrorKind::DispatchOnUndefinedClass(class));
+                    sym::OBJECT
+                }
+            }
+            ExprKind::Cond(cond) => {
+                if self.check(&mut cond.pred) != sym::BOOL {
+                    self.error(line, ErrorKind::IfPredicate);
+                }
+                let then_type = self.check(&mut cond.then_expr);
+                let else_type = self.check(&mut cond.else_expr);
+
+                self.join(then_type, else_type)
+            }
+            ExprKind::Loop(loop_) => {
+                if self.check(&mut loop_.pred) != sym::BOOL {
+                    self.error(line, ErrorKind::LoopPredicate);
+                }
+                self.check(&mut loop_.body);
+
+                sym::OBJECT
+            }
+            ExprKind::Case(case) => {
+                self.check(&mut case.expr);
+
+                let mut seen = vec![];
+                let mut ty = None;
+                for branch in &mut case.branches {
+                    if branch.name == sym::SELF {
+                        self.error(branch.line, ErrorKind::CaseBindsSelf);
+                    }
+                    if branch.type_decl == sym::SELF_TYPE {
+                        self.error(branch.line, ErrorKind::CaseSelfType(branch.name));
+                    } else if !self.table.contains(branch.type_decl) {
+                        self.error(branch.line, ErrorKind::UndefinedCaseType(branch.type_decl));
+                    }
+                    if seen.contains(&branch.type_decl) {
+                        self.error(
+                            branch.line,
+                            ErrorKind::DuplicateCaseBranch(branch.type_decl),
+                        );
+                    }
+                    seen.push(branch.type_decl);
+
+                    self.objects.enter_scope();
+                    self.objects.add(branch.name, branch.type_decl);
+                    let branch_type = self.check(&mut branch.expr);
+                    self.objects.exit_scope();
+
+                    ty = Some(match ty {
+                        Some(ty) => self.join(ty, branch_type),
+                        None => branch_type,
+                    });
+                }
+
+                ty.unwrap_or(sym::OBJECT)
+            }
+            ExprKind::Block(block) => {
+                let mut ty = sym::OBJECT;
+                for expr in &mut block.body {
+                    ty = self.check(expr);
+                }
+
+                ty
+            }
+            ExprKind::Let(let_) => {
+                if let_.name == sym::SELF {
+                    self.error(line, ErrorKind::LetBindsSelf);
+                }
+                if !self.is_defined(let_.type_decl) {
+                    self.error(
+                        line,
+                        ErrorKind::UndefinedLetType {
+                            name: let_.name,
+                            type_decl: let_.type_decl,
+                        },
+                    );
+                }
+
+                if !let_.init.is_no_expr() {
+                    let inferred = self.check(&mut let_.init);
+                    if !self.conforms(inferred, let_.type_decl) {
+                        self.error(
+                            line,
+                            ErrorKind::LetInitType {
+                                name: let_.name,
+                                inferred,
+                                declared: let_.type_decl,
+                            },
+                        );
+                    }
+                }
+
+                self.objects.enter_scope();
+                self.objects.add(let_.name, let_.type_decl);
+                let ty = self.check(&mut let_.body);
+                self.objects.exit_scope();
+
+                ty
+            }
+            ExprKind::Binary(binary) => {
+                let lhs = self.check(&mut binary.lhs);
+                let rhs = self.check(&mut binary.rhs);
+
+                let ty = match binary.op {
+                    BinaryOp::Plus | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Divide => sym::INT,
+                    BinaryOp::Lt | BinaryOp::Leq => sym::BOOL,
+                    BinaryOp::Eq => {
+                        let basic = [sym::INT, sym::STRING, sym::BOOL];
+                        if (basic.contains(&lhs) || basic.contains(&rhs)) && lhs != rhs {
+                            self.error(line, ErrorKind::IllegalComparison);
+                        }
+
+                        return Some(sym::BOOL);
+                    }
+                };
+
+                if lhs != sym::INT || rhs != sym::INT {
+                    self.error(
+                        line,
+                        ErrorKind::NonIntArguments {
+                            op: binary.op,
+                            lhs,
+                            rhs,
+                        },
+                    );
+                }
+
+                ty
+            }
+            ExprKind::Unary(unary) => {
+                let operand = self.check(&mut unary.expr);
+
+                match unary.op {
+                    UnaryOp::Neg => {
+                        if operand != sym::INT {
+                            self.error(line, ErrorKind::NegArgument(operand));
+                        }
+                        sym::INT
+                    }
+                    UnaryOp::Comp => {
+                        if operand != sym::BOOL {
+                            self.error(line, ErrorKind::NotArgument(operand));
+                        }
+                        sym::BOOL
+                    }
+                    UnaryOp::IsVoid => sym::BOOL,
+                }
+            }
+            ExprKind::IntConst(_) => sym::INT,
+            ExprKind::BoolConst(_) => sym::BOOL,
+            ExprKind::StringConst(_) => sym::STRING,
+            ExprKind::New(type_name) => {
+                if self.is_defined(*type_name) {
+                    *type_name
+                } else {
+                    self.error(line, ErrorKind::UndefinedNewType(*type_name));
+                    sym::OBJECT
+                }
+            }
+            ExprKind::NoExpr => return None,
+            ExprKind::Object(name) if *name == sym::SELF => sym::SELF_TYPE,
+            ExprKind::Object(name) => match self.objects.lookup(*name).copied() {
+                Some(ty) => ty,
+                None => {
+                    self.error(line, ErrorKind::UndeclaredIdentifier(*name));
+                    sym::OBJECT
+                }
+            },
+        };
+
+        Some(ty)
+    }
+}
+
+impl VisitorMut for TypeChecker<'_> {
+    fn visit_class_mut(&mut self, class: &mut Class) {
+        self.class = class.name;
+        self.file_name = class.file_name;
+
+        // Attributes, including inherited ones, are in scope in every feature
+        self.objects.enter_scope();
+        let ancestors: Vec<_> = self.table.ancestors(class.name).collect();
+        for info in ancestors.into_iter().rev() {
+            for attribute in &info.attributes {
+                self.objects.add(attribute.name, attribute.type_decl);
+            }
+        }
+
+        for feature in &mut class.features {
+            self.visit_feature_mut(feature);
+        }
+
+        if class.name == sym::MAIN {
+            match self.table.method(sym::MAIN, sym::MAIN_METHOD) {
+                None => self.error(class.line, ErrorKind::NoMainMethod),
+                Some(main) if !main.formals.is_empty() => {
+                    self.error(main.line, ErrorKind::MainMethodArguments)
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.objects.exit_scope();
+    }
+
+    fn visit_attribute_mut(&mut self, attribute: &mut Attribute) {
+        if attribute.name == sym::SELF {
+            self.error(attribute.line, ErrorKind::AttributeNamedSelf);
+        }
+
+        if !self.is_defined(attribute.type_decl) {
+            self.error(
+                attribute.line,
+                ErrorKind::UndefinedAttributeType {
+                    attribute: attribute.name,
+                    type_decl: attribute.type_decl,
+                },
+            );
+        }
+
+        if !attribute.init.is_no_expr() {
+            let inferred = self.check(&mut attribute.init);
+
+            if !self.conforms(inferred, attribute.type_decl) {
+                self.error(
+                    attribute.line,
+                    ErrorKind::AttributeInitType {
+                        attribute: attribute.name,
+                        inferred,
+                        declared: attribute.type_decl,
+                    },
+                );
+            }
+        }
+    }
+
+    fn visit_method_mut(&mut self, method: &mut Method) {
+        self.objects.enter_scope();
+        for formal in &method.formals {
+            if formal.type_decl == sym::SELF_TYPE {
+                self.error(formal.line, ErrorKind::FormalSelfType(formal.name));
+            } else if !self.table.contains(formal.type_decl) {
+                self.error(
+                    formal.line,
+                    ErrorKind::UndefinedFormalType {
+                        formal: formal.name,
+                        type_decl: formal.type_decl,
+                    },
+                );
+            }
+            self.objects.add(formal.name, formal.type_decl);
+        }
+
+        let inferred = self.check(&mut method.body);
+        self.objects.exit_scope();
+
+        if !self.is_defined(method.return_type) {
+            self.error(
+                method.line,
+                ErrorKind::UndefinedReturnType {
+                    method: method.name,
+                    return_type: method.return_type,
+                },
+            );
+        } else if !self.conforms(inferred, method.return_type) {
+            self.error(
+                method.line,
+                ErrorKind::ReturnType {
+                    method: method.name,
+                    inferred,
+                    declared: method.return_type,
+                },
+            );
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        expr.ty = self.check_expr(expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::visit::{self, Folder, Visitor};
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn check(input: &str) -> Result<Program, Vec<String>> {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+
+        crate::check(&mut program)
+            .map(|_| program)
+            .map_err(|errors| errors.iter().map(|error| error.kind.to_string()).collect())
+    }
+
+    /// The type of the body of the first method of the first class.
+    fn body_type(program: &Program) -> Symbol {
+        program.classes[0]
+            .features
+            .iter()
+            .find_map(|feature| match feature {
+                Feature::Method(method) => method.body.ty,
+                Feature::Attribute(_) => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_self_type() {
+        let program = check(
+            "class A inherits IO {\n\
+             \tf() : SELF_TYPE { out_string(\"a\").out_int(1) };\n\
+             \tg() : A { let a : A <- new SELF_TYPE in a.copy() };\n\
+             };\n\
+             class B inherits A { h() : Object { f() }; };\n\
+             class Main { main() : Object { 0 }; };\n",
+        )
+        .unwrap();
+
+        assert_eq!(body_type(&program), sym::SELF_TYPE);
+        match &program.classes[0].features[1] {
+            Feature::Method(method) => assert_eq!(method.body.ty, Some(Symbol::intern("A"))),
+            other => panic!("Expected a method, got {:?}", other),
+        }
+        match &program.classes[1].features[0] {
+            Feature::Method(method) => assert_eq!(method.body.ty, Some(sym::SELF_TYPE)),
+            other => panic!("Expected a method, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fold_keeps_types() {
+        struct Identity;
+        impl Folder for Identity {}
+
+        struct Untyped(usize);
+        impl Visitor for Untyped {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if expr.ty.is_none() {
+                    self.0 += 1;
+                }
+                visit::walk_expr(self, expr);
+            }
+        }
+
+        let program = check(
+            "class Main inherits IO {\n\
+             \tx : Int <- 1;\n\
+             \tmain() : Object {{\n\
+             \t\tx <- x + 2;\n\
+             \t\tlet s : String <- \"a\" in out_string(s.concat(\"b\"));\n\
+             \t\tif not isvoid self then self@IO.out_int(~x) else abort() fi;\n\
+             \t\twhile x < 10 loop x <- x * 2 pool;\n\
+             \t\tcase x of i : Int => i; o : Object => new Main; esac;\n\
+             \t}};\n\
+             };\n",
+        )
+        .unwrap();
+
+        let folded = Identity.fold_program(program.clone());
+        let mut untyped = Untyped(0);
+        untyped.visit_program(&folded);
+        assert_eq!(untyped.0, 0);
+        assert_eq!(folded, program);
+    }
+
+    #[test]
+    fn test_join() {
+        let program = check(
+            "class A {\n\
+             \tf(x : Int) : Object {\n\
+             \t\tcase x of i : Int => new B; s : String => new C; o : Object => new A; esac\n\
+             \t};\n\
+             \tg() : A { if true then new B else new C fi };\n\
+             };\n\
+             class B inherits A { };\n\
+             class C inherits B { };\n\
+             class Main { main() : Object { 0 }; };\n",
+        )
+        .unwrap();
+
+        assert_eq!(body_type(&program), Symbol::intern("A"));
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let errors = check(
+            "class Main {\n\
+             \ta : Int <- \"no\";\n\
+             \tmain() : Int {\n\
+             \t\t{\n\
+             \t\t\tself <- 1;\n\
+             \t\t\tb <- 2;\n\
+             \t\t\ta <- true;\n\
+             \t\t\tc;\n\
+             \t\t\tif 1 then 2 else 3 fi;\n\
+             \t\t\twhile a loop a pool;\n\
+             \t\t\t1 + \"two\";\n\
+             \t\t\t1 = \"one\";\n\
+             \t\t\t~true;\n\
+             \t\t\tnot 1;\n\
+             \t\t\tnew Missing;\n\
+             \t\t\tf(1, 2);\n\
+             \t\t\tmain(1);\n\
+             \t\t\tlength();\n\
+             \t\t\tself@Missing.main();\n\
+             \t\t\tself@String.length();\n\
+             \t\t\tlet x : Missing, self : Int <- \"s\" in x;\n\
+             \t\t\tcase a of x : Int => 1; y : Int => 2; self : SELF_TYPE => 3; esac;\n\
+             \t\t\t\"last\";\n\
+             \t\t}\n\
+             \t};\n\
+             \tf(x : Int, y : Bool) : Object { self };\n\
+             };\n",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                "Inferred type String of initialization of attribute a does not conform to declared type Int.",
+                "Cannot assign to 'self'.",
+                "Assignment to undeclared variable b.",
+                "Type Bool of assigned expression does not conform to declared type Int of identifier a.",
+                "Undeclared identifier c.",
+                "Predicate of 'if' does not have type Bool.",
+                "Loop condition does not have type Bool.",
+                "non-Int arguments: Int + String",
+                "Illegal comparison with a basic type.",
+                "Argument of '~' has type Bool instead of Int.",
+                "Argument of 'not' has type Int instead of Bool.",
+                "'new' used with undefined class Missing.",
+                "In call of method f, type Int of parameter y does not conform to declared type Bool.",
+                "Method main called with wrong number of arguments.",
+                "Dispatch to undefined method length.",
+                "Static dispatch to undefined class Missing.",
+                "Expression type SELF_TYPE does not conform to declared static dispatch type String.",
+                "Class Missing of let-bound identifier x is undefined.",
+                "'self' cannot be bound in a 'let' expression.",
+                "Inferred type String of initialization of self does not conform to identifier's declared type Int.",
+                "Duplicate branch Int in case statement.",
+                "'self' bound in 'case'.",
+                "Identifier self declared with type SELF_TYPE in case branch.",
+                "Inferred return type String of method main does not conform to declared return type Int.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Runs `semant` on every program in `tests/programs` and compares what it prints with the
+//! expected output next to the program, `<name>.out`. That's the annotated AST for correct
+//! programs and the error messages, on standard error, for incorrect ones.
+//!
+//! Set `BLESS=1` to write the current output as the expected output instead.
+//!
+//! The reference `coolc` isn't available to the test suite, so the expected outputs were written
+//! with `BLESS=1` rather than by the reference compiler. The annotated ASTs (`small.out`,
+//! `dispatch.out`) were checked by hand against the reference `-semant` format: the node names,
+//! the two space indentation, the `#line` before every node and the `: Type` line after every
+//! expression.
+//!
+//! The expected errors were written the same way. Each message was compared by hand with the
+//! wording of the reference semantic analyzer, including the `file:line: ` prefix and the final
+//! `Compilation halted due to static semantic errors.` line. Programs with more than one error
+//! list them in the order this implementation's passes find them, which can differ from the
+//! reference.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn run(directory: &Path, path: &Path) -> String {
+    // Run from the programs' directory so the file names in errors don't depend on where the
+    // repository is checked out
+    let output = Command::new(env!("CARGO_BIN_EXE_semant"))
+        .arg(path.file_name().unwrap())
+        .current_dir(directory)
+        .output()
+        .unwrap();
+
+    let mut printed = String::from_utf8(output.stdout).unwrap();
+    printed.push_str(&String::from_utf8(output.stderr).unwrap());
+
+    printed
+}
+
+#[test]
+fn test_programs() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut paths: Vec<_> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cl"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let mut failed = vec![];
+    for path in paths {
+        let output = run(&directory, &path);
+        let expected_path = path.with_extension("out");
+
+        if bless {
+            fs::write(&expected_path, &output).unwrap();
+        } else if fs::read_to_string(&expected_path).ok().as_deref() != Some(output.as_str()) {
+            failed.push(path.display().to_string());
+        }
+    }
+
+    assert!(failed.is_empty(), "Unexpected output for {:?}", failed);
+}
+
+#[test]
+fn test_jobs_keep_the_order_of_files() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths: Vec<_> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| Path::new(name).extension().is_some_and(|e| e == "cl"))
+        .collect();
+    paths.sort();
+
+    let output = |jobs: &str| {
+        Command::new(env!("CARGO_BIN_EXE_semant"))
+            .args(["-j", jobs])
+            .args(&paths)
+            .current_dir(&directory)
+            .output()
+            .unwrap()
+    };
+
+    assert_eq!(output("1"), output("4"));
+}
+
+#[test]
+fn test_notes_follow_their_error() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let output = Command::new(env!("CARGO_BIN_EXE_semant"))
+        .args(["--notes", "overrides.cl"])
+        .current_dir(&directory)
+        .output()
+        .unwrap();
+    let errors = String::from_utf8(output.stderr).unwrap();
+
+    assert!(errors.starts_with(
+        "overrides.cl:3: Attribute sides is an attribute of an inherited class.\n\
+         overrides.cl:10: note: the original declaration is here\n"
+    ));
+    // Without the notes the output is the same as the reference semantic analyzer's
+    let expected = fs::read_to_string(directory.join("overrides.out")).unwrap();
+    let without_notes: String = errors
+        .lines()
+        .filter(|line| !line.ends_with("note: the original declaration is here"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    assert_eq!(without_notes, expected);
+}
+
+#[test]
+fn test_deeply_nested_expressions() {
+    // Nested one less than the parser allows, which must parse on the worker threads too
+    let depth = parser::MAX_DEPTH - 1;
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let path = directory.join("nested.cl");
+    fs::write(
+        &path,
+        format!(
+            "class Main {{ main() : Object {{ {}1{} }}; }};\n",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        ),
+    )
+    .unwrap();
+
+    for args in &[&[][..], &["-j", "4"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_semant"))
+            .args(*args)
+            .arg(&path)
+            .output()
+            .unwrap();
+
+        assert!(
+            output.status.success(),
+            "semant {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8(output.stdout).unwrap().contains("_int\n"));
+    }
+}
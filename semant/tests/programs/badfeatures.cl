@@ -0,0 +1,8 @@
+class Main inherits IO {
+  self : Int;
+  a : Int <- "forty-two";
+  b : Missing;
+  f(x : SELF_TYPE, y : Missing) : Int { 1 };
+  g() : Missing { 1 };
+  h() : Bool { 1 };
+};
@@ -0,0 +1,5 @@
+class Main {
+  main(argc : Int) : Object { argc };
+};
+
+class A inherits Main { };
@@ -0,0 +1,7 @@
+class Main {
+  main() : Object { 0 };
+};
+
+class Foo inherits Bar { };
+
+class Bar inherits Foo { };
@@ -0,0 +1,28 @@
+class Main inherits IO {
+  x : Int <- 42;
+  s : String <- "hi\n";
+  a : A <- new B;
+  main() : Object {
+    {
+      out_string(s.concat("!")).out_int(x + 1);
+      let y : Int <- x * 2, z : Bool in
+        if y < 3 then a else new SELF_TYPE fi;
+      case a of
+        b : B => b.f(1);
+        o : Object => o;
+      esac;
+      while not isvoid a loop a <- a.g() pool;
+      a@A.f(~x);
+      self;
+    }
+  };
+};
+
+class A {
+  f(n : Int) : SELF_TYPE { self };
+  g() : A { if true = false then self else new A fi };
+};
+
+class B inherits A {
+  h : Bool <- f(3).g().f(4) = self;
+};
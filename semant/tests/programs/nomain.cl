@@ -0,0 +1,3 @@
+class A inherits IO {
+  f() : Object { out_string("no Main here\n") };
+};
@@ -0,0 +1,3 @@
+class Main {
+  mian() : Object { 0 };
+};
@@ -0,0 +1,13 @@
+class Main inherits Shape {
+  main() : Object { area() };
+  sides : Int <- 4;
+  area() : String { "big" };
+  scale(by : Int, self : Int) : Main { self };
+  scale(by : Int) : Main { self };
+};
+
+class Shape {
+  sides : Int;
+  area() : Int { 0 };
+  scale(by : Int, by : Int) : SELF_TYPE { self };
+};
@@ -0,0 +1,10 @@
+class Main inherits IO {
+  b : Bool <- not isvoid self;
+  main() : SELF_TYPE {
+    case 1 of
+      i : Int => out_int(~i);
+      o : Object => out_string("\t");
+    esac
+  };
+};
+class A { f() : Object { while false loop self@Object.copy() pool }; };
@@ -0,0 +1,302 @@
+//! The bytecode format run by the VM.
+//!
+//! A [`Module`] is a whole compiled program: a constant pool, a table of classes with their
+//! object layouts and vtables, and the methods the vtables point at. Methods are either compiled
+//! code for a stack machine or one of the built-in methods of the basic classes.
+
+use std::fmt;
+
+use common::Symbol;
+
+/// The index of a class in [`Module::classes`].
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct ClassId(pub u32);
+
+/// The index of a method in [`Module::methods`].
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub struct MethodId(pub u32);
+
+/// The basic classes are always the first classes of a module, in this order.
+impl ClassId {
+    pub const OBJECT: Self = Self(0);
+    pub const IO: Self = Self(1);
+    pub const INT: Self = Self(2);
+    pub const STRING: Self = Self(3);
+    pub const BOOL: Self = Self(4);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i32),
+    String(String),
+}
+
+/// A stack machine instruction.
+///
+/// Instructions pop their operands off the operand stack and push their result, if they have one.
+/// Jump targets are indices into the instructions of the current method.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Instruction {
+    /// Push the constant at this index of the constant pool.
+    Constant(u32),
+    True,
+    False,
+    Void,
+    Pop,
+    LoadSelf,
+    LoadLocal(u32),
+    /// Store the value on top of the stack in a local, leaving it on the stack.
+    StoreLocal(u32),
+    LoadAttribute(u32),
+    /// Store the value on top of the stack in an attribute of `self`, leaving it on the stack.
+    StoreAttribute(u32),
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Le,
+    Eq,
+    Neg,
+    Not,
+    IsVoid,
+
+    Jump(u32),
+    JumpIfFalse(u32),
+
+    /// Create an object of the class and run its initializer.
+    New(ClassId),
+    /// Create an object of the class of `self`.
+    NewSelfType,
+    /// Call the method in this slot of the receiver's vtable. The receiver is on top of the
+    /// stack, above the arguments.
+    Dispatch {
+        slot: u32,
+        args: u32,
+    },
+    /// Call a method without looking it up in the receiver's vtable.
+    StaticDispatch {
+        method: MethodId,
+        args: u32,
+    },
+    /// Jump to the branch of the case table at this index that matches the class of the value on
+    /// top of the stack.
+    Case(u32),
+    Return,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Constant(index) => write!(f, "const {}", index),
+            Self::True => write!(f, "true"),
+            Self::False => write!(f, "false"),
+            Self::Void => write!(f, "void"),
+            Self::Pop => write!(f, "pop"),
+            Self::LoadSelf => write!(f, "load_self"),
+            Self::LoadLocal(index) => write!(f, "load_local {}", index),
+            Self::StoreLocal(index) => write!(f, "store_local {}", index),
+            Self::LoadAttribute(index) => write!(f, "load_attr {}", index),
+            Self::StoreAttribute(index) => write!(f, "store_attr {}", index),
+            Self::Add => write!(f, "add"),
+            Self::Sub => write!(f, "sub"),
+            Self::Mul => write!(f, "mul"),
+            Self::Div => write!(f, "div"),
+            Self::Lt => write!(f, "lt"),
+            Self::Le => write!(f, "le"),
+            Self::Eq => write!(f, "eq"),
+            Self::Neg => write!(f, "neg"),
+            Self::Not => write!(f, "not"),
+            Self::IsVoid => write!(f, "isvoid"),
+            Self::Jump(target) => write!(f, "jump {}", target),
+            Self::JumpIfFalse(target) => write!(f, "jump_if_false {}", target),
+            Self::New(class) => write!(f, "new {}", class.0),
+            Self::NewSelfType => write!(f, "new_self_type"),
+            Self::Dispatch { slot, args } => write!(f, "dispatch {} {}", slot, args),
+            Self::StaticDispatch { method, args } => {
+                write!(f, "static_dispatch {} {}", method.0, args)
+            }
+            Self::Case(index) => write!(f, "case {}", index),
+            Self::Return => write!(f, "return"),
+        }
+    }
+}
+
+/// The methods of the basic classes, implemented by the VM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Builtin {
+    Abort,
+    TypeName,
+    Copy,
+    OutString,
+    OutInt,
+    InString,
+    InInt,
+    Length,
+    Concat,
+    Substr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    /// The number of locals besides the arguments, for `let` and `case` bindings.
+    pub locals: u32,
+    pub instructions: Vec<Instruction>,
+    /// The source line of each instruction.
+    pub lines: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MethodBody {
+    Builtin(Builtin),
+    Code(Code),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: Symbol,
+    /// The class that defines the method.
+    pub class: ClassId,
+    pub args: u32,
+    pub body: MethodBody,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: Symbol,
+    pub type_decl: Symbol,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    pub name: Symbol,
+    pub parent: Option<ClassId>,
+    pub file_name: Symbol,
+    /// The attributes of objects of the class, inherited attributes first.
+    pub attributes: Vec<Attribute>,
+    /// The methods of the class, inherited methods first. An overriding method takes the slot of
+    /// the method it overrides.
+    pub vtable: Vec<MethodId>,
+    /// The method that runs the attribute initializers of the class and its ancestors.
+    pub init: MethodId,
+}
+
+/// The branches of a `case`, and where their code starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseTable {
+    pub branches: Vec<(ClassId, u32)>,
+}
+
+/// A compiled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub constants: Vec<Constant>,
+    pub classes: Vec<Class>,
+    pub methods: Vec<Method>,
+    pub case_tables: Vec<CaseTable>,
+    /// The class `Main`, whose `main` method the program starts in.
+    pub main: ClassId,
+}
+
+impl Module {
+    pub fn class(&self, id: ClassId) -> &Class {
+        &self.classes[id.0 as usize]
+    }
+
+    pub fn method(&self, id: MethodId) -> &Method {
+        &self.methods[id.0 as usize]
+    }
+
+    /// The qualified name of a method, e.g. `Main.main`.
+    fn method_name(&self, id: MethodId) -> String {
+        let method = self.method(id);
+
+        format!("{}.{}", self.class(method.class).name, method.name)
+    }
+
+    /// A comment describing the operand of `instruction`, if it has one worth describing.
+    fn describe(&self, instruction: Instruction) -> Option<String> {
+        match instruction {
+            Instruction::Constant(index) => match &self.constants[index as usize] {
+                Constant::Int(value) => Some(value.to_string()),
+                Constant::String(value) => Some(format!("{:?}", value)),
+            },
+            Instruction::New(class) => Some(self.class(class).name.to_string()),
+            Instruction::StaticDispatch { method, .. } => Some(self.method_name(method)),
+            _ => None,
+        }
+    }
+}
+
+/// Disassembles the module.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "constants:")?;
+        for (index, constant) in self.constants.iter().enumerate() {
+            match constant {
+                Constant::Int(value) => writeln!(f, "  {:>4}: int {}", index, value)?,
+                Constant::String(value) => writeln!(f, "  {:>4}: string {:?}", index, value)?,
+            }
+        }
+
+        for (index, class) in self.classes.iter().enumerate() {
+            writeln!(f)?;
+            write!(f, "class {} {}", index, class.name)?;
+            if let Some(parent) = class.parent {
+                write!(f, " inherits {}", self.class(parent).name)?;
+            }
+            writeln!(f)?;
+
+            for (index, attribute) in class.attributes.iter().enumerate() {
+                writeln!(
+                    f,
+                    "  attr {:>4}: {} : {}",
+                    index, attribute.name, attribute.type_decl
+                )?;
+            }
+            for (slot, &method) in class.vtable.iter().enumerate() {
+                writeln!(f, "  slot {:>4}: {}", slot, self.method_name(method))?;
+            }
+        }
+
+        for (index, method) in self.methods.iter().enumerate() {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "method {} {} ({} args)",
+                index,
+                self.method_name(MethodId(index as u32)),
+                method.args
+            )?;
+
+            match &method.body {
+                MethodBody::Builtin(builtin) => writeln!(f, "  builtin {:?}", builtin)?,
+                MethodBody::Code(code) => {
+                    writeln!(f, "  locals {}", code.locals)?;
+                    for (pc, (&instruction, line)) in
+                        code.instructions.iter().zip(&code.lines).enumerate()
+                    {
+                        let text = instruction.to_string();
+                        match self.describe(instruction) {
+                            Some(comment) => {
+                                writeln!(f, "  {:>5} {:>5}  {:<24} ; {}", pc, line, text, comment)?
+                            }
+                            None => writeln!(f, "  {:>5} {:>5}  {}", pc, line, text)?,
+                        }
+                    }
+                }
+            }
+        }
+
+        for (index, table) in self.case_tables.iter().enumerate() {
+            writeln!(f)?;
+            writeln!(f, "case table {}", index)?;
+            for (class, target) in &table.branches {
+                writeln!(f, "  {} => {}", self.class(*class).name, target)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,593 @@
+//! Compiling a type checked program to bytecode.
+//!
+//! The program is first lowered to the [`ir`] of the compiler. Every IR value that isn't a
+//! constant is kept in a local, instructions push their operands, run and store their result, and
+//! the phis of a block are assigned on every edge into it.
+
+use std::collections::HashMap;
+
+use common::ast::Program;
+use common::{sym, Symbol};
+use ir::function::{ArithOp, BlockId, CompareOp, FunctionKind, InstructionKind, Terminator};
+use ir::Value;
+use semant::ClassTable;
+
+use crate::bytecode::{
+    self, Builtin, CaseTable, ClassId, Code, Constant, Instruction, MethodBody, MethodId, Module,
+};
+
+/// The layout of the objects of a class and the slots of its vtable.
+struct Layout {
+    attributes: Vec<bytecode::Attribute>,
+    vtable: Vec<MethodId>,
+    slots: HashMap<Symbol, u32>,
+}
+
+/// The method of the basic classes the VM implements as `name`.
+fn builtin(name: Symbol) -> Builtin {
+    match name {
+        sym::ABORT => Builtin::Abort,
+        sym::TYPE_NAME => Builtin::TypeName,
+        sym::COPY => Builtin::Copy,
+        sym::OUT_STRING => Builtin::OutString,
+        sym::OUT_INT => Builtin::OutInt,
+        sym::IN_STRING => Builtin::InString,
+        sym::IN_INT => Builtin::InInt,
+        sym::LENGTH => Builtin::Length,
+        sym::CONCAT => Builtin::Concat,
+        sym::SUBSTR => Builtin::Substr,
+        _ => panic!("{} is not a method of a basic class", name),
+    }
+}
+
+/// Compile `program`, which must have been type checked with `table` as its class table.
+pub fn compile(program: &Program, table: &ClassTable) -> Module {
+    Compiler::new(table).compile(&ir::lower(program, table))
+}
+
+struct Compiler<'t> {
+    table: &'t ClassTable,
+    class_ids: HashMap<Symbol, ClassId>,
+    layouts: Vec<Layout>,
+    /// The initializer of each class, by class id.
+    inits: Vec<MethodId>,
+    /// The methods each class defines itself, by class id.
+    own_methods: Vec<HashMap<Symbol, MethodId>>,
+    methods: Vec<bytecode::Method>,
+    constants: Vec<Constant>,
+    ints: HashMap<i32, u32>,
+    strings: HashMap<Symbol, u32>,
+    case_tables: Vec<CaseTable>,
+}
+
+impl<'t> Compiler<'t> {
+    fn new(table: &'t ClassTable) -> Self {
+        Self {
+            table,
+            class_ids: table
+                .classes()
+                .enumerate()
+                .map(|(index, info)| (info.name, ClassId(index as u32)))
+                .collect(),
+            layouts: vec![],
+            inits: vec![],
+            own_methods: vec![],
+            methods: vec![],
+            constants: vec![],
+            ints: HashMap::new(),
+            strings: HashMap::new(),
+            case_tables: vec![],
+        }
+    }
+
+    fn compile(mut self, module: &ir::Module) -> Module {
+        let infos: Vec<_> = self.table.classes().collect();
+
+        // Every method gets an id before any code is compiled, so calls can refer to methods
+        // that haven't been compiled yet
+        let init_name = Symbol::intern("_init");
+        for info in &infos {
+            let class = self.class_ids[&info.name];
+            let init = self.declare(init_name, class, 0);
+            self.inits.push(init);
+
+            let mut own = HashMap::new();
+            for method in &info.methods {
+                let id = self.declare(method.name, class, method.formals.len() as u32);
+                own.insert(method.name, id);
+            }
+            self.own_methods.push(own);
+        }
+        for info in &infos {
+            let layout = self.layout(info.name);
+            self.layouts.push(layout);
+        }
+
+        // The methods of the basic classes are built into the VM, the program has code for the
+        // rest
+        for info in &infos[..=ClassId::BOOL.0 as usize] {
+            for method in &info.methods {
+                let id = self.own_methods[self.class_ids[&info.name].0 as usize][&method.name];
+                self.methods[id.0 as usize].body = MethodBody::Builtin(builtin(method.name));
+            }
+        }
+        for function in &module.functions {
+            let class = self.class_ids[&function.class];
+            let id = match function.kind {
+                FunctionKind::Method(name) => self.own_methods[class.0 as usize][&name],
+                FunctionKind::Init => self.inits[class.0 as usize],
+            };
+            let code = MethodCompiler::new(&mut self, class, function).compile(function);
+            self.methods[id.0 as usize].body = MethodBody::Code(code);
+        }
+
+        let class_ids = &self.class_ids;
+        let classes = infos
+            .iter()
+            .zip(self.layouts)
+            .zip(self.inits)
+            .map(|((info, layout), init)| bytecode::Class {
+                name: info.name,
+                parent: info.parent.map(|parent| class_ids[&parent]),
+                file_name: info.file_name,
+                attributes: layout.attributes,
+                vtable: layout.vtable,
+                init,
+            })
+            .collect();
+
+        Module {
+            constants: self.constants,
+            classes,
+            methods: self.methods,
+            case_tables: self.case_tables,
+            main: class_ids[&sym::MAIN],
+        }
+    }
+
+    /// Add a method with no body yet.
+    fn declare(&mut self, name: Symbol, class: ClassId, args: u32) -> MethodId {
+        self.methods.push(bytecode::Method {
+            name,
+            class,
+            args,
+            body: MethodBody::Builtin(Builtin::Abort),
+        });
+
+        MethodId(self.methods.len() as u32 - 1)
+    }
+
+    fn layout(&self, class: Symbol) -> Layout {
+        let mut ancestors: Vec<_> = self.table.ancestors(class).collect();
+        ancestors.reverse();
+
+        let mut layout = Layout {
+            attributes: vec![],
+            vtable: vec![],
+            slots: HashMap::new(),
+        };
+        for ancestor in ancestors {
+            let own = &self.own_methods[self.class_ids[&ancestor.name].0 as usize];
+
+            for attribute in &ancestor.attributes {
+                layout.attributes.push(bytecode::Attribute {
+                    name: attribute.name,
+                    type_decl: attribute.type_decl,
+                });
+            }
+            for method in &ancestor.methods {
+                let id = own[&method.name];
+                match layout.slots.get(&method.name) {
+                    Some(&slot) => layout.vtable[slot as usize] = id,
+                    None => {
+                        layout.slots.insert(method.name, layout.vtable.len() as u32);
+                        layout.vtable.push(id);
+                    }
+                }
+            }
+        }
+
+        layout
+    }
+
+    fn layout_of(&self, class: ClassId) -> &Layout {
+        &self.layouts[class.0 as usize]
+    }
+
+    fn class_id(&self, name: Symbol, current: ClassId) -> ClassId {
+        if name == sym::SELF_TYPE {
+            current
+        } else {
+            self.class_ids[&name]
+        }
+    }
+
+    fn int_constant(&mut self, value: i32) -> u32 {
+        let constants = &mut self.constants;
+        *self.ints.entry(value).or_insert_with(|| {
+            constants.push(Constant::Int(value));
+            constants.len() as u32 - 1
+        })
+    }
+
+    fn string_constant(&mut self, value: Symbol) -> u32 {
+        let constants = &mut self.constants;
+        *self.strings.entry(value).or_insert_with(|| {
+            constants.push(Constant::String(value.as_str().to_string()));
+            constants.len() as u32 - 1
+        })
+    }
+}
+
+/// Compiles one function of the IR.
+struct MethodCompiler<'c, 't> {
+    compiler: &'c mut Compiler<'t>,
+    class: ClassId,
+    /// The instructions that push each constant.
+    constants: HashMap<Value, Instruction>,
+    /// The local each value is kept in, the arguments are the first locals.
+    locals: HashMap<Value, u32>,
+    args: u32,
+    /// The phis assigned on every edge, with the values they're assigned.
+    phis: HashMap<(BlockId, BlockId), Vec<(Value, Value)>>,
+    /// The block being compiled.
+    block: BlockId,
+    /// Where the code of each block starts.
+    starts: HashMap<BlockId, u32>,
+    /// The jumps to blocks, to point at the blocks once they're compiled.
+    jumps: Vec<(u32, BlockId)>,
+    instructions: Vec<Instruction>,
+    lines: Vec<u32>,
+    /// The line of the last instruction of the IR that has one.
+    line: u32,
+}
+
+impl<'c, 't> MethodCompiler<'c, 't> {
+    fn new(compiler: &'c mut Compiler<'t>, class: ClassId, function: &ir::Function) -> Self {
+        let args = function.params.len() as u32;
+        let mut constants = HashMap::new();
+        let mut locals: HashMap<_, _> = (0..args)
+            .map(|index| (Value::param(index as usize), index))
+            .collect();
+        let mut phis = HashMap::new();
+        for (id, block) in function.block_ids().zip(&function.blocks) {
+            for instruction in &block.instructions {
+                let result = match instruction.result {
+                    Some(result) => result,
+                    None => continue,
+                };
+                match &instruction.kind {
+                    InstructionKind::Const(constant) => {
+                        let push = match constant {
+                            ir::function::Constant::Int(value) => {
+                                Instruction::Constant(compiler.int_constant(*value))
+                            }
+                            ir::function::Constant::String(value) => {
+                                Instruction::Constant(compiler.string_constant(*value))
+                            }
+                            ir::function::Constant::Bool(true) => Instruction::True,
+                            ir::function::Constant::Bool(false) => Instruction::False,
+                            ir::function::Constant::Void => Instruction::Void,
+                        };
+                        constants.insert(result, push);
+                        continue;
+                    }
+                    InstructionKind::Phi(incoming) => {
+                        for &(predecessor, value) in incoming {
+                            phis.entry((predecessor, id))
+                                .or_insert_with(Vec::new)
+                                .push((result, value));
+                        }
+                    }
+                    _ => {}
+                }
+                let local = locals.len() as u32;
+                locals.insert(result, local);
+            }
+        }
+
+        let line = compiler
+            .table
+            .get(function.class)
+            .map_or(0, |info| info.line) as u32;
+        Self {
+            compiler,
+            class,
+            constants,
+            locals,
+            args,
+            phis,
+            block: BlockId::ENTRY,
+            starts: HashMap::new(),
+            jumps: vec![],
+            instructions: vec![],
+            lines: vec![],
+            line,
+        }
+    }
+
+    fn compile(mut self, function: &ir::Function) -> Code {
+        for (id, block) in function.block_ids().zip(&function.blocks) {
+            self.block = id;
+            self.starts.insert(id, self.here());
+            for instruction in &block.instructions {
+                self.instruction(instruction.result, &instruction.kind);
+            }
+            self.terminator(&block.terminator);
+        }
+
+        for (jump, block) in std::mem::take(&mut self.jumps) {
+            let target = self.starts[&block];
+            match &mut self.instructions[jump as usize] {
+                Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = target,
+                other => panic!("Can't patch {}", other),
+            }
+        }
+
+        Code {
+            locals: self.locals.len() as u32 - self.args,
+            instructions: self.instructions,
+            lines: self.lines,
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> u32 {
+        self.instructions.push(instruction);
+        self.lines.push(self.line);
+
+        self.instructions.len() as u32 - 1
+    }
+
+    /// The index of the next instruction.
+    fn here(&self) -> u32 {
+        self.instructions.len() as u32
+    }
+
+    fn push(&mut self, value: Value) {
+        let instruction = if value == Value::SELF {
+            Instruction::LoadSelf
+        } else if let Some(&push) = self.constants.get(&value) {
+            push
+        } else {
+            Instruction::LoadLocal(self.locals[&value])
+        };
+        self.emit(instruction);
+    }
+
+    /// Pop the value on top of the stack into the local of `value`.
+    fn store(&mut self, value: Value) {
+        self.emit(Instruction::StoreLocal(self.locals[&value]));
+        self.emit(Instruction::Pop);
+    }
+
+    /// The index of the attribute `name` of the class.
+    fn attribute(&self, name: Symbol) -> u32 {
+        let attributes = &self.compiler.layout_of(self.class).attributes;
+        let index = attributes
+            .iter()
+            .rposition(|attribute| attribute.name == name)
+            .expect("Unknown attribute");
+
+        index as u32
+    }
+
+    /// The method `method` of `class`, as a static dispatch calls it.
+    fn method(&self, class: Symbol, method: Symbol) -> MethodId {
+        let layout = self.compiler.layout_of(self.compiler.class_ids[&class]);
+
+        layout.vtable[layout.slots[&method] as usize]
+    }
+
+    fn instruction(&mut self, result: Option<Value>, kind: &InstructionKind) {
+        match kind {
+            InstructionKind::Const(_) | InstructionKind::Phi(_) => return,
+            InstructionKind::GetAttribute(name) => {
+                let index = self.attribute(*name);
+                self.emit(Instruction::LoadAttribute(index));
+            }
+            InstructionKind::SetAttribute(name, value) => {
+                let index = self.attribute(*name);
+                self.push(*value);
+                self.emit(Instruction::StoreAttribute(index));
+                self.emit(Instruction::Pop);
+            }
+            InstructionKind::Arith { op, lhs, rhs, line } => {
+                self.line = *line as u32;
+                self.push(*lhs);
+                self.push(*rhs);
+                self.emit(match op {
+                    ArithOp::Add => Instruction::Add,
+                    ArithOp::Sub => Instruction::Sub,
+                    ArithOp::Mul => Instruction::Mul,
+                    ArithOp::Div => Instruction::Div,
+                });
+            }
+            InstructionKind::Compare { op, lhs, rhs } => {
+                self.push(*lhs);
+                self.push(*rhs);
+                self.emit(match op {
+                    CompareOp::Lt => Instruction::Lt,
+                    CompareOp::Le => Instruction::Le,
+                    CompareOp::Eq => Instruction::Eq,
+                });
+            }
+            InstructionKind::Neg(value) => {
+                self.push(*value);
+                self.emit(Instruction::Neg);
+            }
+            InstructionKind::Not(value) => {
+                self.push(*value);
+                self.emit(Instruction::Not);
+            }
+            InstructionKind::IsVoid(value) => {
+                self.push(*value);
+                self.emit(Instruction::IsVoid);
+            }
+            // Dispatches and cases check for void themselves, at the line of the check
+            InstructionKind::CheckVoid { line, .. } => self.line = *line as u32,
+            InstructionKind::Dispatch {
+                receiver,
+                class,
+                method,
+                args,
+            } => {
+                for arg in args {
+                    self.push(*arg);
+                }
+                self.push(*receiver);
+                let class = self.compiler.class_id(*class, self.class);
+                let slot = self.compiler.layout_of(class).slots[method];
+                self.emit(Instruction::Dispatch {
+                    slot,
+                    args: args.len() as u32,
+                });
+            }
+            InstructionKind::StaticDispatch {
+                receiver,
+                class,
+                method,
+                args,
+            } => {
+                for arg in args {
+                    self.push(*arg);
+                }
+                self.push(*receiver);
+                let method = self.method(*class, *method);
+                self.emit(Instruction::StaticDispatch {
+                    method,
+                    args: args.len() as u32,
+                });
+            }
+            InstructionKind::New(class) if *class == sym::SELF_TYPE => {
+                self.emit(Instruction::NewSelfType);
+            }
+            InstructionKind::New(class) => {
+                let class = self.compiler.class_ids[class];
+                self.emit(Instruction::New(class));
+            }
+            InstructionKind::Init(parent) => {
+                let method = self.compiler.inits[self.compiler.class_ids[parent].0 as usize];
+                self.emit(Instruction::LoadSelf);
+                self.emit(Instruction::StaticDispatch { method, args: 0 });
+                self.emit(Instruction::Pop);
+            }
+        }
+
+        if let Some(result) = result {
+            self.store(result);
+        }
+    }
+
+    /// Take the edge from the current block to `target`: assign the phis of `target` and jump to
+    /// it.
+    fn edge(&mut self, target: BlockId) {
+        let copies = self
+            .phis
+            .get(&(self.block, target))
+            .cloned()
+            .unwrap_or_default();
+
+        // The phis may read each other, so push every value before assigning any
+        for &(_, value) in &copies {
+            self.push(value);
+        }
+        for &(result, _) in copies.iter().rev() {
+            self.store(result);
+        }
+        let jump = self.emit(Instruction::Jump(0));
+        self.jumps.push((jump, target));
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match terminator {
+            Terminator::Jump(target) => self.edge(*target),
+            Terminator::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.push(*condition);
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.edge(*then_block);
+                let target = self.here();
+                match &mut self.instructions[to_else as usize] {
+                    Instruction::JumpIfFalse(to) => *to = target,
+                    other => panic!("Can't patch {}", other),
+                }
+                self.edge(*else_block);
+            }
+            Terminator::Case { value, branches } => {
+                self.push(*value);
+                let table = self.compiler.case_tables.len() as u32;
+                self.compiler
+                    .case_tables
+                    .push(CaseTable { branches: vec![] });
+                self.emit(Instruction::Case(table));
+
+                // The case leaves the value on the stack for the branch it jumps to
+                let mut targets = vec![];
+                for &(class, block) in branches {
+                    targets.push((self.compiler.class_ids[&class], self.here()));
+                    self.emit(Instruction::Pop);
+                    self.edge(block);
+                }
+                self.compiler.case_tables[table as usize].branches = targets;
+            }
+            Terminator::Return(value) => {
+                self.push(*value);
+                self.emit(Instruction::Return);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    /// Compile and run `input`, returning what it printed.
+    fn run_program(input: &str) -> String {
+        let tokens = Lexer::new(cool::rules()).lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        let mut output = vec![];
+        crate::run(&compile(&program, &table), &b""[..], &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_phis_are_assigned_at_once() {
+        // The loop header has a phi for each of `a` and `b`, which swap on every iteration
+        let output = run_program(
+            "class Main inherits IO {\n\
+             \tmain() : Object {\n\
+             \t\tlet a : Int <- 1, b : Int <- 2, i : Int in {\n\
+             \t\t\twhile i < 3 loop { let t : Int <- a in { a <- b; b <- t; }; i <- i + 1; } pool;\n\
+             \t\t\tout_int(a).out_int(b);\n\
+             \t\t}\n\
+             \t};\n\
+             };\n",
+        );
+
+        assert_eq!(output, "21");
+    }
+
+    #[test]
+    fn test_case_branches_get_the_value() {
+        let output = run_program(
+            "class Main inherits IO {\n\
+             \tmain() : Object {\n\
+             \t\tcase 7 of s : String => out_string(s); i : Int => out_int(i + 1); esac\n\
+             \t};\n\
+             };\n",
+        );
+
+        assert_eq!(output, "8");
+    }
+}
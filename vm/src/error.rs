@@ -0,0 +1,70 @@
+use std::fmt;
+use std::io;
+
+use common::Symbol;
+
+/// An error that stops a running program.
+///
+/// Displayed like the reference runtime reports the same errors.
+#[derive(Debug)]
+pub enum RuntimeError {
+    DispatchToVoid {
+        file_name: Symbol,
+        line: u32,
+    },
+    CaseOnVoid {
+        file_name: Symbol,
+        line: u32,
+    },
+    /// No branch of a `case` matches an object of the class.
+    NoMatch(Symbol),
+    /// `abort` was called on an object of the class.
+    Abort(Symbol),
+    DivisionByZero {
+        file_name: Symbol,
+        line: u32,
+    },
+    SubstrIndexNegative,
+    SubstrIndexTooBig,
+    SubstrLengthNegative,
+    SubstrLengthTooLong,
+    /// The calls nested deeper than the VM allows.
+    StackOverflow,
+    Io(io::Error),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DispatchToVoid { file_name, line } => {
+                write!(f, "{}:{}: Dispatch to void.", file_name, line)
+            }
+            Self::CaseOnVoid { file_name, line } => {
+                write!(
+                    f,
+                    "{}:{}: Match on void in case statement.",
+                    file_name, line
+                )
+            }
+            Self::NoMatch(class) => write!(f, "No match in case statement for Class {}", class),
+            Self::Abort(class) => write!(f, "Abort called from class {}", class),
+            Self::DivisionByZero { file_name, line } => {
+                write!(f, "{}:{}: Division by zero.", file_name, line)
+            }
+            Self::SubstrIndexNegative => write!(f, "Index to substr is negative"),
+            Self::SubstrIndexTooBig => write!(f, "Index to substr is too big"),
+            Self::SubstrLengthNegative => write!(f, "Length to substr is negative"),
+            Self::SubstrLengthTooLong => write!(f, "Length to substr too long"),
+            Self::StackOverflow => write!(f, "Stack overflow"),
+            Self::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use crate::bytecode::ClassId;
+
+/// Collect once this many objects were allocated since the last collection, or as many as were
+/// live after it if that's more.
+const MIN_THRESHOLD: usize = 10_000;
+
+/// A reference to an object on the heap.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub(crate) struct ObjRef(u32);
+
+/// A COOL value. `Int`s and `Bool`s are stored unboxed, everything else lives on the heap.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub(crate) enum Value {
+    Void,
+    Int(i32),
+    Bool(bool),
+    Ref(ObjRef),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Object {
+    Instance {
+        class: ClassId,
+        fields: Vec<Value>,
+    },
+    /// COOL strings are sequences of bytes, `substr` indexes bytes.
+    String(Vec<u8>),
+}
+
+/// How the garbage collector runs.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct GcOptions {
+    /// Collect at every chance after an allocation instead of only when enough objects were
+    /// allocated, to find objects the VM fails to keep alive.
+    pub stress: bool,
+}
+
+/// What the garbage collector did during a run.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub allocated: usize,
+    pub freed: usize,
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GC: {} collections, {} objects allocated, {} freed, {} live",
+            self.collections,
+            self.allocated,
+            self.freed,
+            self.allocated - self.freed
+        )
+    }
+}
+
+/// The objects allocated by a program.
+///
+/// Objects are never moved, the slots of collected objects are reused. The heap doesn't know
+/// which values the program can still reach, the VM asks it whether it wants to collect when all
+/// of them are in places it can pass to [`collect`](Heap::collect).
+///
+/// The C runtime of the native backends collects the same way, with the same threshold.
+pub(crate) struct Heap {
+    objects: Vec<Option<Object>>,
+    free: Vec<u32>,
+    /// Objects allocated since the last collection.
+    allocated: usize,
+    threshold: usize,
+    options: GcOptions,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub(crate) fn new(options: GcOptions) -> Self {
+        Self {
+            objects: vec![],
+            free: vec![],
+            allocated: 0,
+            threshold: MIN_THRESHOLD,
+            options,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub(crate) fn allocate(&mut self, object: Object) -> ObjRef {
+        self.allocated += 1;
+        self.stats.allocated += 1;
+
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = Some(object);
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(Some(object));
+                ObjRef(self.objects.len() as u32 - 1)
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, object: ObjRef) -> &Object {
+        self.objects[object.0 as usize]
+            .as_ref()
+            .expect("Use of a collected object")
+    }
+
+    pub(crate) fn get_mut(&mut self, object: ObjRef) -> &mut Object {
+        self.objects[object.0 as usize]
+            .as_mut()
+            .expect("Use of a collected object")
+    }
+
+    /// The contents of the string `object`.
+    pub(crate) fn string(&self, object: ObjRef) -> &[u8] {
+        match self.get(object) {
+            Object::String(bytes) => bytes,
+            other => panic!("Expected a string, got {:?}", other),
+        }
+    }
+
+    pub(crate) fn wants_collection(&self) -> bool {
+        self.allocated >= self.threshold || self.options.stress && self.allocated > 0
+    }
+
+    pub(crate) fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// Free every object that can't be reached from `roots`.
+    pub(crate) fn collect(&mut self, roots: impl IntoIterator<Item = Value>) {
+        let mut marked = vec![false; self.objects.len()];
+        let mut pending: Vec<_> = roots.into_iter().collect();
+        while let Some(value) = pending.pop() {
+            let object = match value {
+                Value::Ref(object) => object,
+                _ => continue,
+            };
+            if std::mem::replace(&mut marked[object.0 as usize], true) {
+                continue;
+            }
+            if let Object::Instance { fields, .. } = self.get(object) {
+                pending.extend(fields.iter().copied());
+            }
+        }
+
+        let mut live = 0;
+        for (index, (object, marked)) in self.objects.iter_mut().zip(marked).enumerate() {
+            if marked {
+                live += 1;
+            } else if object.take().is_some() {
+                self.free.push(index as u32);
+                self.stats.freed += 1;
+            }
+        }
+
+        self.stats.collections += 1;
+        self.allocated = 0;
+        self.threshold = live.max(MIN_THRESHOLD);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let mut heap = Heap::new(GcOptions::default());
+        let string = heap.allocate(Object::String(b"kept".to_vec()));
+        let garbage = heap.allocate(Object::String(b"garbage".to_vec()));
+        let instance = heap.allocate(Object::Instance {
+            class: ClassId::OBJECT,
+            fields: vec![Value::Ref(string), Value::Int(1)],
+        });
+
+        heap.collect(vec![Value::Ref(instance), Value::Bool(true)]);
+
+        assert_eq!(heap.string(string), b"kept");
+        assert!(heap.objects[garbage.0 as usize].is_none());
+        // The slot of the garbage is reused
+        assert_eq!(heap.allocate(Object::String(vec![])), garbage);
+        assert_eq!(
+            heap.stats(),
+            GcStats {
+                collections: 1,
+                allocated: 4,
+                freed: 1
+            }
+        );
+    }
+}
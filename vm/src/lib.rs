@@ -0,0 +1,24 @@
+//! A bytecode compiler and stack based virtual machine for COOL.
+//!
+//! [`compile`] turns a type checked program into a [`Module`], which [`run`] runs with the same
+//! semantics and built-in methods as the reference runtime. Objects that the program can't reach
+//! anymore are freed by a mark-sweep garbage collector.
+
+pub mod bytecode;
+mod compiler;
+mod error;
+mod heap;
+mod machine;
+
+pub use crate::bytecode::Module;
+pub use crate::compiler::compile;
+pub use crate::error::RuntimeError;
+pub use crate::heap::{GcOptions, GcStats};
+pub use crate::machine::{run, run_with_gc};
+
+pub mod prelude {
+    pub use crate::bytecode::Module;
+    pub use crate::error::RuntimeError;
+    pub use crate::heap::{GcOptions, GcStats};
+    pub use crate::{compile, run, run_with_gc};
+}
@@ -0,0 +1,626 @@
+//! The stack machine that runs a [`Module`].
+
+use std::io::{BufRead, Write};
+
+use common::Symbol;
+
+use crate::bytecode::*;
+use crate::error::RuntimeError;
+use crate::heap::{GcOptions, GcStats, Heap, Object, Value};
+
+/// The deepest calls may nest before the program is stopped with a stack overflow.
+const MAX_FRAMES: usize = 500_000;
+
+struct Frame {
+    method: MethodId,
+    /// The next instruction to run.
+    pc: usize,
+    /// Where the arguments and locals of the method start on the stack.
+    base: usize,
+    self_value: Value,
+}
+
+/// Run `module`, reading the program's input from `input` and writing its output to `output`.
+pub fn run<R, W>(module: &Module, input: R, output: W) -> Result<(), RuntimeError>
+where
+    R: BufRead,
+    W: Write,
+{
+    run_with_gc(module, input, output, GcOptions::default()).0
+}
+
+/// Run `module` like [`run`], collecting garbage as `options` says. Also returns what the
+/// garbage collector did, whether the program succeeded or not.
+pub fn run_with_gc<R, W>(
+    module: &Module,
+    input: R,
+    output: W,
+    options: GcOptions,
+) -> (Result<(), RuntimeError>, GcStats)
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut vm = Vm::new(module, input, output, options);
+    let result = vm.run();
+
+    (result, vm.heap.stats())
+}
+
+struct Vm<'m, R, W> {
+    module: &'m Module,
+    heap: Heap,
+    /// The values of the constants in the constant pool.
+    constants: Vec<Value>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    input: R,
+    output: W,
+}
+
+impl<'m, R, W> Vm<'m, R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    fn new(module: &'m Module, input: R, output: W, options: GcOptions) -> Self {
+        let mut heap = Heap::new(options);
+        let constants = module
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Constant::Int(value) => Value::Int(*value),
+                Constant::String(value) => {
+                    Value::Ref(heap.allocate(Object::String(value.as_bytes().to_vec())))
+                }
+            })
+            .collect();
+
+        Self {
+            module,
+            heap,
+            constants,
+            stack: vec![],
+            frames: vec![],
+            input,
+            output,
+        }
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        let module = self.module;
+        let main_class = module.class(module.main);
+        let main_method = main_class
+            .vtable
+            .iter()
+            .copied()
+            .find(|&method| module.method(method).name == common::sym::MAIN_METHOD)
+            .expect("Main has no main method");
+
+        let result = self.instantiate(module.main).and_then(|()| {
+            self.execute()?;
+            let main = self.stack.pop().unwrap();
+            self.call(main_method, main, 0)?;
+            self.execute()
+        });
+        self.output.flush()?;
+
+        result
+    }
+
+    /// Push a new object of `class`, running its initializer if it has one.
+    fn instantiate(&mut self, class: ClassId) -> Result<(), RuntimeError> {
+        let value = match class {
+            ClassId::INT => Value::Int(0),
+            ClassId::BOOL => Value::Bool(false),
+            ClassId::STRING => self.string(vec![]),
+            _ => {
+                let fields = self
+                    .module
+                    .class(class)
+                    .attributes
+                    .iter()
+                    .map(|attribute| self.default(attribute.type_decl))
+                    .collect();
+                let object = Value::Ref(self.heap.allocate(Object::Instance { class, fields }));
+
+                return self.call(self.module.class(class).init, object, 0);
+            }
+        };
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// The value of a variable of type `ty` before it's assigned.
+    fn default(&mut self, ty: Symbol) -> Value {
+        match ty {
+            common::sym::INT => Value::Int(0),
+            common::sym::BOOL => Value::Bool(false),
+            common::sym::STRING => self.string(vec![]),
+            _ => Value::Void,
+        }
+    }
+
+    fn string(&mut self, bytes: Vec<u8>) -> Value {
+        Value::Ref(self.heap.allocate(Object::String(bytes)))
+    }
+
+    fn class_of(&self, value: Value) -> ClassId {
+        match value {
+            Value::Void => panic!("Void has no class"),
+            Value::Int(_) => ClassId::INT,
+            Value::Bool(_) => ClassId::BOOL,
+            Value::Ref(object) => match self.heap.get(object) {
+                Object::Instance { class, .. } => *class,
+                Object::String(_) => ClassId::STRING,
+            },
+        }
+    }
+
+    /// Call `method` on `receiver`, with its `args` arguments on top of the stack.
+    ///
+    /// Built-in methods run to completion right away and leave their result on the stack,
+    /// compiled methods get a frame for [`execute`](Self::execute) to run.
+    fn call(&mut self, method: MethodId, receiver: Value, args: u32) -> Result<(), RuntimeError> {
+        match &self.module.method(method).body {
+            MethodBody::Builtin(builtin) => {
+                let base = self.stack.len() - args as usize;
+                let result = self.builtin(*builtin, receiver, base)?;
+                self.stack.truncate(base);
+                self.stack.push(result);
+            }
+            MethodBody::Code(code) => {
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(RuntimeError::StackOverflow);
+                }
+
+                let base = self.stack.len() - args as usize;
+                let locals = self.stack.len() + code.locals as usize;
+                self.stack.resize(locals, Value::Void);
+                self.frames.push(Frame {
+                    method,
+                    pc: 0,
+                    base,
+                    self_value: receiver,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The file and line of the instruction that's running.
+    fn location(&self) -> (Symbol, u32) {
+        let frame = self.frames.last().unwrap();
+        let method = self.module.method(frame.method);
+        let line = match &method.body {
+            MethodBody::Code(code) => code.lines[frame.pc - 1],
+            MethodBody::Builtin(_) => 0,
+        };
+
+        (self.module.class(method.class).file_name, line)
+    }
+
+    fn pop_int(&mut self) -> i32 {
+        match self.stack.pop() {
+            Some(Value::Int(value)) => value,
+            other => panic!("Expected an Int, got {:?}", other),
+        }
+    }
+
+    fn pop_bool(&mut self) -> bool {
+        match self.stack.pop() {
+            Some(Value::Bool(value)) => value,
+            other => panic!("Expected a Bool, got {:?}", other),
+        }
+    }
+
+    fn attributes(&mut self, object: Value) -> &mut Vec<Value> {
+        match object {
+            Value::Ref(object) => match self.heap.get_mut(object) {
+                Object::Instance { fields, .. } => fields,
+                other => panic!("{:?} has no attributes", other),
+            },
+            other => panic!("{:?} has no attributes", other),
+        }
+    }
+
+    fn equal(&self, a: Value, b: Value) -> bool {
+        match (a, b) {
+            (Value::Ref(a), Value::Ref(b)) if a != b => {
+                match (self.heap.get(a), self.heap.get(b)) {
+                    (Object::String(a), Object::String(b)) => a == b,
+                    _ => false,
+                }
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Collect the objects the program can't reach anymore.
+    ///
+    /// Only called between instructions, when every value the program can reach is a constant,
+    /// on the stack or the receiver of a frame.
+    fn collect(&mut self) {
+        let roots = self
+            .constants
+            .iter()
+            .chain(&self.stack)
+            .copied()
+            .chain(self.frames.iter().map(|frame| frame.self_value));
+        self.heap.collect(roots);
+    }
+
+    /// Run until the frame on top of the stack when called returns.
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        let module = self.module;
+        let depth = self.frames.len() - 1;
+
+        while self.frames.len() > depth {
+            if self.heap.wants_collection() {
+                self.collect();
+            }
+
+            let frame = self.frames.last_mut().unwrap();
+            let code = match &module.method(frame.method).body {
+                MethodBody::Code(code) => code,
+                MethodBody::Builtin(_) => unreachable!("Built-in methods have no frame"),
+            };
+            let instruction = code.instructions[frame.pc];
+            frame.pc += 1;
+            let base = frame.base;
+            let self_value = frame.self_value;
+
+            match instruction {
+                Instruction::Constant(index) => self.stack.push(self.constants[index as usize]),
+                Instruction::True => self.stack.push(Value::Bool(true)),
+                Instruction::False => self.stack.push(Value::Bool(false)),
+                Instruction::Void => self.stack.push(Value::Void),
+                Instruction::Pop => {
+                    self.stack.pop();
+                }
+                Instruction::LoadSelf => self.stack.push(self_value),
+                Instruction::LoadLocal(local) => {
+                    self.stack.push(self.stack[base + local as usize]);
+                }
+                Instruction::StoreLocal(local) => {
+                    self.stack[base + local as usize] = *self.stack.last().unwrap();
+                }
+                Instruction::LoadAttribute(index) => {
+                    let value = self.attributes(self_value)[index as usize];
+                    self.stack.push(value);
+                }
+                Instruction::StoreAttribute(index) => {
+                    let value = *self.stack.last().unwrap();
+                    self.attributes(self_value)[index as usize] = value;
+                }
+
+                Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div => {
+                    let rhs = self.pop_int();
+                    let lhs = self.pop_int();
+                    let result = match instruction {
+                        Instruction::Add => lhs.wrapping_add(rhs),
+                        Instruction::Sub => lhs.wrapping_sub(rhs),
+                        Instruction::Mul => lhs.wrapping_mul(rhs),
+                        _ if rhs == 0 => {
+                            let (file_name, line) = self.location();
+                            return Err(RuntimeError::DivisionByZero { file_name, line });
+                        }
+                        _ => lhs.wrapping_div(rhs),
+                    };
+                    self.stack.push(Value::Int(result));
+                }
+                Instruction::Lt | Instruction::Le => {
+                    let rhs = self.pop_int();
+                    let lhs = self.pop_int();
+                    let result = if instruction == Instruction::Lt {
+                        lhs < rhs
+                    } else {
+                        lhs <= rhs
+                    };
+                    self.stack.push(Value::Bool(result));
+                }
+                Instruction::Eq => {
+                    let rhs = self.stack.pop().unwrap();
+                    let lhs = self.stack.pop().unwrap();
+                    let result = self.equal(lhs, rhs);
+                    self.stack.push(Value::Bool(result));
+                }
+                Instruction::Neg => {
+                    let value = self.pop_int();
+                    self.stack.push(Value::Int(value.wrapping_neg()));
+                }
+                Instruction::Not => {
+                    let value = self.pop_bool();
+                    self.stack.push(Value::Bool(!value));
+                }
+                Instruction::IsVoid => {
+                    let value = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(value == Value::Void));
+                }
+
+                Instruction::Jump(target) => self.frames.last_mut().unwrap().pc = target as usize,
+                Instruction::JumpIfFalse(target) => {
+                    if !self.pop_bool() {
+                        self.frames.last_mut().unwrap().pc = target as usize;
+                    }
+                }
+
+                Instruction::New(class) => self.instantiate(class)?,
+                Instruction::NewSelfType => self.instantiate(self.class_of(self_value))?,
+                Instruction::Dispatch { slot, args } => {
+                    let receiver = self.receiver()?;
+                    let class = module.class(self.class_of(receiver));
+                    self.call(class.vtable[slot as usize], receiver, args)?;
+                }
+                Instruction::StaticDispatch { method, args } => {
+                    let receiver = self.receiver()?;
+                    self.call(method, receiver, args)?;
+                }
+                Instruction::Case(index) => {
+                    let value = *self.stack.last().unwrap();
+                    if value == Value::Void {
+                        let (file_name, line) = self.location();
+                        return Err(RuntimeError::CaseOnVoid { file_name, line });
+                    }
+
+                    let branches = &module.case_tables[index as usize].branches;
+                    let class = self.class_of(value);
+                    let target =
+                        std::iter::successors(Some(class), |&class| module.class(class).parent)
+                            .find_map(|ancestor| {
+                                branches
+                                    .iter()
+                                    .find(|(branch, _)| *branch == ancestor)
+                                    .map(|&(_, target)| target)
+                            });
+
+                    match target {
+                        Some(target) => self.frames.last_mut().unwrap().pc = target as usize,
+                        None => return Err(RuntimeError::NoMatch(module.class(class).name)),
+                    }
+                }
+                Instruction::Return => {
+                    let result = self.stack.pop().unwrap();
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.base);
+                    self.stack.push(result);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pop the receiver of a dispatch, which must not be void.
+    fn receiver(&mut self) -> Result<Value, RuntimeError> {
+        match self.stack.pop().unwrap() {
+            Value::Void => {
+                let (file_name, line) = self.location();
+                Err(RuntimeError::DispatchToVoid { file_name, line })
+            }
+            receiver => Ok(receiver),
+        }
+    }
+
+    /// Run a built-in method, whose arguments start at `base` on the stack, and return its
+    /// result.
+    fn builtin(
+        &mut self,
+        builtin: Builtin,
+        receiver: Value,
+        base: usize,
+    ) -> Result<Value, RuntimeError> {
+        let arg = |index: usize| self.stack.get(base + index).copied();
+        let (first, second) = (arg(0), arg(1));
+
+        let result = match builtin {
+            Builtin::Abort => {
+                let class = self.module.class(self.class_of(receiver)).name;
+                return Err(RuntimeError::Abort(class));
+            }
+            Builtin::TypeName => {
+                let class = self.module.class(self.class_of(receiver)).name;
+                self.string(class.as_str().as_bytes().to_vec())
+            }
+            Builtin::Copy => match receiver {
+                Value::Ref(object) => match self.heap.get(object) {
+                    Object::Instance { .. } => {
+                        let copy = self.heap.get(object).clone();
+                        Value::Ref(self.heap.allocate(copy))
+                    }
+                    // Strings are immutable, a copy can be the same object
+                    Object::String(_) => receiver,
+                },
+                _ => receiver,
+            },
+            Builtin::OutString => {
+                self.output.write_all(string_value(&self.heap, first))?;
+                receiver
+            }
+            Builtin::OutInt => {
+                write!(self.output, "{}", int_value(first))?;
+                receiver
+            }
+            Builtin::InString => {
+                let line = self.read_line()?;
+                self.string(line)
+            }
+            Builtin::InInt => {
+                let line = self.read_line()?;
+                Value::Int(parse_int(&line))
+            }
+            Builtin::Length => Value::Int(string_value(&self.heap, Some(receiver)).len() as i32),
+            Builtin::Concat => {
+                let mut bytes = string_value(&self.heap, Some(receiver)).to_vec();
+                bytes.extend_from_slice(string_value(&self.heap, first));
+                self.string(bytes)
+            }
+            Builtin::Substr => {
+                let (index, length) = (int_value(first), int_value(second));
+                let bytes = string_value(&self.heap, Some(receiver));
+                if index < 0 {
+                    return Err(RuntimeError::SubstrIndexNegative);
+                }
+                if length < 0 {
+                    return Err(RuntimeError::SubstrLengthNegative);
+                }
+                let (index, length) = (index as usize, length as usize);
+                if index > bytes.len() {
+                    return Err(RuntimeError::SubstrIndexTooBig);
+                }
+                if index + length > bytes.len() {
+                    return Err(RuntimeError::SubstrLengthTooLong);
+                }
+
+                let substring = bytes[index..index + length].to_vec();
+                self.string(substring)
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// Read a line of input, without the line break.
+    fn read_line(&mut self) -> Result<Vec<u8>, RuntimeError> {
+        // Prompts written before reading have to be visible
+        self.output.flush()?;
+
+        let mut line = vec![];
+        self.input.read_until(b'\n', &mut line)?;
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+
+        Ok(line)
+    }
+}
+
+/// The contents of an argument that must be a `String`.
+fn string_value(heap: &Heap, value: Option<Value>) -> &[u8] {
+    match value {
+        Some(Value::Ref(object)) => heap.string(object),
+        other => panic!("Expected a String, got {:?}", other),
+    }
+}
+
+/// The value of an argument that must be an `Int`.
+fn int_value(value: Option<Value>) -> i32 {
+    match value {
+        Some(Value::Int(value)) => value,
+        other => panic!("Expected an Int, got {:?}", other),
+    }
+}
+
+/// Parse the integer at the start of `line` like the reference runtime's `in_int`, which skips
+/// leading whitespace and reads 0 if there's no integer.
+fn parse_int(line: &[u8]) -> i32 {
+    let line = match line.iter().position(|byte| !byte.is_ascii_whitespace()) {
+        Some(start) => &line[start..],
+        None => return 0,
+    };
+    let (negative, digits) = match line.first() {
+        Some(b'-') => (true, &line[1..]),
+        Some(b'+') => (false, &line[1..]),
+        _ => (false, line),
+    };
+
+    let value = digits
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .fold(0i32, |value, digit| {
+            value.wrapping_mul(10).wrapping_add(i32::from(digit - b'0'))
+        });
+
+    if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::{cool, Lexer};
+    use parser::Parser;
+
+    fn compile_program(input: &str) -> Module {
+        let lexer = Lexer::new(cool::rules());
+        let tokens = lexer.lex(input).unwrap();
+        let mut program = Parser::new(&tokens, Symbol::intern("test.cl"))
+            .parse_program()
+            .unwrap();
+        let table = semant::check(&mut program).unwrap();
+
+        crate::compile(&program, &table)
+    }
+
+    /// Compile and run `input`, returning what it printed.
+    fn run_program(input: &str, stdin: &str) -> Result<String, RuntimeError> {
+        let module = compile_program(input);
+        let mut output = vec![];
+        run(&module, stdin.as_bytes(), &mut output)?;
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_run() {
+        let output = run_program(
+            "class Main inherits IO {\n\
+             \tn : Int <- in_int();\n\
+             \tmain() : Object {\n\
+             \t\tlet i : Int in while i < n loop { out_int(i * i).out_string(\" \"); i <- i + 1; } pool\n\
+             \t};\n\
+             };\n",
+            "4\n",
+        )
+        .unwrap();
+
+        assert_eq!(output, "0 1 4 9 ");
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let error = run_program(
+            "class Main {\n\
+             \tmain() : Object { 1 / (2 - 2) };\n\
+             };\n",
+            "",
+        )
+        .unwrap_err();
+
+        assert_eq!(error.to_string(), "test.cl:2: Division by zero.");
+    }
+
+    #[test]
+    fn test_gc_stress() {
+        let module = compile_program(
+            "class Main inherits IO {\n\
+             \tkept : String <- \"a\";\n\
+             \tmain() : Object {\n\
+             \t\tlet i : Int in while i < 3 loop { kept <- kept.concat(\"b\"); out_string(kept.copy().concat(\" \")); i <- i + 1; } pool\n\
+             \t};\n\
+             };\n",
+        );
+
+        let mut output = vec![];
+        let (result, stats) =
+            run_with_gc(&module, &b""[..], &mut output, GcOptions { stress: true });
+        result.unwrap();
+
+        assert_eq!(output, b"ab abb abbb ");
+        assert!(stats.collections > 0);
+        assert!(stats.freed > 0);
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int(b"42"), 42);
+        assert_eq!(parse_int(b"  -7 apples"), -7);
+        assert_eq!(parse_int(b"apples"), 0);
+        assert_eq!(parse_int(b""), 0);
+    }
+}